@@ -0,0 +1,143 @@
+# Messages of gman. Every message here needs a translation in the other catalogs,
+# missing ones fall back to these
+
+## configuration
+config-nothing-to-migrate = Nothing to migrate
+config-moved = Moved { $from } to { $to }
+config-migrate-failed = Failed to migrate: { $error }
+config-edit-not-found = No configuration file found to edit. Create one with `config --sample`
+config-editing = Editing { $path }
+config-saved = Saved { $path }
+config-no-changes = No changes made
+config-edit-failed = Failed to edit configuration: { $error }
+config-exists = { $path } already exists. Change it with `config --edit`, or pass --force to replace it
+config-wrote = Wrote { $path }
+config-setup-failed = Failed to set up gman: { $error }
+config-setup-prompt = No configuration file was found. Set one up now? [Y/n]{" "}
+config-load-failed = Failed to load configuration file: { $error }
+config-not-found = Failed to load configuration file: no configuration was found, see `config --where`
+alias-failed = Failed to expand alias: { $error }
+config-sample-exhausted = Cannot create sample file, maximum number of tried exceeded (200). Try deleting files named { $name }
+config-loaded = Loaded configuration: { $path }
+config-parse-failed = but it fails to parse: { $error }
+config-read-failed = but it can't be read: { $error }
+config-none = No configuration found
+config-search-order = Search order:
+config-state-not-checked = not checked
+config-state-loaded = loaded
+config-state-not-found = not found
+
+## auth
+auth-login-failed = Failed to sign in to { $repository }: { $error }
+auth-signed-in = Signed in to { $repository }
+auth-logout-failed = Failed to sign out of { $repository }: { $error }
+auth-signed-out = Signed out of { $repository }
+
+## cache
+cache-cleared = Cleared cache
+cache-clear-failed = Failed to clear cache: { $error }
+cache-directory = Cache Directory: { $path }
+cache-count = Content Count: { $count }
+cache-empty = Nothing in cache
+cache-pinned = Pinned { $entry }
+cache-unpinned = Unpinned { $entry }
+cache-pin-failed = Failed to update the pin of { $artifact }: { $error }
+
+## list
+list-outdated-failed = Failed to list outdated products: { $error }
+list-offline = Failed to reach the repositories ({ $error }), showing cached candidates instead
+
+## install
+install-invalid-launch-args = Invalid --launch-args, check the quoting: { $launch_args }
+install-manifest-failed = Failed to load manifest: { $error }
+install-from = Installing { $product } from { $source }
+install-target = Installing { $product }@{ $target }, flavor { $flavor }
+install-no-candidate = Could not construct a Search Candidate from the input parameters. Check that the product/flavor exist
+install-previous-flavor = using previous flavor '{ $flavor }'; pass -f to change
+install-previous-branch = using previous branch '{ $branch }'; pass a build or branch to change
+install-flavors = Installing { $product }@{ $target }, flavors { $flavors }
+install-flavor = Installing { $product }, flavor { $flavor }
+install-flavors-external = Only one --flavor can be installed from --file or --url
+install-flavors-failed = { $count } of { $total } flavors failed to install
+install-canceled = Canceled installation
+install-succeeded = Successfully Installed { $product }
+install-skipped = Skipped installation
+install-failed = Failed to install item: { $error }
+install-remote-succeeded = Installed { $product } { $version } on { $target }
+install-remote-failed = Failed to install { $product } on { $target }: { $error }
+install-container-succeeded = Installed { $product } { $version } in a container of { $image }
+install-container-failed = Failed to install { $product } in a container of { $image }: { $error }
+install-sandbox-flavors = Only one --flavor can be installed in Windows Sandbox
+install-sandbox-started = Started Windows Sandbox installing { $product } from { $path }. Closing it discards the installation
+install-sandbox-failed = Failed to install in Windows Sandbox: { $error }
+install-resume-failed = Failed to resume interrupted installation: { $error }
+install-resolved = Resolved { $target } to { $identifier }@{ $version }
+install-no-nightly = No successful build of { $product } on { $branch } finished within the last { $hours } hours
+install-no-build = No successful build of { $product } found
+install-resolve-failed = Failed to resolve { $target }: { $error }
+install-build-url = Installing build { $version } of { $product }, flavor { $flavor }, from its TeamCity url
+install-build-url-failed = Failed to find the build of the TeamCity url: { $error }
+
+## upgrade
+upgrade-up-to-date = { $product } is up to date
+upgrade-available = Upgrading { $product } from { $installed } to { $available } on { $branch }
+upgrade-succeeded = Upgraded { $product } to { $version }
+upgrade-failed = Failed to upgrade { $product }: { $error }
+upgrade-all-up-to-date = Every installed product is up to date
+upgrade-would = { $product } would be upgraded from { $installed } to { $available } on { $branch }
+upgrade-some-failed = { $count } of { $total } upgrades failed
+
+## diff
+diff-none = No changes between { $product } { $from } and { $to }
+diff-changes = { $count } changes between { $product } { $from } and { $to }:
+diff-failed = Failed to compare builds: { $error }
+
+## inspect
+inspect-artifacts = Artifacts of { $product } { $version } ({ $identifier }), flavor { $flavor }:
+inspect-download-size = Download size: { $size }
+inspect-no-build = No build of { $product } found for { $target }
+inspect-failed = Failed to list artifacts: { $error }
+
+## export, report, prefetch, repo
+export-failed = Failed to export installed products: { $error }
+report-written = Wrote the report to { $path }, attach it to the issue
+report-failed = Failed to write the report: { $error }
+prefetch-failed = Failed to prefetch { $product }: { $error }
+repo-bench-hint = Repositories are searched in the order of the configuration, put the fastest first
+repo-bench-failed = Failed to benchmark repositories: { $error }
+
+## snapshot
+snapshot-saved = Saved snapshot { $name } to { $path }
+snapshot-save-failed = Failed to save snapshot: { $error }
+snapshot-restore-failed = Failed to restore snapshot { $name }: { $error }
+snapshot-restored = Restored snapshot { $name }
+snapshot-none = No snapshots saved
+audit-empty = Nothing has been recorded in the audit log yet
+
+## tag
+tag-added = Tagged { $product } { $version }
+tag-cleared = Removed the notes of { $product } { $version }
+tag-untagged = { $product } { $version } isn't tagged
+tag-none = No builds tagged
+tag-save-failed = Failed to save the tags: { $error }
+
+## open
+open-opened = Opened { $path }
+open-failed = Failed to open the folder: { $error }
+
+## progress, see --progress plain
+progress-percent = { $label }: { $percent }% ({ $position } of { $total })
+progress-bytes = { $label }: { $position }
+progress-done = { $label }: done
+
+## interruptions, the { $not_done } part is one of the not-done messages
+cancel-interrupted = Interrupted, { $not_done }
+cancel-timed-out = Timed out, { $not_done }
+not-done-install = installation was not completed
+not-done-list = candidates were not listed
+not-done-outdated = outdated products were not listed
+not-done-upgrade = upgrade was not completed
+not-done-prefetch = prefetching was not completed
+not-done-snapshot = snapshot was not saved
+
+usage-hint = use -h or --help to show help for this program
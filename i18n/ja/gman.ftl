@@ -0,0 +1,143 @@
+# gman のメッセージ。en/gman.ftl と同じ ID をすべて訳すこと。
+# 訳のないメッセージは英語で表示される
+
+## configuration
+config-nothing-to-migrate = 移行するものはありません
+config-moved = { $from } を { $to } に移動しました
+config-migrate-failed = 移行に失敗しました: { $error }
+config-edit-not-found = 編集する設定ファイルが見つかりません。`config --sample` で作成してください
+config-editing = { $path } を編集しています
+config-saved = { $path } を保存しました
+config-no-changes = 変更はありません
+config-edit-failed = 設定の編集に失敗しました: { $error }
+config-exists = { $path } は既に存在します。`config --edit` で変更するか、--force を指定して置き換えてください
+config-wrote = { $path } を作成しました
+config-setup-failed = gman のセットアップに失敗しました: { $error }
+config-setup-prompt = 設定ファイルが見つかりません。今すぐセットアップしますか? [Y/n]{" "}
+config-load-failed = 設定ファイルの読み込みに失敗しました: { $error }
+config-not-found = 設定ファイルの読み込みに失敗しました: 設定が見つかりません。`config --where` を確認してください
+alias-failed = エイリアスを展開できませんでした: { $error }
+config-sample-exhausted = サンプルファイルを作成できません。試行回数の上限 (200) を超えました。{ $name } という名前のファイルを削除してください
+config-loaded = 読み込んだ設定: { $path }
+config-parse-failed = ただし解析に失敗します: { $error }
+config-read-failed = ただし読み込めません: { $error }
+config-none = 設定が見つかりません
+config-search-order = 検索順:
+config-state-not-checked = 未確認
+config-state-loaded = 読み込み済み
+config-state-not-found = なし
+
+## auth
+auth-login-failed = { $repository } へのサインインに失敗しました: { $error }
+auth-signed-in = { $repository } にサインインしました
+auth-logout-failed = { $repository } からのサインアウトに失敗しました: { $error }
+auth-signed-out = { $repository } からサインアウトしました
+
+## cache
+cache-cleared = キャッシュを削除しました
+cache-clear-failed = キャッシュの削除に失敗しました: { $error }
+cache-directory = キャッシュディレクトリ: { $path }
+cache-count = 件数: { $count }
+cache-empty = キャッシュは空です
+cache-pinned = { $entry } を固定しました
+cache-unpinned = { $entry } の固定を解除しました
+cache-pin-failed = { $artifact } の固定を変更できませんでした: { $error }
+
+## list
+list-outdated-failed = 更新のある製品の一覧を取得できませんでした: { $error }
+list-offline = リポジトリに接続できません ({ $error })。キャッシュ済みの候補を表示します
+
+## install
+install-invalid-launch-args = --launch-args が不正です。引用符を確認してください: { $launch_args }
+install-manifest-failed = マニフェストの読み込みに失敗しました: { $error }
+install-from = { $source } から { $product } をインストールしています
+install-target = { $product }@{ $target } (フレーバー { $flavor }) をインストールしています
+install-no-candidate = 指定されたパラメーターから検索候補を作成できませんでした。製品とフレーバーが存在するか確認してください
+install-previous-flavor = 前回のフレーバー '{ $flavor }' を使用します。変更するには -f を指定してください
+install-previous-branch = 前回のブランチ '{ $branch }' を使用します。変更するにはビルドまたはブランチを指定してください
+install-flavors = { $product }@{ $target } (フレーバー { $flavors }) をインストールしています
+install-flavor = { $product } (フレーバー { $flavor }) をインストールしています
+install-flavors-external = --file または --url からインストールできるフレーバーは 1 つだけです
+install-flavors-failed = { $total } 個中 { $count } 個のフレーバーのインストールに失敗しました
+install-canceled = インストールをキャンセルしました
+install-succeeded = { $product } をインストールしました
+install-skipped = インストールをスキップしました
+install-failed = インストールに失敗しました: { $error }
+install-remote-succeeded = { $target } に { $product } { $version } をインストールしました
+install-remote-failed = { $target } への { $product } のインストールに失敗しました: { $error }
+install-container-succeeded = { $image } のコンテナに { $product } { $version } をインストールしました
+install-container-failed = { $image } のコンテナへの { $product } のインストールに失敗しました: { $error }
+install-sandbox-flavors = Windows サンドボックスにインストールできるフレーバーは 1 つだけです
+install-sandbox-started = { $path } で { $product } をインストールする Windows サンドボックスを起動しました。閉じるとインストールは破棄されます
+install-sandbox-failed = Windows サンドボックスへのインストールに失敗しました: { $error }
+install-resume-failed = 中断されたインストールの再開に失敗しました: { $error }
+install-resolved = { $target } を { $identifier }@{ $version } に解決しました
+install-no-nightly = { $branch } の { $product } で、過去 { $hours } 時間以内に成功したビルドはありません
+install-no-build = { $product } の成功したビルドが見つかりません
+install-resolve-failed = { $target } を解決できませんでした: { $error }
+install-build-url = TeamCity の URL から { $product } のビルド { $version } (フレーバー { $flavor }) をインストールします
+install-build-url-failed = TeamCity の URL のビルドが見つかりませんでした: { $error }
+
+## upgrade
+upgrade-up-to-date = { $product } は最新です
+upgrade-available = { $branch } の { $product } を { $installed } から { $available } にアップグレードしています
+upgrade-succeeded = { $product } を { $version } にアップグレードしました
+upgrade-failed = { $product } のアップグレードに失敗しました: { $error }
+upgrade-all-up-to-date = インストール済みの製品はすべて最新です
+upgrade-would = { $product } は { $branch } の { $installed } から { $available } にアップグレードされます
+upgrade-some-failed = { $total } 件中 { $count } 件のアップグレードに失敗しました
+
+## diff
+diff-none = { $product } { $from } と { $to } の間に変更はありません
+diff-changes = { $product } { $from } と { $to } の間の変更 ({ $count } 件):
+diff-failed = ビルドの比較に失敗しました: { $error }
+
+## inspect
+inspect-artifacts = { $product } { $version } ({ $identifier }) フレーバー { $flavor } の成果物:
+inspect-download-size = ダウンロードサイズ: { $size }
+inspect-no-build = { $target } に { $product } のビルドが見つかりません
+inspect-failed = 成果物の一覧を取得できませんでした: { $error }
+
+## export, report, prefetch, repo
+export-failed = インストール済み製品のエクスポートに失敗しました: { $error }
+report-written = レポートを { $path } に書き出しました。issue に添付してください
+report-failed = レポートの書き出しに失敗しました: { $error }
+prefetch-failed = { $product } の事前ダウンロードに失敗しました: { $error }
+repo-bench-hint = リポジトリは設定の順に検索されます。最も速いものを先頭にしてください
+repo-bench-failed = リポジトリのベンチマークに失敗しました: { $error }
+
+## snapshot
+snapshot-saved = スナップショット { $name } を { $path } に保存しました
+snapshot-save-failed = スナップショットの保存に失敗しました: { $error }
+snapshot-restore-failed = スナップショット { $name } の復元に失敗しました: { $error }
+snapshot-restored = スナップショット { $name } を復元しました
+snapshot-none = 保存されたスナップショットはありません
+audit-empty = 監査ログにはまだ何も記録されていません
+
+## tag
+tag-added = { $product } { $version } にタグを付けました
+tag-cleared = { $product } { $version } のメモを削除しました
+tag-untagged = { $product } { $version } にはタグがありません
+tag-none = タグ付きのビルドはありません
+tag-save-failed = タグの保存に失敗しました: { $error }
+
+## open
+open-opened = { $path } を開きました
+open-failed = フォルダを開けませんでした: { $error }
+
+## progress, see --progress plain
+progress-percent = { $label }: { $percent }% ({ $total } 中 { $position })
+progress-bytes = { $label }: { $position }
+progress-done = { $label }: 完了
+
+## interruptions, the { $not_done } part is one of the not-done messages
+cancel-interrupted = 中断されました。{ $not_done }
+cancel-timed-out = タイムアウトしました。{ $not_done }
+not-done-install = インストールは完了していません
+not-done-list = 候補は一覧表示されていません
+not-done-outdated = 更新のある製品は一覧表示されていません
+not-done-upgrade = アップグレードは完了していません
+not-done-prefetch = 事前ダウンロードは完了していません
+not-done-snapshot = スナップショットは保存されていません
+
+usage-hint = -h または --help でヘルプを表示します
@@ -0,0 +1,181 @@
+use std::{collections::BTreeMap, ffi::OsString, path::PathBuf};
+
+use clap::Command;
+
+use crate::{app, client_config::ClientConfig, config_trust, gman_error::GManError};
+
+/// The value of the option [long] of gman itself in [args], given as `--long value` or `--long=value`
+fn option_value(args: &[OsString], long: &str) -> Option<PathBuf> {
+    let prefix = format!("{}=", long);
+    args.iter().enumerate().find_map(|(i, arg)| {
+        let arg = arg.to_string_lossy();
+        if arg == long {
+            args.get(i + 1).map(PathBuf::from)
+        } else {
+            arg.strip_prefix(&prefix).map(PathBuf::from)
+        }
+    })
+}
+
+/// The arguments in [args] up to `--` that are neither options of [command] nor their values, with the
+/// index they are at
+fn positionals(args: &[OsString], command: &Command) -> Vec<(usize, String)> {
+    let takes_value = |long: &str| {
+        command
+            .get_arguments()
+            .find(|x| x.get_long() == Some(long))
+            .is_some_and(|x| x.get_action().takes_values())
+    };
+
+    let mut positionals = Vec::new();
+    let mut i = 1;
+    while i < args.len() {
+        let arg = args[i].to_string_lossy().to_string();
+        if arg == "--" {
+            break;
+        }
+        if let Some(long) = arg.strip_prefix("--") {
+            if !long.contains('=') && takes_value(long) {
+                i += 1;
+            }
+        } else if !arg.starts_with('-') {
+            positionals.push((i, arg));
+        }
+        i += 1;
+    }
+    positionals
+}
+
+/// The `Aliases` of the configuration the command line would load, see [ClientConfig::aliases].
+/// Read before the arguments are parsed, so nothing is printed or asked: configurations that fail to load or
+/// aren't trusted yet have no aliases, and are reported once they're loaded for real.
+/// Like [Cli::config_path](crate::cli::Cli::config_path), an argument before the command is tried as the
+/// configuration first; an alias in its place just isn't found there
+pub fn load_aliases(args: &[OsString], command: &Command) -> BTreeMap<String, String> {
+    if let Some(dir) = option_value(args, "--config-dir") {
+        app::set_config_directory(dir);
+    }
+    let strict_config = option_value(args, "--config");
+    let config_path = strict_config.clone().or_else(|| {
+        positionals(args, command)
+            .into_iter()
+            .next()
+            .filter(|(_, arg)| command.find_subcommand(arg).is_none())
+            .map(|(_, arg)| PathBuf::from(arg))
+    });
+    let Some((source, path)) =
+        ClientConfig::find_config_file(config_path.as_ref(), strict_config.is_some())
+    else {
+        return BTreeMap::new();
+    };
+    if !config_trust::is_trusted(source, &path) {
+        return BTreeMap::new();
+    }
+    std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|x| ClientConfig::parse(&x).ok())
+        .map(|x| x.aliases)
+        .unwrap_or_default()
+}
+
+/// Replaces the first command in [args] that is one of the [aliases] with the arguments it stands for, e.g.
+/// `gman --log-level debug hk-dev` becomes `gman --log-level debug install hubkit develop`. Commands of
+/// gman itself always win over aliases of the same name
+pub fn expand(
+    args: Vec<OsString>,
+    command: &Command,
+    aliases: &BTreeMap<String, String>,
+) -> Result<Vec<OsString>, GManError> {
+    if aliases.is_empty() {
+        return Ok(args);
+    }
+
+    for (i, arg) in positionals(&args, command) {
+        if command.find_subcommand(&arg).is_some() {
+            break;
+        }
+        if let Some(expansion) = aliases.get(&arg) {
+            let Some(expanded) = shlex::split(expansion) else {
+                return Err(GManError::new(&format!(
+                    "Alias {} is invalid, check the quoting: {}",
+                    arg, expansion
+                )));
+            };
+            log::debug!("Expanding alias {} to {}", arg, expansion);
+            let mut args = args;
+            args.splice(i..=i, expanded.into_iter().map(OsString::from));
+            return Ok(args);
+        }
+    }
+    Ok(args)
+}
+
+/// Adds the [aliases] to [command] as commands of their own, so shell completion offers them
+pub fn with_aliases(command: Command, aliases: &BTreeMap<String, String>) -> Command {
+    let added = aliases
+        .iter()
+        .filter(|(name, _)| command.find_subcommand(name.as_str()).is_none())
+        .collect::<Vec<_>>();
+    added
+        .into_iter()
+        .fold(command, |command, (name, expansion)| {
+            command.subcommand(Command::new(name.to_owned()).about(format!("gman {}", expansion)))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, ffi::OsString};
+
+    use clap::CommandFactory;
+
+    use crate::cli::Cli;
+
+    use super::{expand, with_aliases};
+
+    fn args(line: &str) -> Vec<OsString> {
+        line.split(' ').map(OsString::from).collect()
+    }
+
+    #[test]
+    fn aliases_expand_to_their_command_line() {
+        let aliases = BTreeMap::from([
+            (
+                "hk-dev".to_owned(),
+                "install hubkit develop -f WindowsHubKit --launch-args '--debug --trace'"
+                    .to_owned(),
+            ),
+            ("list".to_owned(), "install hubkit".to_owned()),
+        ]);
+        let command = Cli::command();
+
+        assert_eq!(
+            expand(
+                args("gman --log-level debug hk-dev --prompt false"),
+                &command,
+                &aliases
+            )
+            .unwrap(),
+            args("gman --log-level debug install hubkit develop -f WindowsHubKit --launch-args")
+                .into_iter()
+                .chain([OsString::from("--debug --trace")])
+                .chain(args("--prompt false"))
+                .collect::<Vec<_>>()
+        );
+        /* commands of gman itself aren't shadowed, and neither are their arguments */
+        assert_eq!(
+            expand(args("gman list"), &command, &aliases).unwrap(),
+            args("gman list")
+        );
+        assert_eq!(
+            expand(args("gman install hk-dev"), &command, &aliases).unwrap(),
+            args("gman install hk-dev")
+        );
+
+        let bad = BTreeMap::from([("bad".to_owned(), "install 'hubkit".to_owned())]);
+        assert!(expand(args("gman bad"), &command, &bad).is_err());
+
+        let completing = with_aliases(Cli::command(), &aliases);
+        assert!(completing.find_subcommand("hk-dev").is_some());
+    }
+}
@@ -1,18 +1,181 @@
-use std::path::PathBuf;
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        OnceLock,
+    },
+};
 
+use lazy_static::lazy_static;
 #[allow(unused_imports)]
 use log::Log;
+use tokio::sync::watch;
+
+use crate::{gman_error::GManErrorKind, platform::Architecture};
 
 pub const APP_FOLDER_NAME: &'static str = "gman_5a8f853f-d7e7-4a83-aa21-6ed0585b0c40";
 
 pub const CLIENT_CONFIG_FILE_NAME: &'static str = "./gman_config_client.json5";
 
+/// Folder in the user's configuration directory (`~/.config`, `%APPDATA%`) the configuration is kept in
+pub const CONFIG_FOLDER_NAME: &str = "gman";
+
+/// Process exit code used when the user interrupts gman with Ctrl-C
+pub const EXIT_CODE_INTERRUPTED: i32 = 130;
+
+/// Process exit code used when the `--timeout` deadline passes, the same as coreutils `timeout`
+pub const EXIT_CODE_TIMED_OUT: i32 = 124;
+
+/// Process exit code used when the configuration's policy forbids an install, e.g. one below `MinimumVersions`
+pub const EXIT_CODE_POLICY_VIOLATION: i32 = 3;
+
 static INITD: std::sync::Mutex<bool> = std::sync::Mutex::new(false);
 
+/// Number of operations currently running that know how to clean up after themselves when interrupted
+static CANCELLABLE_OPERATIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether repository responses that fail to parse are dumped, see `--debug-parse`
+static DEBUG_PARSE: AtomicBool = AtomicBool::new(false);
+
+/// Whether TeamCity personal builds are listed and installed, see `--include-personal`
+static INCLUDE_PERSONAL_BUILDS: AtomicBool = AtomicBool::new(false);
+
+/// Whether a requested build whose artifacts expired is replaced by the nearest newer one, see `--nearest`
+static NEAREST_BUILD: AtomicBool = AtomicBool::new(false);
+
+/// Whether builds blocked by a product's `BlockedVersions` or `BlockedBranches` are installed, see `--allow-blocked`
+static ALLOW_BLOCKED_BUILDS: AtomicBool = AtomicBool::new(false);
+
+/// Whether downloads are moved into the cache without checking their SHA-256 checksum, see `--skip-checksum`
+static SKIP_CHECKSUMS: AtomicBool = AtomicBool::new(false);
+
+/// Whether commands needing root fail instead of going through sudo, see `--no-sudo`
+static NO_SUDO: AtomicBool = AtomicBool::new(false);
+
+/// Whether AppX/MSIX packages are provisioned machine-wide instead of installed for the current user, see `--provision`
+static PROVISION_PACKAGES: AtomicBool = AtomicBool::new(false);
+
+/// Whether installs start without checking their prerequisites first, see `--skip-prerequisites`
+static SKIP_PREREQUISITES: AtomicBool = AtomicBool::new(false);
+
+/// Whether PowerShell is run with `-ExecutionPolicy Bypass`, see `--execution-policy-bypass`
+static BYPASS_EXECUTION_POLICY: AtomicBool = AtomicBool::new(false);
+
+/// User configuration directory handed in with `--config-dir`
+static CONFIG_DIRECTORY: OnceLock<PathBuf> = OnceLock::new();
+
+/// Directory of the state shared by the users of the machine, see [get_machine_state_directory]
+static MACHINE_STATE_DIRECTORY: OnceLock<PathBuf> = OnceLock::new();
+
+/// Architecture of the artifacts to install or download, handed in with `--arch`
+static ARCHITECTURE: OnceLock<Architecture> = OnceLock::new();
+
+lazy_static! {
+    /// Why the in-progress operations are being cancelled, if they are
+    static ref INTERRUPT: (
+        watch::Sender<Option<GManErrorKind>>,
+        watch::Receiver<Option<GManErrorKind>>
+    ) = watch::channel(None);
+}
+
 pub fn get_app_temp_directory() -> PathBuf {
     std::env::temp_dir().join(APP_FOLDER_NAME)
 }
 
+/// Directory named by the environment variable [name], if it's set to an absolute path.
+/// The XDG base directory spec has relative paths ignored
+pub fn env_directory(name: &str) -> Option<PathBuf> {
+    std::env::var_os(name)
+        .map(PathBuf::from)
+        .filter(|x| x.is_absolute())
+}
+
+pub fn home_directory(relative: &str) -> PathBuf {
+    PathBuf::from(shellexpand::tilde(&format!("~/{}", relative)).into_owned())
+}
+
+/// Uses [dir] as the user configuration directory instead of the platform's, see `--config-dir`
+pub fn set_config_directory(dir: PathBuf) {
+    let _ = CONFIG_DIRECTORY.set(dir);
+}
+
+/// Directory of the user's configuration: the `--config-dir` if given, otherwise [CONFIG_FOLDER_NAME]
+/// in `%APPDATA%` on Windows, and in `$XDG_CONFIG_HOME` (`~/.config`) elsewhere
+pub fn get_config_directory() -> PathBuf {
+    if let Some(dir) = CONFIG_DIRECTORY.get() {
+        return dir.to_owned();
+    }
+    let base = if cfg!(windows) {
+        env_directory("APPDATA").unwrap_or_else(|| home_directory("AppData/Roaming"))
+    } else {
+        env_directory("XDG_CONFIG_HOME").unwrap_or_else(|| home_directory(".config"))
+    };
+    base.join(CONFIG_FOLDER_NAME)
+}
+
+/// Default cache directory: [APP_FOLDER_NAME] in `%LOCALAPPDATA%` on Windows, and in `$XDG_CACHE_HOME`
+/// (`~/.cache`) elsewhere
+pub fn get_cache_directory() -> PathBuf {
+    let base = if cfg!(windows) {
+        env_directory("LOCALAPPDATA").unwrap_or_else(|| home_directory("AppData/Local"))
+    } else {
+        env_directory("XDG_CACHE_HOME").unwrap_or_else(|| home_directory(".cache"))
+    };
+    base.join(APP_FOLDER_NAME)
+}
+
+/// Directory of state gman keeps between runs, e.g. what was last installed: [CONFIG_FOLDER_NAME] in
+/// `%LOCALAPPDATA%` on Windows, and in `$XDG_STATE_HOME` (`~/.local/state`) elsewhere
+pub fn get_state_directory() -> PathBuf {
+    let base = if cfg!(windows) {
+        env_directory("LOCALAPPDATA").unwrap_or_else(|| home_directory("AppData/Local"))
+    } else {
+        env_directory("XDG_STATE_HOME").unwrap_or_else(|| home_directory(".local/state"))
+    };
+    base.join(CONFIG_FOLDER_NAME)
+}
+
+/// Directory of the state about the machine rather than a user, the install journal and the audit log, so
+/// they tell about the installs of everyone using it: [CONFIG_FOLDER_NAME] in `%ProgramData%` on Windows,
+/// `/Users/Shared` on macOS and `/var/lib` elsewhere. Whoever creates it lets every other user add to it.
+/// Users who can't write there, e.g. on Linux before gman ran as root once, keep it in their
+/// [get_state_directory]. Unlike the cache, `--cache-dir` doesn't move it
+pub fn get_machine_state_directory() -> PathBuf {
+    MACHINE_STATE_DIRECTORY
+        .get_or_init(|| {
+            let base = if cfg!(windows) {
+                env_directory("ProgramData").unwrap_or_else(|| PathBuf::from(r"C:\ProgramData"))
+            } else if cfg!(target_os = "macos") {
+                PathBuf::from("/Users/Shared")
+            } else {
+                PathBuf::from("/var/lib")
+            };
+            let machine = base.join(CONFIG_FOLDER_NAME);
+            let journal = machine.join(crate::journal::JOURNAL_DIRECTORY_NAME);
+            /* the first user to run gman would own it otherwise, and the others couldn't add their installs */
+            let shared = crate::util::create_shared_directory(&machine)
+                .and_then(|_| crate::util::create_shared_directory(&journal));
+            if let Err(e) = shared {
+                log::debug!("Failed to create {}: {}", journal.display(), e);
+            }
+            if crate::util::is_writable_directory(&journal) {
+                machine
+            } else {
+                log::debug!(
+                    "Can't write to {}, keeping the journal in the user's state directory",
+                    machine.display()
+                );
+                get_state_directory()
+            }
+        })
+        .to_owned()
+}
+
+/// Where the cache was kept on every platform before [get_cache_directory], for `config --migrate`
+pub fn get_legacy_cache_directory() -> PathBuf {
+    home_directory(".cache").join(APP_FOLDER_NAME)
+}
+
 /// Disables global logging, and returns the last level used
 pub fn disable_logging() -> log::LevelFilter {
     let last_level = log::max_level();
@@ -34,3 +197,156 @@ pub fn init_logging(max_level: Option<log::LevelFilter>) {
         *r = true;
     }
 }
+
+pub fn enable_debug_parse() {
+    DEBUG_PARSE.store(true, Ordering::SeqCst);
+}
+
+pub fn is_debug_parse() -> bool {
+    DEBUG_PARSE.load(Ordering::SeqCst)
+}
+
+pub fn include_personal_builds() {
+    INCLUDE_PERSONAL_BUILDS.store(true, Ordering::SeqCst);
+}
+
+pub fn is_including_personal_builds() -> bool {
+    INCLUDE_PERSONAL_BUILDS.load(Ordering::SeqCst)
+}
+
+pub fn use_nearest_build() {
+    NEAREST_BUILD.store(true, Ordering::SeqCst);
+}
+
+pub fn is_using_nearest_build() -> bool {
+    NEAREST_BUILD.load(Ordering::SeqCst)
+}
+
+pub fn allow_blocked_builds() {
+    ALLOW_BLOCKED_BUILDS.store(true, Ordering::SeqCst);
+}
+
+pub fn is_allowing_blocked_builds() -> bool {
+    ALLOW_BLOCKED_BUILDS.load(Ordering::SeqCst)
+}
+
+pub fn skip_checksums() {
+    SKIP_CHECKSUMS.store(true, Ordering::SeqCst);
+}
+
+pub fn is_skipping_checksums() -> bool {
+    SKIP_CHECKSUMS.load(Ordering::SeqCst)
+}
+
+pub fn disable_sudo() {
+    NO_SUDO.store(true, Ordering::SeqCst);
+}
+
+pub fn is_sudo_disabled() -> bool {
+    NO_SUDO.load(Ordering::SeqCst)
+}
+
+pub fn provision_packages() {
+    PROVISION_PACKAGES.store(true, Ordering::SeqCst);
+}
+
+pub fn is_provisioning_packages() -> bool {
+    PROVISION_PACKAGES.load(Ordering::SeqCst)
+}
+
+/// Picks the artifacts built for [architecture] of flavors publishing several, see `--arch`
+pub fn set_architecture(architecture: Architecture) {
+    let _ = ARCHITECTURE.set(architecture);
+}
+
+/// The architecture given with `--arch`, if any
+pub fn architecture() -> Option<Architecture> {
+    ARCHITECTURE.get().copied()
+}
+
+pub fn skip_prerequisites() {
+    SKIP_PREREQUISITES.store(true, Ordering::SeqCst);
+}
+
+pub fn is_skipping_prerequisites() -> bool {
+    SKIP_PREREQUISITES.load(Ordering::SeqCst)
+}
+
+pub fn bypass_execution_policy() {
+    BYPASS_EXECUTION_POLICY.store(true, Ordering::SeqCst);
+}
+
+#[cfg(target_os = "windows")]
+pub fn is_bypassing_execution_policy() -> bool {
+    BYPASS_EXECUTION_POLICY.load(Ordering::SeqCst)
+}
+
+/// Listens for Ctrl-C for the rest of the process lifetime.
+///
+/// If a [CancellableOperation] is in progress, it is signalled to cancel and clean up, and is expected
+/// to return an `Interrupted` error. Otherwise the process exits immediately with [EXIT_CODE_INTERRUPTED]
+pub fn install_interrupt_handler() {
+    tokio::spawn(async {
+        loop {
+            if tokio::signal::ctrl_c().await.is_err() {
+                log::warn!("Failed to listen for Ctrl-C, interruptions won't be cleaned up");
+                return;
+            }
+            if CANCELLABLE_OPERATIONS.load(Ordering::SeqCst) > 0 {
+                log::debug!("Received Ctrl-C, cancelling in-progress operations");
+                let _ = INTERRUPT.0.send(Some(GManErrorKind::Interrupted));
+            } else {
+                eprintln!("Interrupted");
+                std::process::exit(EXIT_CODE_INTERRUPTED);
+            }
+        }
+    });
+}
+
+/// Ends the process once [timeout] has passed, like an interruption.
+///
+/// A [CancellableOperation] in progress is signalled to cancel and clean up, and is expected to return
+/// a `TimedOut` error. Otherwise the process exits immediately with [EXIT_CODE_TIMED_OUT]
+pub fn install_deadline(timeout: std::time::Duration) {
+    tokio::spawn(async move {
+        tokio::time::sleep(timeout).await;
+        if CANCELLABLE_OPERATIONS.load(Ordering::SeqCst) > 0 {
+            log::debug!(
+                "Timeout of {:?} passed, cancelling in-progress operations",
+                timeout
+            );
+            let _ = INTERRUPT.0.send(Some(GManErrorKind::TimedOut));
+        } else {
+            eprintln!("Timed out after {}s", timeout.as_secs());
+            std::process::exit(EXIT_CODE_TIMED_OUT);
+        }
+    });
+}
+
+/// Resolves once the process is interrupted with Ctrl-C or its `--timeout` passes, with
+/// `Interrupted` or `TimedOut` respectively
+pub async fn wait_for_interrupt() -> GManErrorKind {
+    let mut rx = INTERRUPT.1.clone();
+    let reason = rx.wait_for(|reason| reason.is_some()).await.map(|x| *x);
+    match reason {
+        Ok(Some(kind)) => kind,
+        /* the sender lives in a static, so this never happens */
+        _ => std::future::pending().await,
+    }
+}
+
+/// Marks an operation that handles Ctrl-C itself (via [wait_for_interrupt]) for as long as it's alive
+pub struct CancellableOperation;
+
+impl CancellableOperation {
+    pub fn begin() -> Self {
+        CANCELLABLE_OPERATIONS.fetch_add(1, Ordering::SeqCst);
+        CancellableOperation
+    }
+}
+
+impl Drop for CancellableOperation {
+    fn drop(&mut self) {
+        CANCELLABLE_OPERATIONS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,162 @@
+use std::time::{Duration, Instant};
+
+use futures_util::StreamExt;
+use reqwest::{header::RANGE, Url};
+use tabled::settings::{object::Rows, Alignment, Modify, Style};
+
+use crate::{
+    client_config::CandidateRepository, download, gman_error::GManError, team_city,
+    RepositoryCredentials,
+};
+
+/// Round trips averaged for the latency of a repository
+const LATENCY_SAMPLES: u32 = 3;
+
+/// Measured performance of a repository, see `repo bench`
+#[derive(Debug)]
+pub struct RepositoryBench {
+    pub repository: String,
+    /// Average time until the repository answers a trivial request
+    pub latency: Option<Duration>,
+    /// Bytes downloaded of the sample artifact and how long it took
+    pub download: Option<(u64, Duration)>,
+    /// Product and version of the artifact downloaded
+    pub sample: Option<String>,
+    /// Why a measurement couldn't be taken
+    pub error: Option<String>,
+}
+
+impl RepositoryBench {
+    pub fn new(repository: &str) -> Self {
+        Self {
+            repository: repository.to_owned(),
+            latency: None,
+            download: None,
+            sample: None,
+            error: None,
+        }
+    }
+
+    /// Download speed in bytes per second
+    pub fn throughput(&self) -> Option<u64> {
+        self.download
+            .filter(|(_, elapsed)| !elapsed.is_zero())
+            .map(|(bytes, elapsed)| (bytes as f64 / elapsed.as_secs_f64()) as u64)
+    }
+}
+
+/// Average time [repo] takes to answer its server version request, which does no real work on the server
+pub async fn measure_latency(
+    http_client: &reqwest::Client,
+    repo: &CandidateRepository,
+) -> Result<Duration, Box<dyn std::error::Error>> {
+    let repo_url = repo.repository_server.as_ref().ok_or_else(|| {
+        GManError::new(&format!(
+            "Repository {} has no server to measure",
+            repo.name
+        ))
+    })?;
+    let mut url = team_city::ensure_scheme(repo_url)?;
+    url.set_path("app/rest/server/version");
+
+    let mut total = Duration::ZERO;
+    for _ in 0..LATENCY_SAMPLES {
+        let started = Instant::now();
+        let response = download::authorize(
+            http_client.get(url.clone()),
+            repo.repository_credentials.as_ref(),
+        )
+        .send()
+        .await?;
+        total += started.elapsed();
+        if !response.status().is_success() {
+            return Err(Box::new(GManError::new(&format!(
+                "Repository {} responded with status code {}",
+                repo.name,
+                response.status()
+            ))));
+        }
+    }
+    Ok(total / LATENCY_SAMPLES)
+}
+
+/// Downloads the first [sample_bytes] of [url], asking for just that range, and returns how many bytes
+/// arrived in how long. Servers that ignore the range are cut off once enough has arrived
+pub async fn measure_download(
+    http_client: &reqwest::Client,
+    url: &Url,
+    credentials: Option<&RepositoryCredentials>,
+    sample_bytes: u64,
+) -> Result<(u64, Duration), Box<dyn std::error::Error>> {
+    let started = Instant::now();
+    let response = download::authorize(
+        http_client
+            .get(url.clone())
+            .header(RANGE, format!("bytes=0-{}", sample_bytes.saturating_sub(1))),
+        credentials,
+    )
+    .send()
+    .await?;
+    if !response.status().is_success() {
+        return Err(Box::new(GManError::new(&format!(
+            "Download responded with status code {}",
+            response.status()
+        ))));
+    }
+
+    let mut downloaded: u64 = 0;
+    let mut byte_stream = response.bytes_stream();
+    while let Some(item) = byte_stream.next().await {
+        downloaded += item?.len() as u64;
+        if downloaded >= sample_bytes {
+            break;
+        }
+    }
+    Ok((downloaded, started.elapsed()))
+}
+
+/// Prints the [results] fastest first, so the configured repository order can be compared against them
+pub fn print_bench(results: &mut [RepositoryBench]) {
+    results.sort_by_key(|x| std::cmp::Reverse(x.throughput()));
+
+    let mut builder = tabled::builder::Builder::default();
+    builder.push_record(["Repository", "Latency", "Throughput", "Sample", "Error"]);
+    for result in results.iter() {
+        builder.push_record([
+            result.repository.to_owned(),
+            result
+                .latency
+                .map(|x| format!("{} ms", x.as_millis()))
+                .unwrap_or_default(),
+            result
+                .throughput()
+                .map(|x| format!("{}/s", indicatif::HumanBytes(x)))
+                .unwrap_or_default(),
+            result.sample.to_owned().unwrap_or_default(),
+            result.error.to_owned().unwrap_or_default(),
+        ]);
+    }
+
+    let mut table = builder.build();
+    table
+        .with(Style::sharp())
+        .with(Modify::new(Rows::first()).with(Alignment::center()));
+    println!("{table}");
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::RepositoryBench;
+
+    #[test]
+    fn throughput_from_download() {
+        let mut bench = RepositoryBench::new("TeamCity");
+        assert_eq!(bench.throughput(), None);
+        bench.download = Some((8 * 1024 * 1024, Duration::from_secs(2)));
+        assert_eq!(bench.throughput(), Some(4 * 1024 * 1024));
+        bench.download = Some((1024, Duration::ZERO));
+        assert_eq!(bench.throughput(), None);
+    }
+}
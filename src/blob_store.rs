@@ -0,0 +1,220 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::download;
+
+/// Directory inside the cache directory holding the artifact contents, named by their SHA-256
+pub const BLOB_DIRECTORY_NAME: &str = "blobs";
+
+/// File in [BLOB_DIRECTORY_NAME] recording which blob each cache entry points at, and which entries are pinned
+const INDEX_FILE_NAME: &str = "index.json";
+
+/// Which blob (by SHA-256) each cache entry (by file name) shares its contents with
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+struct BlobIndex {
+    #[serde(rename = "Entries", default)]
+    entries: BTreeMap<String, String>,
+    /// Cache entries (by file name) that are kept when the cache is cleared, e.g. a build a customer issue
+    /// reproduces with
+    #[serde(rename = "Pinned", default, skip_serializing_if = "BTreeSet::is_empty")]
+    pinned: BTreeSet<String>,
+}
+
+impl BlobIndex {
+    fn path(cache_directory: &Path) -> PathBuf {
+        blob_directory(cache_directory).join(INDEX_FILE_NAME)
+    }
+
+    fn load(cache_directory: &Path) -> Self {
+        std::fs::read_to_string(Self::path(cache_directory))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, cache_directory: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(blob_directory(cache_directory))?;
+        std::fs::write(
+            Self::path(cache_directory),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        Ok(())
+    }
+}
+
+pub fn blob_directory(cache_directory: &Path) -> PathBuf {
+    cache_directory.join(BLOB_DIRECTORY_NAME)
+}
+
+/// Replaces the cache entry at [entry_path] with a hard link to the blob of its contents, so the same
+/// artifact cached under several names (e.g. reached by branch and by version) is only stored once.
+///
+/// The entry keeps its name and stays a regular file for everything reading the cache. On file systems
+/// without hard links the entry is left as it is. Returns the SHA-256 of the contents
+pub fn deduplicate(
+    cache_directory: &Path,
+    entry_path: &Path,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let sha256 = download::sha256_file(entry_path)?;
+    let blob = blob_directory(cache_directory).join(&sha256);
+    std::fs::create_dir_all(blob_directory(cache_directory))?;
+
+    if blob.exists() {
+        if !is_same_file(&blob, entry_path) {
+            /* link next to the entry first, so the entry is never missing if linking fails */
+            let mut linked = entry_path.as_os_str().to_owned();
+            linked.push(".link");
+            let linked = PathBuf::from(linked);
+            let _ = std::fs::remove_file(&linked);
+            match std::fs::hard_link(&blob, &linked) {
+                Ok(_) => {
+                    std::fs::rename(&linked, entry_path)?;
+                    log::debug!(
+                        "{} has the same contents as blob {}, linked it",
+                        entry_path.display(),
+                        sha256
+                    );
+                }
+                Err(e) => log::debug!("Can't hard link cache entries, keeping a copy: {}", e),
+            }
+        }
+    } else if let Err(e) = std::fs::hard_link(entry_path, &blob) {
+        log::debug!("Can't hard link cache entries, keeping a copy: {}", e);
+        return Ok(sha256);
+    }
+
+    if let Some(name) = entry_path.file_name() {
+        let mut index = BlobIndex::load(cache_directory);
+        index
+            .entries
+            .insert(name.to_string_lossy().to_string(), sha256.to_owned());
+        index.save(cache_directory)?;
+    }
+    Ok(sha256)
+}
+
+/// Pins the cache entry [entry_name] so clearing the cache keeps it, or unpins it again
+pub fn set_pinned(
+    cache_directory: &Path,
+    entry_name: &str,
+    pinned: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut index = BlobIndex::load(cache_directory);
+    if pinned {
+        index.pinned.insert(entry_name.to_owned());
+    } else {
+        index.pinned.remove(entry_name);
+    }
+    index.save(cache_directory)
+}
+
+/// File names of the pinned cache entries
+pub fn pinned(cache_directory: &Path) -> BTreeSet<String> {
+    BlobIndex::load(cache_directory).pinned
+}
+
+/// Forgets entries that were removed from the cache, and deletes the blobs no entry points at anymore.
+/// Returns how many blobs were deleted
+pub fn prune(cache_directory: &Path) -> Result<usize, Box<dyn std::error::Error>> {
+    let directory = blob_directory(cache_directory);
+    if !directory.exists() {
+        return Ok(0);
+    }
+
+    let mut index = BlobIndex::load(cache_directory);
+    index
+        .entries
+        .retain(|name, _| cache_directory.join(name).exists());
+    index
+        .pinned
+        .retain(|name| cache_directory.join(name).exists());
+
+    let mut removed = 0;
+    for entry in std::fs::read_dir(&directory)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name == INDEX_FILE_NAME || index.entries.values().any(|x| *x == name) {
+            continue;
+        }
+        log::debug!("Removing unused blob {}", name);
+        std::fs::remove_file(entry.path())?;
+        removed += 1;
+    }
+    index.save(cache_directory)?;
+    Ok(removed)
+}
+
+/// Whether [a] and [b] are links to the same file
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) if a == b => return true,
+        _ => {}
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if let (Ok(a), Ok(b)) = (std::fs::metadata(a), std::fs::metadata(b)) {
+            return a.dev() == b.dev() && a.ino() == b.ino();
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{blob_directory, deduplicate, pinned, prune, set_pinned};
+
+    #[test]
+    fn identical_entries_share_a_blob() {
+        let cache = std::env::temp_dir().join("gman_tests").join("blob_store");
+        let _ = std::fs::remove_dir_all(&cache);
+        std::fs::create_dir_all(&cache).unwrap();
+
+        let by_branch =
+            cache.join("hubkit@windows@WindowsHubKit@develop@5.2.1-7049@GravioHubKit.msi");
+        let by_version =
+            cache.join("hubkit@windows@WindowsHubKit@5.2.1-7049@5.2.1-7049@GravioHubKit.msi");
+        std::fs::write(&by_branch, "installer").unwrap();
+        std::fs::write(&by_version, "installer").unwrap();
+
+        let sha256 = deduplicate(&cache, &by_branch).unwrap();
+        assert_eq!(deduplicate(&cache, &by_version).unwrap(), sha256);
+        assert!(blob_directory(&cache).join(&sha256).exists());
+        assert_eq!(std::fs::read_to_string(&by_version).unwrap(), "installer");
+
+        /* the blob stays while an entry still points at it */
+        std::fs::remove_file(&by_branch).unwrap();
+        assert_eq!(prune(&cache).unwrap(), 0);
+        std::fs::remove_file(&by_version).unwrap();
+        assert_eq!(prune(&cache).unwrap(), 1);
+        assert!(!blob_directory(&cache).join(&sha256).exists());
+    }
+
+    #[test]
+    fn pins_are_kept_until_unpinned_or_removed() {
+        let cache = std::env::temp_dir()
+            .join("gman_tests")
+            .join("blob_store_pins");
+        let _ = std::fs::remove_dir_all(&cache);
+        std::fs::create_dir_all(&cache).unwrap();
+
+        let entry = "hubkit@windows@WindowsHubKit@develop@5.2.1-7049@GravioHubKit.msi";
+        std::fs::write(cache.join(entry), "installer").unwrap();
+        set_pinned(&cache, entry, true).unwrap();
+        deduplicate(&cache, &cache.join(entry)).unwrap();
+        prune(&cache).unwrap();
+        assert!(pinned(&cache).contains(entry));
+
+        set_pinned(&cache, entry, false).unwrap();
+        assert!(pinned(&cache).is_empty());
+
+        set_pinned(&cache, entry, true).unwrap();
+        std::fs::remove_file(cache.join(entry)).unwrap();
+        prune(&cache).unwrap();
+        assert!(pinned(&cache).is_empty());
+    }
+}
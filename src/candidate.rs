@@ -5,17 +5,19 @@ use std::{
     fmt::Display,
     ops::Deref,
     path::{Path, PathBuf},
-    process::Command,
     str::FromStr,
 };
 
 use tabled::Tabled;
 
+#[cfg(target_os = "windows")]
+use crate::powershell::PowerShellScript;
 use crate::{
     app,
     gman_error::GManError,
-    platform::Platform,
+    platform::{Architecture, Platform},
     product::{Flavor, PackageType, Product},
+    system_runner::SystemRunner,
 };
 use lazy_static::lazy_static;
 
@@ -30,19 +32,57 @@ pub struct TablePrinter {
     #[tabled(order = 3)]
     pub flavor: String,
     #[tabled(order = 4)]
-    pub installed: bool,
+    pub size: String,
     #[tabled(order = 5)]
+    pub installed: bool,
+    #[tabled(order = 6)]
     pub path: String,
 }
 
+impl TablePrinter {
+    /// Order of rows in a table: by product, newest version first, then by branch and flavor, so listings come
+    /// out the same on every run whatever order the repositories answered in. Every column takes part, so no
+    /// two different rows are ever equal
+    pub fn cmp_rows(&self, other: &Self) -> std::cmp::Ordering {
+        /* newest first, numerically where the versions are numbers, and those before the ones that aren't */
+        let version_numbers = |x: &str| -> Option<Vec<u64>> {
+            x.split(['.', '-'])
+                .map(|part| part.parse::<u64>().ok())
+                .collect()
+        };
+        self.name
+            .to_lowercase()
+            .cmp(&other.name.to_lowercase())
+            .then_with(|| version_numbers(&other.version).cmp(&version_numbers(&self.version)))
+            .then_with(|| other.version.cmp(&self.version))
+            .then_with(|| {
+                self.identifier
+                    .to_lowercase()
+                    .cmp(&other.identifier.to_lowercase())
+            })
+            .then_with(|| self.flavor.to_lowercase().cmp(&other.flavor.to_lowercase()))
+            .then_with(|| self.name.cmp(&other.name))
+            .then_with(|| self.identifier.cmp(&other.identifier))
+            .then_with(|| self.flavor.cmp(&other.flavor))
+            .then_with(|| self.installed.cmp(&other.installed))
+            .then_with(|| self.path.cmp(&other.path))
+            .then_with(|| self.size.cmp(&other.size))
+    }
+}
+
 impl Into<TablePrinter> for InstallationCandidate {
     fn into(self) -> TablePrinter {
         TablePrinter {
             path: self.make_cached_file_name(),
-            identifier: self.identifier,
+            identifier: if self.expired {
+                format!("{} (expired)", self.identifier)
+            } else {
+                self.identifier
+            },
             name: self.product_name,
             version: self.version.into(),
             flavor: self.flavor.id,
+            size: String::default(),
             installed: self.installed,
         }
     }
@@ -56,6 +96,7 @@ impl From<InstalledProduct> for TablePrinter {
             name: value.product_name,
             version: value.version.0,
             flavor: String::default(),
+            size: String::default(),
             installed: true,
         }
     }
@@ -98,25 +139,55 @@ impl SearchCandidate {
                     .iter()
                     .find(|x| x.id.to_lowercase() == flavor_lower)
             }
+            /* ARM64 machines prefer a flavor with a build of their own over one running emulated */
             None => product
                 .flavors
                 .iter()
-                .find(|x| x.platform == current_platform),
+                .filter(|x| x.platform == current_platform)
+                .find(|x| {
+                    Architecture::of_machine() == Some(Architecture::Arm64)
+                        && x.has_build_for(Architecture::Arm64)
+                })
+                .or_else(|| {
+                    product
+                        .flavors
+                        .iter()
+                        .find(|x| x.platform == current_platform)
+                }),
         };
 
         if flavor_str.is_none() {
             eprintln!("No flavor found, not even default");
             return None;
         }
+        let flavor = match flavor_str.unwrap().for_machine() {
+            Ok(x) => x,
+            Err(e) => {
+                eprintln!("{}", e);
+                return None;
+            }
+        };
 
         Some(SearchCandidate {
             product_name: product_name.to_owned(),
             version: version.map(|x| Version::new(x)),
             identifier: identifier.map(|x| x.to_owned()),
-            flavor: flavor_str.unwrap().to_owned(),
+            flavor,
         })
     }
 
+    /// Key of the builds this search is after, leaving out the version and branch if they weren't given
+    pub fn key(&self) -> CandidateKey {
+        let mut key = CandidateKey::new(&self.product_name).flavor(&self.flavor.id);
+        if let Some(version) = &self.version {
+            key = key.version(version);
+        }
+        if let Some(identifier) = &self.identifier {
+            key = key.identifier(identifier);
+        }
+        key
+    }
+
     pub fn version_or_identifier_string(&self) -> &str {
         if let Some(v) = &self.version {
             &v
@@ -136,6 +207,73 @@ impl Version {
         Self(version_str.to_owned())
     }
 
+    /// Finds a version number embedded in a file name, e.g. `hubkit_5.2.1-8219_all.deb` -> `5.2.1-8219`
+    pub fn from_file_name(file_name: &str) -> Option<Version> {
+        FILE_NAME_VERSION_REGEX
+            .find(file_name)
+            .map(|m| Version::new(m.as_str()))
+    }
+
+    /// The version's numbers without leading zeros, padded with zeros to four and joined by dots, e.g. `5.2.1-7049`
+    /// is `5.2.1.7049` and `5.02` is `5.2.0.0`, so keys match whichever separators the version was written with.
+    /// Versions that aren't numbers are lowercased
+    fn normalized(&self) -> String {
+        match VERSION_REGEX.captures(&self.0) {
+            Some(caps) => {
+                let mut parts = caps
+                    .iter()
+                    .skip(1)
+                    .filter_map(|m| m.map(|m| m.as_str().trim_start_matches('0')))
+                    .map(|x| if x.is_empty() { "0" } else { x })
+                    .collect::<Vec<&str>>();
+                while parts.len() < 4 {
+                    parts.push("0");
+                }
+                parts.join(".")
+            }
+            None => self.0.to_lowercase(),
+        }
+    }
+
+    /// Whether this and [other] name the same build, whichever separators they were written with, e.g.
+    /// `5.2.1-7049` and `5.2.1.7049`
+    pub fn same_build(&self, other: &Version) -> bool {
+        self.normalized() == other.normalized()
+    }
+
+    /// Whether this is a version of numbers like `5.2.1-7049`, which versions can be compared with
+    pub fn is_numbered(&self) -> bool {
+        VERSION_REGEX.is_match(&self.0)
+    }
+
+    /// This version written with the separators of [build_number], so a version an installed product reports,
+    /// e.g. `5.2.1.7049`, can be looked up as the build number it came from, like `5.2.1-7049`. Unchanged if
+    /// the two don't have the same count of numbers
+    pub fn numbered_like(&self, build_number: &Version) -> Version {
+        let numbers = match VERSION_REGEX.captures(&self.0) {
+            Some(caps) => caps
+                .iter()
+                .skip(1)
+                .filter_map(|m| m.map(|m| m.as_str()))
+                .collect::<Vec<&str>>(),
+            None => return self.clone(),
+        };
+        let separators = build_number
+            .0
+            .chars()
+            .filter(|x| !x.is_ascii_digit())
+            .collect::<Vec<char>>();
+        if !VERSION_REGEX.is_match(&build_number.0) || separators.len() + 1 != numbers.len() {
+            return self.clone();
+        }
+        let mut numbered = numbers[0].to_owned();
+        for (separator, number) in separators.iter().zip(&numbers[1..]) {
+            numbered.push(*separator);
+            numbered.push_str(number);
+        }
+        Version::new(&numbered)
+    }
+
     pub fn make_version_4_parts(&self) -> Version {
         let mut s = self.0.to_owned();
         let mut count = s.split('.').count();
@@ -161,6 +299,8 @@ lazy_static! {
     static ref VERSION_REGEX: Regex =
         Regex::new(r#"^(\d{1,})(?:[.-](\d{1,}))?(?:[.-](\d{1,}))?(?:[.-](\d{1,}))?$"#)
             .expect("Failed to create Version 1 regex");
+    static ref FILE_NAME_VERSION_REGEX: Regex = Regex::new(r#"\d{1,}(?:[.-]\d{1,}){1,3}"#)
+        .expect("Failed to create file name version regex");
 }
 
 impl PartialOrd for Version {
@@ -223,6 +363,67 @@ impl Into<String> for Version {
     }
 }
 
+/// Identity of a build: its product, flavor, branch and version, normalized the way gman looks them up.
+/// Product names, flavor ids and branches ignore case, and versions are compared by their numbers, so
+/// `5.2.1-7049` is `5.2.1.7049`. Equal keys hash the same, so they can key a map
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CandidateKey {
+    product_name: String,
+    flavor: Option<String>,
+    identifier: Option<String>,
+    version: Option<String>,
+}
+
+impl CandidateKey {
+    pub fn new(product_name: &str) -> Self {
+        Self {
+            product_name: product_name.to_lowercase(),
+            flavor: None,
+            identifier: None,
+            version: None,
+        }
+    }
+
+    pub fn flavor(mut self, flavor_id: &str) -> Self {
+        self.flavor = Some(flavor_id.to_lowercase());
+        self
+    }
+
+    pub fn identifier(mut self, identifier: &str) -> Self {
+        self.identifier = Some(identifier.to_lowercase());
+        self
+    }
+
+    pub fn version(mut self, version: &Version) -> Self {
+        self.version = Some(version.normalized());
+        self
+    }
+
+    /// The key of the product and flavor only, matching any build of them
+    pub fn without_build(&self) -> Self {
+        Self {
+            identifier: None,
+            version: None,
+            ..self.clone()
+        }
+    }
+
+    /// Whether [other] is the same product and the parts both keys have are the same. A part either of them
+    /// leaves out, like the flavor of a product found installed by its name, matches anything
+    pub fn matches(&self, other: &CandidateKey) -> bool {
+        fn part_matches(a: &Option<String>, b: &Option<String>) -> bool {
+            match (a, b) {
+                (Some(a), Some(b)) => a == b,
+                _ => true,
+            }
+        }
+        self.product_name == other.product_name
+            && part_matches(&self.flavor, &other.flavor)
+            && part_matches(&self.identifier, &other.identifier)
+            && part_matches(&self.version, &other.version)
+    }
+}
+
 #[derive(Debug)]
 pub enum InstallationResult {
     Canceled,
@@ -249,7 +450,7 @@ impl FromStr for InstallOverwriteOptions {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct InstallationCandidate {
     pub remote_id: String,
 
@@ -264,14 +465,26 @@ pub struct InstallationCandidate {
     pub flavor: Flavor,
 
     pub installed: bool,
+
+    /// Whether TeamCity cleaned up the build's artifacts, so it can no longer be downloaded
+    pub expired: bool,
 }
 
 #[cfg(target_os = "macos")]
-const MAC_APPLICATIONS_DIR: &'static str = "/Applications";
+pub(crate) const MAC_APPLICATIONS_DIR: &'static str = "/Applications";
 
 impl InstallationCandidate {
+    pub fn key(&self) -> CandidateKey {
+        CandidateKey::new(&self.product_name)
+            .flavor(&self.flavor.id)
+            .identifier(&self.identifier)
+            .version(&self.version)
+    }
+
+    /// Whether [installed_product] is this candidate's product, in any version. It has to be this flavor
+    /// too when it's known which flavor is installed
     pub fn product_equals(&self, installed_product: &InstalledProduct) -> bool {
-        &installed_product.product_name == &self.product_name
+        self.key().without_build().matches(&installed_product.key())
     }
 
     /// Returns the file name of the file this InstallationCandidate represents
@@ -291,16 +504,22 @@ impl InstallationCandidate {
     ///
     /// format is "product_name@platform@flavor_name@identifier@version@binary_name"
     /// e.g., "graviostudio@windows@sideloading@develop@5.2.1-7033@GravioStudio.msi"
+    ///
+    /// Binaries picked for an architecture with `--arch` have it before the binary name,
+    /// e.g., "graviostudio@windows@sideloading@develop@5.2.1-7033@arm64@GravioStudio.msi"
     pub fn make_cached_file_name(&self) -> String {
-        format!(
-            "{}@{}@{}@{}@{}@{}",
-            &self.product_name,
-            &self.flavor.platform,
-            &self.flavor.id,
-            &self.identifier,
-            &self.version,
-            &self.get_binary_file_name()
-        )
+        let mut parts = vec![
+            self.product_name.to_owned(),
+            self.flavor.platform.to_string(),
+            self.flavor.id.to_owned(),
+            self.identifier.to_owned(),
+            self.version.to_string(),
+        ];
+        if let Some(architecture) = &self.flavor.architecture {
+            parts.push(architecture.to_string());
+        }
+        parts.push(self.get_binary_file_name());
+        parts.join("@")
     }
 
     /// Gets the path of the file that the InstallationCandidate downloads to on disk
@@ -313,38 +532,127 @@ impl InstallationCandidate {
         dir.as_ref().join(fname)
     }
 
+    /// Installs this item from [binary_path], see [InstallationCandidate::install_in]. What the install
+    /// unpacks goes to gman's folder of the system temp directory
     pub fn install<P>(
         &self,
+        runner: &dyn SystemRunner,
+        binary_path: P,
+        options: InstallOverwriteOptions,
+    ) -> Result<InstallationResult, Box<dyn std::error::Error>>
+    where
+        P: AsRef<Path>,
+    {
+        self.install_in(runner, binary_path, options, &app::get_app_temp_directory())
+    }
+
+    /// Installs this item from [binary_path]. Archives the install unpacks first, like Mac `.zip` artifacts,
+    /// are extracted into [work_directory], e.g. the configured temp download directory
+    pub fn install_in<P>(
+        &self,
+        runner: &dyn SystemRunner,
         binary_path: P,
         options: InstallOverwriteOptions,
+        work_directory: &Path,
     ) -> Result<InstallationResult, Box<dyn std::error::Error>>
     where
         P: AsRef<Path>,
     {
+        #[cfg(not(target_os = "macos"))]
+        let _ = work_directory;
         let installation_result: InstallationResult;
         #[cfg(target_os = "windows")]
         {
-            installation_result = self.install_windows(binary_path, options)?;
+            installation_result = self.install_windows(runner, binary_path, options)?;
         }
 
         #[cfg(target_os = "macos")]
         {
-            installation_result = install_mac(binary_path, options)?;
+            installation_result = install_mac(
+                runner,
+                binary_path,
+                options,
+                self.flavor.metadata.as_ref(),
+                work_directory,
+            )?;
         }
 
         #[cfg(target_os = "linux")]
-        {}
+        {
+            let _ = options;
+            installation_result = self.install_linux(runner, binary_path)?;
+        }
 
         Ok(installation_result)
     }
 
+    /// Installs standalone programs and archives to the user's data directory, with a desktop entry and a
+    /// link in `~/.local/bin`, and Debian packages with apt. Other package types aren't installed on Linux
+    #[cfg(target_os = "linux")]
+    fn install_linux<P>(
+        &self,
+        runner: &dyn SystemRunner,
+        binary_path: P,
+    ) -> Result<InstallationResult, Box<dyn std::error::Error>>
+    where
+        P: AsRef<Path>,
+    {
+        if self.flavor.package_type == PackageType::Deb {
+            /* apt only takes a path to a local package if it isn't relative, joining keeps absolute ones */
+            let package = std::env::current_dir()?.join(binary_path);
+            return install_deb(
+                runner,
+                package.to_string_lossy().as_ref(),
+                self.flavor.metadata.as_ref(),
+            );
+        }
+        if self.flavor.package_type != PackageType::StandaloneExe {
+            log::warn!(
+                "{:?} packages can't be installed on Linux",
+                self.flavor.package_type
+            );
+            return Ok(InstallationResult::Skipped);
+        }
+        let metadata = self.flavor.metadata.as_ref();
+        crate::desktop::install(
+            runner,
+            &self.product_name,
+            &self.version,
+            binary_path.as_ref(),
+            metadata.and_then(|x| x.executable.as_deref()),
+            metadata
+                .and_then(|x| x.launch_args.as_deref())
+                .unwrap_or_default(),
+        )?;
+        Ok(InstallationResult::Succeeded)
+    }
+
     /// Uses `open` to launch this item on mac system
     #[cfg(target_os = "macos")]
-    fn start_program_mac(&self) -> Result<(), Box<dyn std::error::Error>> {
+    fn start_program_mac(
+        &self,
+        runner: &dyn SystemRunner,
+    ) -> Result<(), Box<dyn std::error::Error>> {
         log::info!("Attempting to automatically launch application");
         if let Some(metadata) = &self.flavor.metadata {
             if let Some(bundle_name) = &metadata.cf_bundle_name {
-                let output = Command::new("open").arg("-a").arg(bundle_name).output()?;
+                let mut args: Vec<String> = vec!["-a".into(), bundle_name.to_owned()];
+                if let Some(env) = &metadata.launch_environment {
+                    for (key, value) in env {
+                        args.push("--env".into());
+                        args.push(format!("{}={}", key, value));
+                    }
+                }
+                if let Some(launch_args) = &metadata.launch_args {
+                    if !launch_args.is_empty() {
+                        args.push("--args".into());
+                        args.extend(launch_args.iter().cloned());
+                    }
+                }
+                let output = runner.run(
+                    "open",
+                    &args.iter().map(|x| x.as_str()).collect::<Vec<&str>>(),
+                )?;
 
                 if output.status.success() {
                     return Ok(());
@@ -358,55 +666,235 @@ impl InstallationCandidate {
         Ok(())
     }
 
-    /// Launches this item on the system
-    pub fn start_program(&self) -> Result<(), Box<dyn std::error::Error>> {
+    /// Launches this item on the system.
+    ///
+    /// On Windows, [package_full_name] is the AppX/MsiX package this item was installed as, if known.
+    /// Its manifest gives the exact AppUserModelID to launch, instead of searching the Start Menu by `NameRegex`
+    pub fn start_program(
+        &self,
+        runner: &dyn SystemRunner,
+        package_full_name: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        #[cfg(target_os = "windows")]
+        {
+            self.start_program_windows(runner, package_full_name)
+        }
+
+        #[cfg(target_os = "macos")]
+        {
+            let _ = package_full_name;
+            self.start_program_mac(runner)
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            let _ = package_full_name;
+            self.start_program_linux(runner)
+        }
+    }
+
+    /// Launches the program of this item detached from gman with `setsid`: the one linked into `~/.local/bin`
+    /// for standalone programs, and the flavor's `Executable` command for Deb packages
+    #[cfg(target_os = "linux")]
+    fn start_program_linux(
+        &self,
+        runner: &dyn SystemRunner,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        log::info!("Attempting to automatically launch application");
+        let metadata = self.flavor.metadata.as_ref();
+        let program = match self.flavor.package_type {
+            PackageType::StandaloneExe => crate::desktop::bin_link(&self.product_name)
+                .to_string_lossy()
+                .to_string(),
+            PackageType::Deb => match metadata.and_then(|x| x.executable.as_ref()) {
+                Some(executable) => executable.to_owned(),
+                None => {
+                    log::info!(
+                        "Flavor {} has no Executable to launch, not launching it",
+                        &self.flavor.id
+                    );
+                    return Ok(());
+                }
+            },
+            _ => return Ok(()),
+        };
+
+        let mut args: Vec<String> = vec!["-f".into()];
+        if let Some(env) = metadata.and_then(|x| x.launch_environment.as_ref()) {
+            if !env.is_empty() {
+                args.push("env".into());
+                args.extend(env.iter().map(|(key, value)| format!("{}={}", key, value)));
+            }
+        }
+        args.push(program.to_owned());
+        if let Some(launch_args) = metadata.and_then(|x| x.launch_args.as_ref()) {
+            args.extend(launch_args.iter().cloned());
+        }
+        let output = runner.run(
+            "setsid",
+            &args.iter().map(|x| x.as_str()).collect::<Vec<&str>>(),
+        )?;
+        if output.status.success() {
+            return Ok(());
+        }
+        Err(Box::new(GManError::new(&format!(
+            "Failed to launch {}: {}",
+            program, output.status
+        ))))
+    }
+
+    /// Whether the application launched by [start_program] is currently running.
+    /// Returns `None` if the flavor's metadata doesn't give enough information to tell
+    pub fn is_running(
+        &self,
+        runner: &dyn SystemRunner,
+    ) -> Result<Option<bool>, Box<dyn std::error::Error>> {
         #[cfg(target_os = "windows")]
         {
-            self.start_program_windows()
+            self.is_running_windows(runner)
         }
 
         #[cfg(target_os = "macos")]
         {
-            self.start_program_mac()
+            self.is_running_mac(runner)
         }
+
+        #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+        {
+            let _ = runner;
+            Ok(None)
+        }
+    }
+
+    /// Checks for a process started from the AppX package's install location, or from the `InstallPath` of standalone executables
+    #[cfg(target_os = "windows")]
+    fn is_running_windows(
+        &self,
+        runner: &dyn SystemRunner,
+    ) -> Result<Option<bool>, Box<dyn std::error::Error>> {
+        let metadata = match &self.flavor.metadata {
+            Some(m) => m,
+            None => return Ok(None),
+        };
+        let script = match (
+            &self.flavor.package_type,
+            &metadata.name_regex,
+            &metadata.install_path,
+        ) {
+            (PackageType::AppX | PackageType::MsiX, Some(name_regex), _) => PowerShellScript::new(
+                r#"$pkg = Get-AppxPackage | Where-Object {$_.PackageFamilyName.StartsWith($NameRegex)} | Select-Object -First 1
+                if (-not $pkg) { exit 1 }
+                $running = Get-Process | Where-Object {$_.Path -and $_.Path.StartsWith($pkg.InstallLocation)}
+                if ($running) { exit 0 } else { exit 1 }"#,
+            )
+            .arg("NameRegex", name_regex),
+            (PackageType::StandaloneExe, _, Some(install_path)) => PowerShellScript::new(
+                r#"$running = Get-Process | Where-Object {$_.Path -eq $InstallPath}
+                if ($running) { exit 0 } else { exit 1 }"#,
+            )
+            .arg("InstallPath", install_path),
+            _ => return Ok(None),
+        };
+        let output = script.run(runner)?;
+        match output.status.code() {
+            Some(0) => Ok(Some(true)),
+            Some(1) => Ok(Some(false)),
+            _ => Err(Box::new(GManError::new(&format!(
+                "Failed to check whether {} is running: {}",
+                self.product_name, output.status
+            )))),
+        }
+    }
+
+    /// Checks launchctl for the flavor's `CFBundleIdentifier`, or falls back to the `CFBundleName` executable path
+    #[cfg(target_os = "macos")]
+    fn is_running_mac(
+        &self,
+        runner: &dyn SystemRunner,
+    ) -> Result<Option<bool>, Box<dyn std::error::Error>> {
+        let metadata = match &self.flavor.metadata {
+            Some(m) => m,
+            None => return Ok(None),
+        };
+        if let Some(bundle_id) = &metadata.cf_bundle_id {
+            let running = get_running_app_pids_mac(runner)?;
+            return Ok(Some(running.iter().any(|x| x.contains(bundle_id.as_str()))));
+        }
+        if let Some(bundle_name) = &metadata.cf_bundle_name {
+            /* pgrep exits with 1 when nothing matched */
+            let pattern = format!("{}.app/Contents/MacOS/", bundle_name);
+            let output = runner.run("pgrep", &["-f", &pattern])?;
+            return match output.status.code() {
+                Some(0) => Ok(Some(true)),
+                Some(1) => Ok(Some(false)),
+                _ => Err(Box::new(GManError::new(&format!(
+                    "Failed to check whether {} is running: {}",
+                    bundle_name, output.status
+                )))),
+            };
+        }
+        Ok(None)
     }
 
     #[cfg(target_os = "windows")]
-    fn start_program_windows(&self) -> Result<(), Box<dyn std::error::Error>> {
+    fn start_program_windows(
+        &self,
+        runner: &dyn SystemRunner,
+        package_full_name: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
         log::info!("Attempting to automatically launch application");
+        let (environment, launch_args) = match &self.flavor.metadata {
+            Some(metadata) => (
+                powershell_environment(metadata.launch_environment.as_ref()),
+                metadata.launch_args.clone().unwrap_or_default(),
+            ),
+            None => (PowerShellScript::default(), Vec::new()),
+        };
+        /* Start-Process fails on an empty -ArgumentList */
+        let launch = if launch_args.is_empty() {
+            ""
+        } else {
+            " -ArgumentList $LaunchArgs"
+        };
         match self.flavor.package_type {
             PackageType::AppX | PackageType::MsiX => {
                 if let Some(metadata) = &self.flavor.metadata {
                     if let Some(name_regex) = &metadata.name_regex {
-                        let command = {
-                            let parts = [
-                                r#"Function Get-App-Name {
-                                    $x=Get-StartApps | Where-Object {$_.AppId.StartsWith('"#,
-                                &name_regex,
-                                r#"')} | Select-Object -First 1 | Select -ExpandProperty AppId
-                                    return $x
-                                }
-                                    
-                                Function start_app {
-                                        param([string]$fname)
-                                        explorer.exe "shell:AppsFolder\$fname"
-                                }
-                                    
-                                start_app (Get-App-Name)"#,
-                            ];
-
-                            String::from_iter(parts)
+                        if metadata
+                            .launch_environment
+                            .as_ref()
+                            .is_some_and(|x| !x.is_empty())
+                        {
+                            log::warn!("LaunchEnvironment isn't applied to {}: Windows starts packaged apps itself, they don't inherit the environment of gman", &self.product_name);
+                        }
+                        /* explorer can't pass arguments along, Start-Process can */
+                        let start = if launch.is_empty() {
+                            r#"explorer.exe "shell:AppsFolder\$fname""#.to_owned()
+                        } else {
+                            format!(r#"Start-Process "shell:AppsFolder\$fname"{}"#, launch)
                         };
-
-                        let output = Command::new("powershell")
-                            .arg("-Command")
-                            .arg(command)
-                            .output()?;
+                        let script = powershell_resolve_app_id(name_regex, package_full_name)
+                            .append(PowerShellScript::new(&format!(
+                                "\nif (-not $fname) {{ exit 2 }}\n{}",
+                                start
+                            )))
+                            .arg_list("LaunchArgs", &launch_args);
+
+                        let output = script.run(runner)?;
+                        for line in output.stdout.lines().filter(|x| x.starts_with("AppId ")) {
+                            log::trace!("Considered {}", line);
+                        }
 
                         if output.status.success() {
                             log::debug!("Successfully started application");
                             return Ok(());
                         }
+                        if output.status.code() == Some(2) {
+                            return Err(Box::new(GManError::new(&format!(
+                                "Failed to autorun application: no installed application has an AppId starting with {}, check the NameRegex",
+                                name_regex
+                            ))));
+                        }
                         return Err(Box::new(GManError::new(&format!(
                             "Failed to autorun application: Command returned an error: {}",
                             output.status
@@ -417,7 +905,31 @@ impl InstallationCandidate {
                 return Err(Box::new(GManError::new("Can't autorun application: NameRegex must be supplied for AppX and MsiX package types, but one was not found")));
             }
             PackageType::Msi => {}
-            PackageType::StandaloneExe => {}
+            PackageType::StandaloneExe => {
+                if let Some(install_path) = self
+                    .flavor
+                    .metadata
+                    .as_ref()
+                    .and_then(|x| x.install_path.as_ref())
+                {
+                    let script = environment
+                        .append(PowerShellScript::new(&format!(
+                            "Start-Process -FilePath $InstallPath{}",
+                            launch
+                        )))
+                        .arg("InstallPath", install_path)
+                        .arg_list("LaunchArgs", &launch_args);
+                    let output = script.run(runner)?;
+                    if output.status.success() {
+                        log::debug!("Successfully started application");
+                        return Ok(());
+                    }
+                    return Err(Box::new(GManError::new(&format!(
+                        "Failed to autorun application: Command returned an error: {}",
+                        output.status
+                    ))));
+                }
+            }
             _ => {}
         }
 
@@ -427,6 +939,7 @@ impl InstallationCandidate {
     #[cfg(target_os = "windows")]
     fn install_windows<P>(
         &self,
+        runner: &dyn SystemRunner,
         binary_path: P,
         _options: InstallOverwriteOptions,
     ) -> Result<InstallationResult, Box<dyn std::error::Error>>
@@ -440,17 +953,14 @@ impl InstallationCandidate {
             let tmp_folder = app::get_app_temp_directory().join(self.make_cached_file_name());
             std::fs::create_dir_all(&tmp_folder)?;
 
-            let unzip_command = format!(
-                "Expand-Archive \"{}\" \"{}\" -force",
-                &binary_path.as_ref().to_str().unwrap(),
-                &tmp_folder.to_str().unwrap()
-            );
             /* extract zip to temporary directory */
             log::debug!("Sending extract-archive request to powershell");
-            let unzip_output = Command::new("powershell")
-                .arg("-Command")
-                .arg(unzip_command)
-                .output()?;
+            let unzip_output = PowerShellScript::new(
+                "Expand-Archive -LiteralPath $Archive -DestinationPath $Destination -Force",
+            )
+            .arg("Archive", &binary_path.as_ref().to_string_lossy())
+            .arg("Destination", &tmp_folder.to_string_lossy())
+            .run(runner)?;
 
             if !unzip_output.status.success() {
                 // Convert the output bytes to a string
@@ -464,55 +974,75 @@ impl InstallationCandidate {
                 ))));
             }
 
-            /* run the  Install.ps1 */
-            match std::fs::read_dir(tmp_folder) {
-                Ok(list_dir) => {
-                    for entry_result in list_dir {
-                        if let Ok(entry) = entry_result {
-                            if entry.metadata().unwrap().is_dir() {
-                                let install_script_loc = entry.path().join("Install.ps1");
-                                if Path::exists(&install_script_loc) {
-                                    log::debug!("found {} install.ps1 file", self.product_name);
-                                    let install_output = Command::new("powershell")
-                                        .arg("-Command")
-                                        .arg(install_script_loc.to_str().unwrap())
-                                        .output()?;
-
-                                    if !install_output.status.success() {
-                                        log::debug!(
-                                            "Failed to install {}: {}",
-                                            self.product_name,
-                                            install_output.status.code().unwrap()
-                                        );
-                                        return Err(Box::new(GManError::new(
-                                                     &format!("Failed to install {}, couldn't run install script successfully", self.product_name),
-                                                 )));
-                                    }
-                                    return Ok(InstallationResult::Succeeded);
-                                }
-                                break;
-                            }
-                        }
-                    }
-                }
-                Err(_) => {
-                    log::error!("Failed to read temporary extracted directory");
-                    return Err(Box::new(GManError::new(
-                        "Failed to read temporary extracted directory",
-                    )));
+            /* run the Install.ps1 of the one folder meant for this machine, never several of them */
+            let folders = install_script_folders(&tmp_folder).map_err(|e| {
+                log::error!("Failed to read temporary extracted directory: {}", e);
+                GManError::new("Failed to read temporary extracted directory")
+            })?;
+            if folders.is_empty() {
+                return Err(Box::new(GManError::new(&format!(
+                    "Failed to install {}, the archive has no Install.ps1",
+                    self.product_name
+                ))));
+            }
+            let architecture = app::architecture()
+                .map(|x| x.to_string())
+                .unwrap_or_else(appx_architecture);
+            let folder = select_install_script_folder(&folders, &architecture)?;
+            log::debug!(
+                "Installing {} with {}",
+                self.product_name,
+                folder.join(INSTALL_SCRIPT_FILE_NAME).display()
+            );
+            if app::is_provisioning_packages() {
+                /* Install.ps1 only installs for the current user, provision what it would install */
+                let package = find_appx_package(folder)?;
+                provision_appx(runner, &package, Some(&folder.join("Dependencies")))?;
+                return Ok(InstallationResult::Succeeded);
+            }
+
+            let log_directory = crate::support::installer_log_directory(self);
+            std::fs::create_dir_all(&log_directory)?;
+            let transcript = log_directory.join(INSTALL_SCRIPT_TRANSCRIPT_FILE_NAME);
+            let _ = std::fs::remove_file(&transcript);
+            /* a script that fails by writing an error rather than exiting with a code still fails the install */
+            let install_output = PowerShellScript::new(
+                "Start-Transcript -LiteralPath $Transcript -Force | Out-Null\ntry { & $Script; $succeeded = $?; $code = $LASTEXITCODE } finally { Stop-Transcript | Out-Null }\nif (-not $succeeded -and -not $code) { $code = 1 }\nexit $code",
+            )
+            .arg("Transcript", &transcript.to_string_lossy())
+            .arg(
+                "Script",
+                &folder.join(INSTALL_SCRIPT_FILE_NAME).to_string_lossy(),
+            )
+            .run(runner)?;
+
+            if !install_output.status.success() {
+                log::debug!(
+                    "Failed to install {}: {}",
+                    self.product_name,
+                    install_output.status
+                );
+                let mut message = format!(
+                    "Failed to install {}, couldn't run install script successfully ({})",
+                    self.product_name, install_output.status
+                );
+                let output = install_script_output(&transcript, &install_output.stderr);
+                if !output.is_empty() {
+                    message.push_str(&format!(":\n{}", output));
                 }
+                return Err(Box::new(GManError::new(&message)));
             }
+            return Ok(InstallationResult::Succeeded);
         }
         /* Try misx */
         else if self.flavor.package_type == PackageType::MsiX {
-            let install_command = format!(
-                "Add-AppxPackage \"{}\"",
-                binary_path.as_ref().to_str().unwrap()
-            );
-            let install_output = Command::new("powershell")
-                .arg("-Command")
-                .arg(install_command)
-                .output()?;
+            if app::is_provisioning_packages() {
+                provision_appx(runner, binary_path.as_ref(), None)?;
+                return Ok(InstallationResult::Succeeded);
+            }
+            let install_output = PowerShellScript::new("Add-AppxPackage -Path $Package")
+                .arg("Package", &binary_path.as_ref().to_string_lossy())
+                .run(runner)?;
 
             if !install_output.status.success() {
                 // Convert the output bytes to a string
@@ -528,9 +1058,17 @@ impl InstallationCandidate {
             }
             return Ok(InstallationResult::Succeeded);
         } else if self.flavor.package_type == PackageType::Msi {
-            let output = Command::new("msiexec")
-                .args(["/i", binary_path.as_ref().to_str().unwrap(), "/passive"])
-                .output()?;
+            let log_directory = crate::support::installer_log_directory(self);
+            std::fs::create_dir_all(&log_directory)?;
+            let args = msiexec_args(
+                binary_path.as_ref().to_str().unwrap(),
+                self.flavor.metadata.as_ref(),
+                &log_directory.join(MSIEXEC_LOG_FILE_NAME),
+            )?;
+            let output = runner.run(
+                "msiexec",
+                &args.iter().map(|x| x.as_str()).collect::<Vec<&str>>(),
+            )?;
 
             // Check if the command was successful
             if output.status.success() {
@@ -552,85 +1090,417 @@ impl InstallationCandidate {
     }
 }
 
-/// Mounts an image given by [binary_path] via `hdiutil`
-#[cfg(target_os = "macos")]
-fn mount_volume_mac<P>(binary_path: P) -> Result<Option<PathBuf>, Box<dyn std::error::Error>>
-where
-    P: AsRef<Path>,
-{
-    let output = Command::new("hdiutil")
-        .arg("attach")
-        .arg(binary_path.as_ref().to_str().unwrap())
-        .output()?;
-
-    // Check if the command was successful
-    if output.status.success() {
-        log::debug!("Successfully mounted dmg file");
-        // Convert the output bytes to a string
-        let result = String::from_utf8_lossy(&output.stdout);
-        let lines = result.split('\n');
+/// Verbose log msiexec writes to the installer log directory, bundled if the install fails
+#[cfg(target_os = "windows")]
+const MSIEXEC_LOG_FILE_NAME: &str = "msiexec.log";
 
-        let mut mount_point: Option<PathBuf> = None;
-        for line in lines {
-            let trimmed = line.trim();
-            let caps_volume: Vec<&str> = match MOUNTED_VOLUME_REGEX.captures(trimmed) {
-                Some(c) => c,
-                None => {
-                    continue;
-                }
+/// Arguments for msiexec to install [package] with the transforms and public properties given in [metadata],
+/// writing its verbose log to [log]. Transforms have to be local files by now, the ones from the build are
+/// downloaded before installing
+#[cfg(target_os = "windows")]
+fn msiexec_args(
+    package: &str,
+    metadata: Option<&crate::product::FlavorMetadata>,
+    log: &Path,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut args = vec![
+        "/i".to_owned(),
+        package.to_owned(),
+        "/passive".to_owned(),
+        "/l*v".to_owned(),
+        log.to_string_lossy().to_string(),
+    ];
+    if let Some(transforms) = metadata.and_then(|x| x.msi_transforms.as_ref()) {
+        let mut paths: Vec<String> = Vec::new();
+        for transform in transforms {
+            let path = PathBuf::from(crate::client_config::ClientConfig::shell_expand(transform));
+            if !path.is_file() {
+                return Err(Box::new(GManError::new(&format!(
+                    "MSI transform {} not found",
+                    path.display()
+                ))));
             }
-            .iter()
-            .skip(1)
-            .filter_map(|m| m.map(|m| m.as_str()))
-            .collect();
-            let mp = caps_volume.first().unwrap().to_string();
-            let pb = PathBuf::from_str(&mp).unwrap();
-            mount_point = Some(pb);
-            break;
+            paths.push(path.to_string_lossy().to_string());
+        }
+        if !paths.is_empty() {
+            args.push(format!("TRANSFORMS={}", paths.join(";")));
+        }
+    }
+    if let Some(properties) = metadata.and_then(|x| x.msi_properties.as_ref()) {
+        for (key, value) in properties {
+            args.push(format!(
+                "{}={}",
+                key.to_uppercase(),
+                crate::client_config::ClientConfig::shell_expand(value)
+            ));
         }
-        Ok(mount_point)
-    } else {
-        Err(Box::new(GManError::new(
-            "Unknown error occurred while making temporary folder",
-        )))
     }
+    Ok(args)
 }
 
-/// Given a mounted volume at [volume], finds the first .app or .pkg file and returns it, if any
-#[cfg(target_os = "macos")]
-fn find_mounted_application(
-    volume: &Path,
-) -> Result<Option<MountedMacPackage>, Box<dyn std::error::Error>> {
-    let vol_str = volume.to_string_lossy();
-    log::info!("Got mount point for application: {}", vol_str);
-    log::info!("Checking if mounted contents are .app or .pkg");
+/// Script an AppX zip installs its package with, in a folder named like the package
+#[cfg(target_os = "windows")]
+const INSTALL_SCRIPT_FILE_NAME: &str = "Install.ps1";
 
-    let package_type: Option<MountedMacPackage> = {
-        let output = Command::new("ls").arg(&volume).output()?;
-        if output.status.success() {
-            log::debug!("ls'd mounted volume");
-            let result = String::from_utf8_lossy(&output.stdout);
-            let lines = result.split('\n').collect::<Vec<&str>>();
-            let found_app = lines.iter().find(|x| x.ends_with(".app"));
-            match found_app {
-                Some(app_path) => {
-                    let full_path = volume.join(app_path);
+/// Transcript of Install.ps1 in the installer log directory, shown and bundled if it fails
+#[cfg(target_os = "windows")]
+const INSTALL_SCRIPT_TRANSCRIPT_FILE_NAME: &str = "Install.ps1.log";
 
-                    Some(MountedMacPackage {
-                        is_app: true,
-                        is_pkg: false,
-                        path: full_path,
-                    })
-                }
-                None => {
-                    let found_pkg = lines.iter().find(|x| x.ends_with(".pkg"));
-                    match found_pkg {
-                        Some(app_path) => {
-                            let full_path = volume.join(app_path);
-                            Some(MountedMacPackage {
-                                is_app: false,
-                                is_pkg: true,
-                                path: full_path,
+/// How many of the last lines of the Install.ps1 transcript a failure shows
+#[cfg(target_os = "windows")]
+const INSTALL_SCRIPT_OUTPUT_LINES: usize = 20;
+
+/// Folders of the extracted AppX zip in [directory] that have an Install.ps1, by name
+#[cfg(target_os = "windows")]
+fn install_script_folders(directory: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut folders = std::fs::read_dir(directory)?
+        .flatten()
+        .map(|x| x.path())
+        .filter(|x| x.join(INSTALL_SCRIPT_FILE_NAME).is_file())
+        .collect::<Vec<PathBuf>>();
+    folders.sort();
+    Ok(folders)
+}
+
+/// Architecture of this machine the way AppX packages name it: x64, arm64 or x86. An x64 gman emulated on ARM64
+/// asks IsWow64Process2, see [Architecture::of_machine], and a 32 bit gman on 64 bit Windows finds the machine's in
+/// PROCESSOR_ARCHITEW6432. `--arch` overrides it
+#[cfg(target_os = "windows")]
+fn appx_architecture() -> String {
+    if let Some(machine) = Architecture::of_machine() {
+        return machine.to_string();
+    }
+    let processor = std::env::var("PROCESSOR_ARCHITEW6432")
+        .or_else(|_| std::env::var("PROCESSOR_ARCHITECTURE"))
+        .unwrap_or_default();
+    match processor.to_uppercase().as_str() {
+        "AMD64" => "x64",
+        "ARM64" => "arm64",
+        "X86" => "x86",
+        _ => match std::env::consts::ARCH {
+            "aarch64" => "arm64",
+            "x86" => "x86",
+            _ => "x64",
+        },
+    }
+    .to_owned()
+}
+
+/// The one of [folders], those of an AppX zip with an Install.ps1, to install on a machine of [architecture].
+/// Zips built for several architectures have a folder for each, named like its package, e.g.
+/// `GravioStudio_5.2.4683.0_arm64_Test`. A folder for neutral packages is taken if none is for the architecture
+#[cfg(target_os = "windows")]
+fn select_install_script_folder<'a>(
+    folders: &'a [PathBuf],
+    architecture: &str,
+) -> Result<&'a PathBuf, Box<dyn std::error::Error>> {
+    if let [single] = folders {
+        return Ok(single);
+    }
+    let names = |folders: &[&PathBuf]| {
+        folders
+            .iter()
+            .map(|x| {
+                x.file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    };
+    let for_architecture = |architecture: &str| {
+        folders
+            .iter()
+            .filter(|x| {
+                x.file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_lowercase()
+                    .split('_')
+                    .any(|part| part == architecture)
+            })
+            .collect::<Vec<&PathBuf>>()
+    };
+    let mut matching = for_architecture(&architecture.to_lowercase());
+    if matching.is_empty() {
+        matching = for_architecture("neutral");
+    }
+    match matching.as_slice() {
+        [single] => Ok(single),
+        [] => Err(Box::new(GManError::new(&format!(
+            "None of the install scripts in the archive is for {} machines: {}",
+            architecture,
+            names(&folders.iter().collect::<Vec<&PathBuf>>())
+        )))),
+        several => Err(Box::new(GManError::new(&format!(
+            "Several install scripts in the archive are for {} machines, so which to run isn't clear: {}",
+            architecture,
+            names(several)
+        )))),
+    }
+}
+
+/// The last lines Install.ps1 printed, from its [transcript], or from [stderr] if it didn't get to write one
+#[cfg(target_os = "windows")]
+fn install_script_output(transcript: &Path, stderr: &str) -> String {
+    let text = std::fs::read_to_string(transcript).unwrap_or_else(|_| stderr.to_owned());
+    let lines = text
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .map(|x| x.trim_end())
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<&str>>();
+    lines[lines.len().saturating_sub(INSTALL_SCRIPT_OUTPUT_LINES)..].join("\n")
+}
+
+/// Extensions of the packages an AppX zip has next to its Install.ps1
+#[cfg(target_os = "windows")]
+const APPX_PACKAGE_EXTENSIONS: [&str; 4] = ["appx", "appxbundle", "msix", "msixbundle"];
+
+/// The package in the extracted AppX [directory], the one Install.ps1 would install
+#[cfg(target_os = "windows")]
+fn find_appx_package(directory: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    for entry in std::fs::read_dir(directory)?.flatten() {
+        let path = entry.path();
+        let is_package = path
+            .extension()
+            .map(|x| x.to_string_lossy().to_lowercase())
+            .map(|x| APPX_PACKAGE_EXTENSIONS.contains(&x.as_str()))
+            .unwrap_or(false);
+        if is_package && path.is_file() {
+            return Ok(path);
+        }
+    }
+    Err(Box::new(GManError::new(&format!(
+        "No package to provision found in {}",
+        directory.display()
+    ))))
+}
+
+/// PowerShell script provisioning [package] machine-wide with the packages in [dependencies] it needs, those
+/// of this machine's architecture and the neutral ones
+#[cfg(target_os = "windows")]
+fn provision_appx_script(package: &Path, dependencies: Option<&Path>) -> PowerShellScript {
+    let mut script = PowerShellScript::new(
+        "$ErrorActionPreference = 'Stop'\n$arguments = @{ Online = $true; PackagePath = $Package; SkipLicense = $true }\n",
+    )
+    .arg("Package", &package.to_string_lossy());
+    if let Some(dependencies) = dependencies {
+        script = script.append(
+            PowerShellScript::new(
+                "$architecture = $env:PROCESSOR_ARCHITECTURE.Replace('AMD64', 'x64').ToLower()\n$dependencies = @(Get-ChildItem -LiteralPath $Dependencies -Recurse -Include *.appx, *.msix -ErrorAction SilentlyContinue | Where-Object { $_.Directory.Name -in @('Dependencies', $architecture) } | ForEach-Object { $_.FullName })\nif ($dependencies) { $arguments.DependencyPackagePath = $dependencies }\n",
+            )
+            .arg("Dependencies", &dependencies.to_string_lossy()),
+        );
+    }
+    script.append(PowerShellScript::new(
+        "Add-AppxProvisionedPackage @arguments | Out-Null\n",
+    ))
+}
+
+/// Provisions [package] machine-wide, so every account created afterwards gets it. That needs an administrator,
+/// so the script is run elevated
+#[cfg(target_os = "windows")]
+fn provision_appx(
+    runner: &dyn SystemRunner,
+    package: &Path,
+    dependencies: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    log::debug!("Provisioning {}", package.display());
+    let output = provision_appx_script(package, dependencies).run_elevated(runner)?;
+    if output.status.success() {
+        return Ok(());
+    }
+    Err(Box::new(GManError::new(&format!(
+        "Failed to provision {} ({}): {}",
+        package.display(),
+        output.status,
+        output.stderr.trim()
+    ))))
+}
+
+/// PowerShell statements setting the launch [environment] for the processes started after them
+#[cfg(target_os = "windows")]
+fn powershell_environment(
+    environment: Option<&std::collections::BTreeMap<String, String>>,
+) -> PowerShellScript {
+    let (names, values): (Vec<String>, Vec<String>) = environment
+        .map(|env| {
+            env.iter()
+                .map(|(key, value)| (key.to_owned(), value.to_owned()))
+                .unzip()
+        })
+        .unwrap_or_default();
+    PowerShellScript::new(
+        "for ($i = 0; $i -lt $EnvironmentNames.Count; $i++) { [Environment]::SetEnvironmentVariable($EnvironmentNames[$i], $EnvironmentValues[$i]) }\n",
+    )
+    .arg_list("EnvironmentNames", &names)
+    .arg_list("EnvironmentValues", &values)
+}
+
+/// PowerShell statements setting `$fname` to the AppUserModelID to launch, or leaving it empty if nothing was found.
+///
+/// The applications declared in the manifest of [package_full_name] are tried first, then the Start Menu entries whose
+/// AppId starts with [name_regex]. Every AppId considered is written out as `AppId <source>: <id>` for trace logging
+#[cfg(target_os = "windows")]
+fn powershell_resolve_app_id(
+    name_regex: &str,
+    package_full_name: Option<&str>,
+) -> PowerShellScript {
+    let mut script = PowerShellScript::new("$fname = $null\n");
+    if let Some(full_name) = package_full_name {
+        script = script.append(
+            PowerShellScript::new(
+                r#"$pkg = Get-AppxPackage | Where-Object {$_.PackageFullName -eq $PackageFullName} | Select-Object -First 1
+if ($pkg) {
+    foreach ($application in (Get-AppxPackageManifest -Package $pkg.PackageFullName).Package.Applications.Application) {
+        $id = "$($pkg.PackageFamilyName)!$($application.Id)"
+        Write-Output "AppId manifest: $id"
+        if (-not $fname) { $fname = $id }
+    }
+}
+"#,
+            )
+            .arg("PackageFullName", full_name),
+        );
+    }
+    script.append(
+        PowerShellScript::new(
+            r#"if (-not $fname) {
+    foreach ($startApp in (Get-StartApps | Where-Object {$_.AppId.StartsWith($NameRegex)})) {
+        Write-Output "AppId start menu: $($startApp.AppId) ($($startApp.Name))"
+        if (-not $fname) { $fname = $startApp.AppId }
+    }
+}"#,
+        )
+        .arg("NameRegex", name_regex),
+    )
+}
+
+/// Times `hdiutil` is tried to attach or detach an image before giving up
+#[cfg(target_os = "macos")]
+const HDIUTIL_ATTEMPTS: u32 = 3;
+
+/// Wait before retrying `hdiutil`, doubled after every further failure
+#[cfg(target_os = "macos")]
+const HDIUTIL_BACKOFF: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Runs `hdiutil` with [args], retrying with backoff while it fails, e.g. because Finder or Spotlight are
+/// still busy with the volume. Returns the output of the last attempt
+#[cfg(target_os = "macos")]
+fn run_hdiutil_with_retry(
+    runner: &dyn SystemRunner,
+    args: &[&str],
+) -> Result<crate::system_runner::CommandOutput, Box<dyn std::error::Error>> {
+    let mut delay = HDIUTIL_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        let output = runner.run("hdiutil", args)?;
+        if output.status.success() || attempt >= HDIUTIL_ATTEMPTS {
+            return Ok(output);
+        }
+        log::debug!(
+            "hdiutil {} failed (attempt {} of {}), retrying in {:?}: {}",
+            args.join(" "),
+            attempt,
+            HDIUTIL_ATTEMPTS,
+            delay,
+            output.stderr.trim()
+        );
+        std::thread::sleep(delay);
+        delay *= 2;
+        attempt += 1;
+    }
+}
+
+/// Mounts an image given by [binary_path] via `hdiutil`.
+/// The volume isn't shown in Finder, which would otherwise keep it busy when it's detached again
+#[cfg(target_os = "macos")]
+fn mount_volume_mac<P>(
+    runner: &dyn SystemRunner,
+    binary_path: P,
+) -> Result<Option<PathBuf>, Box<dyn std::error::Error>>
+where
+    P: AsRef<Path>,
+{
+    let output = run_hdiutil_with_retry(
+        runner,
+        &["attach", "-nobrowse", binary_path.as_ref().to_str().unwrap()],
+    )?;
+
+    // Check if the command was successful
+    if output.status.success() {
+        log::debug!("Successfully mounted dmg file");
+        // Convert the output bytes to a string
+        let result = &output.stdout;
+        let lines = result.split('\n');
+
+        let mut mount_point: Option<PathBuf> = None;
+        for line in lines {
+            let trimmed = line.trim();
+            let caps_volume: Vec<&str> = match MOUNTED_VOLUME_REGEX.captures(trimmed) {
+                Some(c) => c,
+                None => {
+                    continue;
+                }
+            }
+            .iter()
+            .skip(1)
+            .filter_map(|m| m.map(|m| m.as_str()))
+            .collect();
+            let mp = caps_volume.first().unwrap().to_string();
+            let pb = PathBuf::from_str(&mp).unwrap();
+            mount_point = Some(pb);
+            break;
+        }
+        Ok(mount_point)
+    } else {
+        Err(Box::new(GManError::new(&format!(
+            "Failed to mount {} ({}): {}",
+            binary_path.as_ref().display(),
+            output.status,
+            output.stderr.trim()
+        ))))
+    }
+}
+
+/// Given a mounted volume at [volume], finds the first .app or .pkg file and returns it, if any
+#[cfg(target_os = "macos")]
+fn find_mounted_application(
+    runner: &dyn SystemRunner,
+    volume: &Path,
+) -> Result<Option<MountedMacPackage>, Box<dyn std::error::Error>> {
+    let vol_str = volume.to_string_lossy();
+    log::info!("Got mount point for application: {}", vol_str);
+    log::info!("Checking if mounted contents are .app or .pkg");
+
+    let package_type: Option<MountedMacPackage> = {
+        let output = runner.run("ls", &[&vol_str])?;
+        if output.status.success() {
+            log::debug!("ls'd mounted volume");
+            let result = &output.stdout;
+            let lines = result.split('\n').collect::<Vec<&str>>();
+            let found_app = lines.iter().find(|x| x.ends_with(".app"));
+            match found_app {
+                Some(app_path) => {
+                    let full_path = volume.join(app_path);
+
+                    Some(MountedMacPackage {
+                        is_app: true,
+                        is_pkg: false,
+                        path: full_path,
+                    })
+                }
+                None => {
+                    let found_pkg = lines.iter().find(|x| x.ends_with(".pkg"));
+                    match found_pkg {
+                        Some(app_path) => {
+                            let full_path = volume.join(app_path);
+                            Some(MountedMacPackage {
+                                is_app: false,
+                                is_pkg: true,
+                                path: full_path,
                             })
                         }
                         None => None,
@@ -648,20 +1518,51 @@ fn find_mounted_application(
     Ok(package_type)
 }
 
+/// Arguments for `installer` to install [package] to the target volume given in [metadata] (`/` by default),
+/// applying its choices XML if it has one
+#[cfg(target_os = "macos")]
+fn installer_args_mac(
+    package: &MountedMacPackage,
+    metadata: Option<&crate::product::FlavorMetadata>,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let target = metadata
+        .and_then(|x| x.installer_target.as_deref())
+        .unwrap_or("/");
+    let mut args = vec![
+        "-pkg".to_owned(),
+        package.path.to_string_lossy().to_string(),
+        "-target".to_owned(),
+        target.to_owned(),
+    ];
+    if let Some(choices) = metadata.and_then(|x| x.installer_choices_xml.as_ref()) {
+        let choices = PathBuf::from(crate::client_config::ClientConfig::shell_expand(choices));
+        if !choices.exists() {
+            return Err(Box::new(GManError::new(&format!(
+                "Installer choices file {} not found",
+                choices.display()
+            ))));
+        }
+        args.push("-applyChoiceChangesXML".to_owned());
+        args.push(choices.to_string_lossy().to_string());
+    }
+    Ok(args)
+}
+
 /// Given a mac .pkg package type, install it to the system
 #[cfg(target_os = "macos")]
 fn install_mac_pkg(
+    runner: &dyn SystemRunner,
     package: &MountedMacPackage,
-    volume: &Path,
     options: InstallOverwriteOptions,
+    metadata: Option<&crate::product::FlavorMetadata>,
 ) -> Result<InstallationResult, Box<dyn std::error::Error>> {
     log::debug!("Inner contensts are .pkg, will run dpkg installer");
-    let output = Command::new("installer")
-        .arg("-pkg")
-        .arg(&volume)
-        .arg("-target")
-        .arg("/")
-        .output()?;
+    let args = installer_args_mac(package, metadata)?;
+    /* installer refuses to run without root */
+    let output = runner.run_elevated(
+        "installer",
+        &args.iter().map(|x| x.as_str()).collect::<Vec<&str>>(),
+    )?;
 
     if output.status.success() {
         log::debug!("Successfully ran installer for package contents");
@@ -671,20 +1572,74 @@ fn install_mac_pkg(
             &output.status
         );
         return Err(Box::new(GManError::new(&format!(
-            "Failed to run installer for package contents: {}",
-            &output.status
+            "Failed to run installer for package contents ({}): {}",
+            &output.status,
+            output.stderr.trim()
         ))));
     }
     Ok(InstallationResult::Succeeded)
 }
-/// Given a Mac .app package type, install it to the system
+/// Checks with Gatekeeper (`spctl --assess`) whether the mounted [package] is notarized, before it's installed.
+///
+/// A rejected package is refused if [allow_unnotarized] is `false`, and only warned about if it isn't set.
+/// Returns whether the quarantine attribute has to be removed after installing, so the app can be opened
+#[cfg(target_os = "macos")]
+fn check_notarization(
+    runner: &dyn SystemRunner,
+    package: &MountedMacPackage,
+    allow_unnotarized: Option<bool>,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let assessment_type = if package.is_pkg { "install" } else { "execute" };
+    let output = runner.run(
+        "spctl",
+        &[
+            "--assess",
+            "--type",
+            assessment_type,
+            &package.path.to_string_lossy(),
+        ],
+    )?;
+    if output.status.success() {
+        log::debug!("{} is notarized", package.path.display());
+        return Ok(false);
+    }
+
+    let reason = output.stderr.trim();
+    match allow_unnotarized {
+        Some(true) => {
+            eprintln!(
+                "{} is not notarized ({}), installing it anyway as AllowUnnotarized is set",
+                package.get_filename(),
+                reason
+            );
+            Ok(package.is_app)
+        }
+        Some(false) => Err(Box::new(GManError::new(&format!(
+            "{} is not notarized ({}), not installing it as AllowUnnotarized is false",
+            package.get_filename(),
+            reason
+        )))),
+        None => {
+            eprintln!(
+                "Warning: {} is not notarized ({}), macOS may refuse to open it. Set AllowUnnotarized in the flavor's Metadata to allow it",
+                package.get_filename(),
+                reason
+            );
+            Ok(false)
+        }
+    }
+}
+
+/// Given a Mac .app package type, install it to the system.
+/// With [strip_quarantine], the quarantine attribute is removed from the copy so an unnotarized app can be opened
 #[cfg(target_os = "macos")]
 fn install_mac_app(
+    runner: &dyn SystemRunner,
     package: &MountedMacPackage,
     options: InstallOverwriteOptions,
+    strip_quarantine: bool,
 ) -> Result<InstallationResult, Box<dyn std::error::Error>> {
-    use indicatif::ProgressBar;
-    use std::time::Duration;
+    use crate::progress::Progress;
 
     let package_file_name = package.get_filename();
     let folder_name = match options {
@@ -731,60 +1686,194 @@ fn install_mac_app(
         &dst.to_string_lossy()
     );
 
-    let progress_bar = ProgressBar::new_spinner()
-        .with_message(format!("Copying contents to {}", dst.to_string_lossy()));
-
-    progress_bar.enable_steady_tick(Duration::from_millis(10));
-    let output = Command::new("cp")
-        .arg("-R")
-        .arg("-a")
-        .arg("-f")
-        .arg(src)
-        .arg(&dst)
-        .output()?;
-    progress_bar.finish_with_message("Copied items to folder");
-    let ir = if output.status.success() {
-        log::debug!("Copied app to {}", dst.to_string_lossy());
-        InstallationResult::Succeeded
-    } else {
-        InstallationResult::Canceled
-    };
+    /* ditto merges into a bundle that's already there, keeping the files of the old version the new one dropped */
+    if dst.exists() {
+        log::debug!("Removing the previous {}", dst.display());
+        std::fs::remove_dir_all(&dst)?;
+    }
 
-    Ok(ir)
+    /* ditto keeps extended attributes, resource forks and symlinks of the bundle intact. It doesn't report
+     * progress, so the bundle is measured while it's being copied */
+    let total = crate::util::directory_size(src);
+    let mut progress_bar = Progress::download(&package_file_name, Some(total));
+    let output = std::thread::scope(|scope| {
+        let copy = scope.spawn(|| {
+            runner
+                .run("ditto", &[src.to_str().unwrap(), dst.to_str().unwrap()])
+                .map_err(|e| e.to_string())
+        });
+        while !copy.is_finished() {
+            progress_bar.set_position(crate::util::directory_size(&dst));
+            std::thread::sleep(std::time::Duration::from_millis(200));
+        }
+        copy.join()
+            .unwrap_or_else(|_| Err("copying the app panicked".to_owned()))
+    })
+    .map_err(|e| GManError::new(&e))?;
+    if !output.status.success() {
+        progress_bar.abandon();
+        return Err(Box::new(GManError::new(&format!(
+            "Failed to copy {} to {} ({}): {}",
+            src.display(),
+            dst.display(),
+            output.status,
+            output.stderr.trim()
+        ))));
+    }
+    progress_bar.set_position(total);
+    progress_bar.finish();
+    log::debug!("Copied app to {}", dst.to_string_lossy());
+
+    /* a partial copy still looks like an app, make sure it's the one that was on the image */
+    match bundle_version_mac(src) {
+        Some(expected) => {
+            let copied = bundle_version_mac(&dst);
+            if copied.as_deref() != Some(expected.as_str()) {
+                return Err(Box::new(GManError::new(&format!(
+                    "Copied app at {} has CFBundleVersion {}, expected {}",
+                    dst.display(),
+                    copied.unwrap_or("(none)".to_owned()),
+                    expected
+                ))));
+            }
+        }
+        None => log::debug!(
+            "{} has no CFBundleVersion, can't verify the copy",
+            src.display()
+        ),
+    }
+
+    if strip_quarantine {
+        crate::transform::strip_quarantine(runner, &dst)?;
+    }
+    Ok(InstallationResult::Succeeded)
+}
+
+/// `CFBundleVersion` in the Info.plist of the [app] bundle, if it has one
+#[cfg(target_os = "macos")]
+fn bundle_version_mac(app: &Path) -> Option<String> {
+    let info: std::collections::HashMap<String, plist::Value> =
+        plist::from_file(app.join("Contents").join("Info.plist")).ok()?;
+    info.get("CFBundleVersion")?
+        .as_string()
+        .map(|x| x.to_owned())
+}
+
+/// Installs the Debian package at the absolute path [package] with apt, which also pulls in its dependencies.
+/// The `ServiceName` in [metadata] is stopped first, and restarted and health checked after.
+/// [runner] may be a remote machine's, see [crate::ssh::SshRunner]
+pub fn install_deb(
+    runner: &dyn SystemRunner,
+    package: &str,
+    metadata: Option<&crate::product::FlavorMetadata>,
+) -> Result<InstallationResult, Box<dyn std::error::Error>> {
+    let service = metadata.and_then(|x| x.service_name.as_deref());
+    if let Some(service) = service {
+        crate::service::stop(runner, service)?;
+    }
+
+    let output = runner.run_elevated("apt-get", &["install", "-y", package])?;
+    if !output.status.success() {
+        return Err(Box::new(GManError::new(&format!(
+            "Failed to install {} ({}): {}",
+            package,
+            output.status,
+            output.stderr.trim()
+        ))));
+    }
+
+    if let Some(service) = service {
+        crate::service::restart(runner, service)?;
+        let timeout = metadata
+            .and_then(|x| x.service_health_check_seconds)
+            .unwrap_or(crate::service::DEFAULT_HEALTH_CHECK_SECONDS);
+        crate::service::wait_until_active(
+            runner,
+            service,
+            std::time::Duration::from_secs(timeout),
+        )?;
+    }
+    Ok(InstallationResult::Succeeded)
 }
-/// Given a binary installer at [binary_path], installs this item to the system
+
+/// Given a binary installer at [binary_path], installs this item to the system. Zip archives are extracted
+/// into [work_directory]
 #[cfg(target_os = "macos")]
 fn install_mac<P>(
+    runner: &dyn SystemRunner,
     binary_path: P,
     options: InstallOverwriteOptions,
+    metadata: Option<&crate::product::FlavorMetadata>,
+    work_directory: &Path,
 ) -> Result<InstallationResult, Box<dyn std::error::Error>>
 where
     P: AsRef<Path>,
 {
+    let binary_path = binary_path.as_ref();
+    let extension = binary_path
+        .extension()
+        .map(|x| x.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    /* bare packages are installed straight from the cache */
+    if extension == "pkg" {
+        let package = MountedMacPackage {
+            is_pkg: true,
+            is_app: false,
+            path: binary_path.to_path_buf(),
+        };
+        return install_mac_package(runner, &package, options, metadata);
+    }
+
+    /* zip archives are extracted to a temporary folder, and installed like a mounted volume */
+    if extension == "zip" {
+        let extracted = work_directory
+            .join("mac_zip")
+            .join(binary_path.file_stem().unwrap_or_default());
+        if extracted.exists() {
+            std::fs::remove_dir_all(&extracted)?;
+        }
+        std::fs::create_dir_all(&extracted)?;
+        runner.capture(
+            "ditto",
+            &[
+                "-x",
+                "-k",
+                binary_path.to_str().unwrap(),
+                extracted.to_str().unwrap(),
+            ],
+        )?;
+        let installation_result = match find_mounted_application(runner, &extracted)? {
+            Some(package) => {
+                install_mac_package(runner, &package, options, metadata)
+            }
+            None => {
+                log::warn!("Extracted archive but found neither app nor pkg in it");
+                Ok(InstallationResult::Canceled)
+            }
+        };
+        let _ = std::fs::remove_dir_all(&extracted);
+        return installation_result;
+    }
+
     /* mount the dmg file */
-    let mount = mount_volume_mac(binary_path)?;
+    let mount = mount_volume_mac(runner, binary_path)?;
 
     match mount {
         Some(volume) => {
-            let package_type: Option<MountedMacPackage> = find_mounted_application(&volume)?;
+            let package_type: Option<MountedMacPackage> =
+                find_mounted_application(runner, &volume)?;
 
             let installation_result: Result<InstallationResult, Box<dyn std::error::Error>> =
                 if let Some(package) = package_type {
-                    if package.is_app {
-                        install_mac_app(&package, options)
-                    } else if package.is_pkg {
-                        install_mac_pkg(&package, &volume, options)
-                    } else {
-                        log::warn!("Mounted item but contents were neither app nor pkg");
-                        Ok(InstallationResult::Skipped)
-                    }
+                    install_mac_package(runner, &package, options, metadata)
                 } else {
                     log::warn!("Mounted item but could not extract contents");
                     Ok(InstallationResult::Canceled)
                 };
 
             /* Unmount regardless of error status */
-            unmount_volume_mac(&volume)?;
+            unmount_volume_mac(runner, &volume)?;
 
             installation_result
         }
@@ -795,17 +1884,48 @@ where
     }
 }
 
+/// Installs the app or pkg [package] (found in a mounted image, an extracted archive, or the cache itself),
+/// after checking its notarization
+#[cfg(target_os = "macos")]
+fn install_mac_package(
+    runner: &dyn SystemRunner,
+    package: &MountedMacPackage,
+    options: InstallOverwriteOptions,
+    metadata: Option<&crate::product::FlavorMetadata>,
+) -> Result<InstallationResult, Box<dyn std::error::Error>> {
+    let allow_unnotarized = metadata.and_then(|x| x.allow_unnotarized);
+    match check_notarization(runner, package, allow_unnotarized) {
+        Err(e) => Err(e),
+        Ok(strip_quarantine) if package.is_app => {
+            install_mac_app(runner, package, options, strip_quarantine)
+        }
+        Ok(_) if package.is_pkg => install_mac_pkg(runner, package, options, metadata),
+        Ok(_) => {
+            log::warn!("Mounted item but contents were neither app nor pkg");
+            Ok(InstallationResult::Skipped)
+        }
+    }
+}
+
 /// Uses `hdiutil` to unmount a disk image given by [volume]
 #[cfg(target_os = "macos")]
-fn unmount_volume_mac<P>(volume: P) -> Result<(), Box<dyn std::error::Error>>
+fn unmount_volume_mac<P>(
+    runner: &dyn SystemRunner,
+    volume: P,
+) -> Result<(), Box<dyn std::error::Error>>
 where
     P: AsRef<Path>,
 {
     let volume = volume.as_ref().as_os_str().to_str().unwrap();
-    let output = Command::new("hdiutil")
-        .arg("detach")
-        .arg(&volume)
-        .output()?;
+    let mut output = run_hdiutil_with_retry(runner, &["detach", volume])?;
+    if !output.status.success() {
+        log::warn!(
+            "Volume at {} is busy ({}), forcing it to detach",
+            volume,
+            output.stderr.trim()
+        );
+        output = runner.run("hdiutil", &["detach", "-force", volume])?;
+    }
 
     if output.status.success() {
         log::debug!("Unmounted volume at {}", volume);
@@ -813,8 +1933,10 @@ where
     } else {
         log::error!("Failed to unmount volume at {}", &volume);
         Err(Box::new(GManError::new(&format!(
-            "Failed to unmount volume at {}",
-            volume
+            "Failed to unmount volume at {} ({}): {}",
+            volume,
+            output.status,
+            output.stderr.trim()
         ))))
     }
 }
@@ -824,9 +1946,11 @@ impl FromStr for InstallationCandidate {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let splits = s.split('@').collect::<Vec<_>>();
-        if splits.len() != 6 {
-            return Err(GManError::new("Not an InstallationCandidate string"));
-        }
+        let architecture = match splits.len() {
+            6 => None,
+            7 => Some(Architecture::from_str(splits[5])?),
+            _ => return Err(GManError::new("Not an InstallationCandidate string")),
+        };
         let product_name = splits[0];
         let flavor_str = splits[2];
         let identifier = splits[3];
@@ -840,15 +1964,39 @@ impl FromStr for InstallationCandidate {
             identifier: identifier.to_owned(),
             flavor: Flavor {
                 id: flavor_str.into(),
+                architecture,
                 ..Flavor::empty()
             },
             installed: false,
+            expired: false,
         };
 
         Ok(c)
     }
 }
 
+/// Who an installed product is installed for
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum InstallScope {
+    /// Every user of the machine, e.g. MSIs registered under HKLM and apps in /Applications
+    #[default]
+    Machine,
+    /// Only the named user, e.g. AppX packages and per-user MSIs
+    User(String),
+    /// Every account created on the machine from now on, i.e. AppX packages provisioned with `install --provision`
+    Provisioned,
+}
+
+impl Display for InstallScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InstallScope::Machine => f.write_str("machine"),
+            InstallScope::User(user) => write!(f, "user {}", user),
+            InstallScope::Provisioned => f.write_str("provisioned"),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct InstalledProduct {
     pub product_name: String,
@@ -857,8 +2005,15 @@ pub struct InstalledProduct {
 
     pub package_name: String,
     pub package_type: PackageType,
+    /// Id of the flavor that's installed, when the package it was found by belongs to a single flavor
+    pub flavor: Option<String>,
 
     pub path: PathBuf,
+
+    pub scope: InstallScope,
+    /// Bytes the installation takes on disk, when the package manager reports it, see
+    /// [InstalledProduct::footprint]
+    pub size: Option<u64>,
 }
 
 #[cfg(target_os = "windows")]
@@ -869,25 +2024,87 @@ impl From<InstalledAppXProduct> for InstalledProduct {
             version: value.version,
             package_name: value.package_full_name,
             package_type: PackageType::AppX,
+            flavor: value.flavor,
             path: PathBuf::new(),
+            /* AppX packages are always registered per user, the queried user unless told otherwise */
+            scope: InstallScope::User(value.user.unwrap_or_else(crate::util::current_user)),
+            size: None,
         }
     }
 }
 
 impl InstalledProduct {
+    /// Key of this product and its version, and its flavor if that's known. Which branch it was built
+    /// from isn't
+    pub fn key(&self) -> CandidateKey {
+        let key = CandidateKey::new(&self.product_name).version(&self.version);
+        match &self.flavor {
+            Some(flavor) => key.flavor(flavor),
+            None => key,
+        }
+    }
+
+    /// Bytes the installation takes on disk: what its package manager reports, like the `EstimatedSize` of
+    /// an MSI, or else what's measured under its [path], like a macOS .app bundle. None if neither is known
+    pub fn footprint(&self) -> Option<u64> {
+        if self.size.is_some() {
+            return self.size;
+        }
+        /* products without a folder of their own, like AppX and Deb packages, have no path */
+        if !self.path.is_absolute() || !self.path.exists() {
+            return None;
+        }
+        Some(crate::util::directory_size(&self.path))
+    }
+
     /// Terminates the processes associated with this item
-    pub fn shutdown(&self) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn shutdown(&self, runner: &dyn SystemRunner) -> Result<(), Box<dyn std::error::Error>> {
         log::debug!("Shutting down {} if running", &self.product_name);
 
         #[cfg(target_os = "macos")]
         /* Shut down the running process, if any */
-        shutdown_program_mac(&self)?;
+        shutdown_program_mac(runner, &self)?;
+
+        #[cfg(target_os = "windows")]
+        shutdown_program_windows(runner, &self)?;
+
+        #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+        let _ = runner;
 
         Ok(())
     }
 
+    /// Descriptions of the processes of this item that are currently running, if any
+    pub fn running_processes(
+        &self,
+        runner: &dyn SystemRunner,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        #[cfg(target_os = "windows")]
+        {
+            get_running_processes_windows(runner, self)
+        }
+
+        #[cfg(target_os = "macos")]
+        {
+            Ok(get_running_app_pids_mac(runner)?
+                .into_iter()
+                .filter(|x| x.contains(&self.package_name))
+                .collect())
+        }
+
+        #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+        {
+            let _ = runner;
+            Ok(Vec::new())
+        }
+    }
+
     /// Whether this item should be uninstalled -- used primarily on Mac installations where multiple items may inhabit the /Applicatiosn folder
-    pub fn should_uninstall<P>(&self, binary_path: P) -> Result<bool, Box<dyn std::error::Error>>
+    pub fn should_uninstall<P>(
+        &self,
+        runner: &dyn SystemRunner,
+        binary_path: P,
+    ) -> Result<bool, Box<dyn std::error::Error>>
     where
         P: AsRef<Path>,
     {
@@ -897,10 +2114,11 @@ impl InstalledProduct {
         );
         #[cfg(target_os = "macos")]
         {
-            self.should_uninstall_mac(binary_path)
+            self.should_uninstall_mac(runner, binary_path)
         }
         #[cfg(not(target_os = "macos"))]
         {
+            let _ = (runner, binary_path);
             log::trace!("Not linux or mac, will mark this item for uninstallation unconditionally");
             Ok(true)
         }
@@ -908,24 +2126,32 @@ impl InstalledProduct {
 
     /// Checks whether this item should be uninstalled. For .app items, this means checking for installed applications with the same folder name
     #[cfg(target_os = "macos")]
-    fn should_uninstall_mac<P>(&self, binary_path: P) -> Result<bool, Box<dyn std::error::Error>>
+    fn should_uninstall_mac<P>(
+        &self,
+        runner: &dyn SystemRunner,
+        binary_path: P,
+    ) -> Result<bool, Box<dyn std::error::Error>>
     where
         P: AsRef<Path>,
     {
-        if let PackageType::App = self.package_type {
+        let is_disk_image = binary_path
+            .as_ref()
+            .extension()
+            .is_some_and(|x| x.eq_ignore_ascii_case("dmg"));
+        if let (PackageType::App, true) = (&self.package_type, is_disk_image) {
             log::trace!(
                 "Item is macos .app package type, will mount and examine the actual contents"
             );
             // 1. Mount the volume
-            let mount = mount_volume_mac(binary_path)?;
+            let mount = mount_volume_mac(runner, binary_path)?;
             // 2. Get the actual .app folder name for the inner application
             let package = match mount {
                 Some(volume) => {
                     let package_type: Option<MountedMacPackage> =
-                        find_mounted_application(&volume)?;
+                        find_mounted_application(runner, &volume)?;
 
                     /* Unmount regardless of error status */
-                    unmount_volume_mac(&volume)?;
+                    unmount_volume_mac(runner, &volume)?;
 
                     package_type
                 }
@@ -948,20 +2174,35 @@ impl InstalledProduct {
             }
             return Ok(false);
         }
-        log::trace!("Item is not a .app package, will mark this item for uninstallation");
+        log::trace!(
+            "Item is not a .app package in a disk image, will mark this item for uninstallation"
+        );
         Ok(true)
     }
 
-    /// Uninstalls this item from the system
-    pub fn uninstall(&self) -> Result<(), Box<dyn std::error::Error>> {
+    /// Uninstalls this item from the system.
+    ///
+    /// On Mac the app is moved to the Trash, so an accidental uninstall can be undone, unless [permanently] is set
+    pub fn uninstall(
+        &self,
+        runner: &dyn SystemRunner,
+        permanently: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
         log::debug!("Uninstalling {}", &self.product_name);
         #[cfg(target_os = "windows")]
         if self.package_type == PackageType::AppX {
-            let command = format!("Remove-AppxPackage {}", self.package_name);
-            let output = Command::new("powershell")
-                .arg("-Command")
-                .arg(command)
-                .output()?;
+            /* provisioned packages are removed from the image, the accounts that got them keep them */
+            let output = if self.scope == InstallScope::Provisioned {
+                PowerShellScript::new(
+                    "Remove-AppxProvisionedPackage -Online -PackageName $PackageName | Out-Null",
+                )
+                .arg("PackageName", &self.package_name)
+                .run_elevated(runner)?
+            } else {
+                PowerShellScript::new("Remove-AppxPackage -Package $PackageName")
+                    .arg("PackageName", &self.package_name)
+                    .run(runner)?
+            };
 
             // Check if the command was successful
             if output.status.success() {
@@ -975,9 +2216,7 @@ impl InstalledProduct {
                 self.product_name
             ))));
         } else if self.package_type == PackageType::Msi {
-            let output = Command::new("msiexec")
-                .args(["/x", self.package_name.as_str(), "/passive"])
-                .output()?;
+            let output = runner.run("msiexec", &["/x", self.package_name.as_str(), "/passive"])?;
 
             // Check if the command was successful
             if output.status.success() {
@@ -996,24 +2235,62 @@ impl InstalledProduct {
         {
             /* Move entry in /Applications to trash */
             if let Some(path) = get_path_to_application_mac(&self)? {
-                log::debug!("Sending {} to trash", &path.to_str().unwrap());
-                let output = Command::new("rm").arg("-r").arg(path).output()?;
+                let output = if permanently {
+                    log::debug!("Deleting {}", &path.to_str().unwrap());
+                    runner.run("rm", &["-r", path.to_str().unwrap()])?
+                } else {
+                    log::debug!("Sending {} to trash", &path.to_str().unwrap());
+                    runner.run("osascript", &trash_args_mac(path.to_str().unwrap()))?
+                };
                 if output.status.success() {
-                    log::debug!("Successfully removed Application to trash");
+                    log::debug!(
+                        "Successfully removed Application{}",
+                        if permanently { "" } else { " to trash" }
+                    );
                     return Ok(());
                 }
                 return Err(Box::new(GManError::new(&format!(
-                    "Failed to remove application from {} directory: {}",
-                    &MAC_APPLICATIONS_DIR, output.status
+                    "Failed to remove application from {} directory: {} {}",
+                    &MAC_APPLICATIONS_DIR,
+                    output.status,
+                    output.stderr.trim()
                 ))));
             }
         }
+        #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+        let _ = permanently;
         #[cfg(target_os = "linux")]
-        {}
-        Ok(())
+        if self.package_type == PackageType::Deb {
+            /* purging also deletes the package's configuration files */
+            let action = if permanently { "purge" } else { "remove" };
+            let output =
+                runner.run_elevated("apt-get", &[action, "-y", self.package_name.as_str()])?;
+            if !output.status.success() {
+                return Err(Box::new(GManError::new(&format!(
+                    "Failed to remove package {} ({}): {}",
+                    self.package_name,
+                    output.status,
+                    output.stderr.trim()
+                ))));
+            }
+            log::debug!("Successfully uninstalled {}", self.product_name);
+        }
+        Ok(())
     }
 }
 
+/// JavaScript for Automation moving the path it's given to the Trash, where it can be put back from. It uses
+/// NSFileManager rather than telling Finder to, which needs the Automation permission and fails without it
+#[cfg(target_os = "macos")]
+const TRASH_SCRIPT_MAC: &str = "function run(argv) { ObjC.import('Foundation'); const error = Ref(); if (!$.NSFileManager.defaultManager.trashItemAtURLResultingItemURLError($.NSURL.fileURLWithPath(argv[0]), null, error)) { throw new Error(ObjC.unwrap(error[0].localizedDescription)); } }";
+
+/// Arguments of `osascript` moving [path] to the Trash, see [TRASH_SCRIPT_MAC]. The path is handed to the
+/// script as an argument, so it needs no quoting
+#[cfg(target_os = "macos")]
+fn trash_args_mac(path: &str) -> [&str; 5] {
+    ["-l", "JavaScript", "-e", TRASH_SCRIPT_MAC, path]
+}
+
 /// Information about the mounted package structure of this candidate on MacOS, like whether it is an App or Pkg, and what the path to its final destination is
 #[cfg(any(target_os = "macos", target_os = "linux"))]
 #[derive(Debug)]
@@ -1092,36 +2369,35 @@ fn get_path_to_application_mac(
 
 /// Gets the PIDs of every process running on a Mac system. Uses launchctl
 #[cfg(target_os = "macos")]
-fn get_running_app_pids_mac() -> Result<Vec<String>, Box<dyn std::error::Error>> {
+fn get_running_app_pids_mac(
+    runner: &dyn SystemRunner,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
     log::debug!("Getting running processes");
     let mut pid_labels: Vec<String> = Vec::new();
 
-    let output = Command::new("launchctl").arg("list").output()?;
-
-    if output.status.success() {
-        let result = String::from_utf8_lossy(&output.stdout);
-        let lines = result.split('\n');
-        for line in lines {
-            let splits = line.split('\t').collect::<Vec<&str>>();
-            if splits.len() > 2 {
-                let label = splits[2];
-                pid_labels.push(label.into());
-            }
+    let result = runner
+        .capture("launchctl", &["list"])
+        .map_err(|_| GManError::new("Couldnt get PIDs for determinng running applications"))?;
+    let lines = result.split('\n');
+    for line in lines {
+        let splits = line.split('\t').collect::<Vec<&str>>();
+        if splits.len() > 2 {
+            let label = splits[2];
+            pid_labels.push(label.into());
         }
-
-        Ok(pid_labels)
-    } else {
-        Err(Box::new(GManError::new(
-            "Couldnt get PIDs for determinng running applications",
-        )))
     }
+
+    Ok(pid_labels)
 }
 
 /// shuts down a program, usually by its Identifier.
 /// This is the first step before Uninstalling
 #[cfg(target_os = "macos")]
-fn shutdown_program_mac(installed: &InstalledProduct) -> Result<(), Box<dyn std::error::Error>> {
-    let running_processes = get_running_app_pids_mac()?;
+fn shutdown_program_mac(
+    runner: &dyn SystemRunner,
+    installed: &InstalledProduct,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let running_processes = get_running_app_pids_mac(runner)?;
 
     match running_processes
         .iter()
@@ -1129,10 +2405,7 @@ fn shutdown_program_mac(installed: &InstalledProduct) -> Result<(), Box<dyn std:
     {
         Some(running) => {
             log::debug!("Stopping application {}", running.as_str());
-            let output = Command::new("launchctl")
-                .arg("stop")
-                .arg(running.as_str())
-                .output()?;
+            let output = runner.run("launchctl", &["stop", running.as_str()])?;
 
             // Check if the command was successful
             if output.status.success() {
@@ -1158,6 +2431,79 @@ fn shutdown_program_mac(installed: &InstalledProduct) -> Result<(), Box<dyn std:
     }
 }
 
+/// PowerShell pipeline yielding the processes started from the install location of [installed],
+/// looked up from its AppX package or its MSI uninstall registry key
+#[cfg(target_os = "windows")]
+fn powershell_product_processes(installed: &InstalledProduct) -> Option<PowerShellScript> {
+    let root = match installed.package_type {
+        PackageType::AppX | PackageType::MsiX => PowerShellScript::new(
+            "$root = (Get-AppxPackage | Where-Object {$_.PackageFullName -eq $PackageFullName} | Select-Object -First 1).InstallLocation\n",
+        )
+        .arg("PackageFullName", &installed.package_name),
+        PackageType::Msi => PowerShellScript::new(
+            "$root = (Get-ItemProperty -LiteralPath $UninstallKey -ErrorAction SilentlyContinue).InstallLocation\n",
+        )
+        .arg(
+            "UninstallKey",
+            &format!(
+                r"HKLM:\Software\Microsoft\Windows\CurrentVersion\Uninstall\{}",
+                installed.package_name
+            ),
+        ),
+        _ => return None,
+    };
+    Some(root.append(PowerShellScript::new(
+        "if ($root) { Get-Process | Where-Object {$_.Path -and $_.Path.StartsWith($root, [StringComparison]::OrdinalIgnoreCase)}",
+    )))
+}
+
+/// Lists the processes of [installed] as `name (pid)`
+#[cfg(target_os = "windows")]
+fn get_running_processes_windows(
+    runner: &dyn SystemRunner,
+    installed: &InstalledProduct,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let processes = match powershell_product_processes(installed) {
+        Some(p) => p,
+        None => return Ok(Vec::new()),
+    };
+    let output = processes
+        .append(PowerShellScript::new(
+            r#" | ForEach-Object { Write-Output "$($_.ProcessName) ($($_.Id))" } }"#,
+        ))
+        .capture(runner)?;
+    Ok(output
+        .lines()
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty())
+        .map(|x| x.to_owned())
+        .collect())
+}
+
+/// Stops the processes of [installed], the Windows counterpart of [shutdown_program_mac]
+#[cfg(target_os = "windows")]
+fn shutdown_program_windows(
+    runner: &dyn SystemRunner,
+    installed: &InstalledProduct,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let processes = match powershell_product_processes(installed) {
+        Some(p) => p,
+        None => return Ok(()),
+    };
+    let output = processes
+        .append(PowerShellScript::new(" | Stop-Process -Force }"))
+        .run(runner)?;
+    if output.status.success() {
+        log::debug!("Stopped running processes of {}", &installed.product_name);
+        Ok(())
+    } else {
+        Err(Box::new(GManError::new(&format!(
+            "Failed to stop running processes of {}: {}",
+            &installed.product_name, output.status
+        ))))
+    }
+}
+
 /// Package information on Windows only AppX cadidates, such as the name, version, and full identifier
 #[cfg(windows)]
 #[derive(Debug, Deserialize)]
@@ -1168,6 +2514,16 @@ pub struct InstalledAppXProduct {
     pub version: Version,
     #[serde(rename = "PackageFullName")]
     pub package_full_name: String,
+    /// User the package or MSI is registered for, None for the current user's AppX packages.
+    /// Empty for machine-wide MSIs
+    #[serde(rename = "User", default)]
+    pub user: Option<String>,
+    /// Kilobytes the MSI reports it takes on disk in the registry, None for AppX packages
+    #[serde(rename = "EstimatedSize", default)]
+    pub estimated_size: Option<u64>,
+    /// Id of the flavor the package belongs to, when only one flavor of its product matches it
+    #[serde(skip)]
+    pub flavor: Option<String>,
 }
 
 #[cfg(test)]
@@ -1178,13 +2534,54 @@ mod tests {
         product::{self, Flavor, FlavorMetadata, TeamCityMetadata},
     };
 
+    #[cfg(target_os = "windows")]
+    use crate::powershell::DecodedScript;
+
     use super::InstallationCandidate;
 
+    #[test]
+    fn version_from_file_name() {
+        assert_eq!(
+            Version::from_file_name("hubkit_5.2.1-8219_all.deb").unwrap(),
+            Version::new("5.2.1-8219")
+        );
+        assert_eq!(
+            Version::from_file_name("GravioStudio-5.2.4683.msix").unwrap(),
+            Version::new("5.2.4683")
+        );
+        assert!(Version::from_file_name("GravioHubKit4.msi").is_none());
+    }
+
+    #[test]
+    fn version_numbered_like_build_number() {
+        let build_number = Version::new("5.2.10-7070");
+        assert_eq!(
+            Version::new("5.2.1.7049")
+                .numbered_like(&build_number)
+                .to_string(),
+            "5.2.1-7049"
+        );
+        assert_eq!(
+            Version::new("5.2.1")
+                .numbered_like(&build_number)
+                .to_string(),
+            "5.2.1"
+        );
+        assert_eq!(
+            Version::new("5.2.1-7049")
+                .numbered_like(&Version::new("develop"))
+                .to_string(),
+            "5.2.1-7049"
+        );
+    }
+
     #[test]
     fn test_cached_file_name() {
         let i = InstallationCandidate {
             flavor: Flavor {
                 autorun: false,
+                transforms: Vec::new(),
+                architecture: None,
                 id: "WindowsHubKit".into(),
                 metadata: Some(FlavorMetadata {
                     cf_bundle_name: None,
@@ -1194,12 +2591,28 @@ mod tests {
                     name_regex: None,
                     launch_args: None,
                     run_as_service: None,
+                    installer_handles_upgrade: None,
+                    msi_transforms: None,
+                    msi_properties: None,
+                    executable: None,
+                    service_name: None,
+                    service_health_check_seconds: None,
+                    launch_environment: None,
+                    data_paths: None,
+                    log_paths: None,
+                    allow_unnotarized: None,
+                    installer_choices_xml: None,
+                    installer_target: None,
+                    checksum_artifact: None,
                     stop_command: None,
                 }),
                 package_type: product::PackageType::Msi,
                 teamcity_metadata: TeamCityMetadata {
                     teamcity_binary_path: "GravioHubKit.msi".into(),
                     teamcity_id: "Gravio_GravioHubKit4".into(),
+                    branches: None,
+                    branch_policy: None,
+                    architectures: None,
                 },
                 platform: Platform::Windows,
             },
@@ -1209,6 +2622,7 @@ mod tests {
             remote_id: String::default(),
             repo_location: String::default(),
             installed: false,
+            expired: false,
         };
 
         let fname = i.make_cached_file_name();
@@ -1286,4 +2700,874 @@ mod tests {
         let o = v0.partial_cmp(&v1);
         assert_eq!(o.unwrap(), std::cmp::Ordering::Less);
     }
+
+    #[test]
+    fn table_rows_sort_the_same_in_any_order() {
+        use super::TablePrinter;
+
+        let row = |name: &str, version: &str, identifier: &str, flavor: &str| TablePrinter {
+            name: name.into(),
+            version: version.into(),
+            identifier: identifier.into(),
+            flavor: flavor.into(),
+            size: String::default(),
+            installed: false,
+            path: String::default(),
+        };
+        let sorted = vec![
+            row("GravioStudio", "5.2.4683", "develop", "Sideloading"),
+            row("GravioStudio", "5.2.4683", "develop", "WindowsAppStore"),
+            row("GravioStudio", "5.2.4683", "reorg_login", "Sideloading"),
+            row("HubKit", "5.2.10", "master", "WindowsHubkit"),
+            row("hubkit", "5.2.9-7049", "develop", "WindowsHubkit"),
+            row("HubKit", "5.2.9", "develop", "WindowsHubkit"),
+            row("HubKit", "nightly", "develop", "WindowsHubkit"),
+        ];
+        let key = |rows: &[TablePrinter]| {
+            rows.iter()
+                .map(|x| format!("{} {} {} {}", x.name, x.version, x.identifier, x.flavor))
+                .collect::<Vec<String>>()
+        };
+
+        let mut rows = sorted
+            .iter()
+            .map(|x| row(&x.name, &x.version, &x.identifier, &x.flavor))
+            .collect::<Vec<TablePrinter>>();
+        for shift in 0..rows.len() {
+            rows.rotate_left(shift);
+            rows.reverse();
+            rows.sort_by(TablePrinter::cmp_rows);
+            assert_eq!(key(&rows), key(&sorted));
+        }
+    }
+
+    #[test]
+    fn candidate_keys_tell_flavors_apart() {
+        use crate::candidate::{CandidateKey, InstalledProduct};
+        use std::{collections::HashSet, str::FromStr};
+
+        let store = InstallationCandidate::from_str(
+            "GravioStudio@Windows@WindowsAppStore@develop@5.2.4683@graviostudio.zip",
+        )
+        .unwrap();
+        let sideloading = InstallationCandidate::from_str(
+            "GravioStudio@Windows@Sideloading@develop@5.2.4683@graviostudio_sideloading.zip",
+        )
+        .unwrap();
+        let installed = InstalledProduct {
+            product_name: "graviostudio".into(),
+            version: Version::new("5.2.4683.0"),
+            package_name: "InfoteriaPte.Ltd.GravioStudio_5.2.4683.0_x64__abcdefgh".into(),
+            package_type: product::PackageType::AppX,
+            flavor: Some("sideloading".into()),
+            path: Default::default(),
+            scope: Default::default(),
+            size: None,
+        };
+        assert!(!store.product_equals(&installed));
+        assert!(sideloading.product_equals(&installed));
+        assert!(sideloading.key().matches(&installed.key()));
+
+        /* which flavor is installed isn't always known */
+        let unknown = InstalledProduct {
+            flavor: None,
+            ..installed
+        };
+        assert!(store.product_equals(&unknown));
+        assert!(store.key().matches(&unknown.key()));
+        assert!(!CandidateKey::new("GravioStudio")
+            .version(&Version::new("5.2.4682"))
+            .matches(&unknown.key()));
+
+        let same = InstallationCandidate::from_str(
+            "graviostudio@Windows@SIDELOADING@Develop@5.2.4683-0@GravioStudio.zip",
+        )
+        .unwrap();
+        let keys = HashSet::from([store.key(), sideloading.key(), same.key()]);
+        assert_eq!(keys.len(), 2);
+        assert!(keys.contains(&same.key()));
+    }
+
+    fn make_candidate(package_type: product::PackageType, binary: &str) -> InstallationCandidate {
+        InstallationCandidate {
+            flavor: Flavor {
+                autorun: false,
+                transforms: Vec::new(),
+                architecture: None,
+                id: "TestFlavor".into(),
+                metadata: None,
+                package_type,
+                teamcity_metadata: TeamCityMetadata {
+                    teamcity_binary_path: binary.into(),
+                    teamcity_id: "Test_Build".into(),
+                    branches: None,
+                    branch_policy: None,
+                    architectures: None,
+                },
+                platform: Platform::platform_for_current_platform().unwrap(),
+            },
+            identifier: "develop".to_owned(),
+            version: Version::new("5.2.3-7023"),
+            product_name: "HubKit".into(),
+            remote_id: String::default(),
+            repo_location: String::default(),
+            installed: false,
+            expired: false,
+        }
+    }
+
+    #[test]
+    fn cached_file_name_records_architecture() {
+        use crate::platform::Architecture;
+        use std::str::FromStr;
+
+        let mut candidate = make_candidate(product::PackageType::AppX, "x64/graviostudio.zip");
+        candidate.flavor.teamcity_metadata.architectures = Some(
+            [
+                (Architecture::X64, "x64/graviostudio.zip".into()),
+                (Architecture::Arm64, "arm64/graviostudio.zip".into()),
+            ]
+            .into(),
+        );
+        assert_eq!(
+            candidate.flavor.for_architecture(None).unwrap(),
+            candidate.flavor
+        );
+
+        let configured = candidate.flavor.clone();
+        candidate.flavor = configured
+            .for_architecture(Some(Architecture::Arm64))
+            .unwrap();
+        assert_eq!(
+            candidate
+                .flavor
+                .teamcity_metadata
+                .teamcity_binary_path
+                .to_string_lossy(),
+            "arm64/graviostudio.zip"
+        );
+        let fname = candidate.make_cached_file_name();
+        assert!(fname.ends_with("@TestFlavor@develop@5.2.3-7023@arm64@graviostudio.zip"));
+
+        let cached = InstallationCandidate::from_str(&fname).unwrap();
+        assert_eq!(cached.flavor.architecture, Some(Architecture::Arm64));
+        assert_eq!(cached.version, candidate.version);
+        assert!(InstallationCandidate::from_str(&fname.replace("@arm64@", "@sparc@")).is_err());
+
+        let mut x64_only = configured;
+        x64_only.teamcity_metadata.architectures =
+            Some([(Architecture::X64, "x64/graviostudio.zip".into())].into());
+        assert_eq!(
+            x64_only
+                .for_architecture(Some(Architecture::Arm64))
+                .unwrap_err()
+                .to_string(),
+            "Flavor TestFlavor has no arm64 build, only x64"
+        );
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn install_msi_runs_msiexec() {
+        use crate::candidate::{InstallOverwriteOptions, InstallationResult};
+        use crate::system_runner::MockRunner;
+
+        let runner = MockRunner::new();
+        let candidate = make_candidate(product::PackageType::Msi, "GravioHubKit.msi");
+        let result = candidate
+            .install(
+                &runner,
+                "C:\\cache\\GravioHubKit.msi",
+                InstallOverwriteOptions::Overwrite,
+            )
+            .unwrap();
+
+        assert!(matches!(result, InstallationResult::Succeeded));
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(
+            calls[0].command_line(),
+            format!(
+                "msiexec /i C:\\cache\\GravioHubKit.msi /passive /l*v {}",
+                crate::support::installer_log_directory(&candidate)
+                    .join("msiexec.log")
+                    .display()
+            )
+        );
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn install_msi_applies_transforms_and_properties() {
+        use crate::candidate::InstallOverwriteOptions;
+        use crate::system_runner::MockRunner;
+
+        let dir = std::env::temp_dir()
+            .join("gman_tests")
+            .join("msi_transforms");
+        std::fs::create_dir_all(&dir).unwrap();
+        let branding = dir.join("branding.mst");
+        let region = dir.join("jp.mst");
+        std::fs::write(&branding, "").unwrap();
+        std::fs::write(&region, "").unwrap();
+
+        let runner = MockRunner::new();
+        let mut candidate = make_candidate(product::PackageType::Msi, "GravioHubKit.msi");
+        candidate.flavor.metadata = Some(FlavorMetadata {
+            msi_transforms: Some(vec![
+                branding.to_string_lossy().to_string(),
+                region.to_string_lossy().to_string(),
+            ]),
+            msi_properties: Some(std::collections::BTreeMap::from([(
+                "Region".to_owned(),
+                "JP".to_owned(),
+            )])),
+            ..Default::default()
+        });
+        candidate
+            .install(
+                &runner,
+                "C:\\cache\\GravioHubKit.msi",
+                InstallOverwriteOptions::Overwrite,
+            )
+            .unwrap();
+        assert_eq!(
+            runner.calls()[0].command_line(),
+            format!(
+                "msiexec /i C:\\cache\\GravioHubKit.msi /passive /l*v {} TRANSFORMS={};{} REGION=JP",
+                crate::support::installer_log_directory(&candidate)
+                    .join("msiexec.log")
+                    .display(),
+                branding.display(),
+                region.display()
+            )
+        );
+
+        candidate.flavor.metadata = Some(FlavorMetadata {
+            msi_transforms: Some(vec![dir.join("missing.mst").to_string_lossy().to_string()]),
+            ..Default::default()
+        });
+        let e = candidate
+            .install(
+                &runner,
+                "C:\\cache\\GravioHubKit.msi",
+                InstallOverwriteOptions::Overwrite,
+            )
+            .unwrap_err();
+        assert!(e.to_string().contains("missing.mst not found"));
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn install_msi_user_canceled() {
+        use crate::candidate::InstallOverwriteOptions;
+        use crate::system_runner::{CommandOutput, MockRunner};
+
+        let runner = MockRunner::new().respond(CommandOutput::failed(1602, ""));
+        let candidate = make_candidate(product::PackageType::Msi, "GravioHubKit.msi");
+        let result = candidate.install(
+            &runner,
+            "GravioHubKit.msi",
+            InstallOverwriteOptions::Overwrite,
+        );
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "User canceled installation"
+        );
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn install_msix_adds_appx_package() {
+        use crate::candidate::{InstallOverwriteOptions, InstallationResult};
+        use crate::system_runner::MockRunner;
+
+        let runner = MockRunner::new();
+        let candidate = make_candidate(product::PackageType::MsiX, "handbookx.msix");
+        let result = candidate
+            .install(
+                &runner,
+                "handbookx.msix",
+                InstallOverwriteOptions::Overwrite,
+            )
+            .unwrap();
+
+        assert!(matches!(result, InstallationResult::Succeeded));
+        let calls = runner.calls();
+        assert_eq!(calls[0].program, "powershell");
+        let script = DecodedScript::from_args(&calls[0].args);
+        assert_eq!(script.script, "Add-AppxPackage -Path $Package");
+        assert_eq!(script.arg("Package"), "handbookx.msix");
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn install_script_chosen_for_the_architecture() {
+        use super::select_install_script_folder;
+        use std::path::PathBuf;
+
+        let folders = [
+            PathBuf::from(r"C:\Temp\GravioStudio_5.2.4683.0_arm64_Test"),
+            PathBuf::from(r"C:\Temp\GravioStudio_5.2.4683.0_x64_Test"),
+        ];
+        assert_eq!(
+            select_install_script_folder(&folders, "x64").unwrap(),
+            &folders[1]
+        );
+        assert_eq!(
+            select_install_script_folder(&folders, "ARM64").unwrap(),
+            &folders[0]
+        );
+        let e = select_install_script_folder(&folders, "x86").unwrap_err();
+        assert!(e.to_string().contains("GravioStudio_5.2.4683.0_arm64_Test"));
+
+        /* a single folder is run whatever it's named, neutral ones when none is for the architecture */
+        assert!(select_install_script_folder(&folders[..1], "x64").is_ok());
+        let folders = [
+            PathBuf::from(r"C:\Temp\GravioStudio_5.2.4683.0_arm64_Test"),
+            PathBuf::from(r"C:\Temp\GravioStudio_5.2.4683.0_neutral_Test"),
+        ];
+        assert_eq!(
+            select_install_script_folder(&folders, "x64").unwrap(),
+            &folders[1]
+        );
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn uninstall_appx_removes_package() {
+        use crate::candidate::InstalledProduct;
+        use crate::system_runner::MockRunner;
+
+        let runner = MockRunner::new();
+        let installed = InstalledProduct {
+            product_name: "GravioStudio".into(),
+            version: Version::new("5.2.4670.0"),
+            package_name: "InfoteriaPte.Ltd.GravioStudio_5.2.4670.0_x64__mrnz526z5qc9p".into(),
+            package_type: product::PackageType::AppX,
+            flavor: None,
+            path: Default::default(),
+            scope: Default::default(),
+            size: None,
+        };
+        installed.uninstall(&runner, false).unwrap();
+
+        let script = DecodedScript::from_args(&runner.calls()[0].args);
+        assert_eq!(script.script, "Remove-AppxPackage -Package $PackageName");
+        assert_eq!(
+            script.arg("PackageName"),
+            "InfoteriaPte.Ltd.GravioStudio_5.2.4670.0_x64__mrnz526z5qc9p"
+        );
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn provisioned_appx_is_provisioned_and_removed_elevated() {
+        use crate::candidate::{InstallScope, InstalledProduct};
+        use crate::system_runner::MockRunner;
+        use std::path::Path;
+
+        let script = super::provision_appx_script(
+            Path::new("C:\\Temp\\gman\\Studio\\GravioStudio.msixbundle"),
+            Some(Path::new("C:\\Temp\\gman\\Studio\\Dependencies")),
+        );
+        let script = DecodedScript::from_args(&script.command_line());
+        assert_eq!(
+            script.arg("Package"),
+            "C:\\Temp\\gman\\Studio\\GravioStudio.msixbundle"
+        );
+        assert_eq!(
+            script.arg("Dependencies"),
+            "C:\\Temp\\gman\\Studio\\Dependencies"
+        );
+        assert!(script
+            .script
+            .contains("Get-ChildItem -LiteralPath $Dependencies"));
+        assert!(script
+            .script
+            .ends_with("Add-AppxProvisionedPackage @arguments | Out-Null\n"));
+
+        let runner = MockRunner::new();
+        let installed = InstalledProduct {
+            product_name: "GravioStudio".into(),
+            version: Version::new("5.2.4670.0"),
+            package_name: "InfoteriaPte.Ltd.GravioStudio_5.2.4670.0_neutral_~_mrnz526z5qc9p".into(),
+            package_type: product::PackageType::AppX,
+            flavor: None,
+            path: Default::default(),
+            scope: InstallScope::Provisioned,
+            size: None,
+        };
+        installed.uninstall(&runner, false).unwrap();
+
+        let calls = runner.calls();
+        assert!(calls[0].elevated);
+        let script = DecodedScript::from_args(&calls[0].args);
+        assert_eq!(
+            script.script,
+            "Remove-AppxProvisionedPackage -Online -PackageName $PackageName | Out-Null"
+        );
+        assert_eq!(
+            script.arg("PackageName"),
+            "InfoteriaPte.Ltd.GravioStudio_5.2.4670.0_neutral_~_mrnz526z5qc9p"
+        );
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn start_appx_passes_launch_args_but_not_environment() {
+        use crate::system_runner::MockRunner;
+
+        let runner = MockRunner::new();
+        let mut candidate = make_candidate(product::PackageType::AppX, "graviostudio.zip");
+        candidate.flavor.metadata = Some(FlavorMetadata {
+            name_regex: Some("InfoteriaPte.Ltd.GravioStudio".into()),
+            launch_args: Some(vec!["--debug".into(), "it's".into()]),
+            launch_environment: Some([("GRAVIO_LOG".to_owned(), "trace".to_owned())].into()),
+            ..Default::default()
+        });
+        candidate.start_program(&runner, None).unwrap();
+
+        let script = DecodedScript::from_args(&runner.calls()[0].args);
+        assert!(!script.arguments.contains_key("EnvironmentNames"));
+        assert_eq!(script.arguments["LaunchArgs"], vec!["--debug", "it's"]);
+        assert!(script
+            .script
+            .contains(r#"Start-Process "shell:AppsFolder\$fname" -ArgumentList $LaunchArgs"#));
+        assert!(!script.script.contains("explorer.exe"));
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn start_appx_resolves_app_id_from_package_manifest() {
+        use crate::system_runner::MockRunner;
+
+        let mut candidate = make_candidate(product::PackageType::AppX, "graviostudio.zip");
+        candidate.flavor.metadata = Some(FlavorMetadata {
+            name_regex: Some("InfoteriaPte.Ltd.GravioStudio".into()),
+            ..Default::default()
+        });
+
+        let runner = MockRunner::new();
+        let full_name = "InfoteriaPte.Ltd.GravioStudio_5.2.4683.0_x64__abcdefgh";
+        candidate.start_program(&runner, Some(full_name)).unwrap();
+        let decoded = DecodedScript::from_args(&runner.calls()[0].args);
+        assert_eq!(decoded.arg("PackageFullName"), full_name);
+        let script = &decoded.script;
+        assert!(script.contains("$_.PackageFullName -eq $PackageFullName"));
+        assert!(script.contains("Get-AppxPackageManifest"));
+        /* the manifest is tried before the start menu */
+        assert!(
+            script.find("Get-AppxPackageManifest").unwrap() < script.find("Get-StartApps").unwrap()
+        );
+
+        let runner = MockRunner::new();
+        candidate.start_program(&runner, None).unwrap();
+        let script = DecodedScript::from_args(&runner.calls()[0].args);
+        assert!(!script.script.contains("Get-AppxPackageManifest"));
+        assert!(script.script.contains("$_.AppId.StartsWith($NameRegex)"));
+        assert_eq!(script.arg("NameRegex"), "InfoteriaPte.Ltd.GravioStudio");
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn start_appx_reports_missing_app_id() {
+        use crate::system_runner::{CommandOutput, MockRunner};
+
+        let runner = MockRunner::new().respond(CommandOutput::failed(2, ""));
+        let mut candidate = make_candidate(product::PackageType::AppX, "graviostudio.zip");
+        candidate.flavor.metadata = Some(FlavorMetadata {
+            name_regex: Some("InfoteriaPte.Ltd.GravioStudio".into()),
+            ..Default::default()
+        });
+        let e = candidate.start_program(&runner, None).unwrap_err();
+        assert!(e.to_string().contains("check the NameRegex"));
+
+        /* running check looks for a process in the package's install location */
+        let runner = MockRunner::new().respond(CommandOutput::failed(1, ""));
+        assert_eq!(candidate.is_running(&runner).unwrap(), Some(false));
+        let script = DecodedScript::from_args(&runner.calls()[0].args);
+        assert!(script
+            .script
+            .contains("$_.PackageFamilyName.StartsWith($NameRegex)"));
+        assert_eq!(script.arg("NameRegex"), "InfoteriaPte.Ltd.GravioStudio");
+        assert_eq!(
+            candidate.is_running(&MockRunner::new()).unwrap(),
+            Some(true)
+        );
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn mac_running_check_uses_bundle_id() {
+        use crate::system_runner::{CommandOutput, MockRunner};
+
+        let mut candidate = make_candidate(product::PackageType::App, "GravioHubKit.dmg");
+        candidate.flavor.metadata = Some(FlavorMetadata {
+            cf_bundle_id: Some("com.asteria.mac.gravio4".into()),
+            ..Default::default()
+        });
+        let runner = MockRunner::new().respond(CommandOutput::ok(
+            "PID\tStatus\tLabel\n812\t0\tapplication.com.asteria.mac.gravio4.1234.5678\n",
+        ));
+        assert_eq!(candidate.is_running(&runner).unwrap(), Some(true));
+
+        let runner = MockRunner::new().respond(CommandOutput::ok("PID\tStatus\tLabel\n"));
+        assert_eq!(candidate.is_running(&runner).unwrap(), Some(false));
+
+        candidate.flavor.metadata = None;
+        assert_eq!(candidate.is_running(&MockRunner::new()).unwrap(), None);
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn start_mac_passes_launch_args_and_environment() {
+        use crate::system_runner::MockRunner;
+
+        let runner = MockRunner::new();
+        let mut candidate = make_candidate(product::PackageType::App, "GravioHubKit.dmg");
+        candidate.flavor.metadata = Some(FlavorMetadata {
+            cf_bundle_name: Some("Gravio HubKit".into()),
+            launch_args: Some(vec!["--debug".into()]),
+            launch_environment: Some([("GRAVIO_LOG".to_owned(), "trace".to_owned())].into()),
+            ..Default::default()
+        });
+        candidate.start_program(&runner, None).unwrap();
+
+        assert_eq!(
+            runner.calls()[0].command_line(),
+            "open -a Gravio HubKit --env GRAVIO_LOG=trace --args --debug"
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn start_linux_deb_runs_its_executable_detached() {
+        use crate::system_runner::MockRunner;
+
+        let runner = MockRunner::new();
+        let mut candidate = make_candidate(product::PackageType::Deb, "graviohubkit.deb");
+        candidate.start_program(&runner, None).unwrap();
+        assert!(
+            runner.calls().is_empty(),
+            "nothing to launch without an Executable"
+        );
+
+        candidate.flavor.metadata = Some(FlavorMetadata {
+            executable: Some("graviohubkit".into()),
+            launch_args: Some(vec!["--debug".into()]),
+            launch_environment: Some([("GRAVIO_LOG".to_owned(), "trace".to_owned())].into()),
+            ..Default::default()
+        });
+        candidate.start_program(&runner, None).unwrap();
+        assert_eq!(
+            runner.calls()[0].command_line(),
+            "setsid -f env GRAVIO_LOG=trace graviohubkit --debug"
+        );
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn install_mac_pkg_mounts_installs_and_unmounts() {
+        use crate::candidate::{InstallOverwriteOptions, InstallationResult};
+        use crate::system_runner::{CommandOutput, MockRunner};
+
+        let runner = MockRunner::new()
+            .respond(CommandOutput::ok(
+                "/dev/disk4          \tGUID_partition_scheme\t\n/dev/disk4s1        \tApple_HFS      \t/Volumes/Gravio Studio\n",
+            ))
+            .respond(CommandOutput::ok("Gravio Studio.pkg\n"));
+        let candidate = make_candidate(product::PackageType::Pkg, "GravioStudio.dmg");
+        let result = candidate
+            .install(
+                &runner,
+                "/cache/GravioStudio.dmg",
+                InstallOverwriteOptions::Overwrite,
+            )
+            .unwrap();
+
+        assert!(matches!(result, InstallationResult::Succeeded));
+        let calls = runner
+            .calls()
+            .iter()
+            .map(|x| x.command_line())
+            .collect::<Vec<String>>();
+        assert_eq!(calls[0], "hdiutil attach -nobrowse /cache/GravioStudio.dmg");
+        assert_eq!(calls[1], "ls /Volumes/Gravio Studio");
+        assert_eq!(
+            calls[2],
+            "spctl --assess --type install /Volumes/Gravio Studio/Gravio Studio.pkg"
+        );
+        assert_eq!(
+            calls[3],
+            "installer -pkg /Volumes/Gravio Studio/Gravio Studio.pkg -target /"
+        );
+        assert_eq!(calls[4], "hdiutil detach /Volumes/Gravio Studio");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn install_deb_restarts_service() {
+        use crate::candidate::{InstallOverwriteOptions, InstallationResult};
+        use crate::system_runner::{CommandOutput, MockRunner};
+
+        let runner = MockRunner::new()
+            .respond(CommandOutput::ok(""))
+            .respond(CommandOutput::ok(""))
+            .respond(CommandOutput::ok(""))
+            .respond(CommandOutput::ok(""))
+            .respond(CommandOutput::ok("active\n"));
+        let mut candidate = make_candidate(product::PackageType::Deb, "hubkit_5.2.3-7023_all.deb");
+        candidate.flavor.metadata = Some(FlavorMetadata {
+            service_name: Some("graviohubkit.service".into()),
+            ..Default::default()
+        });
+        let result = candidate
+            .install(
+                &runner,
+                "/cache/hubkit_5.2.3-7023_all.deb",
+                InstallOverwriteOptions::Overwrite,
+            )
+            .unwrap();
+
+        assert!(matches!(result, InstallationResult::Succeeded));
+        let calls = runner
+            .calls()
+            .iter()
+            .map(|x| x.command_line())
+            .collect::<Vec<String>>();
+        assert_eq!(
+            calls,
+            vec![
+                "systemctl stop graviohubkit.service",
+                "apt-get install -y /cache/hubkit_5.2.3-7023_all.deb",
+                "systemctl daemon-reload",
+                "systemctl restart graviohubkit.service",
+                "systemctl is-active graviohubkit.service",
+            ]
+        );
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn install_bare_mac_pkg_without_mounting() {
+        use crate::candidate::{InstallOverwriteOptions, InstallationResult};
+        use crate::system_runner::MockRunner;
+
+        let runner = MockRunner::new();
+        let candidate = make_candidate(product::PackageType::Pkg, "GravioStudio.pkg");
+        let result = candidate
+            .install(&runner, "/cache/GravioStudio.pkg", InstallOverwriteOptions::Overwrite)
+            .unwrap();
+
+        assert!(matches!(result, InstallationResult::Succeeded));
+        let calls = runner
+            .calls()
+            .iter()
+            .map(|x| x.command_line())
+            .collect::<Vec<String>>();
+        assert_eq!(
+            calls,
+            vec![
+                "spctl --assess --type install /cache/GravioStudio.pkg",
+                "installer -pkg /cache/GravioStudio.pkg -target /",
+            ]
+        );
+        assert!(runner.calls()[1].elevated, "installer needs root");
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn install_mac_pkg_applies_choices_and_target() {
+        use crate::candidate::InstallOverwriteOptions;
+        use crate::system_runner::MockRunner;
+
+        let dir = std::env::temp_dir().join("gman_tests").join("installer_choices");
+        std::fs::create_dir_all(&dir).unwrap();
+        let choices = dir.join("choices.xml");
+        std::fs::write(&choices, "<plist></plist>").unwrap();
+
+        let runner = MockRunner::new();
+        let mut candidate = make_candidate(product::PackageType::Pkg, "GravioStudio.pkg");
+        candidate.flavor.metadata = Some(FlavorMetadata {
+            installer_choices_xml: Some(choices.to_string_lossy().to_string()),
+            installer_target: Some("/Volumes/Data".into()),
+            ..Default::default()
+        });
+        candidate
+            .install(&runner, "/cache/GravioStudio.pkg", InstallOverwriteOptions::Overwrite)
+            .unwrap();
+
+        assert_eq!(
+            runner.calls().last().unwrap().command_line(),
+            format!(
+                "installer -pkg /cache/GravioStudio.pkg -target /Volumes/Data -applyChoiceChangesXML {}",
+                choices.display()
+            )
+        );
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn busy_volume_detached_by_force() {
+        use crate::system_runner::{CommandOutput, MockRunner};
+
+        let busy = || CommandOutput::failed(16, "hdiutil: couldn't unmount \"disk4\" - Resource busy");
+        let runner = MockRunner::new()
+            .respond(busy())
+            .respond(busy())
+            .respond(busy());
+        super::unmount_volume_mac(&runner, "/Volumes/HandbookX").unwrap();
+
+        let calls = runner
+            .calls()
+            .iter()
+            .map(|x| x.command_line())
+            .collect::<Vec<String>>();
+        assert_eq!(calls.len(), 4);
+        assert_eq!(calls[2], "hdiutil detach /Volumes/HandbookX");
+        assert_eq!(calls[3], "hdiutil detach -force /Volumes/HandbookX");
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn trash_args_pass_path_without_finder() {
+        let args = super::trash_args_mac("/Applications/Gravio \"HubKit\".app");
+        assert_eq!(args[..3], ["-l", "JavaScript", "-e"]);
+        assert!(args[3].contains("NSFileManager"));
+        assert!(!args[3].contains("Finder"));
+        assert_eq!(args[4], "/Applications/Gravio \"HubKit\".app");
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn install_mac_app_copies_with_ditto() {
+        use crate::candidate::{InstallOverwriteOptions, InstallationResult};
+        use crate::system_runner::{CommandOutput, MockRunner};
+
+        let runner = MockRunner::new()
+            .respond(CommandOutput::ok(
+                "/dev/disk4s1        \tApple_HFS      \t/Volumes/HandbookX\n",
+            ))
+            .respond(CommandOutput::ok("HandbookX.app\n"));
+        let candidate = make_candidate(product::PackageType::App, "HandbookX.dmg");
+        let result = candidate
+            .install(&runner, "/cache/HandbookX.dmg", InstallOverwriteOptions::Overwrite)
+            .unwrap();
+
+        assert!(matches!(result, InstallationResult::Succeeded));
+        let calls = runner
+            .calls()
+            .iter()
+            .map(|x| x.command_line())
+            .collect::<Vec<String>>();
+        assert_eq!(
+            calls[3],
+            "ditto /Volumes/HandbookX/HandbookX.app /Applications/HandbookX.app"
+        );
+        assert_eq!(calls[4], "hdiutil detach /Volumes/HandbookX");
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn install_mac_refuses_unnotarized_when_not_allowed() {
+        use crate::candidate::InstallOverwriteOptions;
+        use crate::system_runner::{CommandOutput, MockRunner};
+
+        let runner = MockRunner::new()
+            .respond(CommandOutput::ok(
+                "/dev/disk4s1        \tApple_HFS      \t/Volumes/HandbookX\n",
+            ))
+            .respond(CommandOutput::ok("HandbookX.app\n"))
+            .respond(CommandOutput::failed(
+                3,
+                "/Volumes/HandbookX/HandbookX.app: rejected",
+            ));
+        let mut candidate = make_candidate(product::PackageType::App, "HandbookX.dmg");
+        candidate.flavor.metadata = Some(FlavorMetadata {
+            allow_unnotarized: Some(false),
+            ..Default::default()
+        });
+        assert!(candidate
+            .install(&runner, "/cache/HandbookX.dmg", InstallOverwriteOptions::Overwrite)
+            .is_err());
+
+        let calls = runner.calls();
+        assert!(!calls.iter().any(|x| x.program == "cp"));
+        assert_eq!(
+            calls.last().unwrap().command_line(),
+            "hdiutil detach /Volumes/HandbookX",
+            "unmounted even though the install was refused"
+        );
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn windows_processes_found_and_stopped_by_install_location() {
+        use crate::candidate::InstalledProduct;
+        use crate::system_runner::{CommandOutput, MockRunner};
+
+        let installed = InstalledProduct {
+            product_name: "GravioStudio".into(),
+            version: Version::new("5.2.4683.0"),
+            package_name: "InfoteriaPte.Ltd.GravioStudio_5.2.4683.0_x64__abcdefgh".into(),
+            package_type: product::PackageType::AppX,
+            flavor: None,
+            path: Default::default(),
+            scope: Default::default(),
+            size: None,
+        };
+        let runner = MockRunner::new().respond(CommandOutput::ok(
+            "GravioStudio (4242)\r\nGravioStudio.Helper (4243)\r\n",
+        ));
+        assert_eq!(
+            installed.running_processes(&runner).unwrap(),
+            vec!["GravioStudio (4242)", "GravioStudio.Helper (4243)"]
+        );
+        let script = DecodedScript::from_args(&runner.calls()[0].args);
+        assert!(script
+            .script
+            .contains("$_.PackageFullName -eq $PackageFullName"));
+        assert_eq!(
+            script.arg("PackageFullName"),
+            "InfoteriaPte.Ltd.GravioStudio_5.2.4683.0_x64__abcdefgh"
+        );
+
+        let runner = MockRunner::new();
+        installed.shutdown(&runner).unwrap();
+        let script = DecodedScript::from_args(&runner.calls()[0].args).script;
+        assert!(script.contains("Stop-Process -Force"));
+        assert!(script.contains("InstallLocation"));
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn shutdown_mac_stops_running_label() {
+        use crate::candidate::InstalledProduct;
+        use crate::system_runner::{CommandOutput, MockRunner};
+
+        let runner = MockRunner::new().respond(CommandOutput::ok(
+            "PID\tStatus\tLabel\n-\t0\tcom.apple.something\n4242\t0\tapplication.com.asteria.mac.gravio4.1234\n",
+        ));
+        let installed = InstalledProduct {
+            product_name: "HubKit".into(),
+            version: Version::new("5.2.1.8213"),
+            package_name: "com.asteria.mac.gravio4".into(),
+            package_type: product::PackageType::App,
+            flavor: None,
+            path: Default::default(),
+            scope: Default::default(),
+            size: None,
+        };
+        installed.shutdown(&runner).unwrap();
+
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(
+            calls[1].command_line(),
+            "launchctl stop application.com.asteria.mac.gravio4.1234"
+        );
+    }
 }
@@ -3,8 +3,14 @@ use regex::Regex;
 use std::{path::PathBuf, str::FromStr};
 
 use clap::{Parser, Subcommand};
+use clap_complete::engine::ArgValueCompleter;
 
-use crate::gman_error::GManError;
+use crate::{
+    completion,
+    gman_error::GManError,
+    output::{ListFormat, OutputFormat},
+    progress::ProgressMode,
+};
 
 #[derive(Debug, Parser)]
 #[command(author, version, about, long_about = None)]
@@ -13,12 +19,83 @@ pub struct Cli {
     /// Determines where to load the configuration json from
     pub config_path: Option<PathBuf>,
 
+    /// Loads the configuration only from this file (or directory), without searching anywhere else.
+    /// Takes precedence over CONFIG_PATH
+    #[clap(long = "config", value_name = "PATH")]
+    #[arg(global = true)]
+    pub strict_config: Option<PathBuf>,
+
+    /// Directory to use as the user configuration directory, instead of `$XDG_CONFIG_HOME/gman`
+    /// (`~/.config/gman`) or `%APPDATA%\gman` on Windows
+    #[clap(long, value_name = "DIR")]
+    #[arg(global = true)]
+    pub config_dir: Option<PathBuf>,
+
+    /// Directory to keep the cache in for this run, e.g. a CI workspace, instead of the configuration's
+    /// CacheDirectory and CacheDirectories
+    #[clap(long, value_name = "DIR")]
+    #[arg(global = true)]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Directory to download to for this run instead of the configuration's TempDownloadDirectory. Downloads
+    /// go to a `gman-tmp` folder in it, which is emptied whenever gman starts except for partial downloads to
+    /// resume, so the rest of the directory is left alone
+    #[clap(long, value_name = "DIR")]
+    #[arg(global = true)]
+    pub temp_dir: Option<PathBuf>,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 
     #[clap(long)]
     #[arg(global = true)]
     pub log_level: Option<log::LevelFilter>,
+
+    /// Gives up with exit code 124 when the command hasn't finished after this many seconds,
+    /// cleaning up any download in progress
+    #[clap(long, value_name = "SECS")]
+    #[arg(global = true)]
+    pub timeout: Option<u64>,
+
+    /// Prints the offending part of repository responses that can't be parsed
+    #[clap(long)]
+    #[arg(global = true)]
+    pub debug_parse: bool,
+
+    /// How to show the progress of downloads: redrawn bars, or `plain` lines every few percent for
+    /// screen readers and logs
+    #[clap(long, value_enum, default_value_t = ProgressMode::Bar)]
+    #[arg(global = true)]
+    pub progress: ProgressMode,
+
+    /// How `list`, `installed` and `cache --list` print what they found: a table, or `json` or `csv`
+    /// records with every field for scripts
+    #[clap(long, value_enum, default_value_t = ListFormat::Table)]
+    #[arg(global = true)]
+    pub format: ListFormat,
+
+    /// Also lists and installs TeamCity personal builds, which are left out by default
+    #[clap(long)]
+    #[arg(global = true)]
+    pub include_personal: bool,
+
+    /// Never runs commands through sudo. Installs that need root, like Mac packages, fail with
+    /// instructions instead
+    #[clap(long)]
+    #[arg(global = true)]
+    pub no_sudo: bool,
+
+    /// Starts installs without checking for admin rights, writable folders, the PowerShell execution
+    /// policy and authorized Android devices first, and doesn't check the PowerShell language mode on Windows
+    #[clap(long)]
+    #[arg(global = true)]
+    pub skip_prerequisites: bool,
+
+    /// Runs PowerShell with `-ExecutionPolicy Bypass`, for machines whose execution policy keeps AppX
+    /// install scripts from running. Has no effect on policies set by group policy
+    #[clap(long)]
+    #[arg(global = true)]
+    pub execution_policy_bypass: bool,
 }
 
 #[derive(Debug, Subcommand)]
@@ -31,11 +108,24 @@ pub enum Commands {
             help = "if true, shows results that may already be installed on your computer"
         )]
         show_installed: bool,
+        #[clap(
+            short,
+            long,
+            conflicts_with = "show_installed",
+            help = "Only shows installed products with a newer build on the branch they were installed from"
+        )]
+        outdated: bool,
+        #[clap(
+            long,
+            help = "Also shows the builds blocked by the products' BlockedVersions and BlockedBranches"
+        )]
+        show_blocked: bool,
     },
     /// Uninstalls the candidate
     Uninstall {
         #[clap(
-            help = "Product name, taken from the `products` section of the gman_client_config.json5"
+            help = "Product name, taken from the `products` section of the gman_client_config.json5",
+            add = ArgValueCompleter::new(completion::complete_product_name)
         )]
         name: String,
 
@@ -53,17 +143,43 @@ pub enum Commands {
             help = "whether to prompt to uninstall. Only used when multiple identical products are installed. Set to false to uninstall all products automatically"
         )]
         prompt: Option<bool>,
+
+        #[clap(
+            long,
+            help = "Also delete the data and config directories listed in the product's DataPaths, after confirmation. On Mac, deletes the app instead of moving it to the Trash"
+        )]
+        purge: bool,
+        #[clap(
+            long,
+            value_enum,
+            default_value_t = OutputFormat::Text,
+            help = "`json` ends the uninstall with a result object on stdout, for scripts"
+        )]
+        output: OutputFormat,
     },
     /// Installs the [candidate] with optional [version]
+    #[command(disable_version_flag = true)]
     Install {
         #[clap(
-            help = "Product name, taken from the `products` section of the gman_client_config.json5"
+            help = "Product name, taken from the `products` section of the gman_client_config.json5, or the url of a TeamCity build to install",
+            required_unless_present_any = ["file", "url", "manifest"],
+            add = ArgValueCompleter::new(completion::complete_product_name)
+        )]
+        name: Option<String>,
+        #[clap(
+            help = "Build number, git branch/tag, `latest` for the newest build, or `nightly` for the newest build of the last 24h on the nightly branch",
+            conflicts_with_all = ["file", "url", "manifest"],
+            add = ArgValueCompleter::new(completion::complete_build_or_branch)
         )]
-        name: String,
-        #[clap(help = "Build number, or git branch/tag")]
         build_or_branch: Option<String>,
-        #[clap(short, long, help = "Product flavor (e.g.,, Sideloading, Arm64 etc)")]
-        flavor: Option<String>,
+        #[clap(
+            short,
+            long,
+            value_delimiter = ',',
+            help = "Product flavor (e.g.,, Sideloading, Arm64 etc). Several comma separated flavors are installed side by side",
+            add = ArgValueCompleter::new(completion::complete_flavor)
+        )]
+        flavor: Vec<String>,
         #[clap(
             short,
             long,
@@ -80,21 +196,372 @@ pub enum Commands {
             help = "whether to launch the installaed application automatically after a successful installation. Leave blank to defer to the configuration json settings for the product flavor."
         )]
         autorun: Option<bool>,
+        #[clap(
+            long,
+            requires = "product",
+            conflicts_with = "url",
+            help = "Installs a local installer file instead of searching the repositories. Requires --product"
+        )]
+        file: Option<PathBuf>,
+        #[clap(
+            long,
+            requires = "product",
+            help = "Downloads and installs the installer at this url instead of searching the repositories. Requires --product"
+        )]
+        url: Option<String>,
+        #[clap(
+            long,
+            requires = "url",
+            help = "SHA-256 checksum (hex, optionally prefixed with `sha256:`) the --url download must match"
+        )]
+        checksum: Option<String>,
+        #[clap(
+            long,
+            conflicts_with = "name",
+            help = "Product name the --file/--url installer belongs to",
+            add = ArgValueCompleter::new(completion::complete_product_name)
+        )]
+        product: Option<String>,
+        #[clap(
+            long,
+            conflicts_with = "name",
+            help = "Version of the --file/--url installer. Inferred from the file name if not specified"
+        )]
+        version: Option<String>,
+        #[clap(
+            long,
+            conflicts_with_all = ["name", "file", "url", "product"],
+            help = "Installs every product listed in a manifest written by `export`"
+        )]
+        manifest: Option<PathBuf>,
+        #[clap(
+            long,
+            allow_hyphen_values = true,
+            conflicts_with = "manifest",
+            help = "Arguments to launch the application with when autorunning, overriding the configured LaunchArgs (e.g. \"--debug --port 8080\")"
+        )]
+        launch_args: Option<String>,
+        #[clap(
+            long,
+            value_name = "SECONDS",
+            help = "When autorunning, wait up to this many seconds for the application to be running and fail otherwise. Overrides AutorunVerifySeconds"
+        )]
+        wait_for_launch: Option<u64>,
+        #[clap(
+            long,
+            help = "Close running instances of the product without asking before upgrading it"
+        )]
+        force: bool,
+        #[clap(
+            long,
+            help = "Installs builds blocked by the product's BlockedVersions and BlockedBranches too"
+        )]
+        allow_blocked: bool,
+        #[clap(
+            long,
+            help = "If the requested build's artifacts were cleaned up, installs the nearest newer build on its branch instead"
+        )]
+        nearest: bool,
+        #[clap(
+            long,
+            conflicts_with = "checksum",
+            help = "Moves downloads into the cache without checking them against the checksum the server reports or the flavor's ChecksumArtifact"
+        )]
+        skip_checksum: bool,
+        #[clap(
+            long,
+            help = "Windows AppX/MSIX: provisions the package machine-wide, so every account created afterwards gets it, instead of installing it for the current user. Needs administrator rights"
+        )]
+        provision: bool,
+        #[clap(
+            long,
+            value_name = "ARCH",
+            value_parser = ["x64", "arm64"],
+            help = "Installs the build for this architecture, for flavors publishing one per architecture or a bundle of them. Defaults to the flavor's TeamCityBinaryPath, and to this machine's architecture within bundles"
+        )]
+        arch: Option<String>,
+        #[clap(
+            long,
+            conflicts_with_all = ["file", "url", "manifest", "target", "provision"],
+            help = "Windows: installs the build in a new Windows Sandbox instead of on this machine, to try it out without leaving anything behind. Needs the Windows Sandbox feature"
+        )]
+        sandbox: bool,
+        #[clap(
+            long,
+            value_name = "IMAGE",
+            conflicts_with_all = ["file", "url", "manifest", "target", "sandbox", "provision"],
+            help = "Installs the newest Linux Deb build of the branch in a throwaway Docker container of this image, e.g. debian:bookworm, to check the package installs from any machine with Docker"
+        )]
+        container: Option<String>,
+        #[clap(
+            long,
+            value_name = "NAME",
+            conflicts_with_all = ["file", "url", "manifest"],
+            help = "Installs the newest Deb build of the branch onto this SSH target from the configuration's SshTargets, e.g. a Raspberry Pi, instead of this machine"
+        )]
+        target: Option<String>,
+        #[clap(
+            long,
+            value_enum,
+            default_value_t = OutputFormat::Text,
+            help = "`json` ends the installation with a result object on stdout, for scripts"
+        )]
+        output: OutputFormat,
+    },
+    /// Upgrades installed products to the newest build on the branch they were installed from, every
+    /// installed product unless one is named. Fails if a named product isn't installed
+    Upgrade {
+        #[clap(
+            help = "Product name, taken from the `products` section of the gman_client_config.json5. Upgrades every installed product if omitted",
+            add = ArgValueCompleter::new(completion::complete_product_name)
+        )]
+        name: Option<String>,
+        #[clap(
+            long,
+            value_delimiter = ',',
+            conflicts_with = "name",
+            help = "Only upgrades these products, comma separated",
+            add = ArgValueCompleter::new(completion::complete_product_name)
+        )]
+        only: Vec<String>,
+        #[clap(
+            long,
+            help = "Prints the upgrades that would be installed without installing them"
+        )]
+        dry_run: bool,
+        #[clap(
+            long,
+            help = "whether to launch the upgraded application automatically. Leave blank to defer to the configuration json settings for the product flavor."
+        )]
+        autorun: Option<bool>,
+        #[clap(
+            long,
+            help = "Close running instances of the product instead of failing when it's running"
+        )]
+        force: bool,
+        #[clap(
+            long,
+            help = "Upgrades to builds blocked by the products' BlockedVersions and BlockedBranches too"
+        )]
+        allow_blocked: bool,
+        #[clap(
+            long,
+            value_enum,
+            default_value_t = OutputFormat::Text,
+            help = "`json` ends the upgrade of each product with a result object on stdout, for scripts"
+        )]
+        output: OutputFormat,
     },
     /// Clears the cache of all matching criteria, or all of it, if nothing specified
     Cache {
-        #[clap(short, long, help = "Whether to clear the cache")]
+        #[clap(
+            short,
+            long,
+            help = "Whether to clear the cache. Pinned artifacts are kept"
+        )]
         clear: bool,
         #[clap(short, long, help = "List which candidates are cached on disk")]
         list: bool,
+        #[command(subcommand)]
+        command: Option<CacheCommand>,
     },
     /// Lists items that are installed on this machine
-    Installed,
+    Installed {
+        #[clap(
+            short,
+            long,
+            help = "Also lists products installed only for other users, e.g. their AppX packages (Windows, needs administrator rights) or standalone programs (Linux)"
+        )]
+        all_users: bool,
+        #[clap(
+            short,
+            long,
+            help = "Also shows how much disk space each product takes, measuring those whose package manager doesn't report it"
+        )]
+        verbose: bool,
+    },
+
+    /// Prints the commits that went into the builds between two versions of a product
+    Diff {
+        #[clap(
+            help = "Product name, taken from the `products` section of the gman_client_config.json5",
+            add = ArgValueCompleter::new(completion::complete_product_name)
+        )]
+        name: String,
+        #[clap(help = "Older build number, e.g. 5.2.0-7015")]
+        from: String,
+        #[clap(help = "Newer build number, e.g. 5.2.1-7049")]
+        to: String,
+        #[clap(
+            short,
+            long,
+            help = "Product flavor, defaults to the flavor for this platform"
+        )]
+        flavor: Option<String>,
+    },
+
+    /// Lists the files in a build's artifacts on the repository, without downloading them
+    Inspect {
+        #[clap(
+            help = "Product name, taken from the `products` section of the gman_client_config.json5",
+            add = ArgValueCompleter::new(completion::complete_product_name)
+        )]
+        name: String,
+        #[clap(
+            help = "Build number, git branch/tag, `latest` or `nightly`",
+            add = ArgValueCompleter::new(completion::complete_build_or_branch)
+        )]
+        build_or_branch: String,
+        #[clap(
+            short,
+            long,
+            help = "Product flavor, defaults to the flavor for this platform"
+        )]
+        flavor: Option<String>,
+    },
+
+    /// Downloads the newest build of a branch into the cache ahead of time, e.g. from a scheduled task
+    Prefetch {
+        #[clap(
+            help = "Product name, taken from the `products` section of the gman_client_config.json5",
+            add = ArgValueCompleter::new(completion::complete_product_name)
+        )]
+        name: String,
+        #[clap(
+            help = "Git branch to download the newest build of",
+            add = ArgValueCompleter::new(completion::complete_build_or_branch)
+        )]
+        branch: String,
+        #[clap(
+            long,
+            value_delimiter = ',',
+            help = "Flavors to download, comma separated, or `all` for every flavor of every platform. Defaults to the flavor for this platform"
+        )]
+        flavors: Vec<String>,
+        #[clap(
+            long,
+            value_name = "ARCH",
+            value_parser = ["x64", "arm64"],
+            help = "Downloads the build for this architecture, for flavors publishing one per architecture"
+        )]
+        arch: Option<String>,
+    },
+
+    /// Prints a manifest of the products installed on this machine, for use with `install --manifest`
+    Export,
+
+    /// Bundles the environment, the configuration with its secrets redacted, the install journal and the
+    /// logs of the latest failed installs into a zip, for filing issues against gman itself
+    Report {
+        #[clap(
+            short,
+            long,
+            help = "Where to write the zip, the support folder of the cache if not given"
+        )]
+        output: Option<PathBuf>,
+    },
+
+    /// Shows the audit log of the changes gman made to this machine
+    Audit {
+        #[command(subcommand)]
+        command: AuditCommand,
+    },
+
+    /// Saves and restores named states of the installed products
+    Snapshot {
+        #[command(subcommand)]
+        command: SnapshotCommand,
+    },
+
+    /// Opens a folder of an installed product in Explorer, Finder or the file manager: where it's installed,
+    /// or with --data/--logs the `DataPaths`/`LogPaths` of its flavors
+    Open {
+        #[clap(
+            help = "Product name, taken from the `products` section of the gman_client_config.json5",
+            add = ArgValueCompleter::new(completion::complete_product_name)
+        )]
+        name: String,
+        #[clap(
+            long,
+            conflicts_with_all = ["logs", "install_dir"],
+            help = "Opens the data and config directories listed in the product's DataPaths"
+        )]
+        data: bool,
+        #[clap(
+            long,
+            conflicts_with = "install_dir",
+            help = "Opens the log directories listed in the product's LogPaths"
+        )]
+        logs: bool,
+        #[clap(
+            long,
+            help = "Opens the folder the product is installed in, the default"
+        )]
+        install_dir: bool,
+    },
+
+    /// Tags a build of a product with a note, e.g. the bug it reproduces, shown next to it by `list` and
+    /// `cache --list`. Lists the tagged builds, of the product if one is given, when no version is given
+    Tag {
+        #[clap(help = "Name of the product, e.g. HubKit")]
+        product_name: Option<String>,
+        #[clap(
+            requires = "product_name",
+            help = "Version of the build, e.g. 5.2.1-7049"
+        )]
+        version: Option<String>,
+        #[clap(
+            help = "Note to tag the build with, e.g. \"repro bug GRV-1234\". Shows the notes of the build if not given",
+            conflicts_with = "clear"
+        )]
+        note: Option<String>,
+        #[clap(long, requires = "version", help = "Removes every note of the build")]
+        clear: bool,
+    },
+
+    /// Works with the configured repositories
+    Repo {
+        #[command(subcommand)]
+        command: RepoCommand,
+    },
+
+    /// Signs in to and out of repositories
+    Auth {
+        #[command(subcommand)]
+        command: AuthCommand,
+    },
+
+    /// Interactively creates a configuration file
+    Init {
+        #[clap(long, help = "Overwrite the configuration file if it already exists")]
+        force: bool,
+    },
 
     /// Deals with the configuration
     Config {
         #[clap(short, long, help = "Generates a new sample configuration file")]
         sample: bool,
+        #[clap(
+            short,
+            long,
+            conflicts_with = "sample",
+            help = "Opens the configuration file in $EDITOR, and only saves the changes if they are valid"
+        )]
+        edit: bool,
+        #[clap(
+            short,
+            long,
+            conflicts_with_all = ["sample", "edit"],
+            help = "Prints which configuration file is loaded, and every location searched for one in order"
+        )]
+        r#where: bool,
+        #[clap(
+            short,
+            long,
+            conflicts_with_all = ["sample", "edit", "where"],
+            help = "Moves the configuration into the user config directory, and the cache out of ~/.cache on platforms that keep it elsewhere"
+        )]
+        migrate: bool,
     },
 }
 
@@ -104,17 +571,124 @@ pub enum ConfigCommand {
     New,
 }
 
+#[derive(Debug, Subcommand)]
+pub enum CacheCommand {
+    /// Pins cached artifacts so clearing the cache keeps them, e.g. a build a customer issue reproduces with
+    Pin {
+        #[clap(
+            help = "Version of the cached build, e.g. 5.2.1-7049, or the file name of the cache entry"
+        )]
+        artifact: String,
+    },
+    /// Unpins cached artifacts, so clearing the cache removes them again
+    Unpin {
+        #[clap(
+            help = "Version of the cached build, e.g. 5.2.1-7049, or the file name of the cache entry"
+        )]
+        artifact: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SnapshotCommand {
+    /// Saves the products installed on this machine as [name], keeping their installers cached
+    Save {
+        #[clap(help = "Name of the snapshot, e.g. `release` or `develop`")]
+        name: String,
+    },
+    /// Installs the products saved in the snapshot [name]
+    Restore {
+        #[clap(help = "Name of the snapshot")]
+        name: String,
+        #[clap(
+            long,
+            help = "whether to prompt to uninstall/replace. Set to false to uninstall all products automatically"
+        )]
+        prompt: Option<bool>,
+        #[clap(
+            long,
+            help = "whether to launch the installed applications automatically after a successful installation"
+        )]
+        autorun: Option<bool>,
+    },
+    /// Lists the saved snapshots
+    List,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AuditCommand {
+    /// Prints the latest entries of the audit log, oldest first
+    Tail {
+        #[clap(
+            short = 'n',
+            long,
+            default_value_t = 20,
+            help = "Number of entries to print"
+        )]
+        lines: usize,
+        #[clap(long, help = "Print the entries as the json lines they're stored as")]
+        json: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum RepoCommand {
+    /// Measures the latency and download throughput of repositories, to help order them in the configuration
+    Bench {
+        #[clap(
+            help = "Name of the repository, from the `Repositories` section of the gman_client_config.json5. Measures every repository if not specified"
+        )]
+        name: Option<String>,
+        #[clap(
+            long,
+            default_value_t = 8,
+            value_name = "MIB",
+            help = "How much of a sample artifact to download from each repository, in MiB"
+        )]
+        sample_size: u64,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AuthCommand {
+    /// Signs in to a repository, keeping the tokens in the system keyring
+    Login {
+        #[clap(
+            long,
+            value_name = "REPOSITORY",
+            help = "Signs in to the repository with the OAuth device-code flow of its OAuth RepositoryCredentials"
+        )]
+        oauth: String,
+    },
+    /// Removes the stored tokens of a repository
+    Logout {
+        #[clap(
+            help = "Name of the repository, from the `Repositories` section of the gman_client_config.json5"
+        )]
+        repository: String,
+    },
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Target {
     Version(String),
     Identifier(String),
+    /// `nightly`: the newest build finished within [NIGHTLY_WINDOW] on the product's nightly branch
+    Nightly,
+    /// `latest`: the newest build on any listed branch, regardless of date
+    Latest,
 }
 
+/// How recent a build must be to count as the `nightly` one
+pub const NIGHTLY_WINDOW: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
+
 impl ToString for Target {
     fn to_string(&self) -> String {
         match self {
             Target::Version(s) => s.to_owned(),
             Target::Identifier(s) => s.to_owned(),
+            Target::Nightly => "nightly".to_owned(),
+            Target::Latest => "latest".to_owned(),
         }
     }
 }
@@ -123,6 +697,12 @@ impl FromStr for Target {
     type Err = GManError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("nightly") {
+            return Ok(Target::Nightly);
+        }
+        if s.eq_ignore_ascii_case("latest") {
+            return Ok(Target::Latest);
+        }
         match VERSION_REGEX.find_iter(s).next() {
             Some(c) => {
                 let matches_vesion = c.as_str().to_owned();
@@ -140,9 +720,11 @@ lazy_static! {
 
 #[cfg(test)]
 mod tests {
-    use std::str::FromStr;
+    use std::{path::PathBuf, str::FromStr};
 
-    use super::Target;
+    use clap::Parser;
+
+    use super::{AuditCommand, Cli, Commands, Target};
 
     #[test]
     fn parse_target_identifier() {
@@ -166,10 +748,196 @@ mod tests {
         assert_eq!(target, Target::Version("5.2.1-7322".to_owned()))
     }
 
+    #[test]
+    fn parse_target_aliases() {
+        assert_eq!(Target::from_str("nightly").unwrap(), Target::Nightly);
+        assert_eq!(Target::from_str("Latest").unwrap(), Target::Latest);
+        assert_eq!(
+            Target::from_str("nightly_fixes").unwrap(),
+            Target::Identifier("nightly_fixes".to_owned())
+        );
+    }
+
     #[test]
     fn target_to_string() {
         let target = Target::Identifier("master".to_owned());
 
         assert_eq!(target.to_string(), "master")
     }
+
+    #[test]
+    fn parse_install_url() {
+        let cli = Cli::try_parse_from([
+            "gman",
+            "install",
+            "--url",
+            "https://example.com/handbookx.msix",
+            "--product",
+            "HandbookX",
+            "--version",
+            "1.2.3",
+            "--checksum",
+            "sha256:abcd",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Commands::Install { url, checksum, .. }) => {
+                assert_eq!(url.as_deref(), Some("https://example.com/handbookx.msix"));
+                assert_eq!(checksum.as_deref(), Some("sha256:abcd"));
+            }
+            _ => panic!("Expected install command"),
+        }
+
+        assert!(Cli::try_parse_from([
+            "gman",
+            "install",
+            "--url",
+            "https://example.com/handbookx.msix",
+            "--file",
+            "x.msix",
+            "--product",
+            "HandbookX"
+        ])
+        .is_err());
+        assert!(Cli::try_parse_from(["gman", "install", "hubkit", "--checksum", "abcd"]).is_err());
+    }
+
+    #[test]
+    fn parse_install_manifest() {
+        let cli = Cli::try_parse_from(["gman", "install", "--manifest", "machine.json"]).unwrap();
+        match cli.command {
+            Some(Commands::Install { name, manifest, .. }) => {
+                assert_eq!(name, None);
+                assert_eq!(manifest, Some(PathBuf::from("machine.json")));
+            }
+            _ => panic!("Expected install command"),
+        }
+        assert!(
+            Cli::try_parse_from(["gman", "install", "hubkit", "--manifest", "machine.json"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn parse_install_ssh_target() {
+        let cli = Cli::try_parse_from([
+            "gman", "install", "hubkit", "develop", "--target", "pi-lab-3",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Commands::Install { target, .. }) => {
+                assert_eq!(target.as_deref(), Some("pi-lab-3"));
+            }
+            _ => panic!("Expected install command"),
+        }
+        assert!(Cli::try_parse_from([
+            "gman",
+            "install",
+            "--file",
+            "hubkit.deb",
+            "--product",
+            "HubKit",
+            "--target",
+            "pi-lab-3"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn parse_report() {
+        let cli = Cli::try_parse_from(["gman", "report", "-o", "gman-report.zip"]).unwrap();
+        match cli.command {
+            Some(Commands::Report { output }) => {
+                assert_eq!(output, Some(PathBuf::from("gman-report.zip")))
+            }
+            _ => panic!("Expected report command"),
+        }
+    }
+
+    #[test]
+    fn parse_audit_tail() {
+        let cli = Cli::try_parse_from(["gman", "audit", "tail", "-n", "5", "--json"]).unwrap();
+        match cli.command {
+            Some(Commands::Audit {
+                command: AuditCommand::Tail { lines, json },
+            }) => {
+                assert_eq!(lines, 5);
+                assert!(json);
+            }
+            _ => panic!("Expected audit tail command"),
+        }
+    }
+
+    #[test]
+    fn parse_install_provision() {
+        let cli = Cli::try_parse_from(["gman", "install", "graviostudio", "--provision"]).unwrap();
+        match cli.command {
+            Some(Commands::Install { provision, .. }) => assert!(provision),
+            _ => panic!("Expected install command"),
+        }
+    }
+
+    #[test]
+    fn parse_install_launch_args() {
+        let cli = Cli::try_parse_from([
+            "gman",
+            "install",
+            "hubkit",
+            "develop",
+            "--launch-args",
+            "--debug --port 8080",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Commands::Install { launch_args, .. }) => {
+                assert_eq!(launch_args.as_deref(), Some("--debug --port 8080"));
+            }
+            _ => panic!("Expected install command"),
+        }
+    }
+
+    #[test]
+    fn parse_install_file() {
+        let cli = Cli::try_parse_from([
+            "gman",
+            "install",
+            "--file",
+            "./GravioHubKit.msi",
+            "--product",
+            "HubKit",
+            "--version",
+            "5.2.1-7049",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Commands::Install {
+                name,
+                file,
+                product,
+                version,
+                ..
+            }) => {
+                assert_eq!(name, None);
+                assert_eq!(file, Some(PathBuf::from("./GravioHubKit.msi")));
+                assert_eq!(product.as_deref(), Some("HubKit"));
+                assert_eq!(version.as_deref(), Some("5.2.1-7049"));
+            }
+            _ => panic!("Expected install command"),
+        }
+
+        /* --file needs to know the product, and can't be mixed with a repository search */
+        assert!(Cli::try_parse_from(["gman", "install", "--file", "x.msi"]).is_err());
+        assert!(Cli::try_parse_from(["gman", "install", "--product", "HubKit"]).is_err());
+        assert!(Cli::try_parse_from(["gman", "install"]).is_err());
+        assert!(Cli::try_parse_from([
+            "gman",
+            "install",
+            "hubkit",
+            "--file",
+            "x.msi",
+            "--product",
+            "HubKit"
+        ])
+        .is_err());
+    }
 }
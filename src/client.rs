@@ -1,29 +1,312 @@
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::str::FromStr as _;
+use std::time::{Duration, SystemTime};
 
-use std::process::Command;
-
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+use crate::candidate::InstallScope;
 #[cfg(target_os = "windows")]
 use crate::candidate::InstalledAppXProduct;
 use crate::candidate::{
-    InstallOverwriteOptions, InstallationCandidate, InstallationResult, InstalledProduct,
-    SearchCandidate, TablePrinter, Version,
+    CandidateKey, InstallOverwriteOptions, InstallationCandidate, InstallationResult,
+    InstalledProduct, SearchCandidate, TablePrinter, Version,
 };
-
-use crate::gman_error::GManError;
+#[cfg(target_os = "windows")]
+use crate::powershell::PowerShellScript;
+
+use crate::audit::{AuditAction, AuditEntry, AuditingRunner};
+use crate::bench::RepositoryBench;
+use crate::clock::{Clock, SystemClock};
+use crate::gman_error::{GManError, GManErrorKind};
+use crate::i18n::tr;
+use crate::install_summary::{ArtifactSource, InstallSummary};
+use crate::interaction::{ConsoleInteraction, Interaction};
+use crate::journal::{JournalAction, JournalEntry};
+use crate::manifest::{Manifest, ManifestEntry};
 use crate::platform::Platform;
 use crate::product::PackageType;
 use crate::product::Product;
-use crate::{app, product, team_city, util, CandidateRepository, ClientConfig};
+#[cfg(target_os = "windows")]
+use crate::sandbox;
+use crate::system_runner::{ProcessRunner, SystemRunner};
+use crate::transaction::{InstallPhase, InstallTransaction};
+use crate::{
+    app, audit, bench, blob_store, candidate, container, download, journal, notify, output,
+    preflight, product, snapshot, ssh, support, tags, team_city, transform, util,
+    CandidateRepository, ClientConfig, RepositoryType,
+};
+use futures_util::StreamExt;
 
 use tabled::settings::{object::Rows, Alignment, Modify, Style};
 
+/// Identifier (branch) recorded for artifacts installed from a local file instead of a repository
+pub const LOCAL_FILE_IDENTIFIER: &str = "local";
+
+/// Identifier (branch) recorded for artifacts installed from an arbitrary url
+pub const URL_IDENTIFIER: &str = "url";
+
+/// Put after the identifier of pinned cache entries by `cache --list`, see [Client::list_cache_with_pins]
+pub const PINNED_LABEL: &str = " (pinned)";
+
+/// Put after the identifier of cached candidates `list` falls back to, see [Client::list_cached_candidates]
+pub const CACHED_LABEL: &str = " (cached)";
+
+/// Folder of the cache and temp download directories MSI transforms from the repositories are downloaded to.
+/// Kept apart from the installers, so they're never taken for one
+const MSI_TRANSFORMS_FOLDER_NAME: &str = "msi_transforms";
+
+/// How often to check whether an autorun application has started, see [ClientConfig::autorun_verify_seconds]
+const AUTORUN_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long partial downloads in the temp download directory are kept to be resumed, see [download::partial_path]
+const PARTIAL_DOWNLOAD_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// How many artifact sizes [Client::candidate_sizes] looks up at the same time, so listing many builds
+/// doesn't flood the repository with requests
+const SIZE_LOOKUPS_AT_ONCE: usize = 8;
+
+/// An installer that doesn't come from one of the configured repositories
+#[derive(Debug)]
+pub enum ExternalArtifact<'a> {
+    /// Local installer file
+    File(&'a Path),
+    /// Installer hosted at a url, with an optional SHA-256 checksum to verify it against
+    Url {
+        url: &'a str,
+        checksum: Option<&'a str>,
+    },
+}
+
+/// Folder of a product `open` opens
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProductFolder {
+    /// The flavors' `DataPaths`
+    Data,
+    /// The flavors' `LogPaths`
+    Logs,
+    /// Where the product is installed
+    Install,
+}
+
+impl std::fmt::Display for ProductFolder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProductFolder::Data => write!(f, "data"),
+            ProductFolder::Logs => write!(f, "log"),
+            ProductFolder::Install => write!(f, "install"),
+        }
+    }
+}
+
+/// The Debian package name of the Deb [flavor]: matched by its `NameRegex`, or else the `<package>` of its
+/// binary's `<package>_<version>_<arch>.deb` file name
+#[cfg(target_os = "linux")]
+fn deb_package_matches(flavor: &product::Flavor, package: &str) -> bool {
+    match flavor
+        .metadata
+        .as_ref()
+        .and_then(|x| x.name_regex.as_deref())
+    {
+        Some(name_regex) => match regex::Regex::new(name_regex) {
+            Ok(regex) => regex.is_match(package),
+            Err(e) => {
+                log::warn!(
+                    "NameRegex {} of flavor {} is invalid: {}",
+                    name_regex,
+                    flavor.id,
+                    e
+                );
+                false
+            }
+        },
+        None => flavor
+            .teamcity_metadata
+            .teamcity_binary_path
+            .file_name()
+            .map(|x| x.to_string_lossy().to_lowercase())
+            .and_then(|x| x.split('_').next().map(|x| x.to_owned()))
+            .is_some_and(|x| x == package.to_lowercase()),
+    }
+}
+
+/// The packages of the Deb flavors of [products] in the [output] of
+/// `dpkg-query -W -f '${Package}\t${Version}\t${db:Status-Status}\t${Installed-Size}\n'`, their sizes in
+/// KiB. Packages that were removed but left their configuration behind aren't installed
+#[cfg(target_os = "linux")]
+fn parse_dpkg_packages(output: &str, products: &[&Product]) -> Vec<InstalledProduct> {
+    let mut installed = Vec::new();
+    for line in output.lines() {
+        let mut fields = line.split('\t');
+        let (Some(package), Some(version), Some(status)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        if status.trim() != "installed" {
+            continue;
+        }
+        let size = fields
+            .next()
+            .and_then(|x| x.trim().parse::<u64>().ok())
+            .map(|x| x * 1024);
+        let found = products.iter().find_map(|product| {
+            product
+                .flavors
+                .iter()
+                .filter(|x| x.package_type == PackageType::Deb)
+                .find(|x| deb_package_matches(x, package))
+                .map(|flavor| (product, flavor))
+        });
+        if let Some((product, flavor)) = found {
+            /* the epoch of `1:5.2.3-7023` isn't part of the build's version */
+            let version = version.split_once(':').map_or(version, |(_, x)| x);
+            installed.push(InstalledProduct {
+                product_name: product.name.to_owned(),
+                version: Version::new(version),
+                package_name: package.to_owned(),
+                package_type: PackageType::Deb,
+                flavor: Some(flavor.id.to_owned()),
+                /* dpkg installs files all over the file system, a package has no path of its own */
+                path: PathBuf::new(),
+                scope: InstallScope::Machine,
+                size,
+            });
+        }
+    }
+    installed
+}
+
+/// An installed product with a newer build on the branch it was installed from, see `list --outdated`
+#[derive(Debug, PartialEq)]
+pub struct OutdatedProduct {
+    pub product_name: String,
+    pub flavor: String,
+    pub branch: String,
+    pub installed: Version,
+    pub available: Version,
+}
+
+/// Outcome of installing one of the flavors handed to [Client::install_flavors]
+#[derive(Debug)]
+pub struct FlavorInstall {
+    pub flavor: String,
+    /// Version that was to be installed, None if no build was found
+    pub version: Option<Version>,
+    pub result: Result<InstallationResult, Box<dyn std::error::Error>>,
+}
+
+/// What an installation installed, for reporting it
+#[derive(Debug)]
+pub struct InstallReport {
+    pub version: Version,
+    /// Version that was replaced, if any
+    pub previous_version: Option<Version>,
+    /// Cached artifact it was installed from
+    pub artifact: PathBuf,
+    /// Bytes the installation takes on disk, when that can be told
+    pub size: Option<u64>,
+    /// Changes since [previous_version], newest first. Only looked up for upgrades
+    changes: Vec<team_city::TeamCityChange>,
+    summary: InstallSummary,
+}
+
 #[derive(Debug)]
 pub struct Client {
     pub config: ClientConfig,
     http_client: reqwest::Client,
+    interaction: Box<dyn Interaction>,
+    runner: Box<dyn SystemRunner>,
+    clock: Box<dyn Clock>,
+    /// Where the install journal and the audit log are kept, see [app::get_machine_state_directory]
+    state_directory: PathBuf,
+}
+
+/// Builds a [Client] with its http client, prompts, system runner, clock and directories supplied by the caller,
+/// for tests and for embedding gman as a library. Whatever isn't supplied is what the command line uses
+#[derive(Debug)]
+pub struct ClientBuilder {
+    config: ClientConfig,
+    http_client: Option<reqwest::Client>,
+    interaction: Option<Box<dyn Interaction>>,
+    runner: Option<Box<dyn SystemRunner>>,
+    clock: Option<Box<dyn Clock>>,
+    state_directory: Option<PathBuf>,
+}
+
+/* the command line itself only builds clients with the defaults, and the tests don't need every setter */
+#[allow(dead_code)]
+impl ClientBuilder {
+    pub fn new(config: ClientConfig) -> Self {
+        Self {
+            config,
+            http_client: None,
+            interaction: None,
+            runner: None,
+            clock: None,
+            state_directory: None,
+        }
+    }
+
+    /// Client for the repositories, e.g. one with a proxy or extra root certificates
+    pub fn http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.http_client = Some(http_client);
+        self
+    }
+
+    /// Answers the questions otherwise asked on the console
+    pub fn interaction(mut self, interaction: Box<dyn Interaction>) -> Self {
+        self.interaction = Some(interaction);
+        self
+    }
+
+    /// Runs the installers, package managers and other commands
+    pub fn runner(mut self, runner: Box<dyn SystemRunner>) -> Self {
+        self.runner = Some(runner);
+        self
+    }
+
+    pub fn clock(mut self, clock: Box<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Replaces the configured CacheDirectory
+    pub fn cache_directory(mut self, cache_directory: PathBuf) -> Self {
+        self.config.cache_directory = cache_directory;
+        self
+    }
+
+    /// Replaces the configured TempDownloadDirectory
+    pub fn temp_download_directory(mut self, temp_download_directory: PathBuf) -> Self {
+        self.config.temp_download_directory = temp_download_directory;
+        self
+    }
+
+    /// Keeps the install journal and the audit log in [state_directory] instead of the machine's
+    pub fn state_directory(mut self, state_directory: PathBuf) -> Self {
+        self.state_directory = Some(state_directory);
+        self
+    }
+
+    pub fn build(self) -> Client {
+        log::debug!("Instantiating new gman client");
+        Client {
+            config: self.config,
+            http_client: self
+                .http_client
+                .unwrap_or_else(|| reqwest::Client::builder().build().unwrap()),
+            interaction: self
+                .interaction
+                .unwrap_or_else(|| Box::new(ConsoleInteraction)),
+            runner: self.runner.unwrap_or_else(|| Box::new(ProcessRunner)),
+            clock: self.clock.unwrap_or_else(|| Box::new(SystemClock)),
+            state_directory: self
+                .state_directory
+                .unwrap_or_else(app::get_machine_state_directory),
+        }
+    }
 }
+
 impl Client {
     #[cfg(test)]
     pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
@@ -43,19 +326,47 @@ impl Client {
     }
 
     pub fn new(config: ClientConfig) -> Self {
-        log::debug!("Instantiating new gman client");
-        Self {
-            config,
-            http_client: reqwest::Client::builder().build().unwrap(),
-        }
+        ClientBuilder::new(config).build()
+    }
+
+    /// Where the install journal and the audit log are kept
+    pub fn state_directory(&self) -> &Path {
+        &self.state_directory
     }
 
-    /// Deletes the temporary folder
+    /// Deletes the temporary folders, except for recent partial downloads, so they can be resumed
     fn clear_temp(&self) {
         log::debug!("Clearing temporary folders");
         let app_temp_folder = std::env::temp_dir().join(app::APP_FOLDER_NAME);
         let _ = std::fs::remove_dir_all(app_temp_folder);
-        let _ = std::fs::remove_dir_all(&self.config.temp_download_directory);
+
+        let Ok(entries) = fs::read_dir(&self.config.temp_download_directory) else {
+            return;
+        };
+        let now = self.clock.now();
+        for entry in entries.filter_map(|x| x.ok()) {
+            let path = entry.path();
+            if path.is_dir() {
+                let _ = fs::remove_dir_all(&path);
+                continue;
+            }
+            let resumable = entry
+                .file_name()
+                .to_string_lossy()
+                .ends_with(download::PARTIAL_DOWNLOAD_SUFFIX)
+                && entry
+                    .metadata()
+                    .and_then(|x| x.modified())
+                    .ok()
+                    .and_then(|x| now.duration_since(x).ok())
+                    .map(|x| x < PARTIAL_DOWNLOAD_MAX_AGE)
+                    .unwrap_or(false);
+            if resumable {
+                log::debug!("Keeping partial download {}", path.display());
+            } else {
+                let _ = fs::remove_file(&path);
+            }
+        }
     }
 
     fn get_valid_repositories_for_platform(&self) -> Vec<&CandidateRepository> {
@@ -67,15 +378,29 @@ impl Client {
             .repositories
             .iter()
             .filter(|repo| {
-                (repo.repository_folder.is_some() || repo.repository_server.is_some())
-                    && (repo.platforms.is_empty()
-                        || (platform.is_some()
-                            && repo.platforms.contains(platform.as_ref().unwrap())))
+                repo.platforms.is_empty()
+                    || (platform.is_some() && repo.platforms.contains(platform.as_ref().unwrap()))
+            })
+            .filter(|repo| {
+                if !repo.repository_type.is_supported() {
+                    log::warn!(
+                        "Repository {} is a {} repository, which gman can't read builds from yet",
+                        repo.name,
+                        repo.repository_type
+                    );
+                    return false;
+                }
+                /* TeamCity repositories are read from their server, Folder repositories from their folder */
+                match repo.repository_type {
+                    RepositoryType::TeamCity => repo.repository_server.is_some(),
+                    RepositoryType::Folder => repo.repository_folder.is_some(),
+                    _ => true,
+                }
             })
             .collect();
 
         if valid_repositories.is_empty() {
-            log::warn!("No repositories available for searching. Either no repositories are known that match your current platform, or they dont have folder/server set");
+            log::warn!("No repositories available for searching. Either no repositories are known that match your current platform, or they dont have a server set");
         }
 
         valid_repositories
@@ -114,6 +439,7 @@ impl Client {
             current_platform,
             &valid_repositories,
             &self.config.products,
+            &self.config.cache_directory,
         )
         .await?;
 
@@ -122,41 +448,38 @@ impl Client {
         Ok(candidates)
     }
 
+    /// Uninstalls the installations of [name], returning the versions that were uninstalled
     pub fn uninstall<P>(
         &self,
         name: &str,
         version: Option<Version>,
         _path: Option<P>,
         prompt: Option<bool>,
-    ) -> Result<(), Box<dyn std::error::Error>>
+        purge: bool,
+    ) -> Result<Vec<Version>, Box<dyn std::error::Error>>
     where
         P: AsRef<Path>,
     {
         log::debug!("Attempting to find uninstallation target for {}", &name);
 
         println!("Looking to uninstall an item: {}", name);
-        let name_lower = name.to_lowercase();
+        let mut key = CandidateKey::new(name);
+        if let Some(v) = &version {
+            key = key.version(v);
+        }
         let installed = self.get_installed();
         let uninstall_candidates = installed
             .iter()
-            .filter(|candidate| {
-                if candidate.product_name.to_lowercase() == name_lower {
-                    if let Some(v) = &version {
-                        &candidate.version == v
-                    } else {
-                        true
-                    }
-                } else {
-                    false
-                }
-            })
+            .filter(|candidate| key.matches(&candidate.key()))
             .collect::<Vec<&InstalledProduct>>();
 
         if uninstall_candidates.is_empty() {
             eprintln!("No item named {} found on system, cannot uninstall", &name);
             Err(Box::new(GManError::new("No item found")))
         } else {
+            let confirm_purge = prompt.unwrap_or(true);
             let prompt = prompt.unwrap_or(true) && uninstall_candidates.len() > 1;
+            let mut uninstalled = Vec::new();
             for candidate in uninstall_candidates {
                 log::debug!("Found uninstallation target, will attempt an uninstall");
                 println!(
@@ -170,41 +493,219 @@ impl Client {
                 );
 
                 if prompt {
-                    if !Self::prompt_confirm()? {
+                    if !self.interaction.confirm()? {
                         println!("Will not uninstall this item");
                         continue;
                     }
                 }
-                candidate.shutdown()?;
-                candidate.uninstall()?;
+                candidate.shutdown(&self.audited_runner(&candidate.product_name))?;
+                let result =
+                    candidate.uninstall(&self.audited_runner(&candidate.product_name), purge);
+                self.audit_outcome(
+                    AuditAction::Uninstall,
+                    &candidate.product_name,
+                    candidate.version.as_ref(),
+                    &result,
+                );
+                result?;
+                #[cfg(target_os = "linux")]
+                self.remove_installed_files(&candidate.product_name)?;
                 println!("Successfully uninstalled {}", &candidate.product_name);
+                self.record_in_journal(
+                    JournalAction::Uninstall,
+                    &candidate.product_name,
+                    &candidate.version,
+                    None,
+                );
+                uninstalled.push(candidate.version.clone());
+            }
+            if purge && !uninstalled.is_empty() {
+                self.purge_data_paths(name, confirm_purge)?;
             }
-            Ok(())
+            Ok(uninstalled)
         }
     }
 
-    fn prompt_confirm() -> Result<bool, Box<dyn std::error::Error>> {
-        let mut buffer = String::new();
-        std::io::stdin().read_line(&mut buffer)?;
-        Ok(Self::is_console_confirm(&buffer))
+    /// Paths listed in the `DataPaths` of [product_name]'s flavors for this platform that exist on disk
+    fn find_data_paths(&self, product_name: &str) -> Vec<PathBuf> {
+        self.find_metadata_paths(product_name, |x| x.data_paths.as_ref())
     }
 
-    fn prompt_installation_choice() -> Result<InstallOverwriteOptions, Box<dyn std::error::Error>> {
-        if cfg!(windows) {
-            eprintln!("What would you like to do with this item? [o]verwrite, or [c]ancel?");
-        } else {
-            eprintln!("What would you like to do with this item? [o]verwrite, [a]dd an extra installation, or [c]ancel?");
+    /// Metadata of [product_name]'s flavors for this platform
+    fn current_flavor_metadata(&self, product_name: &str) -> Vec<&product::FlavorMetadata> {
+        let current_platform =
+            Platform::platform_for_current_platform().expect("Expected supported platform");
+        self.config
+            .products
+            .iter()
+            .filter(|x| x.name.eq_ignore_ascii_case(product_name))
+            .flat_map(|x| &x.flavors)
+            .filter(|x| x.platform == current_platform)
+            .filter_map(|x| x.metadata.as_ref())
+            .collect()
+    }
+
+    /// Paths [listed] in the metadata of [product_name]'s flavors for this platform that exist on disk
+    fn find_metadata_paths(
+        &self,
+        product_name: &str,
+        listed: fn(&product::FlavorMetadata) -> Option<&Vec<String>>,
+    ) -> Vec<PathBuf> {
+        let mut paths = self
+            .current_flavor_metadata(product_name)
+            .into_iter()
+            .filter_map(listed)
+            .flatten()
+            .map(|x| PathBuf::from(ClientConfig::shell_expand(x)))
+            .filter(|x| x.exists())
+            .collect::<Vec<PathBuf>>();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+
+    /// Folders where [product_name] is installed: those of its installations, and the `InstallPath` of its
+    /// flavors for this platform. Installations that are files, like a StandaloneExe's program, give the
+    /// folder they're in
+    fn find_install_directories(&self, product_name: &str) -> Vec<PathBuf> {
+        let installed = self
+            .get_installed()
+            .into_iter()
+            .filter(|x| x.product_name.eq_ignore_ascii_case(product_name))
+            .map(|x| x.path)
+            .filter(|x| !x.as_os_str().is_empty());
+        let configured = self
+            .current_flavor_metadata(product_name)
+            .into_iter()
+            .filter_map(|x| x.install_path.as_ref())
+            .map(|x| PathBuf::from(ClientConfig::shell_expand(x)));
+        let mut directories = installed
+            .chain(configured)
+            .filter(|x| x.exists())
+            .map(|x| match (x.is_file(), x.parent()) {
+                (true, Some(parent)) => parent.to_path_buf(),
+                _ => x,
+            })
+            .collect::<Vec<PathBuf>>();
+        directories.sort();
+        directories.dedup();
+        directories
+    }
+
+    /// Opens the [folder] folders of [product_name] in Explorer, Finder or the desktop's file manager, and
+    /// returns them. Fails if the product has none on disk
+    pub fn open_product_folder(
+        &self,
+        product_name: &str,
+        folder: ProductFolder,
+    ) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+        if Product::from_name(product_name, &self.config.products).is_none() {
+            return Err(Box::new(GManError::new(&format!(
+                "Product {} is not in the configuration",
+                product_name
+            ))));
+        }
+        let folders = match folder {
+            ProductFolder::Data => self.find_data_paths(product_name),
+            ProductFolder::Logs => self.find_metadata_paths(product_name, |x| x.log_paths.as_ref()),
+            ProductFolder::Install => self.find_install_directories(product_name),
+        };
+        if folders.is_empty() {
+            return Err(Box::new(GManError::new(&format!(
+                "No {} folder of {} found on this machine",
+                folder, product_name
+            ))));
+        }
+        for path in &folders {
+            let path = path.to_string_lossy();
+            let output = if cfg!(target_os = "windows") {
+                self.runner.run("explorer.exe", &[&path])?
+            } else if cfg!(target_os = "macos") {
+                /* an app bundle is revealed, opening it would launch the app */
+                let mut args = Vec::new();
+                if path.ends_with(".app") {
+                    args.push("-R");
+                }
+                args.push(&path);
+                self.runner.run("open", &args)?
+            } else {
+                self.runner.run("xdg-open", &[&path])?
+            };
+            /* explorer exits with 1 even when it opened the folder */
+            if !output.status.success() && !cfg!(target_os = "windows") {
+                return Err(Box::new(GManError::new(&format!(
+                    "Failed to open {}: {}",
+                    path,
+                    output.stderr.trim()
+                ))));
+            }
+        }
+        Ok(folders)
+    }
+
+    /// Deletes the leftover data directories of [product_name] after it was uninstalled, asking first if [prompt] is set
+    fn purge_data_paths(
+        &self,
+        product_name: &str,
+        prompt: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let paths = self.find_data_paths(product_name);
+        if paths.is_empty() {
+            println!("No leftover data found for {}", product_name);
+            return Ok(());
+        }
+
+        println!(
+            "The following data of {} will be permanently deleted:",
+            product_name
+        );
+        for path in &paths {
+            println!("  {}", path.display());
+        }
+        if prompt {
+            println!("Delete? [y/N]");
+            if !self.interaction.confirm()? {
+                println!("Leaving data in place");
+                return Ok(());
+            }
         }
-        let mut buffer = String::new();
-        std::io::stdin().read_line(&mut buffer)?;
-        let s = InstallOverwriteOptions::from_str(&buffer.to_lowercase().trim())?;
-        if cfg!(windows) {
-            if let InstallOverwriteOptions::Add = s {
-                log::debug!("Setting installation option to overwrite, because /add/ isnt supported for Windows installations");
-                return Ok(InstallOverwriteOptions::Overwrite);
+
+        let mut failed: usize = 0;
+        for path in &paths {
+            log::debug!("Purging {}", path.display());
+            let result = if path.is_dir() {
+                fs::remove_dir_all(path)
+            } else {
+                fs::remove_file(path)
+            };
+            audit::record_delete(&self.state_directory, Some(product_name), path, &result);
+            if let Err(e) = result {
+                eprintln!("Failed to delete {}: {}", path.display(), e);
+                failed += 1;
             }
         }
-        Ok(s)
+        if failed > 0 {
+            return Err(Box::new(GManError::new(&format!(
+                "{} of {} data paths of {} couldn't be deleted",
+                failed,
+                paths.len(),
+                product_name
+            ))));
+        }
+        println!("Deleted leftover data of {}", product_name);
+        Ok(())
+    }
+
+    /// Stores the newly cached artifact at [cached_path] content-addressed, see [blob_store::deduplicate].
+    /// The artifact is usable either way, so failures are only logged
+    fn deduplicate_cached(&self, cached_path: &Path) {
+        if let Err(e) = blob_store::deduplicate(&self.config.cache_directory, cached_path) {
+            log::warn!(
+                "Failed to deduplicate cached artifact {}: {}",
+                cached_path.display(),
+                e
+            );
+        }
     }
 
     async fn download(
@@ -212,16 +713,13 @@ impl Client {
         search: &SearchCandidate,
     ) -> Result<Option<InstallationCandidate>, Box<dyn std::error::Error>> {
         let valid_repositories = self.get_valid_repositories_for_platform();
-        let result = team_city::get_with_build_id_by_candidate(
-            &self.http_client,
-            search,
-            &valid_repositories,
-        )
-        .await?;
+        let result = self
+            .find_installable_build(search, &valid_repositories)
+            .await?;
 
         match result {
             Some(found) => {
-                let _ = team_city::download_artifact(
+                let cached_path = team_city::download_artifact(
                     &self.http_client,
                     &found.0,
                     &found.1,
@@ -230,6 +728,7 @@ impl Client {
                     self.config.teamcity_download_chunk_size,
                 )
                 .await?;
+                self.deduplicate_cached(&cached_path);
 
                 Ok(Some(found.0))
             }
@@ -240,18 +739,170 @@ impl Client {
         }
     }
 
-    async fn get_build_server_version_if_higher_or_also_from_cache(
+    /// Finds the build [search] asks for on the [valid_repositories] to install it, passing over the builds its
+    /// product blocks, see [team_city::get_installable_build_by_candidate]
+    async fn find_installable_build<'a>(
         &self,
-        cached: InstallationCandidate,
         search: &SearchCandidate,
-        valid_repositories: &Vec<&CandidateRepository>,
-    ) -> Result<InstallationCandidate, Box<dyn std::error::Error>> {
-        match team_city::get_with_build_id_by_candidate(
+        valid_repositories: &[&'a CandidateRepository],
+    ) -> Result<Option<(InstallationCandidate, &'a CandidateRepository)>, Box<dyn std::error::Error>>
+    {
+        match Product::from_name(&search.product_name, &self.config.products) {
+            Some(product) => {
+                team_city::get_installable_build_by_candidate(
+                    &self.http_client,
+                    search,
+                    product,
+                    valid_repositories,
+                )
+                .await
+            }
+            None => {
+                team_city::get_with_build_id_by_candidate(
+                    &self.http_client,
+                    search,
+                    valid_repositories,
+                )
+                .await
+            }
+        }
+    }
+
+    /// Resolves the `latest`/`nightly` targets: the newest successful build of [search]'s flavor,
+    /// on [branch] if given, finished after [since] if given
+    pub async fn find_newest_build(
+        &self,
+        search: &SearchCandidate,
+        branch: Option<&str>,
+        since: Option<SystemTime>,
+    ) -> Result<Option<InstallationCandidate>, Box<dyn std::error::Error>> {
+        let product = match Product::from_name(&search.product_name, &self.config.products) {
+            Some(p) => p,
+            None => {
+                return Err(Box::new(GManError::new(&format!(
+                    "Product {} is not in the configuration",
+                    search.product_name
+                ))))
+            }
+        };
+        let valid_repositories = self.get_valid_repositories_for_platform();
+        let found = team_city::get_newest_build(
             &self.http_client,
             search,
+            product,
+            branch,
+            since,
             &valid_repositories,
         )
-        .await
+        .await?;
+        Ok(found.map(|(candidate, _)| candidate))
+    }
+
+    /// Resolves a build linked to from the TeamCity web UI to the product, flavor and build to install, looking
+    /// the build up on the configured repository on the same server. That very build is downloaded into the
+    /// cache, so installing the returned search doesn't look for a build of the same number elsewhere
+    pub async fn fetch_build_by_url(
+        &self,
+        build_url: &team_city::BuildUrl,
+    ) -> Result<SearchCandidate, Box<dyn std::error::Error>> {
+        let Some((product, flavor)) =
+            Product::from_teamcity_id(&build_url.build_type_id, &self.config.products)
+        else {
+            return Err(Box::new(GManError::new(&format!(
+                "No flavor in the configuration is built by the TeamCity build configuration {}",
+                build_url.build_type_id
+            ))));
+        };
+        let current_platform = Platform::platform_for_current_platform();
+        if current_platform.as_ref() != Some(&flavor.platform) {
+            return Err(Box::new(GManError::new(&format!(
+                "The TeamCity build configuration {} builds {} for {}, it can't be installed on this machine",
+                build_url.build_type_id, &flavor.id, &flavor.platform
+            ))));
+        }
+        let repo = self
+            .get_valid_repositories_for_platform()
+            .into_iter()
+            .filter(|x| x.repository_type == RepositoryType::TeamCity)
+            .find(|x| {
+                x.repository_server
+                    .as_deref()
+                    .and_then(|x| team_city::ensure_scheme(x).ok())
+                    .and_then(|x| {
+                        x.host_str()
+                            .map(|x| x.eq_ignore_ascii_case(&build_url.host))
+                    })
+                    .unwrap_or(false)
+            });
+        let Some(repo) = repo else {
+            return Err(Box::new(GManError::new(&format!(
+                "No TeamCity repository in the configuration is on {}",
+                build_url.host
+            ))));
+        };
+
+        let build = team_city::get_build_by_id(&self.http_client, repo, build_url.build_id).await?;
+        if build.status.as_deref().is_some_and(|x| x != "SUCCESS") {
+            return Err(Box::new(GManError::new(&format!(
+                "Build {} of {} didn't succeed",
+                build.build_number, product.name
+            ))));
+        }
+        /* a url naming another configuration than the build's own would install the wrong flavor */
+        if let Some(build_type_id) = &build.build_type_id {
+            if !build_type_id.eq_ignore_ascii_case(&flavor.teamcity_metadata.teamcity_id) {
+                return Err(Box::new(GManError::new(&format!(
+                    "Build {} is built by the TeamCity build configuration {}, not by {} of flavor {}",
+                    build_url.build_id, build_type_id, build_url.build_type_id, &flavor.id
+                ))));
+            }
+        }
+        if !build.has_artifacts() {
+            return Err(Box::new(GManError::new(&format!(
+                "Build {} of {} has expired, its artifacts are gone",
+                build.build_number, product.name
+            ))));
+        }
+
+        let candidate = InstallationCandidate {
+            remote_id: build.id.to_string(),
+            product_name: product.name.to_owned(),
+            version: Version::new(&build.build_number),
+            identifier: build.branch_name.unwrap_or(build.build_number.to_owned()),
+            flavor: flavor.for_machine()?,
+            repo_location: repo.repository_server.to_owned().unwrap_or_default(),
+            installed: false,
+            expired: false,
+        };
+        if !self.cached_path(&candidate).exists() {
+            let cached_path = team_city::download_artifact(
+                &self.http_client,
+                &candidate,
+                repo,
+                &self.config.temp_download_directory,
+                &self.config.cache_directory,
+                self.config.teamcity_download_chunk_size,
+            )
+            .await?;
+            self.deduplicate_cached(&cached_path);
+        }
+        Ok(SearchCandidate {
+            product_name: candidate.product_name,
+            version: Some(candidate.version),
+            identifier: Some(candidate.identifier),
+            flavor: candidate.flavor,
+        })
+    }
+
+    async fn get_build_server_version_if_higher_or_also_from_cache(
+        &self,
+        cached: InstallationCandidate,
+        search: &SearchCandidate,
+        valid_repositories: &Vec<&CandidateRepository>,
+    ) -> Result<InstallationCandidate, Box<dyn std::error::Error>> {
+        match self
+            .find_installable_build(search, valid_repositories)
+            .await
         {
             Ok(res) => match res {
                 Some(found_on_server) => {
@@ -295,6 +946,7 @@ impl Client {
         }
     }
 
+    /// Installs [search], reporting the outcome to the configured [ClientConfig::notifications]
     pub async fn install(
         &self,
         search: &SearchCandidate,
@@ -302,48 +954,295 @@ impl Client {
         prompt: Option<bool>,
         autorun: Option<bool>,
     ) -> Result<InstallationResult, Box<dyn std::error::Error>> {
-        log::debug!(
-            "Setting up installation prep for {} @ {}",
-            &search.product_name,
-            &search.version_or_identifier_string(),
-        );
+        self.install_reported(search, automatic_upgrade, prompt, autorun)
+            .await
+            .map(|(x, _)| x)
+    }
 
-        /* Locate the resource (check if in cache, if not, check online) */
-        let cached_candidate = self.locate_in_cache(search);
+    /// [Client::install], along with what was installed when it succeeded
+    pub async fn install_reported(
+        &self,
+        search: &SearchCandidate,
+        automatic_upgrade: Option<bool>,
+        prompt: Option<bool>,
+        autorun: Option<bool>,
+    ) -> Result<(InstallationResult, Option<InstallReport>), Box<dyn std::error::Error>> {
+        let mut result = self
+            .install_search(search, automatic_upgrade, prompt, autorun)
+            .await;
+        if let Ok((InstallationResult::Succeeded, Some(report))) = &mut result {
+            /* finding the installation again and measuring it takes a while, only the result object has a use for it */
+            if output::is_json() {
+                report.size = self.installed_footprint(&search.product_name, &report.version);
+            }
+            report.summary.size = report.size;
+            println!("{}", report.summary);
+            self.record_in_journal(
+                JournalAction::Install,
+                &search.product_name,
+                &report.version,
+                report.size,
+            );
+        }
+        self.notify_install(search, &result).await;
+        result
+    }
 
-        let actual_candidate = match cached_candidate {
-            Some(cached) => {
-                log::debug!(
-                    "Found installation executable for {}@{} in path",
-                    &search.product_name,
-                    &search.version_or_identifier_string()
-                );
+    /// Installs [search] in a new Windows Sandbox instead of on this machine, so a risky build can be tried
+    /// out without leaving anything behind. A copy of gman installs the cached artifact in the sandbox, and
+    /// closing the sandbox discards it. Returns the .wsb configuration the sandbox was started with
+    #[cfg(target_os = "windows")]
+    pub async fn install_in_sandbox(
+        &self,
+        search: &SearchCandidate,
+        autorun: Option<bool>,
+    ) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        if search.flavor.platform != Platform::Windows {
+            return Err(Box::new(GManError::new(&format!(
+                "Only Windows flavors can be installed in Windows Sandbox, {} is for {}",
+                &search.flavor.id, &search.flavor.platform
+            ))));
+        }
+        let product =
+            Product::from_name(&search.product_name, &self.config.products).ok_or_else(|| {
+                GManError::new(&format!(
+                    "Product {} is not in the configuration",
+                    &search.product_name
+                ))
+            })?;
+        let candidate = match self.locate_in_cache(search) {
+            Some(cached) => cached,
+            None => self.download(search).await?.ok_or_else(|| {
+                GManError::new(&format!(
+                    "No build of {} to install in Windows Sandbox",
+                    &search.product_name
+                ))
+            })?,
+        };
+        let artifact = self.cached_path(&candidate);
+        Self::verify_artifact(&candidate, &artifact)?;
 
-                if let None = search.version {
-                    let valid_repositories = self.get_valid_repositories_for_platform();
+        let staging = self
+            .config
+            .cache_directory
+            .join(sandbox::SANDBOX_FOLDER_NAME)
+            .join(candidate.make_cached_file_name());
+        let wsb = sandbox::prepare(
+            &staging,
+            &candidate,
+            &artifact,
+            &std::env::current_exe()?,
+            product,
+            &self.config.publisher_identities,
+            autorun,
+        )?;
+        sandbox::launch(self.runner.as_ref(), &wsb)?;
+        Ok(wsb)
+    }
 
-                    match automatic_upgrade {
-                        Some(should_upgrade) => match should_upgrade {
-                            false => {
-                                println!("A candidate for installation has been found in the local cache. Because version information wasnt specified, it may be outdated, but automatic upgrade was false. Will install local cache version.");
-                                cached
-                            }
-                            true => {
-                                println!("A candidate for installation has been found in the local cache. Automatic upgrade is true, will attempt to find later version on build server and will use this cached item as fallback");
+    #[cfg(not(target_os = "windows"))]
+    pub async fn install_in_sandbox(
+        &self,
+        _search: &SearchCandidate,
+        _autorun: Option<bool>,
+    ) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        Err(Box::new(GManError::new(
+            "Windows Sandbox is only available on Windows",
+        )))
+    }
 
-                                self.get_build_server_version_if_higher_or_also_from_cache(
-                                    cached,
-                                    search,
-                                    &valid_repositories,
-                                )
-                                .await?
-                            }
-                        },
-                        None => {
-                            /* version unspecified, prompt user to optionally fetch latest from build server */
-                            println!("A candidate for installation has been found in the local cache, but since the version was unspecified it may be oudated. Would you like to check the remote repositories for updated versions? [y/N]");
+    /// Notes in the install journal that the current user did [action] to [version] of [product_name], and
+    /// the [size] an install took on disk. Failing to is only logged, the journal is informational
+    fn record_in_journal(
+        &self,
+        action: JournalAction,
+        product_name: &str,
+        version: &Version,
+        size: Option<u64>,
+    ) {
+        let mut entry = JournalEntry::new(action, product_name, version);
+        entry.size = size;
+        #[cfg(target_os = "linux")]
+        if action == JournalAction::Install {
+            entry.files = crate::desktop::installed_paths(product_name);
+        }
+        if let Err(e) = journal::append(&self.state_directory, &entry) {
+            log::warn!("Failed to write the install journal: {}", e);
+        }
+    }
+
+    /// Bytes the installation of [version] of [product_name] takes on disk, see [InstalledProduct::footprint]
+    fn installed_footprint(&self, product_name: &str, version: &Version) -> Option<u64> {
+        let key = CandidateKey::new(product_name).version(version);
+        let footprint = self
+            .get_installed()
+            .iter()
+            .find(|x| key.matches(&x.key()))
+            .and_then(|x| x.footprint());
+        /* standalone programs on Linux are only listed once the journal has their install */
+        #[cfg(target_os = "linux")]
+        if footprint.is_none() {
+            let directory = crate::desktop::install_directory(product_name);
+            if directory.exists() {
+                return Some(crate::util::directory_size(directory));
+            }
+        }
+        footprint
+    }
+
+    /// Fails with a report of everything missing for installing [flavor] of [product_name], before anything
+    /// is downloaded or uninstalled. Skipped with `--skip-prerequisites`
+    fn check_prerequisites(
+        &self,
+        product_name: &str,
+        flavor: &product::Flavor,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if app::is_skipping_prerequisites() {
+            return Ok(());
+        }
+        let missing = preflight::check(
+            self.runner.as_ref(),
+            product_name,
+            flavor,
+            &[
+                &self.config.cache_directory,
+                &self.config.temp_download_directory,
+            ],
+        );
+        if missing.is_empty() {
+            return Ok(());
+        }
+        Err(Box::new(GManError::new(
+            preflight::report(product_name, &missing).trim_end(),
+        )))
+    }
+
+    /// The last [count] entries of the audit log, oldest first, as json lines
+    pub fn audit_tail(&self, count: usize) -> Vec<String> {
+        audit::tail(&self.state_directory, count)
+    }
+
+    /// [SystemRunner] adding the commands it runs for [product_name] to the audit log
+    fn audited_runner<'a>(&'a self, product_name: &'a str) -> AuditingRunner<'a> {
+        AuditingRunner::new(self.runner.as_ref(), &self.state_directory, product_name)
+    }
+
+    /// Adds the outcome of doing [action] to [target] of [product_name] to the audit log
+    fn audit_outcome<T>(
+        &self,
+        action: AuditAction,
+        product_name: &str,
+        target: &str,
+        result: &Result<T, Box<dyn std::error::Error>>,
+    ) {
+        audit::append(
+            &self.state_directory,
+            &AuditEntry::new(
+                action,
+                Some(product_name),
+                target,
+                result.as_ref().map(|_| ()).map_err(|e| e.to_string()),
+            ),
+        );
+    }
+
+    /// Posts the [result] of installing [search] to the webhook, if one is configured. Skipped, canceled
+    /// and interrupted installations aren't reported
+    async fn notify_install(
+        &self,
+        search: &SearchCandidate,
+        result: &Result<(InstallationResult, Option<InstallReport>), Box<dyn std::error::Error>>,
+    ) {
+        let Some(webhook) = self
+            .config
+            .notifications
+            .as_ref()
+            .and_then(|x| x.webhook.as_deref())
+        else {
+            return;
+        };
+        let text = match result {
+            Ok((InstallationResult::Succeeded, Some(report))) => {
+                let mut text = notify::install_succeeded_message(
+                    &search.product_name,
+                    report.version.as_ref(),
+                    &notify::host_name(),
+                );
+                if let Some(previous_version) = &report.previous_version {
+                    if !report.changes.is_empty() {
+                        text.push('\n');
+                        text.push_str(&notify::changelog(
+                            previous_version.as_ref(),
+                            &report.changes,
+                        ));
+                    }
+                }
+                text
+            }
+            Err(e) if !GManError::is_cancelled(e.as_ref()) => notify::install_failed_message(
+                &search.product_name,
+                search.version_or_identifier_string(),
+                &notify::host_name(),
+                &e.to_string(),
+            ),
+            _ => return,
+        };
+        notify::post(&self.http_client, webhook, &text).await;
+    }
+
+    async fn install_search(
+        &self,
+        search: &SearchCandidate,
+        automatic_upgrade: Option<bool>,
+        prompt: Option<bool>,
+        autorun: Option<bool>,
+    ) -> Result<(InstallationResult, Option<InstallReport>), Box<dyn std::error::Error>> {
+        log::debug!(
+            "Setting up installation prep for {} @ {}",
+            &search.product_name,
+            &search.version_or_identifier_string(),
+        );
+
+        self.check_prerequisites(&search.product_name, &search.flavor)?;
+        let mut summary = InstallSummary::new(self.clock.now());
+
+        /* Locate the resource (check if in cache, if not, check online) */
+        let cached_candidate = self.locate_in_cache(search);
+        let cached_file_name = cached_candidate.as_ref().map(|x| x.make_cached_file_name());
+
+        let mut actual_candidate = match cached_candidate {
+            Some(cached) => {
+                log::debug!(
+                    "Found installation executable for {}@{} in path",
+                    &search.product_name,
+                    &search.version_or_identifier_string()
+                );
+
+                if let None = search.version {
+                    let valid_repositories = self.get_valid_repositories_for_platform();
+
+                    match automatic_upgrade {
+                        Some(should_upgrade) => match should_upgrade {
+                            false => {
+                                println!("A candidate for installation has been found in the local cache. Because version information wasnt specified, it may be outdated, but automatic upgrade was false. Will install local cache version.");
+                                cached
+                            }
+                            true => {
+                                println!("A candidate for installation has been found in the local cache. Automatic upgrade is true, will attempt to find later version on build server and will use this cached item as fallback");
+
+                                self.get_build_server_version_if_higher_or_also_from_cache(
+                                    cached,
+                                    search,
+                                    &valid_repositories,
+                                )
+                                .await?
+                            }
+                        },
+                        None => {
+                            /* version unspecified, prompt user to optionally fetch latest from build server */
+                            println!("A candidate for installation has been found in the local cache, but since the version was unspecified it may be oudated. Would you like to check the remote repositories for updated versions? [y/N]");
                             println!("{}, {}", &cached.product_name, &cached.version);
-                            if Self::prompt_confirm()? {
+                            if self.interaction.confirm()? {
                                 println!("Will search for more recent versions, and will use this cached item as fallback");
                                 self.get_build_server_version_if_higher_or_also_from_cache(
                                     cached,
@@ -369,102 +1268,1941 @@ impl Client {
                 &search.version_or_identifier_string()
             );
 
-                match self.download(search).await? {
-                    Some(found) => found,
-                    None => return Ok(InstallationResult::Skipped),
+                match self.download(search).await? {
+                    Some(found) => found,
+                    None => return Ok((InstallationResult::Skipped, None)),
+                }
+            }
+        };
+
+        if let Some(reason) = self.blocked_reason(&actual_candidate) {
+            if !app::is_allowing_blocked_builds() {
+                return Err(Box::new(GManError::new(&format!(
+                    "Build {} of {} is blocked by the configuration ({}), pass --allow-blocked to install it anyway",
+                    &actual_candidate.version, &actual_candidate.product_name, reason
+                ))));
+            }
+            eprintln!(
+                "Installing build {} even though it's blocked by the configuration ({})",
+                &actual_candidate.version, reason
+            );
+        }
+        if let Some(minimum) = self.config.minimum_version(&actual_candidate.product_name) {
+            /* a version that can't be compared, like the one of a file named without it, isn't let through */
+            if !matches!(
+                actual_candidate.version.partial_cmp(&minimum),
+                Some(std::cmp::Ordering::Greater | std::cmp::Ordering::Equal)
+            ) {
+                return Err(Box::new(GManError::with_kind(
+                    GManErrorKind::PolicyViolation,
+                    &format!(
+                        "Build {} of {} is below the minimum version {} required by the configuration's MinimumVersions policy",
+                        &actual_candidate.version, &actual_candidate.product_name, minimum
+                    ),
+                )));
+            }
+        }
+
+        /* uninstall any previous, old versions */
+        let binary_path = self.cached_path(&actual_candidate);
+        let artifact = binary_path.clone();
+        summary.product_name = actual_candidate.product_name.clone();
+        summary.version = actual_candidate.version.to_string();
+        summary.build_id = actual_candidate.remote_id.clone();
+        summary.identifier = actual_candidate.identifier.clone();
+        summary.source = Some(self.artifact_source(
+            &actual_candidate,
+            &binary_path,
+            cached_file_name.as_deref(),
+        ));
+        summary.end_phase("fetch", self.clock.now());
+        /* never touch the current installation unless the new installer is actually usable */
+        Self::verify_artifact(&actual_candidate, &binary_path)?;
+        let binary_path = self.transform_artifact(&actual_candidate, &binary_path)?;
+        self.fetch_msi_transforms(&mut actual_candidate).await?;
+        summary.end_phase("prepare", self.clock.now());
+        let all_installed = &self.get_installed();
+        let already_installed = all_installed
+            .iter()
+            .filter(|x| search.key().without_build().matches(&x.key()))
+            /* provisioning replaces only the provisioned package, never the current user's own install */
+            .filter(|x| {
+                x.package_type != PackageType::AppX
+                    || (x.scope == InstallScope::Provisioned) == app::is_provisioning_packages()
+            })
+            .filter(|x| {
+                x.should_uninstall(self.runner.as_ref(), &binary_path)
+                    .unwrap_or(false)
+            })
+            .collect::<Vec<&InstalledProduct>>();
+
+        if already_installed
+            .iter()
+            .any(|x| x.version == actual_candidate.version)
+        {
+            eprintln!(
+                "This version ({}) of the product is already installed on machine. Skipping.",
+                actual_candidate.version
+            );
+            return Ok((InstallationResult::Skipped, None));
+        }
+
+        let install_options = match already_installed.is_empty() {
+            true => InstallOverwriteOptions::Overwrite,
+            false => {
+                eprintln!(
+                    "Product already installed on machine. Uninstalling before continuing..."
+                );
+                if prompt.unwrap_or(true) {
+                    self.interaction.installation_choice()?
+                } else {
+                    InstallOverwriteOptions::Overwrite
+                }
+            }
+        };
+
+        /* from here on the machine may be left without the product if we die, leave a marker to resume from */
+        let mut marker = InstallTransaction::new(&actual_candidate, InstallPhase::Uninstalling)
+            .begin(&self.config.cache_directory)?;
+
+        /* the version being replaced, and its installer from the cache to restore it with if the new one fails */
+        let mut previous: Option<(&InstalledProduct, Option<InstallationCandidate>)> = None;
+        /* the version an installer doing major upgrades replaces by itself, rolling back on its own too */
+        let mut upgraded_in_place: Option<Version> = None;
+        match install_options {
+            InstallOverwriteOptions::Overwrite => {
+                eprintln!("Will overwrite any existing installations with this one");
+
+                if already_installed.is_empty() {
+                    eprintln!("No products to uninstall, continuing with new installation");
+                } else {
+                    for already in &already_installed {
+                        if !self.close_if_running(already, prompt)? {
+                            eprintln!(
+                                "Wont continue with installation while {} is running",
+                                &already.product_name
+                            );
+                            return Ok((InstallationResult::Canceled, None));
+                        }
+                    }
+                    if actual_candidate.flavor.installer_handles_upgrade() {
+                        eprintln!("The installer upgrades the installed product itself, continuing without uninstalling it");
+                        upgraded_in_place = already_installed.first().map(|x| x.version.clone());
+                    } else {
+                        if let Some(first) = already_installed.first() {
+                            let rollback =
+                                self.find_rollback_candidate(first, &actual_candidate.flavor);
+                            if rollback.is_none() {
+                                log::warn!("Previously installed version {} isn't in the cache, it can't be restored if the installation fails", &first.version);
+                            }
+                            previous = Some((*first, rollback));
+                        }
+                        for already in already_installed {
+                            let result = already
+                                .uninstall(&self.audited_runner(&already.product_name), false);
+                            self.audit_outcome(
+                                AuditAction::Uninstall,
+                                &already.product_name,
+                                already.version.as_ref(),
+                                &result,
+                            );
+                            result?;
+                        }
+                        eprintln!(
+                            "Successfully Uninstalled product, continuing with new installation"
+                        );
+                    }
+                }
+            }
+            InstallOverwriteOptions::Add => {
+                eprintln!("Will create an additional installation for this item")
+            }
+            InstallOverwriteOptions::Cancel => {
+                eprintln!("Wont continue with installation");
+                return Ok((InstallationResult::Canceled, None));
+            }
+        }
+
+        let previous_version = previous
+            .as_ref()
+            .map(|(x, _)| x.version.clone())
+            .or(upgraded_in_place);
+        summary.end_phase("uninstall", self.clock.now());
+
+        /* Launch installer, keeping what it says in case it fails */
+        marker.set_phase(InstallPhase::Installing);
+        let installer_logs = support::installer_log_directory(&actual_candidate);
+        if installer_logs.exists() {
+            fs::remove_dir_all(&installer_logs)?;
+        }
+        let auditor = self.audited_runner(&actual_candidate.product_name);
+        let recorder = support::RecordingRunner::new(&auditor);
+        let installation_result = actual_candidate.install_in(
+            &recorder,
+            &binary_path,
+            install_options,
+            &self.config.temp_download_directory,
+        );
+        self.audit_outcome(
+            AuditAction::Install,
+            &actual_candidate.product_name,
+            actual_candidate.version.as_ref(),
+            &installation_result,
+        );
+        if let Err(e) = &installation_result {
+            self.bundle_install_logs(&actual_candidate, e.as_ref(), &recorder.records());
+        }
+        let installation_result = match previous {
+            Some((previous, rollback)) => {
+                self.rollback_if_failed(
+                    installation_result,
+                    &actual_candidate,
+                    previous,
+                    rollback.as_ref(),
+                )
+                .await
+            }
+            None => installation_result,
+        };
+        drop(marker);
+        summary.end_phase("install", self.clock.now());
+
+        /* what changed since the replaced version, only for upgrades of builds the repositories know */
+        let mut changes = Vec::new();
+        if let (Ok(InstallationResult::Succeeded), Some(previous_version)) =
+            (&installation_result, &previous_version)
+        {
+            if actual_candidate.version > *previous_version
+                && actual_candidate.identifier != LOCAL_FILE_IDENTIFIER
+                && actual_candidate.identifier != URL_IDENTIFIER
+            {
+                changes = self
+                    .get_changelog(&actual_candidate, previous_version)
+                    .await;
+                if !changes.is_empty() {
+                    println!("{}", notify::changelog(previous_version.as_ref(), &changes));
+                }
+                summary.end_phase("changelog", self.clock.now());
+            }
+        }
+
+        /* Launch autorun if specified */
+        if let Ok(InstallationResult::Succeeded) = installation_result {
+            let actual_autorun = autorun.unwrap_or(actual_candidate.flavor.autorun);
+            if actual_autorun {
+                #[cfg(target_os = "windows")]
+                let package_full_name = self.find_installed_package_full_name(&actual_candidate);
+                #[cfg(not(target_os = "windows"))]
+                let package_full_name: Option<String> = None;
+                actual_candidate
+                    .start_program(self.runner.as_ref(), package_full_name.as_deref())?;
+                if let Some(seconds) = self.config.autorun_verify_seconds {
+                    self.wait_until_running(&actual_candidate, Duration::from_secs(seconds))
+                        .await?;
+                }
+                summary.end_phase("launch", self.clock.now());
+            }
+        }
+        installation_result.map(|x| {
+            (
+                x,
+                Some(InstallReport {
+                    version: actual_candidate.version,
+                    previous_version,
+                    artifact,
+                    size: None,
+                    changes,
+                    summary,
+                }),
+            )
+        })
+    }
+
+    /// Why [candidate] is blocked by its product's `BlockedVersions` or `BlockedBranches`, if it is
+    pub fn blocked_reason(&self, candidate: &InstallationCandidate) -> Option<String> {
+        Product::from_name(&candidate.product_name, &self.config.products)
+            .and_then(|x| x.blocked_reason(&candidate.version, &candidate.identifier))
+    }
+
+    /// Where the artifact of [candidate] at [binary_path] came from: the cache if it's the one that was found
+    /// there as [cached_file_name], otherwise the repository it was just downloaded from
+    fn artifact_source(
+        &self,
+        candidate: &InstallationCandidate,
+        binary_path: &Path,
+        cached_file_name: Option<&str>,
+    ) -> ArtifactSource {
+        if cached_file_name == Some(candidate.make_cached_file_name().as_str()) {
+            return ArtifactSource::Cache(
+                binary_path
+                    .parent()
+                    .map(Path::to_path_buf)
+                    .unwrap_or_default(),
+            );
+        }
+        let name = self
+            .config
+            .repositories
+            .iter()
+            .find(|x| x.repository_server.as_deref() == Some(candidate.repo_location.as_str()))
+            .map(|x| x.name.clone())
+            .unwrap_or_else(|| candidate.repo_location.clone());
+        ArtifactSource::Repository {
+            name,
+            bytes: fs::metadata(binary_path).map(|x| x.len()).unwrap_or(0),
+        }
+    }
+
+    /// Saves the logs of the failed install of [candidate] for attaching to a bug report, and says where.
+    /// Failing to is only logged, the install's error is what matters
+    fn bundle_install_logs(
+        &self,
+        candidate: &InstallationCandidate,
+        error: &(dyn std::error::Error + 'static),
+        records: &[String],
+    ) {
+        if GManError::is_cancelled(error) {
+            return;
+        }
+        match support::bundle_logs(
+            self.runner.as_ref(),
+            &self.config.cache_directory,
+            &self.state_directory,
+            candidate,
+            error,
+            records,
+        ) {
+            Ok(bundle) => eprintln!(
+                "The logs of the failed installation were saved to {}, attach it when reporting the problem",
+                bundle.display()
+            ),
+            Err(e) => log::warn!("Failed to save the logs of the failed installation: {}", e),
+        }
+    }
+
+    /// Changes that went into [installed] since [previous_version] of the same flavor, newest first.
+    /// [previous_version] is the one the product reported, so it's looked up numbered like the build number
+    /// of [installed]. Empty if the repositories can't tell, which is only logged as it doesn't affect the
+    /// installation
+    async fn get_changelog(
+        &self,
+        installed: &InstallationCandidate,
+        previous_version: &Version,
+    ) -> Vec<team_city::TeamCityChange> {
+        match self
+            .diff_builds(
+                &installed.product_name,
+                previous_version.numbered_like(&installed.version).as_ref(),
+                installed.version.as_ref(),
+                Some(&installed.flavor.id),
+            )
+            .await
+        {
+            Ok(changes) => changes,
+            Err(e) => {
+                log::warn!(
+                    "Couldn't get the changes since {} {}: {}",
+                    &installed.product_name,
+                    previous_version,
+                    e
+                );
+                Vec::new()
+            }
+        }
+    }
+
+    /// Closes [installed] if it's running, so it can be replaced. Asks first unless `--force` was given.
+    /// Returns whether the installation can go ahead
+    fn close_if_running(
+        &self,
+        installed: &InstalledProduct,
+        prompt: Option<bool>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let running = match installed.running_processes(self.runner.as_ref()) {
+            Ok(r) => r,
+            Err(e) => {
+                log::warn!(
+                    "Couldn't check whether {} is running: {}",
+                    &installed.product_name,
+                    e
+                );
+                return Ok(true);
+            }
+        };
+        if running.is_empty() {
+            return Ok(true);
+        }
+
+        eprintln!(
+            "{} is currently running: {}",
+            &installed.product_name,
+            running.join(", ")
+        );
+        if self.config.close_running_applications {
+            eprintln!("Closing it before continuing");
+        } else if prompt.unwrap_or(true) {
+            eprintln!("Close it and continue? [y/N]");
+            if !self.interaction.confirm()? {
+                return Ok(false);
+            }
+        } else {
+            return Err(Box::new(GManError::new(&format!(
+                "{} is running, close it first or pass --force",
+                &installed.product_name
+            ))));
+        }
+        installed.shutdown(&self.audited_runner(&installed.product_name))?;
+        Ok(true)
+    }
+
+    /// Polls until the autorun application of [candidate] is observed running, failing once [timeout] has passed
+    async fn wait_until_running(
+        &self,
+        candidate: &InstallationCandidate,
+        timeout: Duration,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        log::info!(
+            "Waiting up to {}s for {} to start",
+            timeout.as_secs(),
+            &candidate.product_name
+        );
+        let started = self.clock.now();
+        loop {
+            match candidate.is_running(self.runner.as_ref())? {
+                Some(true) => {
+                    log::debug!("{} is running", &candidate.product_name);
+                    return Ok(());
+                }
+                Some(false) => {}
+                None => {
+                    log::warn!(
+                        "Can't tell whether {} is running, its flavor has no NameRegex, InstallPath or CFBundle metadata",
+                        &candidate.product_name
+                    );
+                    return Ok(());
+                }
+            }
+            let elapsed = self.clock.now().duration_since(started).unwrap_or_default();
+            if elapsed >= timeout {
+                return Err(Box::new(GManError::new(&format!(
+                    "{} was installed, but wasn't observed running within {}s of being launched",
+                    &candidate.product_name,
+                    timeout.as_secs()
+                ))));
+            }
+            tokio::time::sleep(AUTORUN_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Checks that the installer for [candidate] exists in the cache and isn't empty
+    fn verify_artifact(
+        candidate: &InstallationCandidate,
+        binary_path: &Path,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match fs::metadata(binary_path) {
+            Ok(m) if m.is_file() && m.len() > 0 => Ok(()),
+            _ => Err(Box::new(GManError::new(&format!(
+                "Installer for {} {} is missing or empty at {}, not continuing with installation",
+                &candidate.product_name,
+                &candidate.version,
+                binary_path.display()
+            )))),
+        }
+    }
+
+    /// Runs the `Transforms` of [candidate]'s flavor on a copy of its cached [artifact], returning the installer
+    /// to run. Without transforms that's the [artifact] itself
+    fn transform_artifact(
+        &self,
+        candidate: &InstallationCandidate,
+        artifact: &Path,
+    ) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        if candidate.flavor.transforms.is_empty() {
+            return Ok(artifact.to_path_buf());
+        }
+        let work_directory = self
+            .config
+            .temp_download_directory
+            .join("transforms")
+            .join(candidate.make_cached_file_name());
+        transform::apply(
+            self.runner.as_ref(),
+            &candidate.flavor.transforms,
+            artifact,
+            &work_directory,
+        )
+    }
+
+    /// Points the MSI transforms of [candidate] that are artifacts of its build at their files in the cache,
+    /// downloading the ones that aren't there yet, so msiexec is only given local files
+    async fn fetch_msi_transforms(
+        &self,
+        candidate: &mut InstallationCandidate,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if candidate.flavor.package_type != PackageType::Msi {
+            return Ok(());
+        }
+        let transforms = match candidate
+            .flavor
+            .metadata
+            .as_ref()
+            .and_then(|x| x.msi_transforms.clone())
+        {
+            Some(t) => t,
+            None => return Ok(()),
+        };
+        let cache_directory = self.config.cache_directory.join(MSI_TRANSFORMS_FOLDER_NAME);
+        let mut resolved: Vec<String> = Vec::new();
+        for transform in transforms {
+            if PathBuf::from(ClientConfig::shell_expand(&transform)).is_absolute() {
+                resolved.push(transform);
+                continue;
+            }
+            let artifact = InstallationCandidate {
+                remote_id: candidate.remote_id.clone(),
+                repo_location: candidate.repo_location.clone(),
+                product_name: candidate.product_name.clone(),
+                version: candidate.version.clone(),
+                identifier: candidate.identifier.clone(),
+                flavor: product::Flavor {
+                    teamcity_metadata: product::TeamCityMetadata {
+                        teamcity_binary_path: PathBuf::from(&transform),
+                        ..candidate.flavor.teamcity_metadata.clone()
+                    },
+                    ..candidate.flavor.clone()
+                },
+                installed: false,
+                expired: false,
+            };
+            let mut cached = artifact.make_output_for_candidate(&cache_directory);
+            if !cached.exists() {
+                eprintln!(
+                    "Downloading the MSI transform {} of {} {}",
+                    &transform, &candidate.product_name, &candidate.version
+                );
+                cached = self
+                    .download_build_artifact(artifact, &cache_directory)
+                    .await?;
+            }
+            resolved.push(cached.to_string_lossy().to_string());
+        }
+        if let Some(metadata) = candidate.flavor.metadata.as_mut() {
+            metadata.msi_transforms = Some(resolved);
+        }
+        Ok(())
+    }
+
+    /// Downloads [artifact], another file of a build already found, to [cache_directory]. Builds from the cache
+    /// don't know their build id, so it's looked up again by version
+    async fn download_build_artifact(
+        &self,
+        mut artifact: InstallationCandidate,
+        cache_directory: &Path,
+    ) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let repositories = self.get_valid_repositories_for_platform();
+        let repo = if artifact.remote_id.is_empty() {
+            let search = SearchCandidate {
+                product_name: artifact.product_name.clone(),
+                version: Some(artifact.version.clone()),
+                identifier: None,
+                flavor: artifact.flavor.clone(),
+            };
+            let (build, repo) = team_city::get_with_build_id_by_candidate(
+                &self.http_client,
+                &search,
+                &repositories,
+            )
+            .await?
+            .ok_or_else(|| {
+                GManError::new(&format!(
+                    "Build {} of {} not found to download {} from",
+                    &artifact.version,
+                    &artifact.product_name,
+                    artifact.get_binary_file_name()
+                ))
+            })?;
+            artifact.remote_id = build.remote_id;
+            artifact.repo_location = build.repo_location;
+            repo
+        } else {
+            repositories
+                .into_iter()
+                .find(|x| x.repository_server.as_deref() == Some(artifact.repo_location.as_str()))
+                .ok_or_else(|| {
+                    GManError::new(&format!(
+                        "Repository {} of {} isn't configured",
+                        &artifact.repo_location, &artifact.product_name
+                    ))
+                })?
+        };
+        team_city::download_artifact(
+            &self.http_client,
+            &artifact,
+            repo,
+            &self
+                .config
+                .temp_download_directory
+                .join(MSI_TRANSFORMS_FOLDER_NAME),
+            &cache_directory.to_path_buf(),
+            self.config.teamcity_download_chunk_size,
+        )
+        .await
+    }
+
+    /// Finds the cached installer of the [installed] product, so it can be reinstalled if an upgrade fails
+    fn find_rollback_candidate(
+        &self,
+        installed: &InstalledProduct,
+        flavor: &product::Flavor,
+    ) -> Option<InstallationCandidate> {
+        let key = installed.key().flavor(&flavor.id);
+        self.list_cache()?
+            .into_iter()
+            .find(|x| key.matches(&x.key()))
+    }
+
+    /// Reinstalls the [previous] version from the cache if installing [failed] didn't succeed,
+    /// so a broken upgrade doesn't leave the machine without the product
+    async fn rollback_if_failed(
+        &self,
+        result: Result<InstallationResult, Box<dyn std::error::Error>>,
+        failed: &InstallationCandidate,
+        previous: &InstalledProduct,
+        rollback: Option<&InstallationCandidate>,
+    ) -> Result<InstallationResult, Box<dyn std::error::Error>> {
+        let reason = match &result {
+            Ok(InstallationResult::Succeeded) => return result,
+            Ok(other) => format!("{:?}", other).to_lowercase(),
+            Err(e) => e.to_string(),
+        };
+        eprintln!(
+            "Installation of {} {} did not complete ({})",
+            &failed.product_name, &failed.version, &reason
+        );
+
+        let rollback = match rollback {
+            Some(r) => r,
+            None => {
+                eprintln!(
+                    "Previously installed version {} is not in the cache, could not roll back",
+                    &previous.version
+                );
+                return result;
+            }
+        };
+
+        eprintln!(
+            "Rolling back to previously installed version {}",
+            &rollback.version
+        );
+        let rollback_path = self.cached_path(rollback);
+        /* installed with the same MSI transforms as the version that failed would have been */
+        let mut rollback = rollback.to_owned();
+        let rollback_result = match self.fetch_msi_transforms(&mut rollback).await {
+            Ok(()) => self
+                .transform_artifact(&rollback, &rollback_path)
+                .and_then(|path| {
+                    rollback.install_in(
+                        &self.audited_runner(&rollback.product_name),
+                        &path,
+                        InstallOverwriteOptions::Overwrite,
+                        &self.config.temp_download_directory,
+                    )
+                }),
+            Err(e) => Err(e),
+        };
+        self.audit_outcome(
+            AuditAction::Install,
+            &rollback.product_name,
+            rollback.version.as_ref(),
+            &rollback_result,
+        );
+        let rollback_error = match rollback_result {
+            Ok(InstallationResult::Succeeded) => {
+                eprintln!("Rolled back to version {}", &rollback.version);
+                return match result {
+                    Err(_) => Err(Box::new(GManError::new(&format!(
+                        "Failed to install {} {}: {}. Rolled back to previously installed version {}",
+                        &failed.product_name, &failed.version, &reason, &rollback.version
+                    )))),
+                    ok => ok,
+                };
+            }
+            Ok(other) => format!("{:?}", other).to_lowercase(),
+            Err(e) => e.to_string(),
+        };
+        Err(Box::new(GManError::new(&format!(
+            "Failed to install {} {}: {}. Rolling back to previously installed version {} also failed: {}",
+            &failed.product_name, &failed.version, &reason, &rollback.version, &rollback_error
+        ))))
+    }
+
+    /// Builds the candidate for an installer that comes from outside the repositories.
+    ///
+    /// The flavor is inferred from the extension of [file_name] and the version from its name, when not given
+    fn make_external_candidate(
+        &self,
+        file_name: &Path,
+        location: &str,
+        identifier: &str,
+        product_name: &str,
+        version: Option<&str>,
+        flavor: Option<&str>,
+    ) -> Result<InstallationCandidate, Box<dyn std::error::Error>> {
+        let product = match Product::from_name(product_name, &self.config.products) {
+            Some(p) => p,
+            None => {
+                return Err(Box::new(GManError::new(&format!(
+                    "Product {} is not in the configuration",
+                    product_name
+                ))))
+            }
+        };
+
+        let flavor = match flavor {
+            Some(f) => match product
+                .flavors
+                .iter()
+                .find(|x| x.id.to_lowercase() == f.to_lowercase())
+            {
+                Some(found) => found,
+                None => {
+                    return Err(Box::new(GManError::new(&format!(
+                        "Product {} has no flavor {}",
+                        &product.name, f
+                    ))))
+                }
+            },
+            None => {
+                let platform = Platform::platform_for_current_platform().unwrap();
+                product.flavor_for_file(file_name, &platform)?
+            }
+        };
+        log::debug!("Installing {} as flavor {}", location, &flavor.id);
+
+        let version = match version {
+            Some(v) => Version::new(v),
+            None => {
+                let name = file_name
+                    .file_name()
+                    .map(|x| x.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                match Version::from_file_name(&name) {
+                    Some(v) => v,
+                    None => {
+                        return Err(Box::new(GManError::new(&format!(
+                            "Couldn't infer the version from {}, specify it with --version",
+                            name
+                        ))))
+                    }
+                }
+            }
+        };
+
+        Ok(InstallationCandidate {
+            remote_id: String::default(),
+            repo_location: location.to_owned(),
+            product_name: product.name.to_owned(),
+            version,
+            identifier: identifier.to_owned(),
+            flavor: flavor.to_owned(),
+            installed: false,
+            expired: false,
+        })
+    }
+
+    /// Installs an installer from outside the configured repositories for [product_name].
+    ///
+    /// The flavor and version are inferred from the file name when not given. The artifact is copied or
+    /// downloaded into the cache under the usual naming, then goes through the regular installation pipeline,
+    /// reported like [Client::install_reported]
+    pub async fn install_external(
+        &self,
+        artifact: &ExternalArtifact<'_>,
+        product_name: &str,
+        version: Option<&str>,
+        flavor: Option<&str>,
+        prompt: Option<bool>,
+        autorun: Option<bool>,
+    ) -> Result<(InstallationResult, Option<InstallReport>), Box<dyn std::error::Error>> {
+        let candidate = match artifact {
+            ExternalArtifact::File(file) => {
+                if !file.is_file() {
+                    return Err(Box::new(GManError::new(&format!(
+                        "Installer file {} does not exist",
+                        file.display()
+                    ))));
+                }
+                let candidate = self.make_external_candidate(
+                    file,
+                    &file.to_string_lossy(),
+                    LOCAL_FILE_IDENTIFIER,
+                    product_name,
+                    version,
+                    flavor,
+                )?;
+
+                /* put into the cache, so it's picked up like any downloaded artifact (and can be rolled back to later) */
+                fs::create_dir_all(&self.config.cache_directory)?;
+                let cached_path = candidate.make_output_for_candidate(&self.config.cache_directory);
+                /* the entry may be a hard link to a blob, which must not be overwritten in place */
+                let _ = fs::remove_file(&cached_path);
+                let placed = util::link_or_copy(file, &cached_path);
+                audit::append(
+                    &self.state_directory,
+                    &AuditEntry::new(
+                        AuditAction::CopyFile,
+                        Some(product_name),
+                        &format!("{} -> {}", file.display(), cached_path.display()),
+                        placed.as_ref().map(|_| ()).map_err(|e| e.to_string()),
+                    ),
+                );
+                let placed = placed?;
+                log::debug!(
+                    "Put {} into cache as {} ({:?})",
+                    file.display(),
+                    cached_path.display(),
+                    placed
+                );
+                self.deduplicate_cached(&cached_path);
+                candidate
+            }
+            ExternalArtifact::Url { url, checksum } => {
+                let url = team_city::ensure_scheme(url)?;
+                let file_name = url
+                    .path_segments()
+                    .and_then(|mut x| x.next_back())
+                    .map(|x| x.to_owned())
+                    .unwrap_or_default();
+                let candidate = self.make_external_candidate(
+                    Path::new(&file_name),
+                    url.as_str(),
+                    URL_IDENTIFIER,
+                    product_name,
+                    version,
+                    flavor,
+                )?;
+
+                let cached_path = download::download_to_cache(
+                    &self.http_client,
+                    &url,
+                    None,
+                    url.as_str(),
+                    &candidate.make_output_for_candidate(&self.config.temp_download_directory),
+                    &candidate.make_output_for_candidate(&self.config.cache_directory),
+                    self.config.teamcity_download_chunk_size,
+                    None,
+                )
+                .await?;
+
+                if let Some(checksum) = checksum {
+                    if let Err(e) = download::verify_sha256(&cached_path, checksum) {
+                        /* never leave an unverified artifact in the cache */
+                        let _ = fs::remove_file(&cached_path);
+                        return Err(e);
+                    }
+                }
+                self.deduplicate_cached(&cached_path);
+                candidate
+            }
+        };
+
+        let search = SearchCandidate {
+            product_name: candidate.product_name.to_owned(),
+            version: Some(candidate.version.clone()),
+            identifier: Some(candidate.identifier.to_owned()),
+            flavor: candidate.flavor.to_owned(),
+        };
+        self.install_reported(&search, Some(false), prompt, autorun)
+            .await
+    }
+
+    /// Finds the builds [from] and [to] of [product_name] on the repositories and returns the VCS changes
+    /// between them, newest first. The order of [from] and [to] doesn't matter
+    pub async fn diff_builds(
+        &self,
+        product_name: &str,
+        from: &str,
+        to: &str,
+        flavor: Option<&str>,
+    ) -> Result<Vec<team_city::TeamCityChange>, Box<dyn std::error::Error>> {
+        let valid_repositories = self.get_valid_repositories_for_platform();
+        let mut found = Vec::new();
+        for version in [from, to] {
+            let search = SearchCandidate::new(
+                product_name,
+                Some(version),
+                None,
+                flavor,
+                &self.config.products,
+            )
+            .ok_or_else(|| {
+                GManError::new(&format!(
+                    "Could not find product {} or its flavor in the configuration",
+                    product_name
+                ))
+            })?;
+            match team_city::get_with_build_id_by_candidate(
+                &self.http_client,
+                &search,
+                &valid_repositories,
+            )
+            .await?
+            {
+                Some(build) => found.push(build),
+                None => {
+                    return Err(Box::new(GManError::new(&format!(
+                        "Build {} of {} was not found on any repository",
+                        version, product_name
+                    ))))
+                }
+            }
+        }
+
+        let (to, to_repo) = found.pop().expect("two builds were found");
+        let (from, _) = found.pop().expect("two builds were found");
+        let (from, to) = if from.version > to.version {
+            (to, from)
+        } else {
+            (from, to)
+        };
+        team_city::get_changes_between(&self.http_client, to_repo, &from, &to).await
+    }
+
+    /// Finds the build matching [search] on the repositories and lists the files in its artifacts
+    pub async fn inspect_build(
+        &self,
+        search: &SearchCandidate,
+    ) -> Result<
+        Option<(InstallationCandidate, Vec<team_city::TeamCityArtifactFile>)>,
+        Box<dyn std::error::Error>,
+    > {
+        let valid_repositories = self.get_valid_repositories_for_platform();
+        let found = team_city::get_with_build_id_by_candidate(
+            &self.http_client,
+            search,
+            &valid_repositories,
+        )
+        .await?;
+        match found {
+            Some((build, repo)) => {
+                let files = team_city::get_artifact_files(&self.http_client, repo, &build).await?;
+                Ok(Some((build, files)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Measures the latency and download throughput of the repository [name], or of every repository
+    /// for this platform. The throughput comes from downloading the first [sample_bytes] of the newest
+    /// build of one of the repository's products
+    pub async fn bench_repositories(
+        &self,
+        name: Option<&str>,
+        sample_bytes: u64,
+    ) -> Result<Vec<RepositoryBench>, Box<dyn std::error::Error>> {
+        let repositories: Vec<&CandidateRepository> = self
+            .get_valid_repositories_for_platform()
+            .into_iter()
+            .filter(|x| x.repository_server.is_some())
+            .filter(|x| name.map(|n| x.name.eq_ignore_ascii_case(n)).unwrap_or(true))
+            .collect();
+        if repositories.is_empty() {
+            return Err(Box::new(GManError::new(&match name {
+                Some(n) => format!("No repository server named {} for this platform", n),
+                None => "No repository servers for this platform".to_owned(),
+            })));
+        }
+        let current_platform = Platform::platform_for_current_platform();
+
+        let mut results = Vec::new();
+        for repo in repositories {
+            println!("Measuring {}...", &repo.name);
+            let mut result = RepositoryBench::new(&repo.name);
+            match bench::measure_latency(&self.http_client, repo).await {
+                Ok(latency) => result.latency = Some(latency),
+                Err(e) => {
+                    result.error = Some(e.to_string());
+                    results.push(result);
+                    continue;
+                }
+            }
+
+            /* the newest build of the first product this repository has one for */
+            let mut sample = None;
+            let products = self.config.products.iter().filter(|p| {
+                repo.products.is_empty()
+                    || repo
+                        .products
+                        .iter()
+                        .any(|x| x.eq_ignore_ascii_case(&p.name))
+            });
+            'products: for product in products {
+                for flavor in product
+                    .flavors
+                    .iter()
+                    .filter(|x| current_platform.as_ref() == Some(&x.platform))
+                {
+                    let search = SearchCandidate {
+                        product_name: product.name.to_owned(),
+                        version: None,
+                        identifier: None,
+                        flavor: flavor.to_owned(),
+                    };
+                    if let Ok(Some((build, _))) = team_city::get_newest_build(
+                        &self.http_client,
+                        &search,
+                        product,
+                        None,
+                        None,
+                        &[repo],
+                    )
+                    .await
+                    {
+                        sample = Some(build);
+                        break 'products;
+                    }
+                }
+            }
+
+            let Some(build) = sample else {
+                result.error = Some("No build to download a sample from".to_owned());
+                results.push(result);
+                continue;
+            };
+            result.sample = Some(format!("{} {}", &build.product_name, &build.version));
+            let measured = match team_city::artifact_download_url(&build.repo_location, &build) {
+                Ok(url) => {
+                    bench::measure_download(
+                        &self.http_client,
+                        &url,
+                        repo.repository_credentials.as_ref(),
+                        sample_bytes,
+                    )
+                    .await
+                }
+                Err(e) => Err(e),
+            };
+            match measured {
+                Ok(download) => result.download = Some(download),
+                Err(e) => result.error = Some(e.to_string()),
+            }
+            results.push(result);
+        }
+        Ok(results)
+    }
+
+    /// Writes the bundle for filing an issue against gman to [output], see [support::report]
+    pub fn report(&self, output: Option<&Path>) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        support::report(
+            self.runner.as_ref(),
+            &self.config,
+            &self.state_directory,
+            output,
+        )
+    }
+
+    /// Describes every product installed on this machine as a [Manifest], which `install --manifest` can
+    /// reproduce on another machine. Flavor and branch are taken from the cached installer of the same version,
+    /// when there is one
+    pub fn export_manifest(&self) -> Manifest {
+        let cached = self.list_cache().unwrap_or_default();
+        let products = self
+            .get_installed()
+            .iter()
+            .map(|x| self.make_manifest_entry(x, &cached))
+            .collect();
+        Manifest { products }
+    }
+
+    fn make_manifest_entry(
+        &self,
+        installed: &InstalledProduct,
+        cached: &[InstallationCandidate],
+    ) -> ManifestEntry {
+        let from_cache = cached.iter().find(|x| installed.key().matches(&x.key()));
+
+        let flavor = match from_cache {
+            Some(c) => Some(c.flavor.id.to_owned()),
+            None if installed.flavor.is_some() => installed.flavor.to_owned(),
+            None => {
+                /* only unambiguous if the product has a single flavor for this platform */
+                let platform = Platform::platform_for_current_platform();
+                let flavors = Product::from_name(&installed.product_name, &self.config.products)
+                    .map(|p| {
+                        p.flavors
+                            .iter()
+                            .filter(|f| Some(&f.platform) == platform.as_ref())
+                            .collect::<Vec<&product::Flavor>>()
+                    })
+                    .unwrap_or_default();
+                match flavors.as_slice() {
+                    [single] => Some(single.id.to_owned()),
+                    _ => None,
+                }
+            }
+        };
+
+        /* builds from a local file or url can't be fetched from a repository again */
+        let branch = from_cache
+            .map(|x| x.identifier.to_owned())
+            .filter(|x| x != LOCAL_FILE_IDENTIFIER && x != URL_IDENTIFIER);
+
+        let repository = self
+            .get_valid_repositories_for_platform()
+            .into_iter()
+            .find(|r| {
+                r.products
+                    .iter()
+                    .any(|p| p.to_lowercase() == installed.product_name.to_lowercase())
+            })
+            .map(|r| r.name.to_owned());
+
+        ManifestEntry {
+            product_name: installed.product_name.to_owned(),
+            version: from_cache
+                .map(|x| x.version.to_string())
+                .unwrap_or_else(|| installed.version.to_string()),
+            flavor,
+            branch,
+            repository,
+        }
+    }
+
+    /// Installed products that have a newer build available on the repositories, see [find_outdated]
+    pub async fn list_outdated(&self) -> Result<Vec<OutdatedProduct>, Box<dyn std::error::Error>> {
+        let candidates = self.list_candidates(None, None).await?;
+        Ok(self.find_outdated(&self.get_installed(), &candidates))
+    }
+
+    /// Which of the [installed] products have a newer build among [candidates] on the branch they were
+    /// installed from (as far as the cache tells, see [make_manifest_entry]), or on the product's nightly
+    /// branch when that isn't known. Builds of other flavors don't count
+    fn find_outdated(
+        &self,
+        installed: &[InstalledProduct],
+        candidates: &[InstallationCandidate],
+    ) -> Vec<OutdatedProduct> {
+        let cached = self.list_cache().unwrap_or_default();
+        let mut outdated = Vec::new();
+        for product in installed {
+            let entry = self.make_manifest_entry(product, &cached);
+            let branch = match entry.branch {
+                Some(b) => b,
+                None => match Product::from_name(&product.product_name, &self.config.products) {
+                    Some(p) => p.nightly_branch().to_owned(),
+                    None => continue,
+                },
+            };
+            let mut key = product.key().without_build().identifier(&branch);
+            if let Some(flavor) = &entry.flavor {
+                key = key.flavor(flavor);
+            }
+            let newest = candidates
+                .iter()
+                .filter(|x| key.matches(&x.key()) && !x.expired)
+                .max_by(|a, b| {
+                    a.version
+                        .partial_cmp(&b.version)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                });
+            if let Some(newest) = newest {
+                if newest.version.partial_cmp(&product.version) == Some(std::cmp::Ordering::Greater)
+                {
+                    outdated.push(OutdatedProduct {
+                        product_name: product.product_name.to_owned(),
+                        flavor: newest.flavor.id.to_owned(),
+                        branch,
+                        installed: product.version.clone(),
+                        available: newest.version.clone(),
+                    });
+                }
+            }
+        }
+        outdated
+    }
+
+    /// The newest builds of every installed product, or only of the products in [only], on the branches they were
+    /// installed from, for `upgrade`. Builds the product blocks are passed over for the newest one on the branch
+    /// that isn't, see [Product::skips_build]. Fails if a product in [only] isn't installed
+    pub async fn find_upgrades(
+        &self,
+        only: &[String],
+    ) -> Result<Vec<OutdatedProduct>, Box<dyn std::error::Error>> {
+        let installed = self.get_installed();
+        /* fail before asking the repositories */
+        self.upgrades_of(only, &installed, &[])?;
+        let candidates = match only {
+            [product_name] => self.list_candidates(Some(product_name), None).await?,
+            _ => self.list_candidates(None, None).await?,
+        };
+        let valid_repositories = self.get_valid_repositories_for_platform();
+        let mut upgrades = Vec::new();
+        for mut upgrade in self.upgrades_of(only, &installed, &candidates)? {
+            let blocked = Product::from_name(&upgrade.product_name, &self.config.products)
+                .is_some_and(|x| x.skips_build(&upgrade.available, &upgrade.branch));
+            if blocked {
+                let Some(search) = SearchCandidate::new(
+                    &upgrade.product_name,
+                    None,
+                    Some(&upgrade.branch),
+                    Some(&upgrade.flavor),
+                    &self.config.products,
+                ) else {
+                    continue;
+                };
+                match self
+                    .find_installable_build(&search, &valid_repositories)
+                    .await?
+                {
+                    Some((build, _)) if build.version > upgrade.installed => {
+                        upgrade.available = build.version;
+                    }
+                    _ => continue,
+                }
+            }
+            upgrades.push(upgrade);
+        }
+        Ok(upgrades)
+    }
+
+    /// The newest of [candidates] for each of the [installed] products, or only those in [only], see [upgrade_of]
+    fn upgrades_of(
+        &self,
+        only: &[String],
+        installed: &[InstalledProduct],
+        candidates: &[InstallationCandidate],
+    ) -> Result<Vec<OutdatedProduct>, GManError> {
+        let mut product_names = only.to_vec();
+        if product_names.is_empty() {
+            product_names = installed
+                .iter()
+                .filter_map(|x| Product::from_name(&x.product_name, &self.config.products))
+                .map(|x| x.name.to_owned())
+                .collect();
+        }
+        product_names.sort_by_key(|x| x.to_lowercase());
+        product_names.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
+
+        let mut upgrades = Vec::new();
+        for product_name in &product_names {
+            upgrades.extend(self.upgrade_of(product_name, installed, candidates)?);
+        }
+        Ok(upgrades)
+    }
+
+    /// The newest of [candidates] for each installed flavor of [product_name], see [find_outdated]. Empty if
+    /// every installed flavor is on its newest build. Fails if the product isn't installed
+    fn upgrade_of(
+        &self,
+        product_name: &str,
+        installed: &[InstalledProduct],
+        candidates: &[InstallationCandidate],
+    ) -> Result<Vec<OutdatedProduct>, GManError> {
+        if !installed
+            .iter()
+            .any(|x| x.product_name.eq_ignore_ascii_case(product_name))
+        {
+            return Err(GManError::new(&format!(
+                "{} is not installed, use `install` to install it",
+                product_name
+            )));
+        }
+        let mut upgrades: Vec<OutdatedProduct> = Vec::new();
+        for outdated in self
+            .find_outdated(installed, candidates)
+            .into_iter()
+            .filter(|x| x.product_name.eq_ignore_ascii_case(product_name))
+        {
+            match upgrades.iter_mut().find(|x| x.flavor == outdated.flavor) {
+                Some(upgrade) => {
+                    if outdated.available.partial_cmp(&upgrade.available)
+                        == Some(std::cmp::Ordering::Greater)
+                    {
+                        *upgrade = outdated;
+                    }
+                }
+                None => upgrades.push(outdated),
+            }
+        }
+        Ok(upgrades)
+    }
+
+    /// Downloads the newest build on [branch] of [product_name] into the cache for each of [flavors], so a later
+    /// install finds it locally. [flavors] are flavor ids, or `all` for every flavor of the product on any
+    /// platform; without any, the default flavor for this platform is used. Already cached builds are skipped
+    pub async fn prefetch(
+        &self,
+        product_name: &str,
+        branch: &str,
+        flavors: &[String],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let product = Product::from_name(product_name, &self.config.products).ok_or_else(|| {
+            GManError::new(&format!(
+                "Product {} is not in the configuration",
+                product_name
+            ))
+        })?;
+        let selected: Vec<&product::Flavor> =
+            if flavors.iter().any(|x| x.eq_ignore_ascii_case("all")) {
+                product.flavors.iter().collect()
+            } else if flavors.is_empty() {
+                let current_platform = Platform::platform_for_current_platform();
+                product
+                    .flavors
+                    .iter()
+                    .filter(|x| current_platform.as_ref() == Some(&x.platform))
+                    .take(1)
+                    .collect()
+            } else {
+                let mut selected = Vec::new();
+                for id in flavors {
+                    match product
+                        .flavors
+                        .iter()
+                        .find(|x| x.id.eq_ignore_ascii_case(id))
+                    {
+                        Some(f) => selected.push(f),
+                        None => {
+                            return Err(Box::new(GManError::new(&format!(
+                                "Product {} has no flavor {}",
+                                &product.name, id
+                            ))))
+                        }
+                    }
+                }
+                selected
+            };
+        if selected.is_empty() {
+            return Err(Box::new(GManError::new(&format!(
+                "Product {} has no flavor for this platform",
+                &product.name
+            ))));
+        }
+
+        let mut failed: usize = 0;
+        for flavor in &selected {
+            let flavor = match flavor.for_machine() {
+                Ok(x) => x,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    failed += 1;
+                    continue;
+                }
+            };
+            let search = SearchCandidate {
+                product_name: product.name.to_owned(),
+                version: None,
+                identifier: Some(branch.to_owned()),
+                flavor: flavor.clone(),
+            };
+            let repositories = self.get_repositories_for_flavor(&flavor);
+            let found = match team_city::get_newest_build(
+                &self.http_client,
+                &search,
+                product,
+                Some(branch),
+                None,
+                &repositories,
+            )
+            .await
+            {
+                Ok(Some(found)) => found,
+                Ok(None) => {
+                    eprintln!("No build of {} on branch {}", &flavor.id, branch);
+                    failed += 1;
+                    continue;
+                }
+                Err(e) => {
+                    eprintln!(
+                        "Failed to look up the newest build of {}: {}",
+                        &flavor.id, e
+                    );
+                    failed += 1;
+                    continue;
+                }
+            };
+
+            let (build, repo) = found;
+            if self.cached_path(&build).exists() {
+                println!("{} {} is already cached", &flavor.id, &build.version);
+                continue;
+            }
+            println!("Downloading {} {}", &flavor.id, &build.version);
+            match team_city::download_artifact(
+                &self.http_client,
+                &build,
+                repo,
+                &self.config.temp_download_directory,
+                &self.config.cache_directory,
+                self.config.teamcity_download_chunk_size,
+            )
+            .await
+            {
+                Ok(cached_path) => self.deduplicate_cached(&cached_path),
+                Err(e) => {
+                    if GManError::is_cancelled(e.as_ref()) {
+                        return Err(e);
+                    }
+                    eprintln!(
+                        "Failed to download {} {}: {}",
+                        &flavor.id, &build.version, e
+                    );
+                    failed += 1;
                 }
             }
-        };
+        }
 
-        /* uninstall any previous, old versions */
-        let binary_path = actual_candidate.make_output_for_candidate(&self.config.cache_directory);
-        let all_installed = &self.get_installed();
-        let already_installed = all_installed
-            .iter()
-            .filter(|x| x.product_name.to_lowercase() == search.product_name.to_lowercase())
-            .filter(|x| x.should_uninstall(&binary_path).unwrap_or(false))
-            .collect::<Vec<&InstalledProduct>>();
+        if failed > 0 {
+            return Err(Box::new(GManError::new(&format!(
+                "{} of {} flavors failed to prefetch",
+                failed,
+                selected.len()
+            ))));
+        }
+        Ok(())
+    }
 
-        if already_installed
+    /// Repositories serving [flavor], which for flavors of other platforms are that platform's repositories
+    fn get_repositories_for_flavor(&self, flavor: &product::Flavor) -> Vec<&CandidateRepository> {
+        self.config
+            .repositories
             .iter()
-            .any(|x| x.version == actual_candidate.version)
-        {
-            eprintln!(
-                "This version ({}) of the product is already installed on machine. Skipping.",
-                actual_candidate.version
+            .filter(|x| {
+                x.repository_server.is_some()
+                    && (x.platforms.is_empty() || x.platforms.contains(&flavor.platform))
+            })
+            .collect()
+    }
+
+    /// Installs the newest build on [branch] of [product_name] onto the SSH target named [target_name]:
+    /// the Debian package is downloaded (or taken from the cache), copied with scp into a fresh temporary
+    /// folder of the target, and installed with apt there, restarting and checking its `ServiceName` if it has
+    /// one.
+    ///
+    /// The flavor is [flavor_id] if given, otherwise the product's first Raspberry Pi Deb flavor
+    pub async fn install_remote(
+        &self,
+        product_name: &str,
+        branch: &str,
+        flavor_id: Option<&str>,
+        target_name: &str,
+    ) -> Result<InstallationCandidate, Box<dyn std::error::Error>> {
+        let target = ssh::find_target(&self.config.ssh_targets, target_name).ok_or_else(|| {
+            GManError::new(&format!(
+                "No SSH target named {} in the configuration",
+                target_name
+            ))
+        })?;
+        let (build, cached_path) = self
+            .cache_newest_deb(
+                product_name,
+                branch,
+                flavor_id,
+                Platform::RaspberryPi,
+                "over SSH",
+            )
+            .await?;
+        Self::verify_artifact(&build, &cached_path)?;
+        let flavor = &build.flavor;
+
+        let runner = ssh::SshRunner::new(target, self.runner.as_ref());
+        let remote_directory = runner.make_upload_directory()?;
+        let remote_path = format!("{}/{}", remote_directory, build.get_binary_file_name());
+        println!(
+            "Copying {} {} to {}",
+            &flavor.id, &build.version, &target.name
+        );
+        let result = runner
+            .upload(cached_path.to_string_lossy().as_ref(), &remote_path)
+            .and_then(|_| {
+                println!(
+                    "Installing {} {} on {}",
+                    &flavor.id, &build.version, &target.name
+                );
+                candidate::install_deb(&runner, &remote_path, flavor.metadata.as_ref())
+            });
+        if let Err(e) = runner.run("rm", &["-rf", &remote_directory]) {
+            log::warn!(
+                "Failed to remove {} from {}: {}",
+                remote_directory,
+                &target.name,
+                e
             );
-            return Ok(InstallationResult::Skipped);
         }
+        result?;
+        Ok(build)
+    }
 
-        let install_options = match already_installed.is_empty() {
-            true => InstallOverwriteOptions::Overwrite,
-            false => {
-                eprintln!(
-                    "Product already installed on machine. Uninstalling before continuing..."
+    /// Installs the newest build on [branch] of [product_name] in a throwaway Docker container of [image],
+    /// to check the Debian package installs on Linux from any desk. Only the package is mounted into the
+    /// container, read only, and the container is removed afterwards. Containers don't run systemd, so the flavor's
+    /// `ServiceName` isn't restarted or checked.
+    ///
+    /// The flavor is [flavor_id] if given, otherwise the product's first Linux Deb flavor
+    pub async fn install_in_container(
+        &self,
+        product_name: &str,
+        branch: &str,
+        flavor_id: Option<&str>,
+        image: &str,
+    ) -> Result<InstallationCandidate, Box<dyn std::error::Error>> {
+        let (build, cached_path) = self
+            .cache_newest_deb(
+                product_name,
+                branch,
+                flavor_id,
+                Platform::Linux,
+                "in a container",
+            )
+            .await?;
+        let Some(file_name) = cached_path.file_name() else {
+            return Err(Box::new(GManError::new(&format!(
+                "{} isn't a file",
+                cached_path.display()
+            ))));
+        };
+
+        println!("Starting a container of {}", image);
+        let container = container::Container::start(self.runner.as_ref(), image, &cached_path)?;
+        println!(
+            "Installing {} {} in the container",
+            &build.flavor.id, &build.version
+        );
+        container.install_deb(&file_name.to_string_lossy())?;
+        Ok(build)
+    }
+
+    /// The newest build on [branch] of the Deb flavor [flavor_id], or of the product's first Deb flavor of
+    /// [default_platform], and where it's cached, downloading it if it isn't yet. [destination] says where
+    /// it's going to be installed, e.g. `over SSH`
+    async fn cache_newest_deb(
+        &self,
+        product_name: &str,
+        branch: &str,
+        flavor_id: Option<&str>,
+        default_platform: Platform,
+        destination: &str,
+    ) -> Result<(InstallationCandidate, PathBuf), Box<dyn std::error::Error>> {
+        let product = Product::from_name(product_name, &self.config.products).ok_or_else(|| {
+            GManError::new(&format!(
+                "Product {} is not in the configuration",
+                product_name
+            ))
+        })?;
+        let flavor = match flavor_id {
+            Some(id) => product
+                .flavors
+                .iter()
+                .find(|x| x.id.eq_ignore_ascii_case(id)),
+            None => product
+                .flavors
+                .iter()
+                .find(|x| x.platform == default_platform && x.package_type == PackageType::Deb),
+        }
+        .ok_or_else(|| {
+            GManError::new(&format!(
+                "Product {} has no {} flavor to install {}",
+                &product.name,
+                flavor_id
+                    .map(|x| x.to_owned())
+                    .unwrap_or_else(|| format!("{} Deb", default_platform)),
+                destination
+            ))
+        })?;
+        if flavor.package_type != PackageType::Deb {
+            return Err(Box::new(GManError::new(&format!(
+                "Only Deb flavors can be installed {}, {} is {:?}",
+                destination, &flavor.id, flavor.package_type
+            ))));
+        }
+
+        let search = SearchCandidate {
+            product_name: product.name.to_owned(),
+            version: None,
+            identifier: Some(branch.to_owned()),
+            flavor: flavor.to_owned(),
+        };
+        let repositories = self.get_repositories_for_flavor(flavor);
+        let (build, repo) = team_city::get_newest_build(
+            &self.http_client,
+            &search,
+            product,
+            Some(branch),
+            None,
+            &repositories,
+        )
+        .await?
+        .ok_or_else(|| {
+            GManError::new(&format!("No build of {} on branch {}", &flavor.id, branch))
+        })?;
+
+        let mut cached_path = self.cached_path(&build);
+        if cached_path.exists() {
+            println!("Using cached {} {}", &flavor.id, &build.version);
+        } else {
+            println!("Downloading {} {}", &flavor.id, &build.version);
+            cached_path = team_city::download_artifact(
+                &self.http_client,
+                &build,
+                repo,
+                &self.config.temp_download_directory,
+                &self.config.cache_directory,
+                self.config.teamcity_download_chunk_size,
+            )
+            .await?;
+            self.deduplicate_cached(&cached_path);
+        }
+        Ok((build, cached_path))
+    }
+
+    /// Installs several flavors of one product, e.g. the Store and Sideloading builds side by side.
+    ///
+    /// The builds of all [searches] are looked up first and downloaded once per artifact, as flavors built
+    /// by the same build configuration share theirs. Then the flavors are installed one after the other, a
+    /// failing flavor doesn't stop the others
+    pub async fn install_flavors(
+        &self,
+        searches: &[SearchCandidate],
+        automatic_upgrade: Option<bool>,
+        prompt: Option<bool>,
+        autorun: Option<bool>,
+    ) -> Result<Vec<FlavorInstall>, Box<dyn std::error::Error>> {
+        let valid_repositories = self.get_valid_repositories_for_platform();
+
+        /* what isn't cached yet is looked up on the repositories, all at once */
+        let lookups = futures_util::future::join_all(searches.iter().map(|search| async {
+            match self.locate_in_cache(search) {
+                Some(_) => Ok(None),
+                None => {
+                    self.find_installable_build(search, &valid_repositories)
+                        .await
+                }
+            }
+        }))
+        .await;
+
+        let mut results: Vec<Option<FlavorInstall>> = Vec::new();
+        let mut builds: Vec<Option<(InstallationCandidate, &CandidateRepository)>> = Vec::new();
+        for (search, lookup) in searches.iter().zip(lookups) {
+            match lookup {
+                Ok(Some(found)) => {
+                    results.push(None);
+                    builds.push(Some(found));
+                }
+                Ok(None) if self.locate_in_cache(search).is_some() => {
+                    results.push(None);
+                    builds.push(None);
+                }
+                Ok(None) => {
+                    results.push(Some(FlavorInstall {
+                        flavor: search.flavor.id.to_owned(),
+                        version: None,
+                        result: Err(Box::new(GManError::new("No build found"))),
+                    }));
+                    builds.push(None);
+                }
+                Err(e) => {
+                    if GManError::is_cancelled(e.as_ref()) {
+                        return Err(e);
+                    }
+                    results.push(Some(FlavorInstall {
+                        flavor: search.flavor.id.to_owned(),
+                        version: None,
+                        result: Err(e),
+                    }));
+                    builds.push(None);
+                }
+            }
+        }
+
+        /* download each artifact once, and link it into the cache entries of the other flavors sharing it */
+        let found: Vec<&(InstallationCandidate, &CandidateRepository)> =
+            builds.iter().flatten().collect();
+        let candidates: Vec<&InstallationCandidate> = found.iter().map(|(x, _)| x).collect();
+        for group in group_by_artifact(&candidates) {
+            let (build, repo) = found[group[0]];
+            let downloaded = if self.cached_path(build).exists() {
+                Ok(self.cached_path(build))
+            } else {
+                team_city::download_artifact(
+                    &self.http_client,
+                    build,
+                    repo,
+                    &self.config.temp_download_directory,
+                    &self.config.cache_directory,
+                    self.config.teamcity_download_chunk_size,
+                )
+                .await
+            };
+            let downloaded = match downloaded {
+                Ok(path) => {
+                    self.deduplicate_cached(&path);
+                    path
+                }
+                Err(e) => {
+                    if GManError::is_cancelled(e.as_ref()) {
+                        return Err(e);
+                    }
+                    log::debug!("Failed to download {}: {}", &build.flavor.id, e);
+                    continue;
+                }
+            };
+            for shared in group.iter().skip(1).map(|x| candidates[*x]) {
+                let cached_path = shared.make_output_for_candidate(&self.config.cache_directory);
+                if cached_path.exists() {
+                    continue;
+                }
+                log::debug!(
+                    "{} shares its artifact with {}",
+                    &shared.flavor.id,
+                    &build.flavor.id
                 );
-                if prompt.unwrap_or(true) {
-                    Self::prompt_installation_choice()?
-                } else {
-                    InstallOverwriteOptions::Overwrite
+                match util::link_or_copy(&downloaded, &cached_path) {
+                    Ok(_) => self.deduplicate_cached(&cached_path),
+                    Err(e) => log::debug!(
+                        "Failed to share the artifact of {}: {}",
+                        &build.flavor.id,
+                        e
+                    ),
                 }
             }
-        };
+        }
 
-        match install_options {
-            InstallOverwriteOptions::Overwrite => {
-                eprintln!("Will overwrite any existing installations with this one");
+        for ((search, build), result) in searches.iter().zip(&builds).zip(results.iter_mut()) {
+            if result.is_some() {
+                continue;
+            }
+            /* install exactly what was looked up, without asking about newer builds again */
+            let exact = build.as_ref().map(|(build, _)| SearchCandidate {
+                product_name: search.product_name.to_owned(),
+                version: Some(build.version.clone()),
+                identifier: Some(build.identifier.to_owned()),
+                flavor: search.flavor.to_owned(),
+            });
+            println!(
+                "{}",
+                tr!(
+                    "install-flavor",
+                    product = search.product_name.as_str(),
+                    flavor = search.flavor.id.as_str(),
+                )
+            );
+            let installed = self
+                .install(
+                    exact.as_ref().unwrap_or(search),
+                    automatic_upgrade,
+                    prompt,
+                    autorun,
+                )
+                .await;
+            if let Err(e) = &installed {
+                if GManError::is_cancelled(e.as_ref()) {
+                    return Err(installed.unwrap_err());
+                }
+            }
+            *result = Some(FlavorInstall {
+                flavor: search.flavor.id.to_owned(),
+                version: exact
+                    .as_ref()
+                    .or(Some(search))
+                    .and_then(|x| x.version.clone())
+                    .or_else(|| self.locate_in_cache(search).map(|x| x.version)),
+                result: installed,
+            });
+        }
 
-                if already_installed.is_empty() {
-                    eprintln!("No products to uninstall, continuing with new installation");
-                } else {
-                    for already in already_installed {
-                        already.uninstall()?;
+        Ok(results.into_iter().flatten().collect())
+    }
+
+    /// Installs the exact versions listed in the [manifest], reporting each one as it goes
+    pub async fn install_manifest(
+        &self,
+        manifest: &Manifest,
+        prompt: Option<bool>,
+        autorun: Option<bool>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut failed: usize = 0;
+        let mut violated_policy = false;
+        for entry in &manifest.products {
+            println!(
+                "Installing {}@{}{}",
+                &entry.product_name,
+                &entry.version,
+                match &entry.flavor {
+                    Some(f) => format!(", flavor {}", f),
+                    None => String::default(),
+                }
+            );
+            let search = match SearchCandidate::new(
+                &entry.product_name,
+                Some(&entry.version),
+                entry.branch.as_deref(),
+                entry.flavor.as_deref(),
+                &self.config.products,
+            ) {
+                Some(s) => s,
+                None => {
+                    eprintln!(
+                        "Could not find product {} in the configuration, skipping",
+                        &entry.product_name
+                    );
+                    failed += 1;
+                    continue;
+                }
+            };
+
+            match self.install(&search, Some(false), prompt, autorun).await {
+                Ok(InstallationResult::Succeeded) => {
+                    println!("Successfully Installed {}", &entry.product_name)
+                }
+                Ok(InstallationResult::Skipped) => println!("Skipped installation"),
+                Ok(InstallationResult::Canceled) => println!("Canceled installation"),
+                Err(e) => {
+                    if GManError::is_cancelled(e.as_ref()) {
+                        return Err(e);
                     }
-                    eprintln!("Successfully Uninstalled product, continuing with new installation");
+                    eprintln!("Failed to install {}: {}", &entry.product_name, e);
+                    violated_policy |=
+                        GManError::is_kind(e.as_ref(), GManErrorKind::PolicyViolation);
+                    failed += 1;
                 }
             }
-            InstallOverwriteOptions::Add => {
-                eprintln!("Will create an additional installation for this item")
-            }
-            InstallOverwriteOptions::Cancel => {
-                eprintln!("Wont continue with installation");
-                return Ok(InstallationResult::Canceled);
+        }
+
+        if failed > 0 {
+            let message = format!(
+                "{} of {} products in the manifest failed to install",
+                failed,
+                manifest.products.len()
+            );
+            /* so the exit code tells a policy refused one of them, like for a single install */
+            if violated_policy {
+                return Err(Box::new(GManError::with_kind(
+                    GManErrorKind::PolicyViolation,
+                    &message,
+                )));
             }
+            return Err(Box::new(GManError::new(&message)));
         }
+        Ok(())
+    }
 
-        /* Launch installer */
-        let installation_result = actual_candidate.install(&binary_path, install_options);
+    /// Checks for an installation that was interrupted by a crash or kill on a previous run,
+    /// and offers to complete it from the cached artifact
+    pub async fn resume_interrupted_install(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let transaction = match InstallTransaction::load(&self.config.cache_directory) {
+            Some(t) => t,
+            None => return Ok(()),
+        };
+        eprintln!(
+            "A previous installation of {} {} ({}) was interrupted while {}, the product may not be installed anymore.",
+            &transaction.product_name,
+            &transaction.version,
+            &transaction.flavor_id,
+            &transaction.phase
+        );
 
-        /* Launch autorun if specified */
-        if let Ok(InstallationResult::Succeeded) = installation_result {
-            let actual_autorun = autorun.unwrap_or(actual_candidate.flavor.autorun);
-            if actual_autorun {
-                actual_candidate.start_program()?;
+        if !self
+            .config
+            .cache_tiers()
+            .iter()
+            .any(|x| transaction.artifact_path(x).exists())
+        {
+            eprintln!("The installer is no longer in the cache, cannot resume the installation");
+            InstallTransaction::discard(&self.config.cache_directory);
+            return Ok(());
+        }
+
+        eprintln!("Would you like to resume the installation from the cached installer? [y/N]");
+        if !self.interaction.confirm()? {
+            eprintln!("Will not resume the interrupted installation");
+            InstallTransaction::discard(&self.config.cache_directory);
+            return Ok(());
+        }
+
+        let search = match SearchCandidate::new(
+            &transaction.product_name,
+            Some(&transaction.version),
+            Some(&transaction.identifier),
+            Some(&transaction.flavor_id),
+            &self.config.products,
+        ) {
+            Some(s) => s,
+            None => {
+                InstallTransaction::discard(&self.config.cache_directory);
+                return Err(Box::new(GManError::new(&format!(
+                    "Product {} with flavor {} is no longer in the configuration",
+                    &transaction.product_name, &transaction.flavor_id
+                ))));
+            }
+        };
+
+        /* the exact version is in the cache, so this never reaches out to the build server */
+        match self
+            .install(&search, Some(false), Some(false), None)
+            .await?
+        {
+            InstallationResult::Succeeded => {
+                println!("Successfully Installed {}", &search.product_name)
             }
+            InstallationResult::Skipped => println!("Skipped installation"),
+            InstallationResult::Canceled => println!("Canceled installation"),
         }
-        installation_result
+        Ok(())
+    }
+
+    /// Path of [candidate]'s artifact in the first cache tier that has it, or where it's downloaded to otherwise
+    fn cached_path(&self, candidate: &InstallationCandidate) -> PathBuf {
+        self.config
+            .cache_tiers()
+            .iter()
+            .map(|x| candidate.make_output_for_candidate(x))
+            .find(|x| x.exists())
+            .unwrap_or_else(|| candidate.make_output_for_candidate(&self.config.cache_directory))
     }
 
+    /// Lists the artifacts in every cache tier. Where tiers hold the same artifact, it's listed once
     pub fn list_cache(&self) -> Option<Vec<InstallationCandidate>> {
-        log::debug!(
-            "Listing contents of cache directory {}",
-            &self.config.cache_directory.to_str().unwrap()
-        );
         let mut found_candidates: Vec<InstallationCandidate> = Vec::new();
-        match fs::read_dir(&self.config.cache_directory) {
-            Ok(list_dir) => {
-                for entry_result in list_dir {
-                    if let Ok(entry) = entry_result {
-                        if let Ok(fname) = entry.file_name().into_string() {
-                            if let Ok(mut ci) = InstallationCandidate::from_str(fname.as_str()) {
-                                if let Some(product) =
-                                    Product::from_name(&ci.product_name, &self.config.products)
-                                {
-                                    if let Some(flavor) = &product.flavors.iter().find(|x| {
-                                        x.id.to_lowercase() == ci.flavor.id.to_lowercase()
-                                    }) {
-                                        ci.flavor = (*flavor).to_owned();
-                                        found_candidates.push(ci);
+        let mut seen: Vec<String> = Vec::new();
+        let mut readable_tiers: usize = 0;
+        for tier in self.config.cache_tiers() {
+            log::debug!(
+                "Listing contents of cache directory {}",
+                tier.to_string_lossy()
+            );
+            let list_dir = match fs::read_dir(tier) {
+                Ok(l) => l,
+                Err(e) => {
+                    log::error!("Failed to read cache directory {}: {}", tier.display(), e);
+                    continue;
+                }
+            };
+            readable_tiers += 1;
+            for entry_result in list_dir {
+                if let Ok(entry) = entry_result {
+                    if let Ok(fname) = entry.file_name().into_string() {
+                        if seen.contains(&fname) {
+                            continue;
+                        }
+                        if let Ok(mut ci) = InstallationCandidate::from_str(fname.as_str()) {
+                            if let Some(product) =
+                                Product::from_name(&ci.product_name, &self.config.products)
+                            {
+                                if let Some(flavor) = &product.flavors.iter().find(|x| {
+                                    CandidateKey::new(&product.name)
+                                        .flavor(&x.id)
+                                        .matches(&ci.key())
+                                }) {
+                                    /* the artifact of the architecture it was downloaded for */
+                                    match flavor.for_architecture(ci.flavor.architecture) {
+                                        Ok(flavor) => {
+                                            ci.flavor = flavor;
+                                            found_candidates.push(ci);
+                                            seen.push(fname);
+                                        }
+                                        Err(e) => log::debug!("Skipping cached {}: {}", fname, e),
                                     }
                                 }
                             }
@@ -472,11 +3210,10 @@ impl Client {
                     }
                 }
             }
-            Err(e) => {
-                log::error!("Failed to read cache directory: {}", e);
-                return None;
-            }
-        };
+        }
+        if readable_tiers == 0 {
+            return None;
+        }
 
         log::debug!("Found {} cached items", found_candidates.len());
 
@@ -502,21 +3239,131 @@ impl Client {
         Some(found_candidates)
     }
 
+    /// The artifacts in every cache tier for `cache --list`, the identifiers of pinned ones labeled as such
+    pub fn list_cache_with_pins(&self) -> Option<Vec<InstallationCandidate>> {
+        let pinned = blob_store::pinned(&self.config.cache_directory)
+            .iter()
+            .filter_map(|x| InstallationCandidate::from_str(x).ok())
+            .map(|x| (x.key(), x.flavor.architecture))
+            .collect::<Vec<_>>();
+        let mut candidates = self.list_cache()?;
+        for candidate in candidates
+            .iter_mut()
+            .filter(|x| pinned.contains(&(x.key(), x.flavor.architecture)))
+        {
+            candidate.identifier.push_str(PINNED_LABEL);
+        }
+        Some(candidates)
+    }
+
+    /// Pins the cached artifacts of [artifact], a build version or the file name of a cache entry, so clearing
+    /// the cache keeps them, or with [pinned] false unpins them. Returns the file names of the entries
+    pub fn pin_cached(
+        &self,
+        artifact: &str,
+        pinned: bool,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let path = &self.config.cache_directory;
+        let version = Version::new(artifact);
+        let mut entries: Vec<String> = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            let matches = name == artifact
+                || InstallationCandidate::from_str(&name)
+                    .map(|x| x.version.same_build(&version))
+                    .unwrap_or(false);
+            if matches {
+                blob_store::set_pinned(path, &name, pinned)?;
+                entries.push(name);
+            }
+        }
+        if entries.is_empty() {
+            return Err(Box::new(GManError::new(&format!(
+                "No cached artifact matches {}",
+                artifact
+            ))));
+        }
+        entries.sort();
+        Ok(entries)
+    }
+
+    /// Cached candidates of the current platform, for listing when the repositories can't be reached.
+    /// Their identifier is labeled as cached, so they aren't mistaken for what the repositories offer
+    pub fn list_cached_candidates(&self) -> Vec<InstallationCandidate> {
+        let current_platform = Platform::platform_for_current_platform();
+        let mut candidates = self.list_cache().unwrap_or_default();
+        candidates.retain(|x| current_platform.as_ref() == Some(&x.flavor.platform));
+        for candidate in candidates.iter_mut() {
+            candidate.identifier.push_str(CACHED_LABEL);
+        }
+        candidates
+    }
+
+    /// Download sizes in bytes of [candidates], in the same order. Cached candidates are measured on disk,
+    /// the others are looked up in the artifact metadata of their repository, [SIZE_LOOKUPS_AT_ONCE] at a time.
+    /// None where the size can't be told, e.g. for installed products no repository has
+    pub async fn candidate_sizes(&self, candidates: &[InstallationCandidate]) -> Vec<Option<u64>> {
+        let lookups = candidates
+            .iter()
+            .enumerate()
+            .map(|(i, candidate)| async move { (i, self.candidate_size(candidate).await) });
+        let mut lookups =
+            futures_util::stream::iter(lookups).buffer_unordered(SIZE_LOOKUPS_AT_ONCE);
+        let mut sizes = vec![None; candidates.len()];
+        while let Some((i, size)) = lookups.next().await {
+            sizes[i] = size;
+        }
+        sizes
+    }
+
+    /// Download size in bytes of [candidate], see [Client::candidate_sizes]
+    async fn candidate_size(&self, candidate: &InstallationCandidate) -> Option<u64> {
+        if let Ok(metadata) = fs::metadata(self.cached_path(candidate)) {
+            return Some(metadata.len());
+        }
+        if candidate.remote_id.is_empty() {
+            return None;
+        }
+        let repo =
+            self.config.repositories.iter().find(|x| {
+                x.repository_server.as_deref() == Some(candidate.repo_location.as_str())
+            })?;
+        match team_city::get_artifact_size(&self.http_client, repo, candidate).await {
+            Ok(size) => size,
+            Err(e) => {
+                log::warn!(
+                    "Failed to get the size of {} {}: {}",
+                    candidate.product_name,
+                    candidate.version,
+                    e
+                );
+                None
+            }
+        }
+    }
+
     /// Attempts to locate the installer for the candiate in the local cache
     fn locate_in_cache(&self, search: &SearchCandidate) -> Option<InstallationCandidate> {
         let mut found_candidates: Vec<InstallationCandidate> = self.list_cache()?;
 
         /* Drop non platform, non product items, non desired flavor items */
         found_candidates.retain(|x| {
-            (x.flavor.platform == search.flavor.platform)
-                && (x.product_name.to_lowercase() == search.product_name.to_lowercase()
-                    && x.flavor.id.to_lowercase() == search.flavor.id.to_lowercase())
+            x.flavor.platform == search.flavor.platform
+                && x.flavor.architecture == search.flavor.architecture
+                && search.key().without_build().matches(&x.key())
         });
 
         for found in found_candidates.into_iter() {
             /* if version is specified, that overrides everything, grab first matching one */
             if let Some(v) = &search.version {
-                if v.to_lowercase() == found.version.to_lowercase() {
+                if CandidateKey::new(&search.product_name)
+                    .version(v)
+                    .matches(&found.key())
+                {
                     log::info!("Found exact version match in cache");
                     return Some(found);
                 }
@@ -524,28 +3371,38 @@ impl Client {
                 continue;
             }
             if let Some(i) = &search.identifier {
-                if i.to_lowercase() == found.identifier.to_lowercase() {
+                if CandidateKey::new(&search.product_name)
+                    .identifier(i)
+                    .matches(&found.key())
+                {
                     log::info!("Found matching identifier in cache");
                     return Some(found);
                 }
                 /* Identifier wasnt a match, but identifier is mandatory. Skip */
                 continue;
             }
-            if search.version.is_none() && search.identifier.is_none() {
-                log::info!("Found matching inexact unspecified version/identifier in cache");
-                return Some(found);
-            }
+            log::info!("Found matching inexact unspecified version/identifier in cache");
+            return Some(found);
         }
 
         None
     }
-    /// Lists items installed to this machine
+    /// Lists items installed to this machine, for every user or the current one
     pub fn get_installed(&self) -> Vec<InstalledProduct> {
+        self.get_installed_for(false)
+    }
+
+    /// Lists items installed to this machine. With [all_users], those installed for other users are
+    /// included too, which only makes a difference on Windows, where AppX packages and some MSIs are per user,
+    /// and on Linux, where standalone programs are
+    pub fn get_installed_for(&self, all_users: bool) -> Vec<InstalledProduct> {
         log::debug!("Getting installed Gravio items");
+        #[cfg(not(any(target_os = "windows", target_os = "linux")))]
+        let _ = all_users;
         #[cfg(target_os = "windows")]
         {
             let candidates = self
-                .get_installed_windows()
+                .get_installed_windows(all_users)
                 .expect("Failed to get installed gravio items");
             candidates
         }
@@ -556,8 +3413,95 @@ impl Client {
                 .expect("Failed to get installed gravio items");
             candidates
         }
-        #[cfg(any(target_os = "linux", target_os = "android"))]
-        {}
+        #[cfg(target_os = "linux")]
+        {
+            self.get_installed_linux(all_users)
+        }
+        #[cfg(target_os = "android")]
+        {
+            Vec::new()
+        }
+    }
+
+    /// Products installed through gman as standalone programs, according to the install journal, for the
+    /// current user or with [all_users] for everyone. Linux has no registry of installed programs outside of
+    /// package managers
+    #[cfg(target_os = "linux")]
+    fn get_installed_linux(&self, all_users: bool) -> Vec<InstalledProduct> {
+        let entries = journal::load(&self.state_directory);
+        let user = crate::util::current_user();
+        let mut installed = crate::desktop::installed(&entries)
+            .into_iter()
+            .filter(|x| all_users || x.user == user)
+            .map(|x| InstalledProduct {
+                product_name: x.product_name.to_owned(),
+                version: Version::new(&x.version),
+                package_name: x.product_name.to_owned(),
+                package_type: PackageType::StandaloneExe,
+                flavor: None,
+                path: crate::desktop::recorded_install_directory(x)
+                    .map(|x| x.to_path_buf())
+                    .unwrap_or_default(),
+                scope: InstallScope::User(x.user.to_owned()),
+                size: None,
+            })
+            .collect::<Vec<InstalledProduct>>();
+        installed.extend(self.get_installed_debs());
+        installed
+    }
+
+    /// Debian packages of the configured Deb flavors that dpkg has installed, see [parse_dpkg_packages]
+    #[cfg(target_os = "linux")]
+    fn get_installed_debs(&self) -> Vec<InstalledProduct> {
+        let products = self.get_products_for_platform();
+        if !products
+            .iter()
+            .flat_map(|x| &x.flavors)
+            .any(|x| x.package_type == PackageType::Deb)
+        {
+            return Vec::new();
+        }
+        match self.runner.capture(
+            "dpkg-query",
+            &[
+                "-W",
+                "-f",
+                "${Package}\t${Version}\t${db:Status-Status}\t${Installed-Size}\n",
+            ],
+        ) {
+            Ok(output) => parse_dpkg_packages(&output, &products),
+            Err(e) => {
+                log::warn!("Failed to list the installed Debian packages: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Removes the files the current user's latest install of [product_name] created, as recorded in the
+    /// install journal. Other users' installs are in their own home, and left alone
+    #[cfg(target_os = "linux")]
+    fn remove_installed_files(&self, product_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let entries = journal::load(&self.state_directory);
+        let user = crate::util::current_user();
+        if let Some(entry) = crate::desktop::installed(&entries)
+            .into_iter()
+            .find(|x| x.user == user && x.product_name.eq_ignore_ascii_case(product_name))
+        {
+            for file in &entry.files {
+                let result = crate::desktop::remove(std::slice::from_ref(file));
+                audit::append(
+                    &self.state_directory,
+                    &AuditEntry::new(
+                        AuditAction::DeleteFile,
+                        Some(product_name),
+                        &file.to_string_lossy(),
+                        result.as_ref().map(|_| ()).map_err(|e| e.to_string()),
+                    ),
+                );
+                result?;
+            }
+        }
+        Ok(())
     }
 
     /// Gets all configured products that are supported for the current executing platform
@@ -624,6 +3568,7 @@ impl Client {
 
                                     let mut product_name: String = String::default();
                                     let mut product_identifier: String = String::default();
+                                    let mut flavor_ids: Vec<&str> = Vec::new();
                                     for product in &self.config.products {
                                         for flavor in &product.flavors {
                                             if flavor.platform == Platform::Mac {
@@ -632,7 +3577,7 @@ impl Client {
                                                         if known_id == found_id {
                                                             product_identifier = known_id.into();
                                                             product_name = product.name.to_owned();
-                                                            break;
+                                                            flavor_ids.push(&flavor.id);
                                                         }
                                                     }
                                                 }
@@ -648,7 +3593,10 @@ impl Client {
                                             )),
                                             package_name: product_identifier,
                                             package_type: PackageType::App,
+                                            flavor: single_flavor(&flavor_ids),
                                             path: app_path,
+                                            scope: InstallScope::Machine,
+                                            size: None,
                                         };
 
                                         installed.push(instaled_product);
@@ -670,12 +3618,83 @@ impl Client {
                 return Err(Box::new(e));
             }
         };
-        Ok(installed)
+        Ok(installed)
+    }
+
+    /// Full name of the AppX/MsiX package [candidate] was just installed as, used to resolve the AppUserModelID to autorun
+    #[cfg(target_os = "windows")]
+    fn find_installed_package_full_name(
+        &self,
+        candidate: &InstallationCandidate,
+    ) -> Option<String> {
+        if !(candidate.flavor.package_type == PackageType::AppX
+            || candidate.flavor.package_type == PackageType::MsiX)
+        {
+            return None;
+        }
+        match self.get_installed_windows(false) {
+            Ok(installed) => installed
+                .into_iter()
+                .find(|x| {
+                    x.package_type == PackageType::AppX
+                        && x.product_name.eq_ignore_ascii_case(&candidate.product_name)
+                })
+                .map(|x| {
+                    log::trace!(
+                        "{} was installed as package {}",
+                        &x.product_name,
+                        &x.package_name
+                    );
+                    x.package_name
+                }),
+            Err(e) => {
+                log::debug!(
+                    "Couldn't find the installed package of {}, falling back to the Start Menu: {}",
+                    &candidate.product_name,
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// AppX packages provisioned for every account created on this machine. Empty if they can't be listed,
+    /// which needs an administrator
+    #[cfg(target_os = "windows")]
+    fn get_provisioned_appx(&self) -> Vec<InstalledAppXProduct> {
+        let command = "Get-AppxProvisionedPackage -Online | ForEach-Object { @{ Name = $_.DisplayName; Version = $_.Version.ToString(); PackageFullName = $_.PackageName } } | ConvertTo-Json -Compress";
+        let output = match PowerShellScript::new(command).run(self.runner.as_ref()) {
+            Ok(o) => o,
+            Err(e) => {
+                log::warn!("Failed to list provisioned packages: {}", e);
+                return Vec::new();
+            }
+        };
+        if !output.status.success() {
+            log::warn!(
+                "Provisioned packages can't be listed, gman may not be running as administrator: {}",
+                output.stderr.trim()
+            );
+            return Vec::new();
+        }
+        let mut result = output.stdout.trim().to_string();
+        if result.is_empty() {
+            return Vec::new();
+        }
+        if !(result.starts_with('[') && result.ends_with(']')) {
+            result.insert(0, '[');
+            result.push(']');
+        };
+        serde_json::from_str(&result).unwrap_or_else(|e| {
+            log::warn!("Failed to read the provisioned packages: {}", e);
+            Vec::new()
+        })
     }
 
     #[cfg(target_os = "windows")]
     fn get_installed_windows<'a>(
         &'a self,
+        all_users: bool,
     ) -> Result<Vec<InstalledProduct>, Box<dyn std::error::Error>> {
         use regex::Regex;
 
@@ -696,38 +3715,37 @@ impl Client {
             return Ok(installed);
         }
 
+        /* the publisher ids come from the repository, so they're passed to PowerShell as data */
+        let publishers = publisher_ids_for_platform
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>();
+
         /* get Appx Packages */
         {
-            let publisher_where = publisher_ids_for_platform
-                .iter()
-                .map(|x| format!("$_.Publisher -eq \"{}\"", x))
-                .collect::<Vec<String>>()
-                .join(" -or ");
-
-            let command = format!(
-                "Get-AppxPackage | Where-Object {{{}}} | Select Name, Version, PackageFullName | ConvertTo-Json -Compress",
-                publisher_where
-            );
-            let output = Command::new("powershell")
-                .arg("-Command")
-                .arg(command)
-                .output()?;
+            /* every user a package is installed for gets its own entry */
+            let command = if all_users {
+                "Get-AppxPackage -AllUsers | Where-Object {$_.Publisher -in $Publishers} | ForEach-Object { $p = $_; $p.PackageUserInformation | Where-Object { $_.InstallState -eq 'Installed' } | ForEach-Object { @{ Name = $p.Name; Version = $p.Version; PackageFullName = $p.PackageFullName; User = $_.UserSecurityId.Username } } } | ConvertTo-Json -Compress"
+            } else {
+                "Get-AppxPackage | Where-Object {$_.Publisher -in $Publishers} | Select Name, Version, PackageFullName | ConvertTo-Json -Compress"
+            };
+            let output = PowerShellScript::new(command)
+                .arg_list("Publishers", &publishers)
+                .run(self.runner.as_ref())?;
 
             // Check if the command was successful
             if output.status.success() {
                 // Convert the output bytes to a string
-                let mut result = String::from_utf8_lossy(&output.stdout)
-                    .to_owned()
-                    .trim()
-                    .to_string();
+                let mut result = output.stdout.trim().to_string();
                 if !(result.starts_with('[') && result.ends_with(']')) {
                     result.insert(0, '[');
                     result.push(']');
                 };
                 let v: Vec<InstalledAppXProduct> = serde_json::from_str(&result)?;
 
-                let closure = |v: &InstalledAppXProduct| -> Result<Option<&'a Product>, GManError> {
+                let closure = |v: &InstalledAppXProduct| -> Result<Option<(&'a Product, Option<String>)>, GManError> {
                     for product in products {
+                        let mut flavor_ids: Vec<&str> = Vec::new();
                         for flavor in &product.flavors {
                             if flavor.package_type == PackageType::AppX
                                 || flavor.package_type == PackageType::MsiX
@@ -737,7 +3755,7 @@ impl Client {
                                         match Regex::new(&dname_regex) {
                                             Ok(rgx) => {
                                                 if rgx.is_match(&v.name) {
-                                                    return Ok(Some(product));
+                                                    flavor_ids.push(&flavor.id);
                                                 }
                                             }
                                             Err(e) => {
@@ -752,15 +3770,36 @@ impl Client {
                                 }
                             }
                         }
+                        if !flavor_ids.is_empty() {
+                            return Ok(Some((product, single_flavor(&flavor_ids))));
+                        }
                     }
                     Ok(None)
                 };
                 for mut appx in v {
-                    if let Some(found) = closure(&appx)? {
+                    if let Some((found, flavor)) = closure(&appx)? {
                         appx.name = found.name.to_owned();
+                        appx.flavor = flavor;
                         installed.push(appx.into());
                     }
                 }
+                /* provisioned packages only matter when looking past the current user, or replacing them */
+                if all_users || app::is_provisioning_packages() {
+                    for provisioned in self.get_provisioned_appx() {
+                        if let Some((found, flavor)) = closure(&provisioned)? {
+                            installed.push(InstalledProduct {
+                                product_name: found.name.to_owned(),
+                                version: provisioned.version,
+                                package_name: provisioned.package_full_name,
+                                package_type: PackageType::AppX,
+                                flavor,
+                                path: PathBuf::new(),
+                                scope: InstallScope::Provisioned,
+                                size: None,
+                            });
+                        }
+                    }
+                }
             } else {
                 // Print the error message if the command failed
                 eprintln!("PowerShell command failed:\n{:?}", output.status);
@@ -770,51 +3809,64 @@ impl Client {
             }
         }
 
-        /* get MSI installed items */
+        /* get MSI installed items, machine-wide from both registry views and per user from the user hives */
         {
-            let publisher_where = publisher_ids_for_platform
-                .iter()
-                .map(|x| format!("$publisher -eq \"{}\"", x))
-                .collect::<Vec<String>>()
-                .join(" -or ");
-
+            let user_roots = if all_users {
+                /* only the hives of users that are logged in (or were loaded otherwise) are there */
+                r#"foreach($hive in Get-ChildItem "Registry::HKEY_USERS" | Where-Object { $_.PSChildName -notlike '*_Classes' }) {
+                    $sid = $hive.PSChildName
+                    try { $user = (New-Object System.Security.Principal.SecurityIdentifier($sid)).Translate([System.Security.Principal.NTAccount]).Value } catch { $user = $sid }
+                    $roots += @{ Path = "Registry::HKEY_USERS\$sid\Software\Microsoft\Windows\CurrentVersion\Uninstall"; User = $user }
+                }"#
+            } else {
+                r#"$roots += @{ Path = "HKCU:\Software\Microsoft\Windows\CurrentVersion\Uninstall"; User = $env:USERNAME }"#
+            };
             let command = {
                 let parts = [
-                    r#"foreach($obj in Get-ChildItem "HKLM:\Software\Microsoft\Windows\CurrentVersion\Uninstall") {
+                    r#"$roots = @(
+                        @{ Path = "HKLM:\Software\Microsoft\Windows\CurrentVersion\Uninstall"; User = "" },
+                        @{ Path = "HKLM:\Software\WOW6432Node\Microsoft\Windows\CurrentVersion\Uninstall"; User = "" }
+                    )
+                    "#,
+                    user_roots,
+                    r#"
+                    foreach($root in $roots) {
+                    if(-not (Test-Path $root.Path)) { continue }
+                    foreach($obj in Get-ChildItem $root.Path) {
                     $dn = $obj.GetValue('DisplayName')
                     $publisher = $obj.GetValue('Publisher')
-                    if($dn -ne $null -and ("#,
-                    &publisher_where,
-                    r#")) {
+                    if($dn -ne $null -and $publisher -in $Publishers) {
                         $key_name = ($obj | Select-Object Name | Split-Path -Leaf).replace('}}', '}')
                         $ver = $obj.GetValue('DisplayVersion')
                         $json = @{
                             "Name" = $dn
                             "Version" = $ver
                             "PackageFullName" = $key_name
+                            "User" = $root.User
+                            "EstimatedSize" = $obj.GetValue('EstimatedSize')
                         }
                         $MyJsonVariable = $json | ConvertTo-Json -Compress
                         Write-Host $MyJsonVariable
                       }
+                    }
                     }"#,
                 ];
                 String::from_iter(parts)
             };
 
-            let output = Command::new("powershell")
-                .arg("-Command")
-                .arg(command)
-                .output()?;
+            let output = PowerShellScript::new(&command)
+                .arg_list("Publishers", &publishers)
+                .run(self.runner.as_ref())?;
 
             // Check if the command was successful
             if output.status.success() {
-                // Convert the output bytes to a string
-                let result = String::from_utf8_lossy(&output.stdout);
-                if result.len() > 0 {
-                    let found_package: InstalledAppXProduct = serde_json::from_str(&result)?;
+                /* one json object per line */
+                for line in output.stdout.lines().filter(|x| !x.trim().is_empty()) {
+                    let found_package: InstalledAppXProduct = serde_json::from_str(line.trim())?;
 
-                    let closure = || -> Result<Option<&'a Product>, GManError> {
+                    let closure = || -> Result<Option<(&'a Product, Option<String>)>, GManError> {
                         for product in products {
+                            let mut flavor_ids: Vec<&str> = Vec::new();
                             for flavor in &product.flavors {
                                 if flavor.package_type == PackageType::Msi {
                                     if let Some(metadata) = &flavor.metadata {
@@ -822,7 +3874,7 @@ impl Client {
                                             match Regex::new(&dname_regex) {
                                                 Ok(rgx) => {
                                                     if rgx.is_match(&found_package.name) {
-                                                        return Ok(Some(product));
+                                                        flavor_ids.push(&flavor.id);
                                                     }
                                                 }
                                                 Err(e) => {
@@ -837,19 +3889,28 @@ impl Client {
                                     }
                                 }
                             }
+                            if !flavor_ids.is_empty() {
+                                return Ok(Some((product, single_flavor(&flavor_ids))));
+                            }
                         }
                         Ok(None)
                     };
 
                     let found_product = closure()?;
 
-                    if let Some(found) = found_product {
+                    if let Some((found, flavor)) = found_product {
                         let installed_product = InstalledProduct {
                             product_name: found.name.to_owned(),
                             version: Version::new(&found_package.version),
                             package_name: found_package.package_full_name.to_owned(),
                             package_type: product::PackageType::Msi,
+                            flavor,
                             path: PathBuf::new(),
+                            scope: match found_package.user {
+                                Some(user) if !user.is_empty() => InstallScope::User(user),
+                                _ => InstallScope::Machine,
+                            },
+                            size: found_package.estimated_size.map(|x| x * 1024),
                         };
 
                         installed.push(installed_product);
@@ -874,13 +3935,110 @@ impl Client {
     pub fn clear_cache(&self) -> Result<(), Box<dyn std::error::Error>> {
         let path = &self.config.cache_directory;
         log::debug!("Clearing cache directory {}", &path.to_str().unwrap());
-        util::remove_dir_contents(path)
+        let pinned = blob_store::pinned(path);
+        let mut kept: usize = 0;
+        let mut kept_pinned: usize = 0;
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            let entry_path = entry.path();
+            if entry.file_type()?.is_dir() {
+                /* snapshots outlive the cache, see [Client::save_snapshot], and so does the journal earlier versions
+                 * kept in it. Blobs are pruned below */
+                if entry.file_name() == snapshot::SNAPSHOT_DIRECTORY_NAME
+                    || entry.file_name() == journal::JOURNAL_DIRECTORY_NAME
+                    || entry.file_name() == blob_store::BLOB_DIRECTORY_NAME
+                {
+                    continue;
+                }
+                util::remove_dir_contents(&entry_path)?;
+                fs::remove_dir(entry_path)?;
+                continue;
+            }
+            if pinned.contains(entry.file_name().to_string_lossy().as_ref()) {
+                log::debug!("Keeping {}, it's pinned", entry_path.to_string_lossy());
+                kept_pinned += 1;
+                continue;
+            }
+            if let Ok(candidate) =
+                InstallationCandidate::from_str(&entry.file_name().to_string_lossy())
+            {
+                if snapshot::is_required_by_any(path, &candidate) {
+                    log::debug!(
+                        "Keeping {}, a snapshot needs it",
+                        entry_path.to_string_lossy()
+                    );
+                    kept += 1;
+                    continue;
+                }
+            }
+            fs::remove_file(entry_path)?;
+        }
+        if kept > 0 {
+            eprintln!("Kept {} cached installers needed by snapshots", kept);
+        }
+        if kept_pinned > 0 {
+            eprintln!("Kept {} pinned cached installers", kept_pinned);
+        }
+        blob_store::prune(path)?;
+        Ok(())
+    }
+
+    /// Saves the products installed on this machine as the snapshot [name], downloading any installer that
+    /// isn't cached yet so the snapshot can be restored later without depending on the build server
+    pub async fn save_snapshot(&self, name: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let manifest = self.export_manifest();
+        for entry in &manifest.products {
+            let search = match SearchCandidate::new(
+                &entry.product_name,
+                Some(&entry.version),
+                entry.branch.as_deref(),
+                entry.flavor.as_deref(),
+                &self.config.products,
+            ) {
+                Some(s) => s,
+                None => continue,
+            };
+            if self.locate_in_cache(&search).is_some() {
+                continue;
+            }
+            eprintln!(
+                "Installer for {} {} isn't cached, downloading it",
+                &entry.product_name, &entry.version
+            );
+            match self.download(&search).await {
+                Ok(Some(_)) => {}
+                Ok(None) => eprintln!(
+                    "Couldn't find {} {} in any repository, restoring it will need the build server",
+                    &entry.product_name, &entry.version
+                ),
+                Err(e) => {
+                    if GManError::is_cancelled(e.as_ref()) {
+                        return Err(e);
+                    }
+                    eprintln!(
+                        "Failed to download {} {}, restoring it will need the build server: {}",
+                        &entry.product_name, &entry.version, e
+                    )
+                }
+            }
+        }
+        snapshot::save(&self.config.cache_directory, name, &manifest)
+    }
+
+    /// Installs the products of the snapshot [name]
+    pub async fn restore_snapshot(
+        &self,
+        name: &str,
+        prompt: Option<bool>,
+        autorun: Option<bool>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let manifest = snapshot::load(&self.config.cache_directory, name)?;
+        self.install_manifest(&manifest, prompt, autorun).await
     }
 
-    /// Whether the given string is any kind of confirmation (yes, y, etc)
-    fn is_console_confirm(val: &str) -> bool {
-        let affirmative = ["y", "yes"];
-        affirmative.iter().any(|v| *v == val.trim().to_lowercase())
+    /// Names of the stored snapshots
+    pub fn list_snapshots(&self) -> Vec<String> {
+        snapshot::list(&self.config.cache_directory)
     }
 
     /// Formats a list of Gravio Candidate items into a table and prints to stdout
@@ -889,6 +4047,7 @@ impl Client {
         candidates: Vec<impl Into<TablePrinter>>,
         show_installed: bool,
         show_flavor: bool,
+        show_size: bool,
         show_path: bool,
     ) {
         log::debug!(
@@ -901,15 +4060,15 @@ impl Client {
             .map(|x| x.into())
             .collect::<Vec<TablePrinter>>();
 
-        data.sort_by(|a, b| {
-            let cmp_name = a.name.cmp(&b.name);
+        data.sort_by(TablePrinter::cmp_rows);
 
-            if cmp_name == std::cmp::Ordering::Equal {
-                b.version.cmp(&a.version)
-            } else {
-                cmp_name
-            }
-        });
+        /* a column for the notes builds are tagged with, only once there are any to show */
+        let tags = tags::Tags::load(&app::get_state_directory());
+        let notes = data
+            .iter()
+            .map(|x| tags.notes(&x.name, &x.version).join("; "))
+            .collect::<Vec<String>>();
+        let show_notes = notes.iter().any(|x| !x.is_empty());
 
         let mut builder = tabled::builder::Builder::default();
         let header_record = {
@@ -917,17 +4076,23 @@ impl Client {
             if show_flavor {
                 header.push("Flavor");
             }
+            if show_size {
+                header.push("Size");
+            }
             if show_installed {
                 header.push("Installed");
             }
             if show_path {
                 header.push("Path");
             }
+            if show_notes {
+                header.push("Notes");
+            }
             header
         };
         let header_record_count = header_record.len();
         builder.push_record(header_record);
-        for item in &data {
+        for (item, notes) in data.iter().zip(notes) {
             let record = {
                 let mut r = vec![
                     item.name.to_owned(),
@@ -937,12 +4102,20 @@ impl Client {
                 if show_flavor {
                     r.push(item.flavor.to_owned());
                 }
+                if show_size {
+                    r.push(item.size.to_owned());
+                }
                 if show_installed && item.installed {
                     r.push(item.installed.to_string());
                 }
                 if show_path && item.installed {
                     r.push(item.path.to_owned())
                 }
+                if show_notes {
+                    /* the notes stay in their column when the cells before them are left out */
+                    r.resize(header_record_count - 1, String::default());
+                    r.push(notes);
+                }
                 r
             };
             builder.push_record(record);
@@ -967,6 +4140,207 @@ impl Client {
     }
 }
 
+/// Bytes each of [installed] takes on disk: what its package manager reports, or else what the install
+/// journal in [state_directory] recorded when gman installed it. The rest are measured on disk if [measure],
+/// which takes a while for large installations
+pub fn installed_sizes(
+    installed: &[InstalledProduct],
+    state_directory: &Path,
+    measure: bool,
+) -> Vec<Option<u64>> {
+    let entries = journal::load(state_directory);
+    installed
+        .iter()
+        .map(|x| {
+            x.size
+                .or_else(|| journal::installed_size(&entries, &x.product_name, &x.version))
+                .or_else(|| if measure { x.footprint() } else { None })
+        })
+        .collect()
+}
+
+/// Prints the [installed] products with who they're installed for, and who installed them according to the
+/// install journal in [state_directory], with the size each takes on disk if [verbose]. The [show_path]
+/// column is only useful where products have one
+pub fn print_installed(
+    installed: &[InstalledProduct],
+    state_directory: &Path,
+    show_path: bool,
+    verbose: bool,
+) {
+    let entries = journal::load(state_directory);
+    let sizes = if verbose {
+        installed_sizes(installed, state_directory, true)
+    } else {
+        Vec::new()
+    };
+
+    let mut builder = tabled::builder::Builder::default();
+    let mut header = vec!["Name", "Version", "Identifier", "Scope", "Installed By"];
+    if verbose {
+        header.push("Size");
+    }
+    if show_path {
+        header.push("Path");
+    }
+    let header_record_count = header.len();
+    builder.push_record(header);
+    for (i, item) in installed.iter().enumerate() {
+        let mut record = vec![
+            item.product_name.to_owned(),
+            item.version.to_string(),
+            item.package_name.to_owned(),
+            item.scope.to_string(),
+            journal::installed_by(&entries, &item.product_name, &item.version)
+                .unwrap_or_default()
+                .to_owned(),
+        ];
+        if verbose {
+            record.push(
+                sizes[i]
+                    .map(|x| indicatif::HumanBytes(x).to_string())
+                    .unwrap_or_default(),
+            );
+        }
+        if show_path {
+            record.push(item.path.to_string_lossy().to_string());
+        }
+        builder.push_record(record);
+    }
+    if installed.is_empty() {
+        builder.push_record(["No products installed"]);
+    }
+
+    let mut table = builder.build();
+    table
+        .with(Style::sharp())
+        .with(Modify::new(Rows::first()).with(Alignment::center()));
+    if installed.is_empty() {
+        table
+            .modify((1, 0), tabled::settings::Span::column(header_record_count))
+            .modify((1, 0), Alignment::center());
+    }
+
+    println!("{table}");
+}
+
+/// Prints the artifact [files] of a build as a table. Directories are listed without a size
+pub fn print_artifact_files(files: &[team_city::TeamCityArtifactFile]) {
+    let mut builder = tabled::builder::Builder::default();
+    builder.push_record(["Name", "Size", "Path"]);
+    for file in files {
+        builder.push_record([
+            file.name.to_owned(),
+            file.size
+                .map(|x| indicatif::HumanBytes(x).to_string())
+                .unwrap_or_default(),
+            file.full_name.to_owned().unwrap_or(file.name.to_owned()),
+        ]);
+    }
+    if files.is_empty() {
+        builder.push_record(["No artifacts"]);
+    }
+
+    let mut table = builder.build();
+    table
+        .with(Style::sharp())
+        .with(Modify::new(Rows::first()).with(Alignment::center()));
+    if files.is_empty() {
+        table
+            .modify((1, 0), tabled::settings::Span::column(3))
+            .modify((1, 0), Alignment::center());
+    }
+
+    println!("{table}");
+}
+
+/// The flavor an installed package belongs to, if [flavor_ids], the flavors that match it, are just the one.
+/// Flavors sharing a package can't be told apart once it's installed
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+fn single_flavor(flavor_ids: &[&str]) -> Option<String> {
+    match flavor_ids {
+        [single] => Some(single.to_string()),
+        _ => None,
+    }
+}
+
+/// Indices of the [builds] grouped by the artifact they download, i.e. flavors of the same build
+/// configuration with the same binary path share one download
+fn group_by_artifact(builds: &[&InstallationCandidate]) -> Vec<Vec<usize>> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for (i, build) in builds.iter().enumerate() {
+        let same_artifact = |other: &InstallationCandidate| {
+            other.repo_location == build.repo_location
+                && other.remote_id == build.remote_id
+                && other.flavor.teamcity_metadata.teamcity_binary_path
+                    == build.flavor.teamcity_metadata.teamcity_binary_path
+        };
+        match groups.iter_mut().find(|x| same_artifact(builds[x[0]])) {
+            Some(group) => group.push(i),
+            None => groups.push(vec![i]),
+        }
+    }
+    groups
+}
+
+/// Prints how installing each flavor of [results] went, see [Client::install_flavors]
+pub fn print_flavor_results(results: &[FlavorInstall]) {
+    let mut builder = tabled::builder::Builder::default();
+    builder.push_record(["Flavor", "Version", "Result"]);
+    for item in results {
+        builder.push_record([
+            item.flavor.to_owned(),
+            item.version
+                .as_ref()
+                .map(|x| x.to_string())
+                .unwrap_or_default(),
+            match &item.result {
+                Ok(InstallationResult::Succeeded) => "Installed".to_owned(),
+                Ok(InstallationResult::Skipped) => "Skipped".to_owned(),
+                Ok(InstallationResult::Canceled) => "Canceled".to_owned(),
+                Err(e) => format!("Failed: {}", e),
+            },
+        ]);
+    }
+
+    let mut table = builder.build();
+    table
+        .with(Style::sharp())
+        .with(Modify::new(Rows::first()).with(Alignment::center()));
+    println!("{table}");
+}
+
+/// Prints the [outdated] products with their installed and available versions side by side
+pub fn print_outdated(outdated: &[OutdatedProduct]) {
+    let mut builder = tabled::builder::Builder::default();
+    builder.push_record(["Name", "Flavor", "Branch", "Installed", "", "Available"]);
+    for item in outdated {
+        builder.push_record([
+            item.product_name.to_owned(),
+            item.flavor.to_owned(),
+            item.branch.to_owned(),
+            item.installed.to_string(),
+            "→".to_owned(),
+            item.available.to_string(),
+        ]);
+    }
+    if outdated.is_empty() {
+        builder.push_record(["Everything is up to date"]);
+    }
+
+    let mut table = builder.build();
+    table
+        .with(Style::sharp())
+        .with(Modify::new(Rows::first()).with(Alignment::center()));
+    if outdated.is_empty() {
+        table
+            .modify((1, 0), tabled::settings::Span::column(6))
+            .modify((1, 0), Alignment::center());
+    }
+
+    println!("{table}");
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -974,12 +4348,18 @@ mod tests {
 
     use crate::{
         app,
-        candidate::SearchCandidate,
+        candidate::{InstalledProduct, SearchCandidate, Version},
         cli::Target,
+        client_config::CacheTier,
         platform::Platform,
         product::{Flavor, FlavorMetadata, PackageType, Product, TeamCityMetadata},
-        team_city, Client,
+        system_runner::MockRunner,
+        team_city, Client, ClientConfig,
     };
+
+    use super::{ClientBuilder, ExternalArtifact, OutdatedProduct, ProductFolder};
+    use crate::interaction::ScriptedInteraction;
+    use crate::manifest::{Manifest, ManifestEntry};
     use clap::builder::OsStr;
     use lazy_static::lazy_static;
 
@@ -987,6 +4367,11 @@ mod tests {
     /* HubKit */
     pub static ref PRODUCT_GRAVIO_HUBKIT: Product = Product {
         name: "HubKit".to_owned(),
+        branches: None,
+        branch_policy: None,
+        nightly_branch: None,
+        blocked_versions: None,
+        blocked_branches: None,
         flavors: vec![
             Flavor{
                 platform: Platform::Windows,
@@ -994,10 +4379,15 @@ mod tests {
                 package_type: PackageType::Msi,
                 teamcity_metadata: TeamCityMetadata {
                     teamcity_id: "Gravio_GravioHubKit4".to_owned(),
+                    branches: None,
+                    branch_policy: None,
+                    architectures: None,
                     teamcity_binary_path: PathBuf::from_str("GravioHubKit.msi").expect("Expected infalable binary msi hubkit path"),
                 },
                 metadata: None,
                 autorun: false,
+                transforms: Vec::new(),
+                architecture: None,
             },
             Flavor{
                 platform: Platform::Mac,
@@ -1005,6 +4395,9 @@ mod tests {
                 package_type: PackageType::App,
                 teamcity_metadata: TeamCityMetadata {
                     teamcity_id: "Gravio_GravioHubKit4".to_owned(),
+                    branches: None,
+                    branch_policy: None,
+                    architectures: None,
                     teamcity_binary_path: PathBuf::from_str("GravioHubKit.dmg").expect("Expected infalable app hubkit path"),
                 },
                 metadata: Some(FlavorMetadata {
@@ -1016,9 +4409,24 @@ mod tests {
                     launch_args: None,
                     stop_command: None,
                     run_as_service: None,
+                    installer_handles_upgrade: None,
+                    msi_transforms: None,
+                    msi_properties: None,
+                    executable: None,
+                    service_name: None,
+                    service_health_check_seconds: None,
+                    launch_environment: None,
+                    data_paths: None,
+                    log_paths: None,
+                    allow_unnotarized: None,
+                    installer_choices_xml: None,
+                    installer_target: None,
+                    checksum_artifact: None,
                 }),
 
                 autorun: false,
+                transforms: Vec::new(),
+                architecture: None,
             },
             // TODO(nf): Linux binaries are named for their version number (i.e., hubkit_5.2.1-8219_all.deb), this makes it hard to automatically extract their binary
         ],
@@ -1027,6 +4435,11 @@ mod tests {
         /* Gravio Studio */
         pub static ref PRODUCT_GRAVIO_STUDIO: Product = Product {
             name: "GravioStudio".to_owned(),
+            branches: None,
+            branch_policy: None,
+            nightly_branch: None,
+            blocked_versions: None,
+            blocked_branches: None,
             flavors: vec![
                 Flavor {
                     platform: Platform::Windows,
@@ -1034,10 +4447,15 @@ mod tests {
                     package_type: PackageType::AppX,
                     teamcity_metadata: TeamCityMetadata {
                         teamcity_id: "Gravio_GravioStudio4forWindows".to_owned(),
+                        branches: None,
+                        branch_policy: None,
+                        architectures: None,
                         teamcity_binary_path: PathBuf::from_str("graviostudio.zip").expect("Expected infalable binary studio path"),
                     },
                     metadata: None,
                     autorun: false,
+                    transforms: Vec::new(),
+                    architecture: None,
                 },
                 Flavor {
                     platform: Platform::Windows,
@@ -1045,10 +4463,15 @@ mod tests {
                     package_type: PackageType::AppX,
                     teamcity_metadata: TeamCityMetadata {
                         teamcity_id: "Gravio_GravioStudio4forWindows".to_owned(),
+                        branches: None,
+                        branch_policy: None,
+                        architectures: None,
                         teamcity_binary_path: PathBuf::from_str("graviostudio_sideloading.zip").expect("Expected infalable binary studio sideloading path"),
                     },
                     metadata: None,
                 autorun: false,
+                transforms: Vec::new(),
+                architecture: None,
                 },
                 Flavor {
                     platform: Platform::Mac,
@@ -1056,6 +4479,9 @@ mod tests {
                     package_type: PackageType::App,
                     teamcity_metadata: TeamCityMetadata {
                         teamcity_id: "Gravio_GravioStudio4ForMac".to_owned(),
+                        branches: None,
+                        branch_policy: None,
+                        architectures: None,
                         teamcity_binary_path: PathBuf::from_str("developerid/GravioStudio.dmg").expect("Expected infalable binary studio mac developer path"),
                     },
                     metadata: Some(FlavorMetadata {
@@ -1067,8 +4493,23 @@ mod tests {
                         launch_args: None,
                         stop_command: None,
                         run_as_service: None,
+                        installer_handles_upgrade: None,
+                        msi_transforms: None,
+                        msi_properties: None,
+                        executable: None,
+                        service_name: None,
+                        service_health_check_seconds: None,
+                        launch_environment: None,
+                        data_paths: None,
+                        log_paths: None,
+                        allow_unnotarized: None,
+                        installer_choices_xml: None,
+                        installer_target: None,
+                        checksum_artifact: None,
                     }),
                     autorun: false,
+                    transforms: Vec::new(),
+                    architecture: None,
                 },
                 Flavor {
                     platform: Platform::Mac,
@@ -1076,6 +4517,9 @@ mod tests {
                     package_type: PackageType::Pkg,
                     teamcity_metadata: TeamCityMetadata {
                         teamcity_id: "Gravio_GravioStudio4ForMac".to_owned(),
+                        branches: None,
+                        branch_policy: None,
+                        architectures: None,
                         teamcity_binary_path: PathBuf::from_str("appstore/Gravio Studio.pkg").expect("Expected infalable binary studio mac appstore path"),
                     },
                     metadata: Some(FlavorMetadata {
@@ -1087,15 +4531,35 @@ mod tests {
                         launch_args: None,
                         stop_command: None,
                         run_as_service: None,
+                        installer_handles_upgrade: None,
+                        msi_transforms: None,
+                        msi_properties: None,
+                        executable: None,
+                        service_name: None,
+                        service_health_check_seconds: None,
+                        launch_environment: None,
+                        data_paths: None,
+                        log_paths: None,
+                        allow_unnotarized: None,
+                        installer_choices_xml: None,
+                        installer_target: None,
+                        checksum_artifact: None,
 
                     }),
                     autorun: false,
+                    transforms: Vec::new(),
+                    architecture: None,
                 }
             ],
         };
 
         pub static ref PRODUCT_HANDBOOK_X: Product = Product {
             name: "HandbookX".to_owned(),
+            branches: None,
+            branch_policy: None,
+            nightly_branch: None,
+            blocked_versions: None,
+            blocked_branches: None,
             flavors: vec![
                 Flavor {
                     platform: Platform::Windows,
@@ -1103,11 +4567,16 @@ mod tests {
                     package_type: PackageType::MsiX,
                     teamcity_metadata: TeamCityMetadata {
                         teamcity_id: "Hubble_HubbleForWindows10".to_owned(),
+                        branches: None,
+                        branch_policy: None,
+                        architectures: None,
                         teamcity_binary_path: PathBuf::from_str("handbookx.msix")
                             .expect("Expected infalable binary handbookx msix path"),
                     },
                     metadata: None,
                     autorun: false,
+                    transforms: Vec::new(),
+                    architecture: None,
                 },
                 Flavor {
                     platform: Platform::Windows,
@@ -1115,11 +4584,16 @@ mod tests {
                     package_type: PackageType::MsiX,
                     teamcity_metadata: TeamCityMetadata {
                         teamcity_id: "Hubble_HubbleForWindows10".to_owned(),
+                        branches: None,
+                        branch_policy: None,
+                        architectures: None,
                         teamcity_binary_path: PathBuf::from_str("sideloadinghandbookx.msix")
                             .expect("Expected infalable binary handbookx msix sideloading path"),
                     },
                     metadata: None,
                     autorun: false,
+                    transforms: Vec::new(),
+                    architecture: None,
                 },
                 Flavor {
                     platform: Platform::Android,
@@ -1127,11 +4601,16 @@ mod tests {
                     package_type: PackageType::Apk,
                     teamcity_metadata: TeamCityMetadata {
                         teamcity_id: "Hubble_2_HubbleFlutter".to_owned(),
+                        branches: None,
+                        branch_policy: None,
+                        architectures: None,
                         teamcity_binary_path: PathBuf::from_str("handbookx-release.apk")
                             .expect("Expected infalable binary handbookx apkk path"),
                     },
                     metadata: None,
                     autorun: false,
+                    transforms: Vec::new(),
+                    architecture: None,
                 },
             ],
         };
@@ -1327,10 +4806,12 @@ mod tests {
             match &target {
                 Target::Identifier(x) => Some(x.as_str()),
                 Target::Version(x) => Some(x.as_str()),
+                _ => None,
             },
             match &target {
                 Target::Identifier(x) => Some(x.as_str()),
                 Target::Version(x) => Some(x.as_str()),
+                _ => None,
             },
             None,
             &client.config.products,
@@ -1351,12 +4832,12 @@ mod tests {
         let candidate = SearchCandidate::new(
             "HubKit".into(),
             match &target {
-                Target::Identifier(_) => None,
                 Target::Version(x) => Some(x.as_str()),
+                _ => None,
             },
             match &target {
                 Target::Identifier(x) => Some(x.as_str()),
-                Target::Version(_) => None,
+                _ => None,
             },
             None,
             &client.config.products,
@@ -1377,12 +4858,12 @@ mod tests {
         let candidate = SearchCandidate::new(
             &PRODUCT_GRAVIO_HUBKIT.name,
             match &target {
-                Target::Identifier(_) => None,
                 Target::Version(x) => Some(x.as_str()),
+                _ => None,
             },
             match &target {
                 Target::Identifier(x) => Some(x.as_str()),
-                Target::Version(_) => None,
+                _ => None,
             },
             None,
             &client.config.products,
@@ -1403,12 +4884,12 @@ mod tests {
         let candidate = SearchCandidate::new(
             &PRODUCT_GRAVIO_STUDIO.name,
             match &target {
-                Target::Identifier(_) => None,
                 Target::Version(x) => Some(x.as_str()),
+                _ => None,
             },
             match &target {
                 Target::Identifier(x) => Some(x.as_str()),
-                Target::Version(_) => None,
+                _ => None,
             },
             None,
             &client.config.products,
@@ -1429,12 +4910,12 @@ mod tests {
         let candidate = SearchCandidate::new(
             &PRODUCT_GRAVIO_STUDIO.name,
             match &target {
-                Target::Identifier(_) => None,
                 Target::Version(x) => Some(x.as_str()),
+                _ => None,
             },
             match &target {
                 Target::Identifier(x) => Some(x.as_str()),
-                Target::Version(_) => None,
+                _ => None,
             },
             None,
             &client.config.products,
@@ -1456,12 +4937,12 @@ mod tests {
         let candidate = SearchCandidate::new(
             &PRODUCT_HANDBOOK_X.name,
             match &target {
-                Target::Identifier(_) => None,
                 Target::Version(x) => Some(x.as_str()),
+                _ => None,
             },
             match &target {
                 Target::Identifier(x) => Some(x.as_str()),
-                Target::Version(_) => None,
+                _ => None,
             },
             None,
             &client.config.products,
@@ -1478,7 +4959,7 @@ mod tests {
     fn uninstall_hubkit() {
         let c = Client::load().expect("Failed to load client");
 
-        let _ = c.uninstall::<OsStr>("hubkit", None, None, None);
+        let _ = c.uninstall::<OsStr>("hubkit", None, None, None, false);
     }
 
     #[test]
@@ -1590,4 +5071,694 @@ mod tests {
         let expanded_no_percent = shellexpand::tilde("%temp%");
         println!("{:#?}", expanded_no_percent);
     }
+
+    /// Client with a fresh cache directory containing empty files named after [cached]
+    fn make_cache_client(name: &str, cached: &[&str], runner: MockRunner) -> Client {
+        let dir = std::env::temp_dir().join("gman_tests").join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for c in cached {
+            std::fs::write(dir.join(c), "installer").unwrap();
+        }
+
+        let mut config = ClientConfig::make_sample();
+        config.cache_directory = dir.clone();
+        config.products = vec![PRODUCT_GRAVIO_HUBKIT.clone()];
+        ClientBuilder::new(config)
+            .http_client(reqwest::Client::new())
+            .runner(Box::new(runner))
+            .state_directory(dir.join("state"))
+            .build()
+    }
+
+    fn make_installed(version: &str) -> InstalledProduct {
+        InstalledProduct {
+            product_name: "HubKit".into(),
+            version: Version::new(version),
+            package_name: "GravioHubKit".into(),
+            package_type: PackageType::Msi,
+            flavor: None,
+            path: PathBuf::default(),
+            scope: Default::default(),
+            size: None,
+        }
+    }
+
+    #[test]
+    fn installed_sizes_from_package_journal_or_disk() {
+        use crate::journal::{self, JournalAction, JournalEntry};
+
+        let temp = tempfile::tempdir().unwrap();
+        let cache = temp.path();
+        let app = cache.join("HubKit.app");
+        std::fs::create_dir_all(&app).unwrap();
+        std::fs::write(app.join("hubkit"), "program").unwrap();
+        journal::append(
+            cache,
+            &JournalEntry {
+                size: Some(2048),
+                ..JournalEntry::new(JournalAction::Install, "HubKit", "5.2.1-7049")
+            },
+        )
+        .unwrap();
+
+        let installed = [
+            InstalledProduct {
+                size: Some(1024),
+                ..make_installed("5.2.1.7001")
+            },
+            make_installed("5.2.1.7049"),
+            InstalledProduct {
+                path: app,
+                ..make_installed("5.2.1.7060")
+            },
+        ];
+        assert_eq!(
+            super::installed_sizes(&installed, cache, false),
+            vec![Some(1024), Some(2048), None]
+        );
+        assert_eq!(
+            super::installed_sizes(&installed, cache, true),
+            vec![Some(1024), Some(2048), Some("program".len() as u64)]
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn installed_debs_from_dpkg() {
+        let product: Product = serde_json::from_str(
+            r#"{"Name": "HubKit", "Flavors": [{"Platform": "Linux", "Id": "LinuxHubKit", "PackageType": "Deb",
+                "TeamCityMetadata": {"TeamCityId": "HubKit_Linux", "TeamCityBinaryPath": "deb/hubkit_5.2.3-7023_amd64.deb"}}]}"#,
+        )
+        .unwrap();
+        let output = "bash\t5.2.15-2\tinstalled\t7164\nhubkit\t1:5.2.3-7023\tinstalled\t184320\nhubkit-old\t5.1.0\tconfig-files\t\n";
+
+        let installed = super::parse_dpkg_packages(output, &[&product]);
+        assert_eq!(installed.len(), 1);
+        assert_eq!(installed[0].product_name, "HubKit");
+        assert_eq!(installed[0].version, Version::new("5.2.3-7023"));
+        assert_eq!(installed[0].flavor.as_deref(), Some("LinuxHubKit"));
+        assert_eq!(installed[0].package_type, PackageType::Deb);
+        assert_eq!(installed[0].footprint(), Some(184320 * 1024));
+    }
+
+    #[test]
+    fn purge_removes_existing_data_paths() {
+        let mut client = make_cache_client("purge", &[], MockRunner::new());
+        let data = client.config.cache_directory.join("HubKitData");
+        std::fs::create_dir_all(data.join("db")).unwrap();
+        std::fs::write(data.join("db").join("hubkit.db"), "data").unwrap();
+        let missing = client.config.cache_directory.join("NotThere");
+
+        let mut flavor = PRODUCT_GRAVIO_HUBKIT.flavors[0].clone();
+        flavor.platform = Platform::platform_for_current_platform().unwrap();
+        flavor.metadata = Some(FlavorMetadata {
+            data_paths: Some(vec![
+                data.to_string_lossy().to_string(),
+                missing.to_string_lossy().to_string(),
+            ]),
+            ..Default::default()
+        });
+        client.config.products[0].flavors.push(flavor);
+
+        assert_eq!(client.find_data_paths("hubkit"), vec![data.clone()]);
+        client.purge_data_paths("HubKit", false).unwrap();
+        assert!(!data.exists());
+        assert!(client.find_data_paths("hubkit").is_empty());
+    }
+
+    #[test]
+    fn open_logs_of_the_flavor() {
+        let mut client = make_cache_client("open_logs", &[], MockRunner::new());
+        let logs = client.config.cache_directory.join("HubKitLogs");
+        std::fs::create_dir_all(&logs).unwrap();
+
+        let err = client
+            .open_product_folder("HubKit", ProductFolder::Logs)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "No log folder of HubKit found on this machine"
+        );
+
+        let mut flavor = PRODUCT_GRAVIO_HUBKIT.flavors[0].clone();
+        flavor.platform = Platform::platform_for_current_platform().unwrap();
+        flavor.metadata = Some(FlavorMetadata {
+            log_paths: Some(vec![logs.to_string_lossy().to_string()]),
+            ..Default::default()
+        });
+        client.config.products[0].flavors.push(flavor);
+        assert_eq!(
+            client
+                .open_product_folder("hubkit", ProductFolder::Logs)
+                .unwrap(),
+            vec![logs]
+        );
+        assert!(client
+            .open_product_folder("NotAProduct", ProductFolder::Data)
+            .is_err());
+    }
+
+    #[test]
+    fn purge_asks_through_the_interaction() {
+        let dir = std::env::temp_dir()
+            .join("gman_tests")
+            .join("purge_interaction");
+        let _ = std::fs::remove_dir_all(&dir);
+        let data = dir.join("HubKitData");
+        std::fs::create_dir_all(&data).unwrap();
+
+        let mut config = ClientConfig::make_sample();
+        let mut flavor = PRODUCT_GRAVIO_HUBKIT.flavors[0].clone();
+        flavor.platform = Platform::platform_for_current_platform().unwrap();
+        flavor.metadata = Some(FlavorMetadata {
+            data_paths: Some(vec![data.to_string_lossy().to_string()]),
+            ..Default::default()
+        });
+        config.products = vec![PRODUCT_GRAVIO_HUBKIT.clone()];
+        config.products[0].flavors.push(flavor);
+        let build = |config: ClientConfig, answer: bool| {
+            ClientBuilder::new(config)
+                .cache_directory(dir.clone())
+                .state_directory(dir.join("state"))
+                .runner(Box::new(MockRunner::new()))
+                .interaction(Box::new(ScriptedInteraction::new().confirm_with(answer)))
+                .build()
+        };
+
+        let client = build(config, false);
+        client.purge_data_paths("HubKit", true).unwrap();
+        assert!(data.exists());
+
+        let client = build(client.config, true);
+        client.purge_data_paths("HubKit", true).unwrap();
+        assert!(!data.exists());
+    }
+
+    #[test]
+    fn rollback_candidate_found_in_cache() {
+        let client = make_cache_client(
+            "rollback_candidate",
+            &[
+                "HubKit@Windows@WindowsHubkit@develop@5.2.1-7049@GravioHubKit.msi",
+                "HubKit@Windows@WindowsHubkit@develop@5.2.1-7060@GravioHubKit.msi",
+            ],
+            MockRunner::new(),
+        );
+        let flavor = &PRODUCT_GRAVIO_HUBKIT.flavors[0];
+
+        let found = client
+            .find_rollback_candidate(&make_installed("5.2.1.7049"), flavor)
+            .expect("Expected previous version to be found in cache");
+        assert_eq!(found.version.to_string(), "5.2.1-7049");
+
+        assert!(client
+            .find_rollback_candidate(&make_installed("5.2.1.7001"), flavor)
+            .is_none());
+    }
+
+    #[test]
+    fn manifest_entry_from_cache() {
+        let client = make_cache_client(
+            "manifest_entry",
+            &[
+                "HubKit@Windows@WindowsHubkit@develop@5.2.1-7049@GravioHubKit.msi",
+                "HubKit@Windows@WindowsHubkit@local@5.2.1-7060@GravioHubKit.msi",
+            ],
+            MockRunner::new(),
+        );
+        let cached = client.list_cache().unwrap();
+
+        let entry = client.make_manifest_entry(&make_installed("5.2.1.7049"), &cached);
+        assert_eq!(entry.version, "5.2.1-7049");
+        assert_eq!(entry.flavor.as_deref(), Some("WindowsHubkit"));
+        assert_eq!(entry.branch.as_deref(), Some("develop"));
+
+        /* installed from a local file, can't be reproduced from a branch */
+        let entry = client.make_manifest_entry(&make_installed("5.2.1.7060"), &cached);
+        assert_eq!(entry.branch, None);
+
+        /* not cached, keeps the installed version */
+        let entry = client.make_manifest_entry(&make_installed("5.2.1.7001"), &cached);
+        assert_eq!(entry.version, "5.2.1.7001");
+        assert_eq!(entry.branch, None);
+    }
+
+    #[test]
+    fn manifest_entry_of_the_installed_flavor() {
+        let client = make_cache_client(
+            "manifest_entry_flavor",
+            &[
+                "HubKit@Windows@WindowsHubkit@develop@5.2.1-7049@GravioHubKit.msi",
+                "HubKit@Mac@MacHubkit@release@5.2.1-7049@GravioHubKit.dmg",
+            ],
+            MockRunner::new(),
+        );
+        let cached = client.list_cache().unwrap();
+
+        let mut installed = make_installed("5.2.1.7049");
+        installed.flavor = Some("machubkit".into());
+        let entry = client.make_manifest_entry(&installed, &cached);
+        assert_eq!(entry.flavor.as_deref(), Some("MacHubkit"));
+        assert_eq!(entry.branch.as_deref(), Some("release"));
+
+        installed.flavor = Some("WindowsHubkit".into());
+        let entry = client.make_manifest_entry(&installed, &cached);
+        assert_eq!(entry.branch.as_deref(), Some("develop"));
+    }
+
+    #[test]
+    fn cache_tiers_searched_in_order() {
+        let local = "HubKit@Windows@WindowsHubkit@develop@5.2.1-7049@GravioHubKit.msi";
+        let shared = "HubKit@Windows@WindowsHubkit@develop@5.2.1-7060@GravioHubKit.msi";
+        let mut client = make_cache_client("cache_tiers_local", &[local], MockRunner::new());
+        let share = make_cache_client("cache_tiers_share", &[local, shared], MockRunner::new())
+            .config
+            .cache_directory;
+        client.config.cache_directories = vec![
+            CacheTier {
+                path: client.config.cache_directory.clone(),
+                read_only: false,
+            },
+            CacheTier {
+                path: share.clone(),
+                read_only: false,
+            },
+        ];
+        client.config.resolve_cache_tiers();
+
+        let cached = client.list_cache().unwrap();
+        assert_eq!(cached.len(), 2);
+        assert_eq!(
+            client.cached_path(&cached[0]),
+            share.join(shared),
+            "only the share has 7060"
+        );
+        assert_eq!(
+            client.cached_path(&cached[1]),
+            client.config.cache_directory.join(local),
+            "the local tier comes first"
+        );
+    }
+
+    #[test]
+    fn outdated_on_installed_branch() {
+        let cached = "HubKit@Windows@WindowsHubkit@develop@5.2.1-7049@GravioHubKit.msi";
+        let client = make_cache_client("outdated", &[cached], MockRunner::new());
+        let available = |identifier: &str, version: &str| {
+            let mut candidate = client.list_cache().unwrap().remove(0);
+            candidate.identifier = identifier.to_owned();
+            candidate.version = Version::new(version);
+            candidate
+        };
+        let candidates = vec![
+            available("develop", "5.2.1-7060"),
+            available("develop", "5.2.1-7055"),
+            available("master", "5.2.1-7070"),
+        ];
+
+        assert_eq!(
+            client.find_outdated(&[make_installed("5.2.1.7049")], &candidates),
+            vec![OutdatedProduct {
+                product_name: "HubKit".into(),
+                flavor: "WindowsHubkit".into(),
+                branch: "develop".into(),
+                installed: Version::new("5.2.1.7049"),
+                available: Version::new("5.2.1-7060"),
+            }],
+            "the newer build on master isn't where 7049 came from"
+        );
+        assert!(client
+            .find_outdated(&[make_installed("5.2.1.7060")], &candidates)
+            .is_empty());
+    }
+
+    #[test]
+    fn outdated_skips_expired_builds() {
+        let cached = "HubKit@Windows@WindowsHubkit@develop@5.2.1-7049@GravioHubKit.msi";
+        let client = make_cache_client("outdated_expired", &[cached], MockRunner::new());
+        let mut expired = client.list_cache().unwrap().remove(0);
+        expired.version = Version::new("5.2.1-7070");
+        expired.expired = true;
+        let mut available = client.list_cache().unwrap().remove(0);
+        available.version = Version::new("5.2.1-7060");
+
+        assert_eq!(
+            client
+                .find_outdated(&[make_installed("5.2.1.7049")], &[expired, available])
+                .into_iter()
+                .map(|x| x.available)
+                .collect::<Vec<Version>>(),
+            vec![Version::new("5.2.1-7060")],
+            "7070 can't be downloaded anymore"
+        );
+    }
+
+    #[test]
+    fn upgrades_of_every_installed_product() {
+        let cached = "HubKit@Windows@WindowsHubkit@develop@5.2.1-7049@GravioHubKit.msi";
+        let client = make_cache_client("upgrades_of", &[cached], MockRunner::new());
+        let mut newer = client.list_cache().unwrap().remove(0);
+        newer.version = Version::new("5.2.1-7060");
+        let installed = [make_installed("5.2.1.7049"), make_installed("5.2.1.7049")];
+
+        let upgrades = client
+            .upgrades_of(&[], &installed, &[newer.clone()])
+            .unwrap();
+        assert_eq!(
+            upgrades
+                .iter()
+                .map(|x| (x.product_name.as_str(), &x.available))
+                .collect::<Vec<_>>(),
+            vec![("HubKit", &Version::new("5.2.1-7060"))]
+        );
+        assert_eq!(
+            client
+                .upgrades_of(&["hubkit".to_owned()], &installed, &[newer.clone()])
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(client
+            .upgrades_of(&["Studio".to_owned()], &installed, &[newer])
+            .is_err());
+        assert!(client.upgrades_of(&[], &[], &[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn upgrade_requires_installed_product() {
+        let cached = "HubKit@Windows@WindowsHubkit@develop@5.2.1-7049@GravioHubKit.msi";
+        let client = make_cache_client("upgrade_of", &[cached], MockRunner::new());
+        let mut newer = client.list_cache().unwrap().remove(0);
+        newer.version = Version::new("5.2.1-7060");
+
+        assert!(client.upgrade_of("HubKit", &[], &[]).is_err());
+        assert_eq!(
+            client
+                .upgrade_of("hubkit", &[make_installed("5.2.1.7049")], &[newer])
+                .unwrap()
+                .into_iter()
+                .map(|x| (x.branch, x.available))
+                .collect::<Vec<_>>(),
+            vec![("develop".to_owned(), Version::new("5.2.1-7060"))]
+        );
+        assert!(client
+            .upgrade_of("HubKit", &[make_installed("5.2.1.7060")], &[])
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn upgrade_of_each_installed_flavor() {
+        let cached = "HubKit@Windows@WindowsHubkit@develop@5.2.1-7049@GravioHubKit.msi";
+        let client = make_cache_client("upgrade_of_flavors", &[cached], MockRunner::new());
+        let mut newer = client.list_cache().unwrap().remove(0);
+        newer.version = Version::new("5.2.1-7060");
+        let mut other_flavor = newer.clone();
+        other_flavor.flavor.id = "OtherHubkit".to_owned();
+        other_flavor.version = Version::new("5.2.1-7055");
+        let mut installed = make_installed("5.2.1.7049");
+        installed.flavor = Some("WindowsHubkit".to_owned());
+        let mut installed_other = make_installed("5.2.1.7049");
+        installed_other.flavor = Some("OtherHubkit".to_owned());
+
+        let mut upgrades = client
+            .upgrade_of(
+                "HubKit",
+                &[installed, installed_other],
+                &[newer, other_flavor],
+            )
+            .unwrap()
+            .into_iter()
+            .map(|x| (x.flavor, x.available))
+            .collect::<Vec<_>>();
+        upgrades.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            upgrades,
+            vec![
+                ("OtherHubkit".to_owned(), Version::new("5.2.1-7055")),
+                ("WindowsHubkit".to_owned(), Version::new("5.2.1-7060")),
+            ]
+        );
+    }
+
+    #[test]
+    fn flavors_of_one_build_share_artifact() {
+        let cached = "HubKit@Windows@WindowsHubkit@develop@5.2.1-7049@GravioHubKit.msi";
+        let client = make_cache_client("group_by_artifact", &[cached], MockRunner::new());
+        let build = |remote_id: &str, flavor_id: &str, binary_path: &str| {
+            let mut candidate = client.list_cache().unwrap().remove(0);
+            candidate.remote_id = remote_id.to_owned();
+            candidate.flavor.id = flavor_id.to_owned();
+            candidate.flavor.teamcity_metadata.teamcity_binary_path = PathBuf::from(binary_path);
+            candidate
+        };
+        let store = build("101", "WindowsAppStore", "Studio.msixbundle");
+        let sideloading = build("101", "Sideloading", "Studio.msixbundle");
+        let arm = build("101", "Arm64", "Studio_arm64.msixbundle");
+        let other_build = build("102", "Sideloading", "Studio.msixbundle");
+
+        assert_eq!(
+            super::group_by_artifact(&[&store, &sideloading, &arm, &other_build]),
+            vec![vec![0, 1], vec![2], vec![3]]
+        );
+    }
+
+    #[tokio::test]
+    async fn candidate_sizes_of_cached_and_unknown() {
+        let cached = "HubKit@Windows@WindowsHubkit@develop@5.2.1-7049@GravioHubKit.msi";
+        let client = make_cache_client("candidate_sizes", &[cached], MockRunner::new());
+        let mut candidates = client.list_cache().unwrap();
+        let mut unknown = client.list_cache().unwrap().remove(0);
+        unknown.version = Version::new("5.2.1-7060");
+        candidates.push(unknown);
+
+        assert_eq!(
+            client.candidate_sizes(&candidates).await,
+            vec![Some("installer".len() as u64), None],
+            "a build that isn't cached and has no repository has no size"
+        );
+    }
+
+    #[test]
+    fn clear_cache_keeps_snapshot_artifacts() {
+        let kept = "HubKit@Windows@WindowsHubkit@develop@5.2.1-7049@GravioHubKit.msi";
+        let removed = "HubKit@Windows@WindowsHubkit@develop@5.2.1-7060@GravioHubKit.msi";
+        let client =
+            make_cache_client("clear_cache_snapshots", &[kept, removed], MockRunner::new());
+        let cache = client.config.cache_directory.clone();
+
+        let manifest = Manifest {
+            products: vec![ManifestEntry {
+                product_name: "HubKit".into(),
+                version: "5.2.1-7049".into(),
+                flavor: Some("WindowsHubkit".into()),
+                branch: Some("develop".into()),
+                repository: None,
+            }],
+        };
+        crate::snapshot::save(&cache, "release", &manifest).unwrap();
+
+        client.clear_cache().unwrap();
+        assert!(cache.join(kept).exists());
+        assert!(!cache.join(removed).exists());
+        assert_eq!(client.list_snapshots(), vec!["release"]);
+    }
+
+    #[test]
+    fn clearing_temp_keeps_partial_downloads() {
+        let mut client = make_cache_client("clear_temp_partial", &[], MockRunner::new());
+        let temp = client.config.cache_directory.join("tmp");
+        client.config.temp_download_directory = temp.clone();
+        std::fs::create_dir_all(temp.join("msi_transforms")).unwrap();
+        std::fs::write(temp.join("GravioHubKit.msi"), "installer").unwrap();
+        std::fs::write(temp.join("GravioHubKit.msi.part"), "instal").unwrap();
+
+        client.clear_temp();
+        assert!(temp.join("GravioHubKit.msi.part").exists());
+        assert!(!temp.join("GravioHubKit.msi").exists());
+        assert!(!temp.join("msi_transforms").exists());
+    }
+
+    #[test]
+    fn clear_cache_keeps_pinned_artifacts() {
+        let pinned = "HubKit@Windows@WindowsHubkit@develop@5.2.1-7049@GravioHubKit.msi";
+        let removed = "HubKit@Windows@WindowsHubkit@develop@5.2.1-7060@GravioHubKit.msi";
+        let client = make_cache_client("clear_cache_pins", &[pinned, removed], MockRunner::new());
+        let cache = client.config.cache_directory.clone();
+
+        assert_eq!(client.pin_cached("5.2.1.7049", true).unwrap(), vec![pinned]);
+        assert!(client.pin_cached("5.2.1-7100", true).is_err());
+        let listed = client.list_cache_with_pins().unwrap();
+        assert_eq!(
+            listed
+                .iter()
+                .filter(|x| x.identifier.ends_with("(pinned)"))
+                .count(),
+            1
+        );
+
+        client.clear_cache().unwrap();
+        assert!(cache.join(pinned).exists());
+        assert!(!cache.join(removed).exists());
+        client.pin_cached(pinned, false).unwrap();
+        client.clear_cache().unwrap();
+        assert!(!cache.join(pinned).exists());
+    }
+
+    #[tokio::test]
+    async fn install_external_file_requires_version() {
+        let client = make_cache_client("install_external_file", &[], MockRunner::new());
+        let file = client.config.cache_directory.join("GravioHubKit.msi");
+        std::fs::write(&file, "installer").unwrap();
+
+        let err = client
+            .install_external(
+                &ExternalArtifact::File(&file),
+                "HubKit",
+                None,
+                Some("WindowsHubkit"),
+                Some(false),
+                Some(false),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("--version"));
+
+        let err = client
+            .install_external(
+                &ExternalArtifact::File(&file),
+                "NotAProduct",
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not in the configuration"));
+    }
+
+    #[cfg(target_os = "windows")]
+    #[tokio::test]
+    async fn wait_until_running_polls_until_started() {
+        use crate::{candidate::InstallationCandidate, system_runner::CommandOutput};
+        use std::time::Duration;
+
+        let mut candidate = InstallationCandidate::from_str(
+            "HubKit@Windows@WindowsHubKit@develop@5.2.1-7049@GravioHubKit.exe",
+        )
+        .unwrap();
+        candidate.flavor.package_type = PackageType::StandaloneExe;
+        candidate.flavor.metadata = Some(FlavorMetadata {
+            install_path: Some(r"C:\Program Files\HubKit\HubKit.exe".into()),
+            ..Default::default()
+        });
+
+        /* not running yet on the first check, running on the second */
+        let client = make_cache_client(
+            "wait_until_running",
+            &[],
+            MockRunner::new()
+                .respond(CommandOutput::failed(1, ""))
+                .respond(CommandOutput::ok("")),
+        );
+        client
+            .wait_until_running(&candidate, Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        /* never shows up */
+        let client = make_cache_client(
+            "wait_until_running_timeout",
+            &[],
+            MockRunner::new().respond(CommandOutput::failed(1, "")),
+        );
+        let e = client
+            .wait_until_running(&candidate, Duration::ZERO)
+            .await
+            .unwrap_err();
+        assert!(e.to_string().contains("wasn't observed running"));
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn running_product_closed_only_when_forced() {
+        use crate::system_runner::CommandOutput;
+
+        let mut installed = make_installed("5.2.1.7049");
+        installed.package_name = "{4C6E0F2B-1D3A-4F5E-9A7B-2C8D0E1F3A5B}".into();
+
+        let runner = MockRunner::new().respond(CommandOutput::ok("HubKit (4242)\n"));
+        let client = make_cache_client("close_running", &[], runner);
+        let e = client
+            .close_if_running(&installed, Some(false))
+            .unwrap_err();
+        assert!(e.to_string().contains("--force"));
+
+        let runner = MockRunner::new().respond(CommandOutput::ok("HubKit (4242)\n"));
+        let mut client = make_cache_client("close_running_forced", &[], runner);
+        client.config.close_running_applications = true;
+        assert!(client.close_if_running(&installed, Some(false)).unwrap());
+
+        /* nothing running, nothing to ask */
+        let client = make_cache_client("close_not_running", &[], MockRunner::new());
+        assert!(client.close_if_running(&installed, Some(false)).unwrap());
+    }
+
+    #[cfg(target_os = "windows")]
+    #[tokio::test]
+    async fn failed_install_rolls_back() {
+        use crate::{
+            candidate::InstallationResult, gman_error::GManError, system_runner::CommandOutput,
+        };
+
+        let cached = [
+            "HubKit@Windows@WindowsHubkit@develop@5.2.1-7049@GravioHubKit.msi",
+            "HubKit@Windows@WindowsHubkit@develop@5.2.1-7060@GravioHubKit.msi",
+        ];
+        let client = make_cache_client("rollback_ok", &cached, MockRunner::new());
+        let cache = client.list_cache().unwrap();
+        let new = cache.iter().find(|x| *x.version == *"5.2.1-7060").unwrap();
+        let old = cache.iter().find(|x| *x.version == *"5.2.1-7049").unwrap();
+
+        let result = client
+            .rollback_if_failed(
+                Err(Box::new(GManError::new("msi broke"))),
+                new,
+                &make_installed("5.2.1.7049"),
+                Some(old),
+            )
+            .await;
+        let message = result.unwrap_err().to_string();
+        assert!(message.contains("msi broke"));
+        assert!(message.contains("Rolled back to previously installed version 5.2.1-7049"));
+
+        /* rollback installer fails too */
+        let client = make_cache_client(
+            "rollback_failed",
+            &cached,
+            MockRunner::new().respond(CommandOutput::failed(1603, "")),
+        );
+        let result = client
+            .rollback_if_failed(
+                Err(Box::new(GManError::new("msi broke"))),
+                new,
+                &make_installed("5.2.1.7049"),
+                Some(old),
+            )
+            .await;
+        assert!(result.unwrap_err().to_string().contains("also failed"));
+
+        /* nothing to do if it went fine */
+        let result = client
+            .rollback_if_failed(
+                Ok(InstallationResult::Succeeded),
+                new,
+                &make_installed("5.2.1.7049"),
+                Some(old),
+            )
+            .await;
+        assert!(matches!(result, Ok(InstallationResult::Succeeded)));
+    }
 }
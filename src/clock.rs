@@ -0,0 +1,16 @@
+use std::time::SystemTime;
+
+/// Tells the time, so timeouts can be tested without waiting for them
+pub trait Clock: std::fmt::Debug + Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+/// [Clock] of the system
+#[derive(Debug)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
@@ -0,0 +1,268 @@
+use std::ffi::OsStr;
+
+use clap_complete::engine::CompletionCandidate;
+
+use crate::{
+    candidate::InstallationCandidate, client::Client, client_config::ClientConfig, config_trust,
+};
+
+/// Flags of the `install` command that consume the following argument as their value
+const INSTALL_VALUE_FLAGS: [&str; 18] = [
+    "-f",
+    "--flavor",
+    "-a",
+    "--automatic-upgrade",
+    "--prompt",
+    "--autorun",
+    "--file",
+    "--url",
+    "--checksum",
+    "--manifest",
+    "--launch-args",
+    "--wait-for-launch",
+    "--version",
+    "--log-level",
+    "--arch",
+    "--target",
+    "--container",
+    "--output",
+];
+
+/// Loads the configuration using the regular search order, without printing anything.
+/// Completions must never write to stdout other than the candidates themselves, so configurations that
+/// aren't trusted yet aren't asked about but complete nothing, see [config_trust::is_trusted]
+fn load_config_quiet() -> Option<ClientConfig> {
+    let (source, path) = ClientConfig::find_config_file::<&str>(None, false)?;
+    if !config_trust::is_trusted(source, &path) {
+        return None;
+    }
+    ClientConfig::load_config_file(&path).ok()
+}
+
+/// Completes product names from the `Products` section of the configuration
+pub fn complete_product_name(current: &OsStr) -> Vec<CompletionCandidate> {
+    let current = current.to_string_lossy().to_lowercase();
+    let config = match load_config_quiet() {
+        Some(c) => c,
+        None => return Vec::new(),
+    };
+
+    config
+        .products
+        .iter()
+        .filter(|p| p.name.to_lowercase().starts_with(&current))
+        .map(|p| CompletionCandidate::new(&p.name))
+        .collect()
+}
+
+/// Completes the `build_or_branch` argument of `install` with the branch names and build numbers
+/// known to the local cache for the product being completed
+pub fn complete_build_or_branch(current: &OsStr) -> Vec<CompletionCandidate> {
+    let args = std::env::args().collect::<Vec<String>>();
+    let product_name = match product_from_install_args(&args) {
+        Some(p) => p,
+        None => return Vec::new(),
+    };
+    let config = match load_config_quiet() {
+        Some(c) => c,
+        None => return Vec::new(),
+    };
+
+    let client = Client::new(config);
+    let cached = client.list_cache().unwrap_or_default();
+
+    let current = current.to_string_lossy();
+    let aliases = [
+        ("latest", "newest build on any branch"),
+        (
+            "nightly",
+            "newest build of the last 24h on the nightly branch",
+        ),
+    ]
+    .into_iter()
+    .filter(|(value, _)| value.starts_with(&current.to_lowercase()))
+    .map(|(value, help)| (value.to_owned(), help.to_owned()));
+
+    branches_and_builds_for_product(&cached, &product_name, &current)
+        .into_iter()
+        .chain(aliases)
+        .map(|(value, help)| CompletionCandidate::new(value).help(Some(help.into())))
+        .collect()
+}
+
+/// Completes flavor ids for the product being completed, or every flavor if the product isn't known yet
+pub fn complete_flavor(current: &OsStr) -> Vec<CompletionCandidate> {
+    let current = current.to_string_lossy();
+    /* `install` takes several comma separated flavors, complete the last one */
+    let (completed, current) = match current.rsplit_once(',') {
+        Some((completed, current)) => (format!("{},", completed), current.to_lowercase()),
+        None => (String::new(), current.to_lowercase()),
+    };
+    let args = std::env::args().collect::<Vec<String>>();
+    let product_name = product_from_install_args(&args).map(|x| x.to_lowercase());
+    let config = match load_config_quiet() {
+        Some(c) => c,
+        None => return Vec::new(),
+    };
+
+    config
+        .products
+        .iter()
+        .filter(|p| match &product_name {
+            Some(name) => &p.name.to_lowercase() == name,
+            None => true,
+        })
+        .flat_map(|p| p.flavors.iter())
+        .filter(|f| f.id.to_lowercase().starts_with(&current))
+        .map(|f| {
+            CompletionCandidate::new(format!("{}{}", completed, f.id))
+                .help(Some(f.platform.to_string().into()))
+        })
+        .collect()
+}
+
+/// Finds the product name argument of an `install` (or `inspect`, `prefetch`) invocation in the raw
+/// command line being completed
+///
+/// i.e., `gman -- gman install -f Sideloading graviostudio dev` -> `graviostudio`
+fn product_from_install_args(args: &[String]) -> Option<String> {
+    let install_pos = args
+        .iter()
+        .position(|x| matches!(x.as_str(), "install" | "inspect" | "prefetch"))?;
+    let mut iter = args.iter().skip(install_pos + 1);
+    while let Some(arg) = iter.next() {
+        if arg == "--product" {
+            return iter.next().map(|x| x.to_owned());
+        }
+        if INSTALL_VALUE_FLAGS.contains(&arg.as_str()) {
+            /* skip the flag value */
+            iter.next();
+            continue;
+        }
+        if arg.starts_with('-') {
+            continue;
+        }
+        return Some(arg.to_owned());
+    }
+    None
+}
+
+/// Collects the branch names and build numbers of cached candidates for [product_name] that start with [prefix],
+/// most recent version first. Returns pairs of (value, description)
+fn branches_and_builds_for_product(
+    cached: &[InstallationCandidate],
+    product_name: &str,
+    prefix: &str,
+) -> Vec<(String, String)> {
+    let product_lower = product_name.to_lowercase();
+    let prefix_lower = prefix.to_lowercase();
+
+    let mut matching = cached
+        .iter()
+        .filter(|x| x.product_name.to_lowercase() == product_lower)
+        .collect::<Vec<&InstallationCandidate>>();
+    matching.sort_by(|a, b| {
+        b.version
+            .partial_cmp(&a.version)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let mut result: Vec<(String, String)> = Vec::new();
+    for candidate in matching {
+        let entries = [
+            (
+                candidate.identifier.to_owned(),
+                format!("branch, newest cached {}", candidate.version),
+            ),
+            (
+                candidate.version.to_string(),
+                format!("build on {}", candidate.identifier),
+            ),
+        ];
+        for (value, help) in entries {
+            if value.to_lowercase().starts_with(&prefix_lower)
+                && !result.iter().any(|(v, _)| v == &value)
+            {
+                result.push((value, help));
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::candidate::InstallationCandidate;
+
+    use super::{branches_and_builds_for_product, product_from_install_args};
+
+    fn args(s: &str) -> Vec<String> {
+        s.split(' ').map(|x| x.to_owned()).collect()
+    }
+
+    #[test]
+    fn product_from_args_simple() {
+        let found = product_from_install_args(&args("gman -- gman install hubkit fea"));
+        assert_eq!(found, Some("hubkit".to_owned()));
+    }
+
+    #[test]
+    fn product_from_args_skips_flags() {
+        let found = product_from_install_args(&args(
+            "gman -- gman install -f Sideloading --prompt false graviostudio dev",
+        ));
+        assert_eq!(found, Some("graviostudio".to_owned()));
+    }
+
+    #[test]
+    fn product_from_args_product_flag() {
+        let found = product_from_install_args(&args(
+            "gman -- gman install --file ./GravioHubKit.msi --product HubKit -f",
+        ));
+        assert_eq!(found, Some("HubKit".to_owned()));
+    }
+
+    #[test]
+    fn product_from_args_prefetch() {
+        let found = product_from_install_args(&args("gman -- gman prefetch hubkit dev"));
+        assert_eq!(found, Some("hubkit".to_owned()));
+    }
+
+    #[test]
+    fn product_from_args_not_install() {
+        let found = product_from_install_args(&args("gman -- gman list"));
+        assert_eq!(found, None);
+    }
+
+    #[test]
+    fn branches_and_builds_sorted_and_filtered() {
+        let cached = [
+            "HubKit@Windows@WindowsHubKit@develop@5.2.1-7049@GravioHubKit.msi",
+            "HubKit@Windows@WindowsHubKit@feature/sensor-refactor-v2@5.2.1-7060@GravioHubKit.msi",
+            "HubKit@Windows@WindowsHubKit@develop@5.2.1-7033@GravioHubKit.msi",
+            "GravioStudio@Windows@Sideloading@develop@5.2.4683@graviostudio.zip",
+        ]
+        .iter()
+        .map(|x| InstallationCandidate::from_str(x).unwrap())
+        .collect::<Vec<InstallationCandidate>>();
+
+        let all = branches_and_builds_for_product(&cached, "hubkit", "");
+        let values = all.iter().map(|x| x.0.as_str()).collect::<Vec<&str>>();
+        assert_eq!(
+            values,
+            vec![
+                "feature/sensor-refactor-v2",
+                "5.2.1-7060",
+                "develop",
+                "5.2.1-7049",
+                "5.2.1-7033"
+            ]
+        );
+
+        let filtered = branches_and_builds_for_product(&cached, "hubkit", "fea");
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].0, "feature/sensor-refactor-v2");
+    }
+}
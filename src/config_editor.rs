@@ -0,0 +1,124 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::{app, client_config::ClientConfig, gman_error::GManError};
+
+/// Editor used when neither `VISUAL` nor `EDITOR` is set
+#[cfg(target_os = "windows")]
+const DEFAULT_EDITOR: &str = "notepad";
+#[cfg(not(target_os = "windows"))]
+const DEFAULT_EDITOR: &str = "vi";
+
+/// Program and arguments of the editor to open files with, from [visual] or [editor] (e.g. `code --wait`),
+/// or the platform default
+fn editor_command(visual: Option<String>, editor: Option<String>) -> Vec<String> {
+    let configured = [visual, editor]
+        .into_iter()
+        .flatten()
+        .map(|x| {
+            x.split_whitespace()
+                .map(|x| x.to_owned())
+                .collect::<Vec<String>>()
+        })
+        .find(|x| !x.is_empty());
+    configured.unwrap_or_else(|| vec![DEFAULT_EDITOR.to_owned()])
+}
+
+/// Opens [path] in the user's editor and waits for it to close
+fn open_in_editor(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let command = editor_command(std::env::var("VISUAL").ok(), std::env::var("EDITOR").ok());
+    log::debug!(
+        "Opening {} with `{}`",
+        path.to_string_lossy(),
+        command.join(" ")
+    );
+    let status = Command::new(&command[0])
+        .args(&command[1..])
+        .arg(path)
+        .status()
+        .map_err(|e| {
+            GManError::new(&format!(
+                "Failed to start editor {}: {}. Set the EDITOR environment variable to choose another one",
+                &command[0], e
+            ))
+        })?;
+    if !status.success() {
+        return Err(Box::new(GManError::new(&format!(
+            "Editor {} exited with {}",
+            &command[0], status
+        ))));
+    }
+    Ok(())
+}
+
+fn ask_edit_again() -> Result<bool, Box<dyn std::error::Error>> {
+    eprint!("Edit again? [Y/n] ");
+    std::io::stderr().flush()?;
+    let mut buffer = String::new();
+    std::io::stdin().read_line(&mut buffer)?;
+    Ok(!buffer.trim().eq_ignore_ascii_case("n"))
+}
+
+/// Lets the user edit the configuration at [path] in their editor.
+///
+/// The editor works on a copy, which only replaces [path] once it parses as a valid configuration.
+/// Returns whether [path] was changed
+pub fn edit_config_file(path: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+    let original = std::fs::read_to_string(path)?;
+    let working_copy: PathBuf = app::get_app_temp_directory().join(format!(
+        "edit_{}",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    if let Some(parent) = working_copy.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&working_copy, &original)?;
+
+    let result = loop {
+        if let Err(e) = open_in_editor(&working_copy) {
+            break Err(e);
+        }
+        let edited = std::fs::read_to_string(&working_copy)?;
+        if edited == original {
+            break Ok(false);
+        }
+        match ClientConfig::parse(&edited) {
+            Ok(_) => {
+                std::fs::write(path, &edited)?;
+                break Ok(true);
+            }
+            Err(e) => {
+                eprintln!("The edited configuration is invalid: {}", e);
+                if !ask_edit_again()? {
+                    break Err(Box::new(GManError::new(
+                        "Discarded the changes, the configuration contained errors",
+                    )) as Box<dyn std::error::Error>);
+                }
+            }
+        }
+    };
+
+    let _ = std::fs::remove_file(&working_copy);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{editor_command, DEFAULT_EDITOR};
+
+    #[test]
+    fn editor_from_environment() {
+        assert_eq!(
+            editor_command(Some("code --wait".into()), Some("vim".into())),
+            vec!["code", "--wait"]
+        );
+        assert_eq!(
+            editor_command(Some(" ".into()), Some("nano".into())),
+            vec!["nano"]
+        );
+        assert_eq!(editor_command(None, None), vec![DEFAULT_EDITOR]);
+    }
+}
@@ -0,0 +1,238 @@
+use std::{
+    io::{IsTerminal, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{app, client_config::ConfigSource, download, gman_error::GManError, ClientConfig};
+
+/// File in the user's profile remembering the configurations outside of it the user agreed to load
+const TRUSTED_CONFIGS_FILE_NAME: &str = "trusted_configs.json";
+
+/// A configuration outside the user profile the user agreed to load. The checksum makes gman ask
+/// again if someone changes the file afterwards
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct TrustedConfig {
+    #[serde(rename = "Path")]
+    path: PathBuf,
+    #[serde(rename = "Sha256")]
+    sha256: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+struct TrustedConfigs {
+    #[serde(rename = "Configs", default)]
+    configs: Vec<TrustedConfig>,
+}
+
+impl TrustedConfigs {
+    fn load(store: &Path) -> Self {
+        std::fs::read_to_string(store)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, store: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = store.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(store, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    fn contains(&self, path: &Path, sha256: &str) -> bool {
+        self.configs
+            .iter()
+            .any(|x| x.path == path && x.sha256 == sha256)
+    }
+
+    fn trust(&mut self, path: &Path, sha256: &str) {
+        self.configs.retain(|x| x.path != path);
+        self.configs.push(TrustedConfig {
+            path: path.to_path_buf(),
+            sha256: sha256.to_owned(),
+        });
+    }
+}
+
+fn user_profile() -> Option<PathBuf> {
+    let home = ClientConfig::shell_expand("~");
+    if home == "~" {
+        return None;
+    }
+    std::fs::canonicalize(home).ok()
+}
+
+fn trusted_configs_store() -> PathBuf {
+    PathBuf::from(ClientConfig::shell_expand(&format!(
+        "~/.config/{}/{}",
+        app::APP_FOLDER_NAME,
+        TRUSTED_CONFIGS_FILE_NAME
+    )))
+}
+
+/// Whether [path] is inside [directory], after resolving links and `..`
+fn is_within(path: &Path, directory: &Path) -> bool {
+    match (
+        std::fs::canonicalize(path),
+        std::fs::canonicalize(directory),
+    ) {
+        (Ok(path), Ok(directory)) => path.starts_with(directory),
+        _ => false,
+    }
+}
+
+/// Refuses configuration files other users can write to, since they could swap in their own
+/// repositories and commands
+#[cfg(unix)]
+pub fn check_permissions(path: &Path) -> Result<(), GManError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = std::fs::metadata(path)
+        .map_err(|e| GManError::new(&format!("Can't read {}: {}", path.display(), e)))?
+        .permissions()
+        .mode();
+    if mode & 0o022 != 0 {
+        return Err(GManError::new(&format!(
+            "Configuration {} is writable by other users (mode {:o}), fix it with `chmod go-w {}`",
+            path.display(),
+            mode & 0o777,
+            path.display()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn check_permissions(_path: &Path) -> Result<(), GManError> {
+    Ok(())
+}
+
+/// Whether the configuration at [path], found through [source], is trusted without asking: handed in,
+/// inside the user profile, or agreed to before. Never prompts, see [ensure_trusted]
+pub fn is_trusted(source: ConfigSource, path: &Path) -> bool {
+    if check_permissions(path).is_err() {
+        return false;
+    }
+    if source == ConfigSource::HandedIn {
+        return true;
+    }
+    if let Some(profile) = user_profile() {
+        if is_within(path, &profile) {
+            return true;
+        }
+    }
+    match (std::fs::canonicalize(path), download::sha256_file(path)) {
+        (Ok(path), Ok(sha256)) => {
+            TrustedConfigs::load(&trusted_configs_store()).contains(&path, &sha256)
+        }
+        _ => false,
+    }
+}
+
+/// Checks the configuration at [path], found through [source], can be loaded.
+///
+/// Files handed in explicitly and files inside the user profile are trusted. Anything else, e.g. a
+/// config in `C:\` picked up by the search from the executable directory, may have been put there by
+/// another user, and is only loaded after the user agrees once. Without a terminal to ask on, it's refused
+pub fn ensure_trusted(source: ConfigSource, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    check_permissions(path)?;
+
+    if is_trusted(source, path) {
+        return Ok(());
+    }
+
+    let path = std::fs::canonicalize(path)?;
+    let sha256 = download::sha256_file(&path)?;
+    let store = trusted_configs_store();
+    let mut trusted = TrustedConfigs::load(&store);
+
+    if !std::io::stdin().is_terminal() {
+        return Err(Box::new(GManError::new(&format!(
+            "Refusing to load configuration {} found in the {}, it is outside your user profile. Pass it with --config to load it anyway",
+            path.display(),
+            source
+        ))));
+    }
+
+    eprintln!(
+        "The configuration {} was found in the {}, outside your user profile.",
+        path.display(),
+        source
+    );
+    eprintln!("Configurations choose the servers gman sends credentials to and the commands it runs, so only load files you know.");
+    eprint!("Trust and load it? [y/N] ");
+    std::io::stderr().flush()?;
+    let mut buffer = String::new();
+    std::io::stdin().read_line(&mut buffer)?;
+    if !buffer.trim().eq_ignore_ascii_case("y") {
+        return Err(Box::new(GManError::new(&format!(
+            "Configuration {} is not trusted",
+            path.display()
+        ))));
+    }
+
+    trusted.trust(&path, &sha256);
+    if let Err(e) = trusted.save(&store) {
+        log::warn!("Failed to remember the trusted configuration: {}", e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_within, TrustedConfigs};
+
+    #[test]
+    fn trusted_configs_round_trip() {
+        let dir = std::env::temp_dir().join("gman_tests").join("config_trust");
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = dir.join("trusted_configs.json");
+        let config = dir.join("gman_config_client.json5");
+
+        let mut trusted = TrustedConfigs::load(&store);
+        assert!(!trusted.contains(&config, "abc"));
+        trusted.trust(&config, "abc");
+        trusted.save(&store).unwrap();
+
+        let mut trusted = TrustedConfigs::load(&store);
+        assert!(trusted.contains(&config, "abc"));
+        assert!(!trusted.contains(&config, "changed"));
+        trusted.trust(&config, "changed");
+        assert_eq!(trusted.configs.len(), 1);
+    }
+
+    #[test]
+    fn within_directory() {
+        let dir = std::env::temp_dir()
+            .join("gman_tests")
+            .join("config_within");
+        std::fs::create_dir_all(dir.join("inner")).unwrap();
+        assert!(is_within(&dir.join("inner"), &dir));
+        assert!(!is_within(
+            &dir.join("inner").join(".."),
+            &dir.join("inner")
+        ));
+        assert!(!is_within(&dir.join("missing"), &dir));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn writable_by_others_is_refused() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir()
+            .join("gman_tests")
+            .join("config_permissions");
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("gman_config_client.json5");
+        std::fs::write(&file, "{}").unwrap();
+
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(super::check_permissions(&file).is_ok());
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o666)).unwrap();
+        assert!(super::check_permissions(&file).is_err());
+    }
+}
@@ -0,0 +1,213 @@
+use std::path::Path;
+
+use crate::{
+    candidate,
+    gman_error::GManError,
+    system_runner::{CommandOutput, SystemRunner},
+};
+
+/// Folder the artifact is mounted in in the container, read only
+const CONTAINER_ARTIFACT_DIRECTORY: &str = "/gman-artifact";
+
+/// Seconds a container idles for before it stops, and `--rm` removes it. Only matters for containers gman
+/// couldn't remove itself, e.g. when it was killed
+const CONTAINER_LIFETIME_SECONDS: &str = "3600";
+
+/// A Docker container gman installs into with `install --container`, started from an image to throw away.
+/// It's removed when dropped
+#[derive(Debug)]
+pub struct Container<'a> {
+    id: String,
+    local: &'a dyn SystemRunner,
+}
+
+impl<'a> Container<'a> {
+    /// Starts a container of [image] in the background with the Debian package [artifact] mounted in it, idling
+    /// until it's removed. The image is run for the architecture of the package, see [docker_platform]
+    pub fn start(
+        local: &'a dyn SystemRunner,
+        image: &str,
+        artifact: &Path,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let file_name = artifact
+            .file_name()
+            .map(|x| x.to_string_lossy().to_string())
+            .ok_or_else(|| GManError::new(&format!("{} isn't a file", artifact.display())))?;
+        let volume = format!(
+            "{}:{}/{}:ro",
+            artifact.to_string_lossy(),
+            CONTAINER_ARTIFACT_DIRECTORY,
+            file_name
+        );
+        let mut args = vec!["run", "--detach", "--rm"];
+        let platform = docker_platform(&file_name);
+        if let Some(platform) = &platform {
+            args.extend(["--platform", platform]);
+        }
+        args.extend([
+            "--volume",
+            &volume,
+            "--entrypoint",
+            "sleep",
+            image,
+            CONTAINER_LIFETIME_SECONDS,
+        ]);
+        let output = local.run("docker", &args).map_err(|e| {
+            GManError::new(&format!("Failed to run docker, is it installed? {}", e))
+        })?;
+        if !output.status.success() {
+            return Err(Box::new(GManError::new(&format!(
+                "Failed to start a container of {} ({}): {}",
+                image,
+                output.status,
+                output.stderr.trim()
+            ))));
+        }
+        Ok(Self {
+            id: output.stdout.trim().to_owned(),
+            local,
+        })
+    }
+
+    /// Installs the cached Debian package [file_name] with apt, along with its dependencies
+    pub fn install_deb(&self, file_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        /* images ship without package lists, the dependencies can't be found without them */
+        let update = self.run_elevated("apt-get", &["update"])?;
+        if !update.status.success() {
+            return Err(Box::new(GManError::new(&format!(
+                "Failed to update the package lists of the container ({}): {}",
+                update.status,
+                update.stderr.trim()
+            ))));
+        }
+        /* there's no systemd to restart the product's service with */
+        candidate::install_deb(
+            self,
+            &format!("{}/{}", CONTAINER_ARTIFACT_DIRECTORY, file_name),
+            None,
+        )?;
+        Ok(())
+    }
+
+    fn exec(
+        &self,
+        user: Option<&str>,
+        program: &str,
+        args: &[&str],
+    ) -> Result<CommandOutput, Box<dyn std::error::Error>> {
+        let mut command = vec!["exec"];
+        if let Some(user) = user {
+            command.extend(["--user", user]);
+        }
+        command.extend(["--env", "DEBIAN_FRONTEND=noninteractive", &self.id, program]);
+        command.extend_from_slice(args);
+        self.local.run("docker", &command)
+    }
+}
+
+/// Docker platform to run the Debian package [file_name] on, from the architecture its name ends in like
+/// `hubkit_5.2.1-7049_amd64.deb`. None for packages of any architecture, or whose name doesn't tell
+fn docker_platform(file_name: &str) -> Option<String> {
+    let (_, architecture) = file_name.strip_suffix(".deb")?.rsplit_once('_')?;
+    let platform = match architecture {
+        "all" => return None,
+        "armhf" => "arm/v7",
+        "armel" => "arm/v5",
+        "i386" => "386",
+        "ppc64el" => "ppc64le",
+        x => x,
+    };
+    Some(format!("linux/{}", platform))
+}
+
+impl SystemRunner for Container<'_> {
+    fn run(
+        &self,
+        program: &str,
+        args: &[&str],
+    ) -> Result<CommandOutput, Box<dyn std::error::Error>> {
+        self.exec(None, program, args)
+    }
+
+    fn run_elevated(
+        &self,
+        program: &str,
+        args: &[&str],
+    ) -> Result<CommandOutput, Box<dyn std::error::Error>> {
+        self.exec(Some("root"), program, args)
+    }
+}
+
+impl Drop for Container<'_> {
+    fn drop(&mut self) {
+        match self.local.run("docker", &["rm", "--force", &self.id]) {
+            Ok(output) if output.status.success() => {}
+            Ok(output) => log::warn!(
+                "Failed to remove container {}: {}",
+                self.id,
+                output.stderr.trim()
+            ),
+            Err(e) => log::warn!("Failed to remove container {}: {}", self.id, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::system_runner::{CommandOutput, MockRunner};
+
+    use super::{docker_platform, Container};
+
+    #[test]
+    fn deb_installed_in_a_removed_container() {
+        let local = MockRunner::new().respond(CommandOutput::ok("4f1c2a\n"));
+        {
+            let container = Container::start(
+                &local,
+                "debian:bookworm",
+                Path::new("/home/dev/.cache/gman/HubKit@Linux@LinuxHubKit@develop@5.2.1-7049@hubkit_5.2.1-7049_amd64.deb"),
+            )
+            .unwrap();
+            container
+                .install_deb(
+                    "HubKit@Linux@LinuxHubKit@develop@5.2.1-7049@hubkit_5.2.1-7049_amd64.deb",
+                )
+                .unwrap();
+        }
+
+        let calls = local
+            .calls()
+            .iter()
+            .map(|x| x.command_line())
+            .collect::<Vec<String>>();
+        assert_eq!(
+            calls,
+            vec![
+                "docker run --detach --rm --platform linux/amd64 --volume /home/dev/.cache/gman/HubKit@Linux@LinuxHubKit@develop@5.2.1-7049@hubkit_5.2.1-7049_amd64.deb:/gman-artifact/HubKit@Linux@LinuxHubKit@develop@5.2.1-7049@hubkit_5.2.1-7049_amd64.deb:ro --entrypoint sleep debian:bookworm 3600",
+                "docker exec --user root --env DEBIAN_FRONTEND=noninteractive 4f1c2a apt-get update",
+                "docker exec --user root --env DEBIAN_FRONTEND=noninteractive 4f1c2a apt-get install -y /gman-artifact/HubKit@Linux@LinuxHubKit@develop@5.2.1-7049@hubkit_5.2.1-7049_amd64.deb",
+                "docker rm --force 4f1c2a",
+            ]
+        );
+        assert!(
+            local.calls().iter().all(|x| !x.elevated),
+            "root is the container's"
+        );
+    }
+
+    #[test]
+    fn docker_platform_of_the_package_architecture() {
+        assert_eq!(
+            docker_platform("hubkit_5.2.1-7049_arm64.deb").as_deref(),
+            Some("linux/arm64")
+        );
+        assert_eq!(
+            docker_platform("hubkit_5.2.1-7049_armhf.deb").as_deref(),
+            Some("linux/arm/v7")
+        );
+        assert_eq!(docker_platform("hubkit_5.2.1-7049_all.deb"), None);
+        assert_eq!(docker_platform("hubkit.deb"), None);
+    }
+}
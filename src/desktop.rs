@@ -0,0 +1,299 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    app,
+    gman_error::GManError,
+    journal::{JournalAction, JournalEntry},
+    system_runner::SystemRunner,
+};
+
+/// Folder in `$XDG_DATA_HOME` (`~/.local/share`) standalone programs are installed to, one folder per product
+const PROGRAMS_FOLDER_NAME: &str = "gman";
+
+/// Extensions of artifacts that are extracted instead of installed as a single binary
+const ARCHIVE_EXTENSIONS: [&str; 5] = [".zip", ".tar.gz", ".tgz", ".tar.xz", ".tar"];
+
+/// `$XDG_DATA_HOME`, or `~/.local/share`
+fn data_directory() -> PathBuf {
+    app::env_directory("XDG_DATA_HOME").unwrap_or_else(|| app::home_directory(".local/share"))
+}
+
+/// Name [product_name] goes by on the command line and in file names, e.g. `graviohubkit`
+fn command_name(product_name: &str) -> String {
+    product_name
+        .to_lowercase()
+        .chars()
+        .filter(|x| x.is_ascii_alphanumeric() || *x == '-' || *x == '_')
+        .collect()
+}
+
+/// Folder [product_name] is installed to
+pub fn install_directory(product_name: &str) -> PathBuf {
+    data_directory()
+        .join(PROGRAMS_FOLDER_NAME)
+        .join(command_name(product_name))
+}
+
+/// Link to the program of [product_name] in `~/.local/bin`, so it's on the PATH
+pub fn bin_link(product_name: &str) -> PathBuf {
+    app::home_directory(".local/bin").join(command_name(product_name))
+}
+
+/// Desktop entry of [product_name], so it shows up in the application menu
+pub fn desktop_entry_path(product_name: &str) -> PathBuf {
+    data_directory()
+        .join("applications")
+        .join(format!("gman-{}.desktop", command_name(product_name)))
+}
+
+/// Whether [artifact] is an archive to extract, rather than the program itself
+fn is_archive(artifact: &Path) -> bool {
+    let name = artifact.to_string_lossy().to_lowercase();
+    ARCHIVE_EXTENSIONS.iter().any(|x| name.ends_with(x))
+}
+
+/// Quotes [arg] for the `Exec` key of a desktop entry, if it needs it
+fn desktop_exec_quote(arg: &str) -> String {
+    if arg
+        .chars()
+        .any(|x| x.is_whitespace() || "\"'\\><~|&;$*?#()`".contains(x))
+    {
+        format!(
+            "\"{}\"",
+            arg.replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('`', "\\`")
+                .replace('$', "\\$")
+        )
+    } else {
+        arg.to_owned()
+    }
+}
+
+/// Contents of the desktop entry launching [executable] with [launch_args]
+fn desktop_entry(
+    product_name: &str,
+    version: &str,
+    executable: &Path,
+    launch_args: &[String],
+) -> String {
+    let exec = std::iter::once(executable.to_string_lossy().to_string())
+        .chain(launch_args.iter().cloned())
+        .map(|x| desktop_exec_quote(&x))
+        .collect::<Vec<String>>()
+        .join(" ");
+    format!(
+        "[Desktop Entry]\nType=Application\nName={}\nComment=Installed by gman, version {}\nExec={}\nPath={}\nTerminal=false\n",
+        product_name,
+        version,
+        exec,
+        executable
+            .parent()
+            .map(|x| x.to_string_lossy().to_string())
+            .unwrap_or_default()
+    )
+}
+
+/// Installs the standalone [artifact] of [product_name]: a bare program is copied, and an archive is extracted,
+/// with [executable] being the program inside it. The program is then linked into `~/.local/bin`, and given a
+/// desktop entry
+pub fn install(
+    runner: &dyn SystemRunner,
+    product_name: &str,
+    version: &str,
+    artifact: &Path,
+    executable: Option<&str>,
+    launch_args: &[String],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let directory = install_directory(product_name);
+    if directory.exists() {
+        std::fs::remove_dir_all(&directory)?;
+    }
+    std::fs::create_dir_all(&directory)?;
+    let artifact_str = artifact.to_string_lossy().to_string();
+    let directory_str = directory.to_string_lossy().to_string();
+
+    let program = if is_archive(artifact) {
+        let executable = executable.ok_or_else(|| {
+            GManError::new(&format!(
+                "{} is an archive, its flavor needs an Executable to link and launch",
+                artifact.display()
+            ))
+        })?;
+        if artifact_str.to_lowercase().ends_with(".zip") {
+            runner.capture("unzip", &["-o", "-q", &artifact_str, "-d", &directory_str])?;
+        } else {
+            runner.capture("tar", &["-xf", &artifact_str, "-C", &directory_str])?;
+        }
+        let program = directory.join(executable);
+        if !program.exists() {
+            return Err(Box::new(GManError::new(&format!(
+                "{} not found in the archive {}",
+                executable,
+                artifact.display()
+            ))));
+        }
+        program
+    } else {
+        let file_name = artifact
+            .file_name()
+            .ok_or_else(|| GManError::new(&format!("{} is not a file", artifact.display())))?;
+        let program = directory.join(file_name);
+        std::fs::copy(artifact, &program)?;
+        program
+    };
+    runner.capture("chmod", &["+x", program.to_string_lossy().as_ref()])?;
+
+    let link = bin_link(product_name);
+    if let Some(parent) = link.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if link.symlink_metadata().is_ok() {
+        std::fs::remove_file(&link)?;
+    }
+    std::os::unix::fs::symlink(&program, &link)?;
+
+    let entry = desktop_entry_path(product_name);
+    if let Some(parent) = entry.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(
+        &entry,
+        desktop_entry(product_name, version, &program, launch_args),
+    )?;
+    log::debug!(
+        "Installed {} to {}, linked from {} and {}",
+        product_name,
+        directory.display(),
+        link.display(),
+        entry.display()
+    );
+
+    Ok(())
+}
+
+/// The paths an install of [product_name] created that are there, for the install journal
+pub fn installed_paths(product_name: &str) -> Vec<PathBuf> {
+    [
+        install_directory(product_name),
+        bin_link(product_name),
+        desktop_entry_path(product_name),
+    ]
+    .into_iter()
+    .filter(|x| x.symlink_metadata().is_ok())
+    .collect()
+}
+
+/// Removes the [paths] an install created. Paths already gone are skipped
+pub fn remove(paths: &[PathBuf]) -> Result<(), Box<dyn std::error::Error>> {
+    for path in paths {
+        match path.symlink_metadata() {
+            Ok(m) if m.is_dir() => std::fs::remove_dir_all(path)?,
+            Ok(_) => std::fs::remove_file(path)?,
+            Err(_) => log::debug!("{} is already gone", path.display()),
+        }
+    }
+    Ok(())
+}
+
+/// Folder the install of [entry] put its program in, as recorded in the journal. Other users' installs
+/// are in their own home, not in [install_directory]
+pub fn recorded_install_directory(entry: &JournalEntry) -> Option<&Path> {
+    entry
+        .files
+        .iter()
+        .find(|x| {
+            x.parent()
+                .and_then(|x| x.file_name())
+                .is_some_and(|x| x == PROGRAMS_FOLDER_NAME)
+        })
+        .map(|x| x.as_path())
+}
+
+/// The journal's latest entry for each product and user, among [entries], that installed files which are
+/// still there. Standalone programs are installed per user, so one user's uninstall doesn't end another's
+pub fn installed(entries: &[JournalEntry]) -> Vec<&JournalEntry> {
+    let mut latest: Vec<&JournalEntry> = Vec::new();
+    for entry in entries.iter().rev() {
+        if latest.iter().any(|x| {
+            x.user == entry.user && x.product_name.eq_ignore_ascii_case(&entry.product_name)
+        }) {
+            continue;
+        }
+        latest.push(entry);
+    }
+    latest
+        .into_iter()
+        .filter(|x| x.action == JournalAction::Install)
+        .filter(|x| x.files.iter().any(|f| f.symlink_metadata().is_ok()))
+        .rev()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::journal::{JournalAction, JournalEntry};
+
+    use super::{desktop_entry, installed, is_archive, recorded_install_directory, remove};
+
+    #[test]
+    fn archives_by_extension() {
+        assert!(is_archive(&PathBuf::from("/cache/hubkit_5.2.1.tar.gz")));
+        assert!(is_archive(&PathBuf::from("/cache/HubKit.ZIP")));
+        assert!(!is_archive(&PathBuf::from("/cache/hubkit.run")));
+    }
+
+    #[test]
+    fn desktop_entry_quotes_exec() {
+        let entry = desktop_entry(
+            "HubKit",
+            "5.2.1-7049",
+            &PathBuf::from("/home/pi/.local/share/gman/hubkit/Hub Kit"),
+            &["--port".to_owned(), "8080".to_owned()],
+        );
+        assert!(entry.starts_with("[Desktop Entry]\n"));
+        assert!(entry.contains("Name=HubKit\n"));
+        assert!(entry.contains("Exec=\"/home/pi/.local/share/gman/hubkit/Hub Kit\" --port 8080\n"));
+        assert!(entry.contains("Path=/home/pi/.local/share/gman/hubkit\n"));
+    }
+
+    #[test]
+    fn installed_until_uninstalled_or_removed() {
+        let dir = std::env::temp_dir().join("gman_tests").join("desktop");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let link = dir.join("hubkit");
+        std::fs::write(&link, "").unwrap();
+
+        let entry = |action, product: &str| JournalEntry {
+            files: vec![link.clone()],
+            ..JournalEntry::new(action, product, "5.2.1-7049")
+        };
+        let program = dir.join("bob").join("gman").join("graviostudio");
+        std::fs::create_dir_all(&program).unwrap();
+        let entries = vec![
+            entry(JournalAction::Install, "HubKit"),
+            JournalEntry {
+                user: "bob".into(),
+                files: vec![program.clone()],
+                ..JournalEntry::new(JournalAction::Install, "GravioStudio", "5.2.1-7049")
+            },
+            entry(JournalAction::Install, "GravioStudio"),
+            entry(JournalAction::Uninstall, "graviostudio"),
+        ];
+        let found = installed(&entries);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].product_name, "HubKit");
+        assert_eq!(found[1].user, "bob");
+        assert_eq!(
+            recorded_install_directory(found[1]),
+            Some(program.as_path())
+        );
+        assert_eq!(recorded_install_directory(found[0]), None);
+
+        remove(&[link.clone(), program, dir.join("missing")]).unwrap();
+        assert!(installed(&entries).is_empty());
+    }
+}
@@ -0,0 +1,554 @@
+use futures_util::StreamExt;
+use lazy_static::lazy_static;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    io::{IsTerminal, Read, SeekFrom},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Mutex,
+};
+
+use reqwest::{
+    header::{HeaderValue, RANGE},
+    RequestBuilder, Url,
+};
+use tokio::io::AsyncSeekExt;
+
+use crate::{
+    app,
+    gman_error::{GManError, GManErrorKind},
+    progress::Progress,
+    redact,
+    throttle::{self, ThrottlePermit},
+    CandidateRepository, RepositoryCredentials,
+};
+
+/// Environment variable supplying the password of BasicAuth repositories that don't configure one
+pub const REPOSITORY_PASSWORD_ENV: &str = "GMAN_REPO_PASSWORD";
+
+/// Suffix of downloads in progress in the temp directory. They're kept when a download fails or is
+/// interrupted, so the next download of the file continues where it stopped
+pub const PARTIAL_DOWNLOAD_SUFFIX: &str = ".part";
+
+lazy_static! {
+    /// Passwords asked for this run, by username, so the user is asked only once
+    static ref ASKED_PASSWORDS: Mutex<HashMap<String, Option<String>>> = Mutex::new(HashMap::new());
+}
+
+/// The password to use for [username]: the [configured] one, else the [from_env] one, else whatever [ask] returns
+fn resolve_password<F>(
+    configured: Option<&String>,
+    from_env: Option<String>,
+    ask: F,
+) -> Option<String>
+where
+    F: FnOnce() -> Option<String>,
+{
+    configured
+        .cloned()
+        .or(from_env.filter(|x| !x.is_empty()))
+        .or_else(ask)
+}
+
+/// Asks for the password of [username], hidden when on a terminal, otherwise read as a line from stdin
+fn ask_password(username: &str) -> Option<String> {
+    let result = if std::io::stdin().is_terminal() {
+        rpassword::prompt_password(format!("Repository password for {}: ", username))
+    } else {
+        let mut buffer = String::new();
+        std::io::stdin()
+            .read_line(&mut buffer)
+            .map(|_| buffer.trim_end_matches(['\r', '\n']).to_owned())
+    };
+    match result {
+        Ok(password) if !password.is_empty() => Some(password),
+        Ok(_) => {
+            log::warn!(
+                "No password given for {}, set {} or Password in the configuration",
+                username,
+                REPOSITORY_PASSWORD_ENV
+            );
+            None
+        }
+        Err(e) => {
+            log::warn!("Failed to read the password for {}: {}", username, e);
+            None
+        }
+    }
+}
+
+/// Adds the repository [credentials] to the request, if there are any.
+///
+/// BasicAuth without a configured password takes it from [REPOSITORY_PASSWORD_ENV], or asks for it once per run.
+/// OAuth sends the access token loaded by [crate::oauth::load_access_tokens], if the user is signed in
+pub fn authorize(
+    request: RequestBuilder,
+    credentials: Option<&RepositoryCredentials>,
+) -> RequestBuilder {
+    match credentials {
+        Some(RepositoryCredentials::BearerToken { token }) => request.bearer_auth(token),
+        Some(RepositoryCredentials::BasicAuth { username, password }) => {
+            let password = match password {
+                Some(p) => Some(p.to_owned()),
+                None => {
+                    let mut asked = ASKED_PASSWORDS.lock().unwrap();
+                    asked
+                        .entry(username.to_owned())
+                        .or_insert_with(|| {
+                            resolve_password(
+                                None,
+                                std::env::var(REPOSITORY_PASSWORD_ENV).ok(),
+                                || ask_password(username),
+                            )
+                        })
+                        .clone()
+                }
+            };
+            request.basic_auth(username, password)
+        }
+        Some(RepositoryCredentials::OAuth {
+            access_token: Some(token),
+            ..
+        }) => request.bearer_auth(token),
+        Some(RepositoryCredentials::OAuth { .. }) | None => request,
+    }
+}
+
+/// Turns a failed response status into an error, printing a readable reason
+fn check_status(status: reqwest::StatusCode, source_name: &str) -> Result<(), GManError> {
+    if status.is_success() {
+        return Ok(());
+    }
+    log::warn!("Failed to download from {}, ({})", source_name, &status);
+    if status == 401 || status == 403 {
+        eprintln!("Not authorized to access {}", source_name);
+        return Err(GManError::new("Not authorized"));
+    }
+    if status == 404 {
+        eprintln!("File not found on {}", source_name);
+        return Err(GManError::new(&format!(
+            "File not found on {}",
+            source_name
+        )));
+    }
+    Err(GManError::new(
+        "Unknown error occurred during download request",
+    ))
+}
+
+/// What the server says a file should look like, checked after downloading it
+#[derive(Debug, Default)]
+pub struct ExpectedFile {
+    /// Size in bytes
+    pub size: Option<u64>,
+    /// SHA-256 checksum, see [verify_sha256]
+    pub sha256: Option<String>,
+}
+
+/// Checks the file at [path] against what's [expected] of it, so a truncated or corrupted download
+/// isn't mistaken for the real thing
+pub fn verify_file(path: &Path, expected: &ExpectedFile) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(size) = expected.size {
+        let actual = std::fs::metadata(path)?.len();
+        if actual != size {
+            return Err(Box::new(GManError::new(&format!(
+                "Download of {} is incomplete, expected {} bytes but got {}",
+                path.display(),
+                size,
+                actual
+            ))));
+        }
+    }
+    if let Some(sha256) = &expected.sha256 {
+        verify_sha256(path, sha256)?;
+    }
+    Ok(())
+}
+
+/// Where the download to [temp_path] is kept while it's in progress, see [PARTIAL_DOWNLOAD_SUFFIX]
+pub fn partial_path(temp_path: &Path) -> PathBuf {
+    let mut partial = temp_path.as_os_str().to_owned();
+    partial.push(PARTIAL_DOWNLOAD_SUFFIX);
+    PathBuf::from(partial)
+}
+
+/// Sends [request] with the credentials of [repo], throttled and retried like its other requests, see
+/// [throttle::send]. Requests that don't go to a repository are sent as they are
+async fn send(
+    repo: Option<&CandidateRepository>,
+    request: RequestBuilder,
+) -> Result<(Option<ThrottlePermit>, reqwest::Response), Box<dyn std::error::Error>> {
+    match repo {
+        Some(repo) => {
+            let request = authorize(request, repo.repository_credentials.as_ref());
+            let (permit, response) = throttle::send(repo, request).await?;
+            Ok((Some(permit), response))
+        }
+        None => Ok((None, request.send().await?)),
+    }
+}
+
+/// Byte to continue a download of [length] bytes from, given the [existing] size of its partial file.
+/// Downloads start over when the length isn't known, or the partial file is larger than the download
+fn resume_offset(existing: Option<u64>, length: Option<u64>) -> u64 {
+    match (existing, length) {
+        (Some(existing), Some(length)) if existing <= length => existing,
+        _ => 0,
+    }
+}
+
+/// Downloads [url] to [temp_path] (as its [partial_path]) in ranges of [chunk_size] bytes with a progress bar,
+/// then moves the complete file to [cache_path]. Servers that don't report a content length are downloaded in
+/// one go. A partial file left by an earlier download is continued from where it stopped.
+///
+/// [source_name] describes where the file comes from in messages (e.g., `repository TeamCity`). Requests to
+/// a [repo] are sent with its credentials and throttle.
+/// If the server told what to expect of the file, it's checked against [expected] before it's cached
+#[allow(clippy::too_many_arguments)]
+pub async fn download_to_cache(
+    http_client: &reqwest::Client,
+    url: &Url,
+    repo: Option<&CandidateRepository>,
+    source_name: &str,
+    temp_path: &Path,
+    cache_path: &Path,
+    chunk_size: u64,
+    expected: Option<&ExpectedFile>,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    log::debug!("Downloading from url {}", redact::redact_url(url.as_str()));
+
+    /* Send HEAD for file size info */
+    let (permit, response) = send(repo, http_client.head(url.clone())).await?;
+    check_status(response.status(), source_name)?;
+    let length = match response.headers().get(reqwest::header::CONTENT_LENGTH) {
+        Some(l) => Some(u64::from_str(l.to_str()?).map_err(|_| "invalid Content-Length header")?),
+        None => None,
+    };
+    /* the ranges need permits of their own */
+    drop(permit);
+
+    /* create the parent directory if necessary */
+    if let Some(prefix) = temp_path.parent() {
+        tokio::fs::create_dir_all(prefix).await?;
+    }
+    let partial_path = partial_path(temp_path);
+    let existing = tokio::fs::metadata(&partial_path)
+        .await
+        .ok()
+        .map(|x| x.len());
+    let offset = resume_offset(existing, length);
+    let mut output_file_temp = if offset > 0 {
+        log::debug!(
+            "Resuming download of {} at byte {}",
+            partial_path.display(),
+            offset
+        );
+        tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(&partial_path)
+            .await?
+    } else {
+        tokio::fs::File::create(&partial_path).await?
+    };
+
+    let ranges: Vec<Option<HeaderValue>> = match length {
+        Some(length) if offset >= length => Vec::new(),
+        Some(length) => PartialRangeIter::new(offset, length - 1, chunk_size)?
+            .map(Some)
+            .collect(),
+        None => {
+            log::debug!("Server didn't report a content length, downloading in a single request");
+            vec![None]
+        }
+    };
+
+    /* disable logging here  */
+    let last_level = app::disable_logging();
+    let mut progress_bar = Progress::download(
+        url.path_segments()
+            .and_then(|mut x| x.next_back())
+            .unwrap_or(source_name),
+        length,
+    );
+    progress_bar.set_position(offset);
+
+    /* Ctrl-C drops the in-flight request, see [app::install_interrupt_handler] */
+    let _cancellable = app::CancellableOperation::begin();
+    let download = async {
+        let mut downloaded: u64 = offset;
+        for range in ranges {
+            let ranged = range.is_some();
+            let mut request = http_client.get(url.clone());
+            if let Some(range) = range {
+                request = request.header(RANGE, range);
+            }
+            /* the permit is held until the range has been read */
+            let (_permit, response) = send(repo, request).await?;
+
+            let status = response.status();
+            if !(status == 200 || status == 206) {
+                return Err(Box::new(GManError::new("Unexpected error during download"))
+                    as Box<dyn std::error::Error>);
+            }
+            /* a server ignoring the range sends the whole file, which replaces whatever was downloaded */
+            let whole_file = ranged && status == 200;
+            if whole_file && downloaded > 0 {
+                log::debug!("Server ignored the range request, downloading the whole file again");
+                output_file_temp.set_len(0).await?;
+                output_file_temp.seek(SeekFrom::Start(0)).await?;
+                downloaded = 0;
+            }
+
+            let mut byte_stream = response.bytes_stream();
+            while let Some(item) = byte_stream.next().await {
+                let bytes = item?;
+                tokio::io::copy(&mut bytes.as_ref(), &mut output_file_temp).await?;
+                downloaded += bytes.len() as u64;
+                progress_bar.set_position(downloaded);
+            }
+            if whole_file {
+                break;
+            }
+        }
+        Ok(())
+    };
+
+    let download_result: Result<(), Box<dyn std::error::Error>> = tokio::select! {
+        result = download => result,
+        kind = app::wait_for_interrupt() => Err(Box::new(GManError::with_kind(
+            kind,
+            if kind == GManErrorKind::TimedOut {
+                "Download timed out"
+            } else {
+                "Download interrupted"
+            },
+        ))),
+    };
+    /* restore logging whether or not the download succeeded */
+    app::enable_logging(last_level);
+
+    if let Err(e) = download_result {
+        progress_bar.abandon();
+        drop(output_file_temp);
+        log::debug!(
+            "Download failed, keeping partial file {} to resume from",
+            partial_path.to_string_lossy()
+        );
+        return Err(e);
+    }
+    progress_bar.finish();
+    drop(output_file_temp);
+
+    /* the resumed parts have to add up to the whole file, or it isn't the file that was started */
+    let expected_length = ExpectedFile {
+        size: length,
+        sha256: None,
+    };
+    let verified = verify_file(&partial_path, &expected_length)
+        .and_then(|_| expected.map_or(Ok(()), |x| verify_file(&partial_path, x)));
+    if let Err(e) = verified {
+        let _ = tokio::fs::remove_file(&partial_path).await;
+        return Err(e);
+    }
+
+    /* Move file to cache directory */
+    if let Some(prefix) = cache_path.parent() {
+        tokio::fs::create_dir_all(prefix).await?;
+    }
+    tokio::fs::rename(&partial_path, cache_path).await?;
+
+    Ok(cache_path.to_path_buf())
+}
+
+/// Lowercase hex SHA-256 digest of the file at [path]
+pub fn sha256_file(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Checks the file at [path] against an [expected] SHA-256 checksum, given as hex with an optional `sha256:` prefix
+pub fn verify_sha256(path: &Path, expected: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let expected = expected.trim();
+    let expected = expected
+        .strip_prefix("sha256:")
+        .or_else(|| expected.strip_prefix("SHA256:"))
+        .unwrap_or(expected)
+        .to_lowercase();
+    let actual = sha256_file(path)?;
+    if actual != expected {
+        return Err(Box::new(GManError::new(&format!(
+            "Checksum mismatch for {}, expected {} but was {}",
+            path.display(),
+            expected,
+            actual
+        ))));
+    }
+    log::debug!("Checksum of {} matches", path.display());
+    Ok(())
+}
+
+/// The checksum in the [contents] of a checksum file, either the bare hex digest or a line written by
+/// `sha256sum`, i.e. the digest followed by the file name. None if it doesn't start with a SHA-256 digest
+pub fn checksum_from_file(contents: &str) -> Option<String> {
+    let digest = contents.split_whitespace().next()?;
+    let digest = digest
+        .strip_prefix("sha256:")
+        .or_else(|| digest.strip_prefix("SHA256:"))
+        .unwrap_or(digest);
+    (digest.len() == 64 && digest.chars().all(|x| x.is_ascii_hexdigit()))
+        .then(|| digest.to_lowercase())
+}
+
+#[derive(Debug)]
+struct PartialRangeIter {
+    start: u64,
+    end: u64,
+    buffer_size: u64,
+}
+
+impl PartialRangeIter {
+    pub fn new(start: u64, end: u64, buffer_size: u64) -> Result<Self, Box<dyn std::error::Error>> {
+        if buffer_size == 0 {
+            Err("invalid buffer_size, give a value greater than zero.")?;
+        }
+        Ok(PartialRangeIter {
+            start,
+            end,
+            buffer_size,
+        })
+    }
+}
+
+impl Iterator for PartialRangeIter {
+    type Item = HeaderValue;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.start > self.end {
+            None
+        } else {
+            let prev_start = self.start;
+            self.start += std::cmp::min(self.buffer_size, self.end - self.start + 1);
+            Some(
+                HeaderValue::from_str(&format!("bytes={}-{}", prev_start, self.start - 1))
+                    .expect("string provided by format!"),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        checksum_from_file, resolve_password, resume_offset, verify_file, verify_sha256,
+        ExpectedFile, PartialRangeIter,
+    };
+
+    #[test]
+    fn basic_auth_password_sources() {
+        let configured = "configured".to_owned();
+        let never_asked = || -> Option<String> { panic!("should not ask") };
+        assert_eq!(
+            resolve_password(Some(&configured), Some("env".into()), never_asked),
+            Some("configured".into())
+        );
+        assert_eq!(
+            resolve_password(None, Some("env".into()), never_asked),
+            Some("env".into())
+        );
+        assert_eq!(
+            resolve_password(None, Some("".into()), || Some("typed".into())),
+            Some("typed".into())
+        );
+        assert_eq!(resolve_password(None, None, || None), None);
+    }
+
+    #[test]
+    fn ranges_cover_whole_file() {
+        let ranges = PartialRangeIter::new(0, 9, 4)
+            .unwrap()
+            .map(|x| x.to_str().unwrap().to_owned())
+            .collect::<Vec<String>>();
+        assert_eq!(ranges, vec!["bytes=0-3", "bytes=4-7", "bytes=8-9"]);
+    }
+
+    #[test]
+    fn downloads_resume_from_partial_files_that_fit() {
+        assert_eq!(resume_offset(None, Some(100)), 0);
+        assert_eq!(resume_offset(Some(40), Some(100)), 40);
+        assert_eq!(resume_offset(Some(100), Some(100)), 100);
+        assert_eq!(resume_offset(Some(120), Some(100)), 0);
+        assert_eq!(resume_offset(Some(40), None), 0);
+        assert_eq!(
+            PartialRangeIter::new(40, 99, 50)
+                .unwrap()
+                .map(|x| x.to_str().unwrap().to_owned())
+                .collect::<Vec<String>>(),
+            vec!["bytes=40-89", "bytes=90-99"]
+        );
+    }
+
+    #[test]
+    fn sha256_verification() {
+        let dir = std::env::temp_dir().join("gman_tests").join("sha256");
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("hello.txt");
+        std::fs::write(&file, "hello").unwrap();
+
+        let hello = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        verify_sha256(&file, hello).unwrap();
+        verify_sha256(&file, &format!("sha256:{}", hello.to_uppercase())).unwrap();
+        assert!(verify_sha256(&file, "deadbeef").is_err());
+    }
+
+    #[test]
+    fn checksum_files() {
+        let hello = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        assert_eq!(
+            checksum_from_file(&format!("{}  GravioHubKit.msi\n", hello.to_uppercase())).as_deref(),
+            Some(hello)
+        );
+        assert_eq!(
+            checksum_from_file(&format!("sha256:{}", hello)).as_deref(),
+            Some(hello)
+        );
+        assert_eq!(checksum_from_file("deadbeef  GravioHubKit.msi"), None);
+        assert_eq!(checksum_from_file(""), None);
+    }
+
+    #[test]
+    fn truncated_download_detected() {
+        let dir = std::env::temp_dir().join("gman_tests").join("verify_file");
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("hello.txt");
+        std::fs::write(&file, "hello").unwrap();
+
+        verify_file(&file, &ExpectedFile::default()).unwrap();
+        verify_file(
+            &file,
+            &ExpectedFile {
+                size: Some(5),
+                sha256: Some(
+                    "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824".into(),
+                ),
+            },
+        )
+        .unwrap();
+        assert!(verify_file(
+            &file,
+            &ExpectedFile {
+                size: Some(104857600),
+                sha256: None,
+            },
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,374 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{
+    candidate::{InstallationCandidate, SearchCandidate, Version},
+    client_config::ClientConfig,
+    gman_error::GManError,
+    platform::Platform,
+    product::{Flavor, Product},
+    CandidateRepository,
+};
+
+/// How deep branch folders may nest below a flavor's folder, e.g. `release/5.2` is two deep
+const MAX_BRANCH_DEPTH: usize = 8;
+
+/// A build found in a `Folder` repository, laid out as
+/// `<RepositoryFolder>/<product>/<flavor>/[<branch>/]<version>/<TeamCityBinaryPath>`
+#[derive(Debug)]
+struct FolderBuild {
+    version: Version,
+    /// The folders between the flavor's and the version's, e.g. `release/5.2`. None for builds kept
+    /// directly in the flavor's folder
+    branch: Option<String>,
+    binary: PathBuf,
+    modified: SystemTime,
+}
+
+impl FolderBuild {
+    fn identifier(&self) -> String {
+        self.branch
+            .clone()
+            .unwrap_or_else(|| self.version.to_string())
+    }
+
+    /// Whether [product] passes over this build when looking for one to install, see [Product::skips_build]
+    fn is_skipped_by(&self, product: Option<&Product>) -> bool {
+        product.is_some_and(|x| x.skips_build(&self.version, &self.identifier()))
+    }
+
+    fn into_candidate(
+        self,
+        product_name: &str,
+        flavor: &Flavor,
+        repo: &CandidateRepository,
+    ) -> InstallationCandidate {
+        InstallationCandidate {
+            remote_id: self.binary.to_string_lossy().to_string(),
+            product_name: product_name.to_owned(),
+            identifier: self.identifier(),
+            version: self.version,
+            flavor: flavor.to_owned(),
+            repo_location: repo.repository_folder.clone().unwrap_or_default(),
+            installed: false,
+            expired: false,
+        }
+    }
+}
+
+/// The folder of [repo], with `~` and environment variables expanded
+fn root(repo: &CandidateRepository) -> Option<PathBuf> {
+    repo.repository_folder
+        .as_deref()
+        .map(|x| PathBuf::from(ClientConfig::shell_expand(x)))
+}
+
+/// The entry of [directory] named [name], ignoring case so folders don't have to match the configuration exactly
+fn child_ignoring_case(directory: &Path, name: &str) -> Option<PathBuf> {
+    let exact = directory.join(name);
+    if exact.is_dir() {
+        return Some(exact);
+    }
+    fs::read_dir(directory)
+        .ok()?
+        .filter_map(|x| x.ok())
+        .find(|x| x.file_name().to_string_lossy().to_lowercase() == name.to_lowercase())
+        .map(|x| x.path())
+}
+
+/// Whether the folder name [name] is a build version, e.g. `5.2.1-7049`
+fn is_version(name: &str) -> bool {
+    Version::from_file_name(name)
+        .map(|x| &*x == name)
+        .unwrap_or(false)
+}
+
+/// Every build of [flavor] of [product_name] in the folder repository at [root]
+fn find_builds(root: &Path, product_name: &str, flavor: &Flavor) -> Vec<FolderBuild> {
+    let Some(flavor_directory) =
+        child_ignoring_case(root, product_name).and_then(|x| child_ignoring_case(&x, &flavor.id))
+    else {
+        log::debug!(
+            "No folder for {} {} in folder repository {}",
+            product_name,
+            &flavor.id,
+            root.display()
+        );
+        return Vec::new();
+    };
+
+    let mut builds = Vec::new();
+    let mut pending: Vec<(PathBuf, Vec<String>)> = vec![(flavor_directory, Vec::new())];
+    while let Some((directory, branch)) = pending.pop() {
+        let entries = match fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::warn!("Failed to read {}: {}", directory.display(), e);
+                continue;
+            }
+        };
+        for entry in entries.filter_map(|x| x.ok()) {
+            let path = entry.path();
+            if !path.is_dir() {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            /* branch folders like `release/5.2` can look like versions too, only the binary tells them apart */
+            if is_version(&name) {
+                let binary = path.join(&flavor.teamcity_metadata.teamcity_binary_path);
+                if let Some(metadata) = fs::metadata(&binary).ok().filter(|x| x.is_file()) {
+                    builds.push(FolderBuild {
+                        version: Version::new(&name),
+                        branch: (!branch.is_empty()).then(|| branch.join("/")),
+                        binary,
+                        modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                    });
+                    continue;
+                }
+                log::trace!(
+                    "No binary at {}, looking for builds below it",
+                    binary.display()
+                );
+            }
+            if branch.len() < MAX_BRANCH_DEPTH {
+                let mut nested = branch.clone();
+                nested.push(name);
+                pending.push((path, nested));
+            }
+        }
+    }
+    builds
+}
+
+/// The newest of [builds] by version
+fn newest(builds: impl Iterator<Item = FolderBuild>) -> Option<FolderBuild> {
+    builds.max_by(|a, b| {
+        a.version
+            .partial_cmp(&b.version)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    })
+}
+
+/// Lists the newest build of every branch of [products] in the folder of [repo], like
+/// [crate::team_city::get_builds] does for TeamCity. Builds kept without a branch folder are each listed
+pub fn get_builds(
+    repo: &CandidateRepository,
+    current_platform: &Platform,
+    products: &[Product],
+) -> Vec<InstallationCandidate> {
+    let Some(root) = root(repo) else {
+        return Vec::new();
+    };
+
+    let mut candidates = Vec::new();
+    for product in products {
+        for flavor in product
+            .flavors
+            .iter()
+            .filter(|x| x.platform == *current_platform)
+        {
+            let mut builds = find_builds(&root, &product.name, flavor);
+            builds.retain(|x| match &x.branch {
+                Some(branch) => product.lists_branch(flavor, branch),
+                None => true,
+            });
+            let mut identifiers = builds.iter().map(|x| x.identifier()).collect::<Vec<_>>();
+            identifiers.sort();
+            identifiers.dedup();
+            for identifier in identifiers {
+                let (on_branch, rest) = builds
+                    .into_iter()
+                    .partition::<Vec<_>, _>(|x| x.identifier() == identifier);
+                builds = rest;
+                if let Some(build) = newest(on_branch.into_iter()) {
+                    candidates.push(build.into_candidate(&product.name, flavor, repo));
+                }
+            }
+        }
+    }
+    candidates
+}
+
+/// Finds the build [search] asks for in the folder of [repo]: the one of its version, or the newest on its branch
+/// that [product] doesn't pass over if given
+pub fn find_build(
+    repo: &CandidateRepository,
+    search: &SearchCandidate,
+    product: Option<&Product>,
+) -> Option<InstallationCandidate> {
+    let root = root(repo)?;
+    let builds = find_builds(&root, &search.product_name, &search.flavor).into_iter();
+    let found = match (&search.version, &search.identifier) {
+        (Some(version), _) => newest(builds.filter(|x| x.version.same_build(version))),
+        (None, Some(identifier)) => {
+            newest(builds.filter(|x| x.identifier() == *identifier && !x.is_skipped_by(product)))
+        }
+        (None, None) => None,
+    };
+    found.map(|x| x.into_candidate(&search.product_name, &search.flavor, repo))
+}
+
+/// The newest build of [search]'s flavor in the folder of [repo], for the `latest` and `nightly` targets.
+/// Only builds on [branch] are considered if given, otherwise those on branches the product lists. With
+/// [since], builds whose binary was last changed before then are ignored, and so are the builds [product]
+/// blocks
+pub fn get_newest_build(
+    repo: &CandidateRepository,
+    search: &SearchCandidate,
+    product: &Product,
+    branch: Option<&str>,
+    since: Option<SystemTime>,
+) -> Option<InstallationCandidate> {
+    let root = root(repo)?;
+    let builds = find_builds(&root, &search.product_name, &search.flavor)
+        .into_iter()
+        .filter(|x| match (branch, &x.branch) {
+            (Some(branch), Some(on)) => on == branch,
+            (Some(_), None) => false,
+            (None, Some(on)) => product.lists_branch(&search.flavor, on),
+            (None, None) => true,
+        })
+        .filter(|x| since.map(|since| x.modified >= since).unwrap_or(true))
+        .filter(|x| !x.is_skipped_by(Some(product)));
+    newest(builds).map(|x| x.into_candidate(&search.product_name, &search.flavor, repo))
+}
+
+/// "Downloads" [candidate] from its folder repository: copies its binary into [temp_dir] first, so an
+/// interrupted copy (e.g. from a network share) never leaves a partial file in the cache, then moves it
+/// into [cache_dir]
+pub fn copy_to_cache<P: AsRef<Path>>(
+    candidate: &InstallationCandidate,
+    temp_dir: P,
+    cache_dir: P,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let source = PathBuf::from(&candidate.remote_id);
+    if !source.is_file() {
+        return Err(Box::new(GManError::new(&format!(
+            "Build {} of {} is no longer in its folder repository at {}",
+            &candidate.version,
+            &candidate.product_name,
+            source.display()
+        ))));
+    }
+
+    let temp_path = candidate.make_output_for_candidate(temp_dir);
+    if let Some(prefix) = temp_path.parent() {
+        fs::create_dir_all(prefix)?;
+    }
+    log::debug!("Copying {} to {}", source.display(), temp_path.display());
+    if let Err(e) = fs::copy(&source, &temp_path) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(Box::new(e));
+    }
+
+    let cache_path = candidate.make_output_for_candidate(cache_dir);
+    if let Some(prefix) = cache_path.parent() {
+        fs::create_dir_all(prefix)?;
+    }
+    /* the entry may be a hard link to a blob, which must not be overwritten in place */
+    let _ = fs::remove_file(&cache_path);
+    fs::rename(&temp_path, &cache_path)?;
+    Ok(cache_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{
+        candidate::{SearchCandidate, Version},
+        client_config::{CandidateRepository, RepositoryType},
+        platform::Platform,
+        product::Product,
+    };
+
+    use super::{copy_to_cache, find_build, get_builds};
+
+    fn place(root: &Path, relative: &str) {
+        let path = root.join(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "installer").unwrap();
+    }
+
+    #[test]
+    fn builds_are_read_from_the_folder_tree() {
+        let dir = std::env::temp_dir()
+            .join("gman_tests")
+            .join("folder_repository");
+        let _ = std::fs::remove_dir_all(&dir);
+        let root = dir.join("builds");
+        let product: Product = serde_json::from_str(
+            r#"{"Name": "HubKit", "Flavors": [{"Platform": "Windows", "Id": "WindowsHubKit", "PackageType": "Msi",
+                "TeamCityMetadata": {"TeamCityId": "HubKit_Windows", "TeamCityBinaryPath": "WindowsHubKit/GravioHubKit.msi"}}]}"#,
+        )
+        .unwrap();
+        place(
+            &root,
+            "HubKit/WindowsHubKit/develop/5.2.1-7049/WindowsHubKit/GravioHubKit.msi",
+        );
+        place(
+            &root,
+            "HubKit/WindowsHubKit/develop/5.2.1-7060/WindowsHubKit/GravioHubKit.msi",
+        );
+        place(
+            &root,
+            "HubKit/WindowsHubKit/release/5.2/5.2.0-7015/WindowsHubKit/GravioHubKit.msi",
+        );
+        std::fs::create_dir_all(root.join("HubKit/WindowsHubKit/develop/5.2.1-7070")).unwrap();
+
+        let repo = CandidateRepository {
+            name: "Share".into(),
+            repository_type: RepositoryType::Folder,
+            platforms: vec![Platform::Windows],
+            products: vec!["HubKit".into()],
+            repository_folder: Some(root.to_string_lossy().to_string()),
+            repository_server: None,
+            repository_credentials: None,
+            use_netrc: false,
+            max_concurrent_requests: None,
+            min_request_interval_ms: None,
+        };
+
+        let mut builds = get_builds(&repo, &Platform::Windows, std::slice::from_ref(&product));
+        builds.sort_by(|a, b| a.identifier.cmp(&b.identifier));
+        assert_eq!(builds.len(), 2);
+        assert_eq!(
+            (builds[0].identifier.as_str(), &builds[0].version),
+            ("develop", &Version::new("5.2.1-7060"))
+        );
+        assert_eq!(builds[1].identifier, "release/5.2");
+
+        let flavor = product.flavors[0].clone();
+        let search = SearchCandidate {
+            product_name: "HubKit".into(),
+            version: Some(Version::new("5.2.1.7049")),
+            identifier: None,
+            flavor,
+        };
+        let found = find_build(&repo, &search, None).unwrap();
+        assert_eq!(found.identifier, "develop");
+
+        /* the newest build on the branch that isn't blocked */
+        let mut blocking = product.clone();
+        blocking.blocked_versions = Some(vec!["5.2.1-7060".into()]);
+        let on_branch = SearchCandidate {
+            product_name: "HubKit".into(),
+            version: None,
+            identifier: Some("develop".into()),
+            flavor: product.flavors[0].clone(),
+        };
+        assert_eq!(
+            find_build(&repo, &on_branch, Some(&blocking))
+                .unwrap()
+                .version,
+            Version::new("5.2.1-7049")
+        );
+
+        let cached = copy_to_cache(&found, dir.join("tmp"), dir.join("cache")).unwrap();
+        assert_eq!(std::fs::read_to_string(cached).unwrap(), "installer");
+        assert!(!found.make_output_for_candidate(dir.join("tmp")).exists());
+    }
+}
@@ -1,17 +1,49 @@
 use core::fmt;
 use std::error::Error;
 
+/// Categories of [GManError] that callers need to react to specifically (e.g., for exit codes)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GManErrorKind {
+    /// Any error without special handling
+    Other,
+    /// The user interrupted the operation (Ctrl-C)
+    Interrupted,
+    /// The `--timeout` deadline passed before the operation finished
+    TimedOut,
+    /// The configuration's policy forbids the operation, e.g. installing a build below its `MinimumVersions`
+    PolicyViolation,
+}
+
 #[derive(Debug)]
 pub struct GManError {
     pub details: String,
+    pub kind: GManErrorKind,
 }
 
 impl GManError {
     pub fn new(msg: &str) -> GManError {
+        GManError::with_kind(GManErrorKind::Other, msg)
+    }
+
+    pub fn with_kind(kind: GManErrorKind, msg: &str) -> GManError {
         GManError {
             details: msg.to_string(),
+            kind,
         }
     }
+
+    /// Whether [err] is a [GManError] of the given [kind]
+    pub fn is_kind(err: &(dyn Error + 'static), kind: GManErrorKind) -> bool {
+        err.downcast_ref::<GManError>()
+            .map(|x| x.kind == kind)
+            .unwrap_or(false)
+    }
+
+    /// Whether [err] stopped the operation early, by interruption or timeout, and should end the command
+    pub fn is_cancelled(err: &(dyn Error + 'static)) -> bool {
+        GManError::is_kind(err, GManErrorKind::Interrupted)
+            || GManError::is_kind(err, GManErrorKind::TimedOut)
+    }
 }
 
 impl fmt::Display for GManError {
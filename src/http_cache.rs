@@ -0,0 +1,147 @@
+use std::path::{Path, PathBuf};
+
+use reqwest::{
+    header::{HeaderMap, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    RequestBuilder, Url,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::redact;
+
+/// Folder inside the cache directory where repository responses are kept for conditional requests
+pub const RESPONSE_CACHE_DIRECTORY_NAME: &str = "responses";
+
+/// A repository response stored along with its validators, so the next request for the same url
+/// can be sent with `If-None-Match`/`If-Modified-Since` and the body reused when the server answers 304
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CachedResponse {
+    #[serde(rename = "Url")]
+    pub url: String,
+    #[serde(rename = "ETag", default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(
+        rename = "LastModified",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub last_modified: Option<String>,
+    #[serde(rename = "Body")]
+    pub body: String,
+}
+
+impl CachedResponse {
+    /// The response to [url] with [body], if its [headers] carry a validator worth caching it for
+    pub fn from_response(url: &Url, headers: &HeaderMap, body: &str) -> Option<Self> {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|x| x.to_str().ok())
+                .map(|x| x.to_owned())
+        };
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED);
+        if etag.is_none() && last_modified.is_none() {
+            return None;
+        }
+        Some(Self {
+            url: url.to_string(),
+            etag,
+            last_modified,
+            body: body.to_owned(),
+        })
+    }
+
+    fn path<P>(cache_directory: P, url: &Url) -> PathBuf
+    where
+        P: AsRef<Path>,
+    {
+        let digest = Sha256::digest(url.as_str().as_bytes());
+        cache_directory
+            .as_ref()
+            .join(RESPONSE_CACHE_DIRECTORY_NAME)
+            .join(format!("{:x}.json", digest))
+    }
+
+    /// Reads the stored response to [url], if any
+    pub fn load<P>(cache_directory: P, url: &Url) -> Option<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let contents = std::fs::read_to_string(Self::path(cache_directory, url)).ok()?;
+        match serde_json::from_str::<Self>(&contents) {
+            Ok(cached) if cached.url == url.as_str() => Some(cached),
+            Ok(_) => None,
+            Err(e) => {
+                log::debug!(
+                    "Ignoring unreadable cached response for {}: {}",
+                    redact::redact_url(url.as_str()),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    pub fn save<P>(&self, cache_directory: P) -> Result<(), Box<dyn std::error::Error>>
+    where
+        P: AsRef<Path>,
+    {
+        let url = Url::parse(&self.url)?;
+        let path = Self::path(cache_directory, &url);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Makes [request] conditional on the stored response being out of date
+    pub fn apply(&self, request: RequestBuilder) -> RequestBuilder {
+        let request = match &self.etag {
+            Some(etag) => request.header(IF_NONE_MATCH, etag),
+            None => request,
+        };
+        match &self.last_modified {
+            Some(last_modified) => request.header(IF_MODIFIED_SINCE, last_modified),
+            None => request,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{
+        header::{HeaderMap, HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH},
+        Url,
+    };
+
+    use super::CachedResponse;
+
+    #[test]
+    fn cached_response_round_trip_and_conditional_headers() {
+        let dir = std::env::temp_dir().join("gman_tests").join("http_cache");
+        let _ = std::fs::remove_dir_all(&dir);
+        let url = Url::parse("https://teamcity.example.com/app/rest/buildTypes/id:HubKit/branches")
+            .unwrap();
+
+        let mut headers = HeaderMap::new();
+        assert!(CachedResponse::from_response(&url, &headers, "{}").is_none());
+
+        headers.insert(ETAG, HeaderValue::from_static("\"abc123\""));
+        let cached = CachedResponse::from_response(&url, &headers, "{\"branch\":[]}").unwrap();
+        assert!(CachedResponse::load(&dir, &url).is_none());
+        cached.save(&dir).unwrap();
+        assert_eq!(CachedResponse::load(&dir, &url).unwrap(), cached);
+
+        let other = Url::parse("https://teamcity.example.com/app/rest/builds").unwrap();
+        assert!(CachedResponse::load(&dir, &other).is_none());
+
+        let request = cached
+            .apply(reqwest::Client::new().get(url))
+            .build()
+            .unwrap();
+        assert_eq!(request.headers()[IF_NONE_MATCH], "\"abc123\"");
+        assert!(request.headers().get(IF_MODIFIED_SINCE).is_none());
+    }
+}
@@ -0,0 +1,162 @@
+use std::sync::OnceLock;
+
+use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource};
+use unic_langid::LanguageIdentifier;
+
+/// Language of the catalog every other catalog falls back to
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// Message catalogs by language, in Fluent syntax
+const CATALOGS: &[(&str, &str)] = &[
+    ("en", include_str!("../i18n/en/gman.ftl")),
+    ("ja", include_str!("../i18n/ja/gman.ftl")),
+];
+
+/// Environment variables naming the language of messages, most specific first as in POSIX
+const LANGUAGE_VARIABLES: &[&str] = &["LC_ALL", "LC_MESSAGES", "LANG"];
+
+/// The catalog of the selected language, followed by the default one for messages it lacks
+static BUNDLES: OnceLock<Vec<FluentBundle<FluentResource>>> = OnceLock::new();
+
+/// Selects the language of messages: the configured [language] if there's a catalog for it, otherwise the
+/// one of the environment. Messages printed before this use the environment's.
+/// Only the first call has an effect
+pub fn init(language: Option<&str>) {
+    let language = select_language(language);
+    if BUNDLES.set(load_bundles(language)).is_err() {
+        log::debug!(
+            "Language of messages was already selected, ignoring {}",
+            language
+        );
+    }
+}
+
+/// The [id] message of the selected language, with its `{ $name }` placeables filled from [args]
+pub fn message(id: &str, args: Option<&FluentArgs>) -> String {
+    let bundles = BUNDLES.get_or_init(|| load_bundles(select_language(None)));
+    for bundle in bundles {
+        let Some(pattern) = bundle.get_message(id).and_then(|x| x.value()) else {
+            continue;
+        };
+        let mut errors = vec![];
+        let text = bundle.format_pattern(pattern, args, &mut errors);
+        if !errors.is_empty() {
+            log::debug!("Failed to format message {}: {:?}", id, errors);
+        }
+        return text.into_owned();
+    }
+    log::debug!("No message {} in any catalog", id);
+    id.to_owned()
+}
+
+/// The message [id] of the selected language, e.g. `tr!("cache-count", count = items.len())`
+macro_rules! tr {
+    ($id:literal) => {
+        $crate::i18n::message($id, None)
+    };
+    ($id:literal, $($name:ident = $value:expr),+ $(,)?) => {{
+        let mut args = fluent_bundle::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)+
+        $crate::i18n::message($id, Some(&args))
+    }};
+}
+pub(crate) use tr;
+
+/// The first of [configured] and the language environment variables (or the system locale, as Windows has
+/// no such variables) there is a catalog for. [DEFAULT_LANGUAGE] when there's none
+fn select_language(configured: Option<&str>) -> &'static str {
+    let environment = LANGUAGE_VARIABLES
+        .iter()
+        .filter_map(|x| std::env::var(x).ok())
+        .chain(sys_locale::get_locale());
+    configured
+        .map(|x| x.to_owned())
+        .into_iter()
+        .chain(environment)
+        .filter(|x| !x.is_empty())
+        .find_map(|x| catalog_language(&x))
+        .unwrap_or(DEFAULT_LANGUAGE)
+}
+
+/// The catalog language matching a locale like `ja_JP.UTF-8`, `ja-JP` or `ja`
+fn catalog_language(locale: &str) -> Option<&'static str> {
+    /* drop the encoding and modifier of POSIX locales */
+    let locale = locale.split(['.', '@']).next().unwrap_or_default();
+    let identifier: LanguageIdentifier = locale.replace('_', "-").parse().ok()?;
+    CATALOGS
+        .iter()
+        .map(|(language, _)| *language)
+        .find(|x| *x == identifier.language.as_str())
+}
+
+fn load_bundles(language: &str) -> Vec<FluentBundle<FluentResource>> {
+    let mut languages = vec![language];
+    if language != DEFAULT_LANGUAGE {
+        languages.push(DEFAULT_LANGUAGE);
+    }
+    languages
+        .into_iter()
+        .filter_map(|language| {
+            let (_, catalog) = CATALOGS.iter().find(|(x, _)| *x == language)?;
+            Some(load_bundle(language, catalog))
+        })
+        .collect()
+}
+
+fn load_bundle(language: &str, catalog: &str) -> FluentBundle<FluentResource> {
+    let identifier: LanguageIdentifier = language.parse().unwrap_or_default();
+    let mut bundle = FluentBundle::new_concurrent(vec![identifier]);
+    /* the unicode isolation marks around placeables show up as garbage in consoles */
+    bundle.set_use_isolating(false);
+    let resource = FluentResource::try_new(catalog.to_owned()).unwrap_or_else(|(resource, e)| {
+        log::debug!("Errors in the {} message catalog: {:?}", language, e);
+        resource
+    });
+    if let Err(e) = bundle.add_resource(resource) {
+        log::debug!("Errors adding the {} message catalog: {:?}", language, e);
+    }
+    bundle
+}
+
+#[cfg(test)]
+mod tests {
+    use fluent_bundle::FluentResource;
+
+    use super::{catalog_language, load_bundle, CATALOGS};
+
+    #[test]
+    fn locales_pick_catalog() {
+        assert_eq!(catalog_language("ja_JP.UTF-8"), Some("ja"));
+        assert_eq!(catalog_language("ja-JP"), Some("ja"));
+        assert_eq!(catalog_language("en_US.UTF-8@euro"), Some("en"));
+        assert_eq!(catalog_language("C"), None);
+        assert_eq!(catalog_language("de_DE"), None);
+    }
+
+    #[test]
+    fn catalogs_parse_and_have_the_same_messages() {
+        fn ids(catalog: &str) -> Vec<&str> {
+            assert!(FluentResource::try_new(catalog.to_owned()).is_ok());
+            let mut ids = catalog
+                .lines()
+                .filter(|x| x.starts_with(|c: char| c.is_ascii_lowercase()))
+                .filter_map(|x| x.split_once(" = ").map(|(id, _)| id))
+                .collect::<Vec<&str>>();
+            ids.sort();
+            ids
+        }
+        let (_, default_catalog) = CATALOGS[0];
+        for (language, catalog) in CATALOGS {
+            assert_eq!(ids(catalog), ids(default_catalog), "{}", language);
+        }
+
+        let bundle = load_bundle("ja", CATALOGS[1].1);
+        let mut args = fluent_bundle::FluentArgs::new();
+        args.set("count", 3);
+        let pattern = bundle.get_message("cache-count").unwrap().value().unwrap();
+        assert_eq!(
+            bundle.format_pattern(pattern, Some(&args), &mut vec![]),
+            "件数: 3"
+        );
+    }
+}
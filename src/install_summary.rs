@@ -0,0 +1,142 @@
+use std::{
+    fmt::Display,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+/// Where the artifact of an install came from
+#[derive(Debug, PartialEq)]
+pub enum ArtifactSource {
+    /// Found in this cache directory
+    Cache(PathBuf),
+    /// Downloaded from the named repository, this many bytes of it
+    Repository { name: String, bytes: u64 },
+}
+
+/// What an install resolved to, where it got the artifact from and how long each of its phases took, printed
+/// at its end to tell why installs are slow on some machines
+#[derive(Debug)]
+pub struct InstallSummary {
+    pub product_name: String,
+    pub version: String,
+    /// Id of the build on its repository, empty for artifacts from outside the repositories
+    pub build_id: String,
+    pub identifier: String,
+    pub source: Option<ArtifactSource>,
+    /// Bytes the installation takes on disk, when that can be told
+    pub size: Option<u64>,
+    phases: Vec<(&'static str, Duration)>,
+    phase_started: SystemTime,
+}
+
+impl InstallSummary {
+    /// A summary of an install started at [now]
+    pub fn new(now: SystemTime) -> Self {
+        Self {
+            product_name: String::new(),
+            version: String::new(),
+            build_id: String::new(),
+            identifier: String::new(),
+            source: None,
+            size: None,
+            phases: Vec::new(),
+            phase_started: now,
+        }
+    }
+
+    /// Records the time since the previous phase ended, or the install started, as the time of [phase]
+    pub fn end_phase(&mut self, phase: &'static str, now: SystemTime) {
+        let elapsed = now
+            .duration_since(self.phase_started)
+            .unwrap_or(Duration::ZERO);
+        self.phases.push((phase, elapsed));
+        self.phase_started = now;
+    }
+
+    /// Time every phase took together
+    pub fn total(&self) -> Duration {
+        self.phases.iter().map(|(_, elapsed)| *elapsed).sum()
+    }
+}
+
+impl Display for InstallSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let seconds = |x: &Duration| format!("{:.1}s", x.as_secs_f64());
+        writeln!(f, "Install summary")?;
+        writeln!(
+            f,
+            "  Build:   {} {} ({}, build id {})",
+            self.product_name,
+            self.version,
+            self.identifier,
+            if self.build_id.is_empty() {
+                "-"
+            } else {
+                &self.build_id
+            }
+        )?;
+        match &self.source {
+            Some(ArtifactSource::Cache(directory)) => {
+                writeln!(f, "  Source:  cache hit in {}", directory.display())?
+            }
+            Some(ArtifactSource::Repository { name, bytes }) => writeln!(
+                f,
+                "  Source:  downloaded from {}, {}",
+                name,
+                indicatif::HumanBytes(*bytes)
+            )?,
+            None => {}
+        }
+        if let Some(size) = self.size {
+            writeln!(f, "  Size:    {} on disk", indicatif::HumanBytes(size))?;
+        }
+        writeln!(
+            f,
+            "  Phases:  {}",
+            self.phases
+                .iter()
+                .map(|(phase, elapsed)| format!("{} {}", phase, seconds(elapsed)))
+                .collect::<Vec<String>>()
+                .join(", ")
+        )?;
+        write!(f, "  Total:   {}", seconds(&self.total()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::{ArtifactSource, InstallSummary};
+
+    #[test]
+    fn summary_of_phases_and_source() {
+        let started = SystemTime::UNIX_EPOCH;
+        let mut summary = InstallSummary::new(started);
+        summary.product_name = "HubKit".into();
+        summary.version = "5.2.1-7049".into();
+        summary.build_id = "123456".into();
+        summary.identifier = "develop".into();
+        summary.source = Some(ArtifactSource::Repository {
+            name: "Gravio TeamCity".into(),
+            bytes: 3 * 1024 * 1024,
+        });
+        summary.end_phase("fetch", started + Duration::from_millis(12_400));
+        summary.end_phase("install", started + Duration::from_millis(30_000));
+
+        assert_eq!(summary.total(), Duration::from_secs(30));
+        assert_eq!(
+            summary.to_string(),
+            "Install summary\n  Build:   HubKit 5.2.1-7049 (develop, build id 123456)\n  Source:  downloaded from Gravio TeamCity, 3.00 MiB\n  Phases:  fetch 12.4s, install 17.6s\n  Total:   30.0s"
+        );
+
+        summary.build_id.clear();
+        summary.source = Some(ArtifactSource::Cache("/home/dev/.cache/gman".into()));
+        let text = summary.to_string();
+        assert!(text.contains("(develop, build id -)"));
+        assert!(text.contains("Source:  cache hit in /home/dev/.cache/gman"));
+
+        summary.size = Some(512 * 1024 * 1024);
+        assert!(summary.to_string().contains("Size:    512.00 MiB on disk"));
+    }
+}
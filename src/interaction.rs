@@ -0,0 +1,88 @@
+use std::str::FromStr;
+
+use crate::candidate::InstallOverwriteOptions;
+
+/// Asks the user to decide on something gman can't decide by itself, like overwriting an installation.
+///
+/// The console implementation reads the answers from stdin, so embedding gman can answer from a UI instead,
+/// and tests can answer without a terminal
+pub trait Interaction: std::fmt::Debug + Send + Sync {
+    /// Waits for a yes or no to the question just printed, no unless it's clearly a yes
+    fn confirm(&self) -> Result<bool, Box<dyn std::error::Error>>;
+
+    /// Asks what to do about a product that's already installed
+    fn installation_choice(&self) -> Result<InstallOverwriteOptions, Box<dyn std::error::Error>>;
+}
+
+/// [Interaction] on the console, answered on stdin
+#[derive(Debug)]
+pub struct ConsoleInteraction;
+
+impl Interaction for ConsoleInteraction {
+    fn confirm(&self) -> Result<bool, Box<dyn std::error::Error>> {
+        let mut buffer = String::new();
+        std::io::stdin().read_line(&mut buffer)?;
+        Ok(is_console_confirm(&buffer))
+    }
+
+    fn installation_choice(&self) -> Result<InstallOverwriteOptions, Box<dyn std::error::Error>> {
+        if cfg!(windows) {
+            eprintln!("What would you like to do with this item? [o]verwrite, or [c]ancel?");
+        } else {
+            eprintln!("What would you like to do with this item? [o]verwrite, [a]dd an extra installation, or [c]ancel?");
+        }
+        let mut buffer = String::new();
+        std::io::stdin().read_line(&mut buffer)?;
+        let s = InstallOverwriteOptions::from_str(buffer.to_lowercase().trim())?;
+        if cfg!(windows) {
+            if let InstallOverwriteOptions::Add = s {
+                log::debug!("Setting installation option to overwrite, because /add/ isnt supported for Windows installations");
+                return Ok(InstallOverwriteOptions::Overwrite);
+            }
+        }
+        Ok(s)
+    }
+}
+
+/// Whether the given string is any kind of confirmation (yes, y, etc)
+fn is_console_confirm(val: &str) -> bool {
+    let affirmative = ["y", "yes"];
+    affirmative.iter().any(|v| *v == val.trim().to_lowercase())
+}
+
+/// [Interaction] giving answers queued up front, for tests. Confirming without an answer left says no, and
+/// installations already there are never overwritten
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct ScriptedInteraction {
+    confirmations: std::sync::Mutex<std::collections::VecDeque<bool>>,
+}
+
+#[cfg(test)]
+impl ScriptedInteraction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues [answer] for the next confirmation asked for
+    pub fn confirm_with(self, answer: bool) -> Self {
+        self.confirmations.lock().unwrap().push_back(answer);
+        self
+    }
+}
+
+#[cfg(test)]
+impl Interaction for ScriptedInteraction {
+    fn confirm(&self) -> Result<bool, Box<dyn std::error::Error>> {
+        Ok(self
+            .confirmations
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or(false))
+    }
+
+    fn installation_choice(&self) -> Result<InstallOverwriteOptions, Box<dyn std::error::Error>> {
+        Ok(InstallOverwriteOptions::Cancel)
+    }
+}
@@ -0,0 +1,187 @@
+use std::{
+    cmp::Ordering,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{candidate::Version, util};
+
+/// Folder inside the machine's state directory the install journal is kept in, see
+/// [crate::app::get_machine_state_directory]
+pub const JOURNAL_DIRECTORY_NAME: &str = "journal";
+
+/// File of the journal, one [JournalEntry] as json per line so it's only ever appended to
+const JOURNAL_FILE_NAME: &str = "installs.jsonl";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum JournalAction {
+    Install,
+    Uninstall,
+}
+
+/// Who installed or uninstalled which product when, so shared machines can tell whose install something is
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct JournalEntry {
+    /// Unix time in seconds
+    #[serde(rename = "Time")]
+    pub time: u64,
+    #[serde(rename = "User")]
+    pub user: String,
+    #[serde(rename = "Action")]
+    pub action: JournalAction,
+    #[serde(rename = "Product")]
+    pub product_name: String,
+    #[serde(rename = "Version")]
+    pub version: String,
+    /// Files and folders the install created outside of a package manager, e.g. the desktop entries of
+    /// standalone programs on Linux, so uninstalling can remove them
+    #[serde(rename = "Files", default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<PathBuf>,
+    /// Bytes the install took on disk once it was done, when that could be told
+    #[serde(rename = "Size", default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+impl JournalEntry {
+    /// An entry for the current user, now
+    pub fn new(action: JournalAction, product_name: &str, version: &str) -> Self {
+        Self {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|x| x.as_secs())
+                .unwrap_or_default(),
+            user: util::current_user(),
+            action,
+            product_name: product_name.to_owned(),
+            version: version.to_owned(),
+            files: Vec::new(),
+            size: None,
+        }
+    }
+}
+
+pub fn journal_path(state_directory: &Path) -> PathBuf {
+    state_directory
+        .join(JOURNAL_DIRECTORY_NAME)
+        .join(JOURNAL_FILE_NAME)
+}
+
+/// Adds [entry] to the end of the journal in [state_directory]
+pub fn append(
+    state_directory: &Path,
+    entry: &JournalEntry,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = journal_path(state_directory);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    util::append_line(path, &serde_json::to_string(entry)?)?;
+    Ok(())
+}
+
+/// The entries of the journal in [state_directory], oldest first. Lines that don't parse are skipped
+pub fn load(state_directory: &Path) -> Vec<JournalEntry> {
+    std::fs::read_to_string(journal_path(state_directory))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|x| serde_json::from_str(x).ok())
+        .collect()
+}
+
+/// The install of [version] of [product_name], if it's the latest of the [entries] about it.
+/// Versions are compared by number, installed products report `5.2.1.7049` for a `5.2.1-7049` build.
+/// None if it wasn't installed through gman, or was uninstalled since
+fn latest_install<'a>(
+    entries: &'a [JournalEntry],
+    product_name: &str,
+    version: &Version,
+) -> Option<&'a JournalEntry> {
+    entries
+        .iter()
+        .rev()
+        .find(|x| {
+            x.product_name.eq_ignore_ascii_case(product_name)
+                && Version::new(&x.version).partial_cmp(version) == Some(Ordering::Equal)
+        })
+        .filter(|x| x.action == JournalAction::Install)
+}
+
+/// Who installed [version] of [product_name], see [latest_install]
+pub fn installed_by<'a>(
+    entries: &'a [JournalEntry],
+    product_name: &str,
+    version: &Version,
+) -> Option<&'a str> {
+    latest_install(entries, product_name, version).map(|x| x.user.as_str())
+}
+
+/// Bytes [version] of [product_name] took on disk when it was installed, see [latest_install]
+pub fn installed_size(
+    entries: &[JournalEntry],
+    product_name: &str,
+    version: &Version,
+) -> Option<u64> {
+    latest_install(entries, product_name, version).and_then(|x| x.size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{append, installed_by, installed_size, load, JournalAction, JournalEntry};
+    use crate::candidate::Version;
+
+    #[test]
+    fn latest_entry_tells_who_installed() {
+        let cache = std::env::temp_dir().join("gman_tests").join("journal");
+        let _ = std::fs::remove_dir_all(&cache);
+
+        let entry = |user: &str, action, version: &str| JournalEntry {
+            time: 0,
+            user: user.to_owned(),
+            action,
+            product_name: "HubKit".to_owned(),
+            version: version.to_owned(),
+            files: Vec::new(),
+            size: None,
+        };
+        append(
+            &cache,
+            &entry("alice", JournalAction::Install, "5.2.1-7049"),
+        )
+        .unwrap();
+        append(&cache, &entry("bob", JournalAction::Install, "5.2.1-7049")).unwrap();
+        append(
+            &cache,
+            &JournalEntry {
+                size: Some(512 * 1024 * 1024),
+                ..entry("alice", JournalAction::Install, "5.2.1-7060")
+            },
+        )
+        .unwrap();
+        append(
+            &cache,
+            &entry("bob", JournalAction::Uninstall, "5.2.1-7060"),
+        )
+        .unwrap();
+
+        let entries = load(&cache);
+        assert_eq!(entries.len(), 4);
+        assert_eq!(
+            installed_by(&entries, "hubkit", &Version::new("5.2.1.7049")),
+            Some("bob")
+        );
+        assert_eq!(
+            installed_by(&entries, "HubKit", &Version::new("5.2.1-7060")),
+            None
+        );
+        assert_eq!(
+            installed_size(&entries, "HubKit", &Version::new("5.2.1-7060")),
+            None
+        );
+        assert_eq!(
+            installed_size(&entries[..3], "HubKit", &Version::new("5.2.1.7060")),
+            Some(512 * 1024 * 1024)
+        );
+    }
+}
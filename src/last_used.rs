@@ -0,0 +1,91 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// File in the state directory remembering how each product was last installed
+const LAST_USED_FILE_NAME: &str = "last_used.json";
+
+/// Flavors and branch a product was last installed with, used when `install` omits them
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct ProductDefaults {
+    #[serde(rename = "Flavors", default, skip_serializing_if = "Vec::is_empty")]
+    pub flavors: Vec<String>,
+    #[serde(rename = "Branch", default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+}
+
+/// The [ProductDefaults] of every product installed so far, by lowercase product name
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct LastUsed {
+    #[serde(rename = "Products", default)]
+    products: BTreeMap<String, ProductDefaults>,
+}
+
+impl LastUsed {
+    fn path(state_directory: &Path) -> PathBuf {
+        state_directory.join(LAST_USED_FILE_NAME)
+    }
+
+    /// What's remembered in [state_directory], nothing if it can't be read
+    pub fn load(state_directory: &Path) -> Self {
+        std::fs::read_to_string(Self::path(state_directory))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, state_directory: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(state_directory)?;
+        std::fs::write(
+            Self::path(state_directory),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        Ok(())
+    }
+
+    pub fn get(&self, product_name: &str) -> Option<&ProductDefaults> {
+        self.products.get(&product_name.to_lowercase())
+    }
+
+    /// Remembers the [flavors] of [product_name] just installed, and the [branch] if it was installed from one
+    pub fn remember(&mut self, product_name: &str, flavors: Vec<String>, branch: Option<&str>) {
+        let defaults = self
+            .products
+            .entry(product_name.to_lowercase())
+            .or_default();
+        defaults.flavors = flavors;
+        if let Some(branch) = branch {
+            defaults.branch = Some(branch.to_owned());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LastUsed, ProductDefaults};
+
+    #[test]
+    fn remembered_defaults_survive_reload() {
+        let state = std::env::temp_dir().join("gman_tests").join("last_used");
+        let _ = std::fs::remove_dir_all(&state);
+        assert_eq!(LastUsed::load(&state), LastUsed::default());
+
+        let mut last_used = LastUsed::default();
+        last_used.remember("GravioStudio", vec!["Sideloading".into()], Some("develop"));
+        /* a version doesn't replace the remembered branch */
+        last_used.remember("GravioStudio", vec!["WindowsAppStore".into()], None);
+        last_used.save(&state).unwrap();
+
+        assert_eq!(
+            LastUsed::load(&state).get("graviostudio"),
+            Some(&ProductDefaults {
+                flavors: vec!["WindowsAppStore".into()],
+                branch: Some("develop".into()),
+            })
+        );
+        assert_eq!(LastUsed::load(&state).get("HubKit"), None);
+    }
+}
@@ -1,84 +1,436 @@
+mod alias;
 mod app;
+mod audit;
+mod bench;
+mod blob_store;
 mod candidate;
 mod cli;
 mod client;
 mod client_config;
+mod clock;
+mod completion;
+mod config_editor;
+mod config_trust;
+mod container;
+#[cfg(target_os = "linux")]
+mod desktop;
+mod download;
+mod folder_repository;
 mod gman_error;
+mod http_cache;
+mod i18n;
+mod install_summary;
+mod interaction;
+mod journal;
+mod last_used;
+mod manifest;
+mod netrc;
+mod notify;
+mod oauth;
+mod output;
 mod platform;
+#[cfg(target_os = "windows")]
+mod powershell;
+mod preflight;
 mod product;
+mod progress;
+mod redact;
+#[cfg(target_os = "windows")]
+mod sandbox;
+mod service;
+mod snapshot;
+mod ssh;
+mod support;
+mod system_runner;
+mod tags;
 mod team_city;
+mod throttle;
+mod transaction;
+mod transform;
 mod util;
-use candidate::{InstallationCandidate, Version};
-use clap::Parser;
+mod wizard;
+use candidate::{InstallationCandidate, TablePrinter, Version};
+use clap::{CommandFactory, Parser};
+use clap_complete::CompleteEnv;
 use cli::Commands;
 use client_config::*;
 use hyper_util::server::conn::auto;
+use std::io::IsTerminal;
 use std::path::PathBuf;
 use std::process::exit;
 use std::str::FromStr;
+use std::time::{Instant, SystemTime};
 
 use crate::candidate::SearchCandidate;
-use crate::cli::{Cli, Target};
-use crate::client::Client;
+use crate::cli::{
+    AuditCommand, AuthCommand, CacheCommand, Cli, RepoCommand, SnapshotCommand, Target,
+};
+use crate::client::{Client, ExternalArtifact};
+use crate::gman_error::{GManError, GManErrorKind};
+use crate::i18n::tr;
+use crate::platform::Architecture;
+use crate::product::Product;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let cli = Cli::parse();
+    /* answer shell completion requests (COMPLETE=<shell>) before anything else touches stdout */
+    CompleteEnv::with_factory(|| {
+        let command = Cli::command();
+        let aliases = alias::load_aliases(&[], &command);
+        alias::with_aliases(command, &aliases)
+    })
+    .complete();
+
+    /* aliases from the configuration stand for whole command lines, see [ClientConfig::aliases] */
+    let args = std::env::args_os().collect::<Vec<_>>();
+    let command = Cli::command();
+    let aliases = alias::load_aliases(&args, &command);
+    let args = match alias::expand(args, &command, &aliases) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", tr!("alias-failed", error = e.to_string()));
+            exit(2);
+        }
+    };
+    let cli = Cli::parse_from(args);
+    let started = Instant::now();
 
     if let Some(ll) = &cli.log_level {
         app::init_logging(Some(*ll));
     }
+    if cli.debug_parse {
+        app::enable_debug_parse();
+    }
+    progress::set_mode(cli.progress);
+    output::set_list_format(cli.format);
+    if cli.include_personal {
+        app::include_personal_builds();
+    }
+    if cli.no_sudo {
+        app::disable_sudo();
+    }
+    if cli.skip_prerequisites {
+        app::skip_prerequisites();
+    }
+    if cli.execution_policy_bypass {
+        app::bypass_execution_policy();
+    }
+    if let Some(dir) = &cli.config_dir {
+        app::set_config_directory(dir.to_owned());
+    }
 
-    let config = match ClientConfig::load_config(cli.config_path) {
-        Ok(c) => c,
-        Err(e) => {
-            eprintln!("Failed to load configuration file: {}", e);
+    /* --config only ever loads the given file */
+    let (config_path, strict_config) = match &cli.strict_config {
+        Some(p) => (Some(p), true),
+        None => (cli.config_path.as_ref(), false),
+    };
+
+    if let Some(Commands::Config { r#where: true, .. }) = &cli.command {
+        print_config_search(config_path, strict_config);
+        exit(0);
+    }
+
+    if let Some(Commands::Config { migrate: true, .. }) = &cli.command {
+        match ClientConfig::migrate_to_user_directories(config_path, strict_config) {
+            Ok(moved) if moved.is_empty() => println!("{}", tr!("config-nothing-to-migrate")),
+            Ok(moved) => {
+                for (from, to) in moved {
+                    println!(
+                        "{}",
+                        tr!(
+                            "config-moved",
+                            from = from.display().to_string(),
+                            to = to.display().to_string()
+                        )
+                    );
+                }
+            }
+            Err(e) => {
+                eprintln!("{}", tr!("config-migrate-failed", error = e.to_string()));
+                exit(1);
+            }
+        }
+        exit(0);
+    }
+
+    /* editing works on the file itself, so a configuration that doesn't load can still be fixed */
+    if let Some(Commands::Config { edit: true, .. }) = &cli.command {
+        let Some((_, path)) = ClientConfig::find_config_file(config_path, strict_config) else {
+            eprintln!("{}", tr!("config-edit-not-found"));
+            exit(1);
+        };
+        println!(
+            "{}",
+            tr!("config-editing", path = path.display().to_string())
+        );
+        match config_editor::edit_config_file(&path) {
+            Ok(true) => println!("{}", tr!("config-saved", path = path.display().to_string())),
+            Ok(false) => println!("{}", tr!("config-no-changes")),
+            Err(e) => {
+                eprintln!("{}", tr!("config-edit-failed", error = e.to_string()));
+                exit(1);
+            }
+        }
+        exit(0);
+    }
+
+    if let Some(Commands::Init { force }) = &cli.command {
+        let path = new_config_path(config_path);
+        if path.exists() && !force {
+            eprintln!(
+                "{}",
+                tr!("config-exists", path = path.display().to_string())
+            );
+            exit(1);
+        }
+        match wizard::run(&path) {
+            Ok(_) => println!("{}", tr!("config-wrote", path = path.display().to_string())),
+            Err(e) => {
+                eprintln!("{}", tr!("config-setup-failed", error = e.to_string()));
+                exit(1);
+            }
+        }
+        exit(0);
+    }
+
+    /* without any configuration, offer to create one instead of failing */
+    if cli.command.is_some()
+        && !matches!(cli.command, Some(Commands::Config { .. }))
+        && ClientConfig::find_config_file(config_path, strict_config).is_none()
+        && std::io::stdin().is_terminal()
+    {
+        eprint!("{}", tr!("config-setup-prompt"));
+        let mut buffer = String::new();
+        if std::io::stdin().read_line(&mut buffer).is_ok()
+            && !buffer.trim().eq_ignore_ascii_case("n")
+        {
+            let path = new_config_path(config_path);
+            if let Err(e) = wizard::run(&path) {
+                eprintln!("{}", tr!("config-setup-failed", error = e.to_string()));
+                exit(1);
+            }
+            println!("{}", tr!("config-wrote", path = path.display().to_string()));
+        }
+    }
+
+    let mut config = match ClientConfig::find_config_file(config_path, strict_config) {
+        Some((source, path)) => {
+            if let Err(e) = config_trust::ensure_trusted(source, &path) {
+                eprintln!("{}", tr!("config-load-failed", error = e.to_string()));
+                exit(1);
+            }
+            match ClientConfig::load_config_file(&path) {
+                Ok(c) => c,
+                Err(e) => {
+                    eprintln!("{}", tr!("config-load-failed", error = e.to_string()));
+                    exit(1);
+                }
+            }
+        }
+        None => {
+            eprintln!("{}", tr!("config-not-found"));
             exit(1);
         }
     };
 
+    /* the command line takes precedence over the configuration */
+    config.override_directories(cli.cache_dir.as_deref(), cli.temp_dir.as_deref());
+    i18n::init(config.language.as_deref());
+    app::install_interrupt_handler();
+    if let Some(timeout) = cli.timeout {
+        app::install_deadline(std::time::Duration::from_secs(timeout));
+    }
+
+    if let Some(Commands::Auth { command }) = &cli.command {
+        match command {
+            AuthCommand::Login { oauth: repository } => {
+                let http_client = reqwest::Client::new();
+                if let Err(e) = oauth::device_login(&http_client, &config, repository).await {
+                    eprintln!(
+                        "{}",
+                        tr!(
+                            "auth-login-failed",
+                            repository = repository.as_str(),
+                            error = e.to_string()
+                        )
+                    );
+                    exit(1);
+                }
+                println!(
+                    "{}",
+                    tr!("auth-signed-in", repository = repository.as_str())
+                );
+            }
+            AuthCommand::Logout { repository } => {
+                if let Err(e) = oauth::logout(&config, repository) {
+                    eprintln!(
+                        "{}",
+                        tr!(
+                            "auth-logout-failed",
+                            repository = repository.as_str(),
+                            error = e.to_string()
+                        )
+                    );
+                    exit(1);
+                }
+                println!(
+                    "{}",
+                    tr!("auth-signed-out", repository = repository.as_str())
+                );
+            }
+        }
+        exit(0);
+    }
+
+    oauth::load_access_tokens(&reqwest::Client::new(), &mut config).await;
+
+    /* every one of these goes through PowerShell on Windows */
+    #[cfg(target_os = "windows")]
+    if !app::is_skipping_prerequisites()
+        && matches!(
+            cli.command,
+            Some(
+                Commands::List { .. }
+                    | Commands::Uninstall { .. }
+                    | Commands::Install { .. }
+                    | Commands::Upgrade { .. }
+                    | Commands::Installed { .. }
+                    | Commands::Diff { .. }
+                    | Commands::Export
+                    | Commands::Snapshot { .. }
+                    | Commands::Open { .. }
+            )
+        )
+    {
+        if let Err(e) = powershell::check(&system_runner::ProcessRunner) {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    }
+
     match &cli.command {
         /* List */
-        Some(Commands::Cache { clear, list: _ }) => {
+        Some(Commands::Cache {
+            clear,
+            list: _,
+            command,
+        }) => {
             let client = Client::new(config);
             client.init();
 
+            let pin = match command {
+                Some(CacheCommand::Pin { artifact }) => Some((artifact, true)),
+                Some(CacheCommand::Unpin { artifact }) => Some((artifact, false)),
+                None => None,
+            };
+            if let Some((artifact, pin)) = pin {
+                match client.pin_cached(artifact, pin) {
+                    Ok(entries) => {
+                        for entry in entries {
+                            if pin {
+                                println!("{}", tr!("cache-pinned", entry = entry));
+                            } else {
+                                println!("{}", tr!("cache-unpinned", entry = entry));
+                            }
+                        }
+                        exit(0)
+                    }
+                    Err(e) => {
+                        eprintln!(
+                            "{}",
+                            tr!(
+                                "cache-pin-failed",
+                                artifact = artifact.as_str(),
+                                error = e.to_string()
+                            )
+                        );
+                        exit(1);
+                    }
+                }
+            }
+
             if *clear {
                 match client.clear_cache() {
                     Ok(_) => {
-                        println!("Cleared cache");
+                        println!("{}", tr!("cache-cleared"));
                         exit(0)
                     }
                     Err(e) => {
-                        eprintln!("Failed to clear cache: {}", e);
+                        eprintln!("{}", tr!("cache-clear-failed", error = e.to_string()));
                         exit(1);
                     }
                 }
+            } else if output::list_format() != output::ListFormat::Table {
+                let items = client.list_cache_with_pins().unwrap_or_default();
+                let sizes = client.candidate_sizes(&items).await;
+                let records = items
+                    .iter()
+                    .zip(sizes)
+                    .map(|(item, size)| output::CandidateRecord {
+                        cached: true,
+                        ..output::CandidateRecord::new(item, size)
+                    })
+                    .collect::<Vec<_>>();
+                output::print_records(&records);
             } else {
                 println!(
-                    "Cache Directory: {}",
-                    client.config.cache_directory.to_str().unwrap()
+                    "{}",
+                    tr!(
+                        "cache-directory",
+                        path = client.config.cache_directory.to_str().unwrap()
+                    )
                 );
-                match client.list_cache() {
+                match client.list_cache_with_pins() {
                     Some(items) => {
-                        println!("Content Count: {}", items.len());
-                        client.format_candidate_table(items, false, false, false);
+                        println!("{}", tr!("cache-count", count = items.len()));
+                        client.format_candidate_table(items, false, false, false, false);
                     }
                     None => {
-                        println!("Nothing in cache");
+                        println!("{}", tr!("cache-empty"));
                     }
                 }
             }
             exit(0);
         }
-        Some(Commands::List { show_installed }) => {
+        Some(Commands::List {
+            show_installed,
+            outdated,
+            show_blocked,
+        }) => {
             let client = Client::new(config);
             client.init();
+            resume_interrupted_install(&client).await;
 
-            let mut candidates = client
-                .list_candidates(None, None)
-                .await
-                .expect("Failed to load candidates");
+            if *outdated {
+                match client.list_outdated().await {
+                    Ok(outdated) if output::list_format() != output::ListFormat::Table => {
+                        let records = outdated
+                            .iter()
+                            .map(output::OutdatedRecord::new)
+                            .collect::<Vec<_>>();
+                        output::print_records(&records);
+                    }
+                    Ok(outdated) => client::print_outdated(&outdated),
+                    Err(e) => {
+                        exit_if_cancelled(e.as_ref(), &tr!("not-done-outdated"));
+                        eprintln!("{}", tr!("list-outdated-failed", error = e.to_string()));
+                        exit(1);
+                    }
+                }
+                exit(0)
+            }
+
+            let mut candidates = match client.list_candidates(None, None).await {
+                Ok(c) => c,
+                Err(e) => {
+                    exit_if_cancelled(e.as_ref(), &tr!("not-done-list"));
+                    eprintln!("{}", tr!("list-offline", error = e.to_string()));
+                    client.list_cached_candidates()
+                }
+            };
+            if !show_blocked {
+                candidates.retain(|x| client.blocked_reason(x).is_none());
+            }
             let installed_candidates = client.get_installed();
             for installed in &installed_candidates {
                 /* Keep Candidate in list if...
@@ -88,10 +440,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                 if !show_installed {
                     candidates.retain_mut(|cd| !cd.product_equals(&installed))
                 } else {
-                    if !candidates
-                        .iter()
-                        .any(|x| x.product_equals(installed) && x.version == installed.version)
-                    {
+                    if !candidates.iter().any(|x| x.key().matches(&installed.key())) {
                         // TODO(nf): the flavor here is a dummy placeholder. This whole "show installed stuff even when not in the list" stuff is very messy
                         candidates.push(InstallationCandidate {
                             remote_id: String::default(),
@@ -101,6 +450,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                             identifier: "--".to_owned(),
                             flavor: product::Flavor::empty(),
                             installed: true,
+                            expired: false,
                         })
                     }
                 }
@@ -108,12 +458,34 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
             /* set the Installed flag */
             for cd in candidates.iter_mut() {
                 for installed in &installed_candidates {
-                    if cd.product_equals(&installed) && cd.version == installed.version {
+                    if cd.key().matches(&installed.key()) {
                         cd.installed = true;
                     }
                 }
             }
-            client.format_candidate_table(candidates, *show_installed, true, false);
+            /* sizes only for what's displayed, so listing doesn't ask for every build there is */
+            let sizes = client.candidate_sizes(&candidates).await;
+            if output::list_format() != output::ListFormat::Table {
+                let records = candidates
+                    .iter()
+                    .zip(sizes)
+                    .map(|(candidate, size)| output::CandidateRecord::new(candidate, size))
+                    .collect::<Vec<_>>();
+                output::print_records(&records);
+                exit(0)
+            }
+            let rows = candidates
+                .into_iter()
+                .zip(sizes)
+                .map(|(candidate, size)| {
+                    let mut row: TablePrinter = candidate.into();
+                    row.size = size
+                        .map(|x| indicatif::HumanBytes(x).to_string())
+                        .unwrap_or_default();
+                    row
+                })
+                .collect::<Vec<TablePrinter>>();
+            client.format_candidate_table(rows, *show_installed, true, true, false);
             exit(0)
         }
         /* Uninstall */
@@ -122,16 +494,45 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
             ver,
             path,
             prompt,
+            purge,
+            output,
         }) => {
+            output::set_format(*output);
             let client = Client::new(config);
             client.init();
 
-            let _ = client.uninstall(
+            let result = client.uninstall(
                 &name,
                 ver.to_owned().map(|x| Version::new(&x)),
                 path.to_owned(),
                 prompt.to_owned(),
+                *purge,
             );
+            let outcome = match result {
+                /* every installation found was kept when asked */
+                Ok(uninstalled) if uninstalled.is_empty() => {
+                    output::CommandResult::new(output::ResultStatus::Canceled, name, started)
+                }
+                Ok(uninstalled) => {
+                    let mut outcome =
+                        output::CommandResult::new(output::ResultStatus::Succeeded, name, started);
+                    /* all the installations removed, several if no version was asked for */
+                    outcome.previous_version = Some(
+                        uninstalled
+                            .iter()
+                            .map(|x| x.to_string())
+                            .collect::<Vec<String>>()
+                            .join(", "),
+                    );
+                    outcome
+                }
+                Err(e) => {
+                    output::CommandResult::failed(name, started, &e.to_string()).print();
+                    eprintln!("{}", e);
+                    exit(1);
+                }
+            };
+            outcome.print();
             exit(0)
         }
         /* Install */
@@ -142,66 +543,614 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
             automatic_upgrade,
             prompt,
             autorun,
+            file,
+            url,
+            checksum,
+            product,
+            version,
+            manifest,
+            launch_args,
+            wait_for_launch,
+            force,
+            allow_blocked,
+            nearest,
+            skip_checksum,
+            provision,
+            arch,
+            sandbox,
+            container,
+            target: ssh_target,
+            output,
         }) => {
+            output::set_format(*output);
+            let mut config = config;
+            if *force {
+                config.close_running_applications = true;
+            }
+            if *allow_blocked {
+                app::allow_blocked_builds();
+            }
+            if *nearest {
+                app::use_nearest_build();
+            }
+            if *skip_checksum {
+                app::skip_checksums();
+            }
+            if *provision {
+                app::provision_packages();
+            }
+            set_architecture(arch.as_deref());
+            if wait_for_launch.is_some() {
+                config.autorun_verify_seconds = *wait_for_launch;
+            }
+            if let Some(launch_args) = launch_args {
+                let product_name = name.as_ref().or(product.as_ref());
+                match (shlex::split(launch_args), product_name) {
+                    (Some(args), Some(product_name)) => {
+                        config.override_launch_args(product_name, args)
+                    }
+                    (None, _) => {
+                        eprintln!(
+                            "{}",
+                            tr!(
+                                "install-invalid-launch-args",
+                                launch_args = launch_args.as_str()
+                            )
+                        );
+                        exit(1);
+                    }
+                    (_, None) => {}
+                }
+            }
             let client = Client::new(config);
             client.init();
+            resume_interrupted_install(&client).await;
 
-            /* find product */
-            let target: Target = match build_or_branch {
-                Some(x) => Target::from_str(x.as_ref()).unwrap(),
-                None => Target::Identifier("master".to_owned()),
-            };
-
-            let candidate = SearchCandidate::new(
-                name,
-                match &target {
-                    Target::Identifier(_) => None,
-                    Target::Version(x) => Some(x.as_str()),
-                },
-                match &target {
-                    Target::Identifier(x) => Some(x.as_str()),
-                    Target::Version(_) => None,
-                },
-                flavor.as_ref().map(|x| x.as_str()),
-                &client.config.products,
-            );
+            /* reproduce an exported machine state */
+            if let Some(manifest_path) = manifest {
+                let manifest = match manifest::Manifest::load(manifest_path) {
+                    Ok(m) => m,
+                    Err(e) => {
+                        eprintln!("{}", tr!("install-manifest-failed", error = e.to_string()));
+                        exit(1);
+                    }
+                };
+                if let Err(e) = client.install_manifest(&manifest, *prompt, *autorun).await {
+                    exit_if_cancelled(e.as_ref(), &tr!("not-done-install"));
+                    eprintln!("{}", e);
+                    exit(failure_exit_code(e.as_ref()));
+                }
+                exit(0);
+            }
 
-            match candidate {
-                Some(candidate) => {
-                    println!(
-                        "Installing {}@{}, flavor {}",
+            /* install onto another machine over SSH */
+            if let (Some(ssh_target), Some(name)) = (ssh_target, name) {
+                let branch = branch_or_last_used(name, build_or_branch.as_deref());
+                match client
+                    .install_remote(
                         name,
-                        target.to_string(),
-                        candidate.flavor.id,
-                    );
-                    match client
-                        .install(&candidate, *automatic_upgrade, *prompt, *autorun)
-                        .await
-                        .expect("Failed to install item")
+                        &branch,
+                        flavor.first().map(|x| x.as_str()),
+                        ssh_target,
+                    )
+                    .await
+                {
+                    Ok(build) => {
+                        println!(
+                            "{}",
+                            tr!(
+                                "install-remote-succeeded",
+                                product = build.product_name.as_str(),
+                                version = build.version.to_string(),
+                                target = ssh_target.as_str()
+                            )
+                        );
+                        exit(0);
+                    }
+                    Err(e) => {
+                        exit_if_cancelled(e.as_ref(), &tr!("not-done-install"));
+                        eprintln!(
+                            "{}",
+                            tr!(
+                                "install-remote-failed",
+                                product = name.as_str(),
+                                target = ssh_target.as_str(),
+                                error = e.to_string()
+                            )
+                        );
+                        exit(1);
+                    }
+                }
+            }
+
+            /* check the Linux package installs, in a container of any machine */
+            if let (Some(image), Some(name)) = (container, name) {
+                let branch = branch_or_last_used(name, build_or_branch.as_deref());
+                match client
+                    .install_in_container(name, &branch, flavor.first().map(|x| x.as_str()), image)
+                    .await
+                {
+                    Ok(build) => {
+                        println!(
+                            "{}",
+                            tr!(
+                                "install-container-succeeded",
+                                product = build.product_name.as_str(),
+                                version = build.version.to_string(),
+                                image = image.as_str()
+                            )
+                        );
+                        exit(0);
+                    }
+                    Err(e) => {
+                        exit_if_cancelled(e.as_ref(), &tr!("not-done-install"));
+                        eprintln!(
+                            "{}",
+                            tr!(
+                                "install-container-failed",
+                                product = name.as_str(),
+                                image = image.as_str(),
+                                error = e.to_string()
+                            )
+                        );
+                        exit(1);
+                    }
+                }
+            }
+
+            let mut name = name.to_owned();
+            let mut flavor = flavor.to_owned();
+            let mut build_or_branch = build_or_branch.to_owned();
+
+            /* a build linked to from TeamCity names its product, flavor and build itself */
+            let build_url = name.as_deref().and_then(team_city::BuildUrl::parse);
+            let mut build_url_search = None;
+            if let Some(build_url) = &build_url {
+                match client.fetch_build_by_url(build_url).await {
+                    Ok(search) => {
+                        let version = search
+                            .version
+                            .as_ref()
+                            .map(|x| x.to_string())
+                            .unwrap_or_default();
+                        println!(
+                            "{}",
+                            tr!(
+                                "install-build-url",
+                                product = search.product_name.as_str(),
+                                flavor = search.flavor.id.as_str(),
+                                version = version.as_str()
+                            )
+                        );
+                        name = Some(search.product_name.to_owned());
+                        flavor = vec![search.flavor.id.to_owned()];
+                        build_or_branch = Some(version);
+                        build_url_search = Some(search);
+                    }
+                    Err(e) => {
+                        eprintln!("{}", tr!("install-build-url-failed", error = e.to_string()));
+                        exit(1);
+                    }
+                }
+            }
+
+            /* an omitted flavor and branch default to what the product was last installed with */
+            if let (Some(name), None, None, None) = (&name, file, url, &build_url) {
+                let last_used = last_used::LastUsed::load(&app::get_state_directory());
+                if let Some(previous) = last_used.get(name) {
+                    let has_flavor = |id: &String| {
+                        Product::from_name(name, &client.config.products).is_some_and(|p| {
+                            p.flavors.iter().any(|x| x.id.eq_ignore_ascii_case(id))
+                        })
+                    };
+                    if flavor.is_empty()
+                        && !previous.flavors.is_empty()
+                        && previous.flavors.iter().all(has_flavor)
                     {
-                        candidate::InstallationResult::Canceled => {
-                            println!("Canceled installation");
+                        flavor = previous.flavors.to_owned();
+                        println!(
+                            "{}",
+                            tr!("install-previous-flavor", flavor = flavor.join(","))
+                        );
+                    }
+                    if let (None, Some(branch)) = (&build_or_branch, &previous.branch) {
+                        println!(
+                            "{}",
+                            tr!("install-previous-branch", branch = branch.as_str())
+                        );
+                        build_or_branch = Some(branch.to_owned());
+                    }
+                }
+            }
+
+            /* several flavors of one product side by side */
+            if flavor.len() > 1 {
+                if file.is_some() || url.is_some() {
+                    eprintln!("{}", tr!("install-flavors-external"));
+                    exit(1);
+                }
+                if *sandbox {
+                    eprintln!("{}", tr!("install-sandbox-flavors"));
+                    exit(1);
+                }
+                let name = name
+                    .as_ref()
+                    .expect("Expected product name to be required without --file/--url");
+                let target: Target = match build_or_branch {
+                    Some(x) => Target::from_str(x.as_ref()).unwrap(),
+                    None => Target::Identifier("master".to_owned()),
+                };
+                let mut searches = Vec::new();
+                for flavor in &flavor {
+                    match search_candidate_for_target(&client, name, &target, Some(flavor)).await {
+                        Some(search) => searches.push(search),
+                        None => {
+                            eprintln!("{}", tr!("install-no-candidate"));
+                            exit(1)
                         }
-                        candidate::InstallationResult::Succeeded => {
-                            println!("Successfully Installed {}", candidate.product_name);
+                    }
+                }
+                println!(
+                    "{}",
+                    tr!(
+                        "install-flavors",
+                        product = name.as_str(),
+                        target = target.to_string(),
+                        flavors = flavor.join(", ")
+                    )
+                );
+                match client
+                    .install_flavors(&searches, *automatic_upgrade, *prompt, *autorun)
+                    .await
+                {
+                    Ok(results) => {
+                        client::print_flavor_results(&results);
+                        let installed = results
+                            .iter()
+                            .filter(|x| {
+                                matches!(
+                                    x.result,
+                                    Ok(candidate::InstallationResult::Succeeded
+                                        | candidate::InstallationResult::Skipped)
+                                )
+                            })
+                            .map(|x| x.flavor.to_owned())
+                            .collect::<Vec<String>>();
+                        if !installed.is_empty() {
+                            remember_last_used(name, installed, &target);
                         }
-                        candidate::InstallationResult::Skipped => {
-                            println!("Skipped installation");
+                        let failed = results
+                            .iter()
+                            .filter_map(|x| x.result.as_ref().err())
+                            .collect::<Vec<_>>();
+                        if !failed.is_empty() {
+                            eprintln!(
+                                "{}",
+                                tr!(
+                                    "install-flavors-failed",
+                                    count = failed.len(),
+                                    total = results.len()
+                                )
+                            );
+                            /* a flavor the policy refused is told apart like for a single install */
+                            exit(
+                                failed
+                                    .iter()
+                                    .map(|e| failure_exit_code(e.as_ref()))
+                                    .max()
+                                    .unwrap_or(1),
+                            );
                         }
                     }
+                    Err(e) => {
+                        exit_if_cancelled(e.as_ref(), &tr!("not-done-install"));
+                        eprintln!("{}", tr!("install-failed", error = e.to_string()));
+                        exit(failure_exit_code(e.as_ref()));
+                    }
+                }
+                exit(0);
+            }
+            let flavor = flavor.first().map(|x| x.as_str());
+
+            let external = match (file, url) {
+                (Some(file), _) => Some(ExternalArtifact::File(file)),
+                (None, Some(url)) => Some(ExternalArtifact::Url {
+                    url,
+                    checksum: checksum.as_deref(),
+                }),
+                (None, None) => None,
+            };
 
-                    exit(0);
+            let (product_name, result) = match external {
+                /* artifact from outside the repositories */
+                Some(artifact) => {
+                    let product_name = product
+                        .as_ref()
+                        .expect("Expected --product to be required alongside --file/--url");
+                    println!(
+                        "{}",
+                        tr!(
+                            "install-from",
+                            product = product_name.as_str(),
+                            source = match &artifact {
+                                ExternalArtifact::File(file) => file.display().to_string(),
+                                ExternalArtifact::Url { url, .. } => redact::redact_url(url),
+                            }
+                        )
+                    );
+                    (
+                        product_name.to_owned(),
+                        client
+                            .install_external(
+                                &artifact,
+                                product_name,
+                                version.as_deref(),
+                                flavor,
+                                *prompt,
+                                *autorun,
+                            )
+                            .await,
+                    )
                 }
                 None => {
-                    eprintln!("Could not construct a Search Candidate from the input parameters. Check that the product/flavor exist");
+                    let name = name
+                        .as_ref()
+                        .expect("Expected product name to be required without --file/--url");
+
+                    /* find product */
+                    let target: Target = match build_or_branch {
+                        Some(x) => Target::from_str(x.as_ref()).unwrap(),
+                        None => Target::Identifier("master".to_owned()),
+                    };
+
+                    /* the very build the url links to, not whichever build has its number */
+                    let candidate = match build_url_search {
+                        Some(search) => Some(search),
+                        None => search_candidate_for_target(&client, name, &target, flavor).await,
+                    };
+
+                    match candidate {
+                        Some(candidate) => {
+                            println!(
+                                "{}",
+                                tr!(
+                                    "install-target",
+                                    product = name.as_str(),
+                                    target = target.to_string(),
+                                    flavor = candidate.flavor.id.as_str(),
+                                )
+                            );
+                            /* try it out in Windows Sandbox, leaving this machine as it is */
+                            if *sandbox {
+                                match client.install_in_sandbox(&candidate, *autorun).await {
+                                    Ok(wsb) => {
+                                        println!(
+                                            "{}",
+                                            tr!(
+                                                "install-sandbox-started",
+                                                product = name.as_str(),
+                                                path = wsb.display().to_string()
+                                            )
+                                        );
+                                        exit(0);
+                                    }
+                                    Err(e) => {
+                                        exit_if_cancelled(e.as_ref(), &tr!("not-done-install"));
+                                        eprintln!(
+                                            "{}",
+                                            tr!("install-sandbox-failed", error = e.to_string())
+                                        );
+                                        exit(1);
+                                    }
+                                }
+                            }
+                            let result = client
+                                .install_reported(&candidate, *automatic_upgrade, *prompt, *autorun)
+                                .await;
+                            if let Ok((candidate::InstallationResult::Succeeded, _)) = &result {
+                                remember_last_used(
+                                    name,
+                                    vec![candidate.flavor.id.to_owned()],
+                                    &target,
+                                );
+                            }
+                            (candidate.product_name.to_owned(), result)
+                        }
+                        None => {
+                            eprintln!("{}", tr!("install-no-candidate"));
+                            output::CommandResult::failed(
+                                name,
+                                started,
+                                &tr!("install-no-candidate"),
+                            )
+                            .print();
+                            exit(1)
+                        }
+                    }
+                }
+            };
+
+            let outcome = output::CommandResult::install(&product_name, started, &result);
+            match result.map(|(x, _)| x) {
+                Ok(candidate::InstallationResult::Canceled) => {
+                    println!("{}", tr!("install-canceled"));
+                }
+                Ok(candidate::InstallationResult::Succeeded) => {
+                    println!("{}", tr!("install-succeeded", product = product_name));
+                }
+                Ok(candidate::InstallationResult::Skipped) => {
+                    println!("{}", tr!("install-skipped"));
+                }
+                Err(e) => {
+                    outcome.print();
+                    exit_if_cancelled(e.as_ref(), &tr!("not-done-install"));
+                    eprintln!("{}", tr!("install-failed", error = e.to_string()));
+                    exit(failure_exit_code(e.as_ref()));
+                }
+            }
+            outcome.print();
+
+            exit(0);
+        }
+        Some(Commands::Upgrade {
+            name,
+            only,
+            dry_run,
+            autorun,
+            force,
+            allow_blocked,
+            output,
+        }) => {
+            output::set_format(*output);
+            let mut config = config;
+            if *force {
+                config.close_running_applications = true;
+            }
+            if *allow_blocked {
+                app::allow_blocked_builds();
+            }
+            let client = Client::new(config);
+            client.init();
+            resume_interrupted_install(&client).await;
+
+            let only = name.iter().chain(only).cloned().collect::<Vec<String>>();
+            let upgrades = match client.find_upgrades(&only).await {
+                Ok(upgrades) => upgrades,
+                Err(e) => {
+                    let products = only.join(", ");
+                    output::CommandResult::failed(&products, started, &e.to_string()).print();
+                    exit_if_cancelled(e.as_ref(), &tr!("not-done-upgrade"));
+                    eprintln!(
+                        "{}",
+                        tr!(
+                            "upgrade-failed",
+                            product = products.as_str(),
+                            error = e.to_string()
+                        )
+                    );
                     exit(1)
                 }
+            };
+            for product in only.iter().filter(|x| {
+                !upgrades
+                    .iter()
+                    .any(|upgrade| upgrade.product_name.eq_ignore_ascii_case(x))
+            }) {
+                println!("{}", tr!("upgrade-up-to-date", product = product.as_str()));
+                output::CommandResult::new(output::ResultStatus::Skipped, product, started).print();
             }
+            if only.is_empty() && upgrades.is_empty() {
+                println!("{}", tr!("upgrade-all-up-to-date"));
+            }
+            if *dry_run {
+                for upgrade in &upgrades {
+                    println!(
+                        "{}",
+                        tr!(
+                            "upgrade-would",
+                            product = upgrade.product_name.as_str(),
+                            installed = upgrade.installed.to_string(),
+                            available = upgrade.available.to_string(),
+                            branch = upgrade.branch.as_str()
+                        )
+                    );
+                }
+                exit(0)
+            }
+
+            /* one failed upgrade doesn't hold back the others */
+            let mut exit_code = 0;
+            let mut failed = 0;
+            for upgrade in &upgrades {
+                println!(
+                    "{}",
+                    tr!(
+                        "upgrade-available",
+                        product = upgrade.product_name.as_str(),
+                        installed = upgrade.installed.to_string(),
+                        available = upgrade.available.to_string(),
+                        branch = upgrade.branch.as_str()
+                    )
+                );
+                let Some(search) = SearchCandidate::new(
+                    &upgrade.product_name,
+                    Some(upgrade.available.as_ref()),
+                    Some(&upgrade.branch),
+                    Some(&upgrade.flavor),
+                    &client.config.products,
+                ) else {
+                    eprintln!("{}", tr!("install-no-candidate"));
+                    output::CommandResult::failed(
+                        &upgrade.product_name,
+                        started,
+                        &tr!("install-no-candidate"),
+                    )
+                    .print();
+                    failed += 1;
+                    exit_code = 1;
+                    continue;
+                };
+
+                /* in place and without questions, running instances fail it unless --force */
+                let result = client
+                    .install_reported(&search, Some(false), Some(false), *autorun)
+                    .await;
+                let outcome =
+                    output::CommandResult::install(&upgrade.product_name, started, &result);
+                match result.map(|(x, _)| x) {
+                    Ok(candidate::InstallationResult::Succeeded) => println!(
+                        "{}",
+                        tr!(
+                            "upgrade-succeeded",
+                            product = upgrade.product_name.as_str(),
+                            version = upgrade.available.to_string()
+                        )
+                    ),
+                    Ok(candidate::InstallationResult::Skipped) => {
+                        println!("{}", tr!("install-skipped"))
+                    }
+                    Ok(candidate::InstallationResult::Canceled) => {
+                        println!("{}", tr!("install-canceled"));
+                        outcome.print();
+                        failed += 1;
+                        exit_code = 1;
+                        continue;
+                    }
+                    Err(e) => {
+                        outcome.print();
+                        exit_if_cancelled(e.as_ref(), &tr!("not-done-upgrade"));
+                        eprintln!(
+                            "{}",
+                            tr!(
+                                "upgrade-failed",
+                                product = upgrade.product_name.as_str(),
+                                error = e.to_string()
+                            )
+                        );
+                        failed += 1;
+                        exit_code = failure_exit_code(e.as_ref());
+                        continue;
+                    }
+                }
+                outcome.print();
+            }
+            if failed > 0 && upgrades.len() > 1 {
+                eprintln!(
+                    "{}",
+                    tr!(
+                        "upgrade-some-failed",
+                        count = failed,
+                        total = upgrades.len()
+                    )
+                );
+            }
+            exit(exit_code)
         }
-        Some(Commands::Installed) => {
+        Some(Commands::Installed { all_users, verbose }) => {
             let client = Client::new(config);
             client.init();
-            let candidates = client.get_installed();
+            resume_interrupted_install(&client).await;
+            let installed = client.get_installed_for(*all_users);
             let show_path: bool = {
                 if cfg!(target_os = "macos") || cfg!(target_os = "linux") {
                     true
@@ -209,10 +1158,380 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                     false
                 }
             };
-            client.format_candidate_table(candidates, false, false, show_path);
+            if output::list_format() != output::ListFormat::Table {
+                let sizes = client::installed_sizes(&installed, client.state_directory(), *verbose);
+                let records = installed
+                    .iter()
+                    .zip(sizes)
+                    .map(|(x, size)| output::InstalledRecord::new(x, size))
+                    .collect::<Vec<_>>();
+                output::print_records(&records);
+                exit(0)
+            }
+            client::print_installed(&installed, client.state_directory(), show_path, *verbose);
+            exit(0)
+        }
+        Some(Commands::Diff {
+            name,
+            from,
+            to,
+            flavor,
+        }) => {
+            let client = Client::new(config);
+            client.init();
+            match client.diff_builds(name, from, to, flavor.as_deref()).await {
+                Ok(changes) if changes.is_empty() => {
+                    println!(
+                        "{}",
+                        tr!(
+                            "diff-none",
+                            product = name.as_str(),
+                            from = from.as_str(),
+                            to = to.as_str()
+                        )
+                    );
+                }
+                Ok(changes) => {
+                    println!(
+                        "{}",
+                        tr!(
+                            "diff-changes",
+                            count = changes.len(),
+                            product = name.as_str(),
+                            from = from.as_str(),
+                            to = to.as_str()
+                        )
+                    );
+                    for change in changes {
+                        println!("{}", change);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}", tr!("diff-failed", error = e.to_string()));
+                    exit(1);
+                }
+            }
+            exit(0)
+        }
+        Some(Commands::Inspect {
+            name,
+            build_or_branch,
+            flavor,
+        }) => {
+            let client = Client::new(config);
+            client.init();
+            let target = Target::from_str(build_or_branch).unwrap();
+            let Some(search) =
+                search_candidate_for_target(&client, name, &target, flavor.as_deref()).await
+            else {
+                eprintln!("{}", tr!("install-no-candidate"));
+                exit(1)
+            };
+            match client.inspect_build(&search).await {
+                Ok(Some((build, files))) => {
+                    println!(
+                        "{}",
+                        tr!(
+                            "inspect-artifacts",
+                            product = build.product_name.as_str(),
+                            version = build.version.to_string(),
+                            identifier = build.identifier.as_str(),
+                            flavor = build.flavor.id.as_str()
+                        )
+                    );
+                    let binary_path = build
+                        .flavor
+                        .teamcity_metadata
+                        .teamcity_binary_path
+                        .to_string_lossy();
+                    if let Some(size) = files
+                        .iter()
+                        .find(|x| x.full_name.as_deref() == Some(binary_path.as_ref()))
+                        .and_then(|x| x.size)
+                    {
+                        println!(
+                            "{}",
+                            tr!(
+                                "inspect-download-size",
+                                size = indicatif::HumanBytes(size).to_string()
+                            )
+                        );
+                    }
+                    client::print_artifact_files(&files);
+                }
+                Ok(None) => {
+                    eprintln!(
+                        "{}",
+                        tr!(
+                            "inspect-no-build",
+                            product = name.as_str(),
+                            target = build_or_branch.as_str()
+                        )
+                    );
+                    exit(1);
+                }
+                Err(e) => {
+                    eprintln!("{}", tr!("inspect-failed", error = e.to_string()));
+                    exit(1);
+                }
+            }
+            exit(0)
+        }
+        Some(Commands::Export) => {
+            let client = Client::new(config);
+            client.init();
+            match client.export_manifest().to_json() {
+                Ok(json) => println!("{}", json),
+                Err(e) => {
+                    eprintln!("{}", tr!("export-failed", error = e.to_string()));
+                    exit(1);
+                }
+            }
+            exit(0)
+        }
+        Some(Commands::Report { output }) => {
+            let client = Client::new(config);
+            client.init();
+            match client.report(output.as_deref()) {
+                Ok(path) => println!(
+                    "{}",
+                    tr!("report-written", path = path.display().to_string())
+                ),
+                Err(e) => {
+                    eprintln!("{}", tr!("report-failed", error = e.to_string()));
+                    exit(1);
+                }
+            }
+            exit(0)
+        }
+        Some(Commands::Prefetch {
+            name,
+            branch,
+            flavors,
+            arch,
+        }) => {
+            set_architecture(arch.as_deref());
+            let client = Client::new(config);
+            client.init();
+            if let Err(e) = client.prefetch(name, branch, flavors).await {
+                exit_if_cancelled(e.as_ref(), &tr!("not-done-prefetch"));
+                eprintln!(
+                    "{}",
+                    tr!(
+                        "prefetch-failed",
+                        product = name.as_str(),
+                        error = e.to_string()
+                    )
+                );
+                exit(1);
+            }
+            exit(0)
+        }
+        Some(Commands::Repo { command }) => {
+            let client = Client::new(config);
+            client.init();
+            match command {
+                RepoCommand::Bench { name, sample_size } => {
+                    match client
+                        .bench_repositories(name.as_deref(), sample_size * 1024 * 1024)
+                        .await
+                    {
+                        Ok(mut results) => {
+                            bench::print_bench(&mut results);
+                            println!("{}", tr!("repo-bench-hint"));
+                        }
+                        Err(e) => {
+                            eprintln!("{}", tr!("repo-bench-failed", error = e.to_string()));
+                            exit(1);
+                        }
+                    }
+                }
+            }
+            exit(0)
+        }
+        Some(Commands::Audit { command }) => {
+            let client = Client::new(config);
+            client.init();
+            match command {
+                AuditCommand::Tail { lines, json } => {
+                    let entries = client.audit_tail(*lines);
+                    if entries.is_empty() {
+                        println!("{}", tr!("audit-empty"));
+                    }
+                    for line in entries {
+                        match serde_json::from_str::<audit::AuditEntry>(&line) {
+                            Ok(entry) if !json => println!("{}", entry),
+                            _ => println!("{}", line),
+                        }
+                    }
+                }
+            }
+            exit(0)
+        }
+        Some(Commands::Snapshot { command }) => {
+            let client = Client::new(config);
+            client.init();
+            match command {
+                SnapshotCommand::Save { name } => match client.save_snapshot(name).await {
+                    Ok(path) => println!(
+                        "{}",
+                        tr!(
+                            "snapshot-saved",
+                            name = name.as_str(),
+                            path = path.display().to_string()
+                        )
+                    ),
+                    Err(e) => {
+                        exit_if_cancelled(e.as_ref(), &tr!("not-done-snapshot"));
+                        eprintln!("{}", tr!("snapshot-save-failed", error = e.to_string()));
+                        exit(1);
+                    }
+                },
+                SnapshotCommand::Restore {
+                    name,
+                    prompt,
+                    autorun,
+                } => {
+                    if let Err(e) = client.restore_snapshot(name, *prompt, *autorun).await {
+                        exit_if_cancelled(e.as_ref(), &tr!("not-done-install"));
+                        eprintln!(
+                            "{}",
+                            tr!(
+                                "snapshot-restore-failed",
+                                name = name.as_str(),
+                                error = e.to_string()
+                            )
+                        );
+                        exit(1);
+                    }
+                    println!("{}", tr!("snapshot-restored", name = name.as_str()));
+                }
+                SnapshotCommand::List => {
+                    let snapshots = client.list_snapshots();
+                    if snapshots.is_empty() {
+                        println!("{}", tr!("snapshot-none"));
+                    }
+                    for name in snapshots {
+                        println!("{}", name);
+                    }
+                }
+            }
+            exit(0)
+        }
+        Some(Commands::Open {
+            name,
+            data,
+            logs,
+            install_dir: _,
+        }) => {
+            let folder = match (*data, *logs) {
+                (true, _) => client::ProductFolder::Data,
+                (_, true) => client::ProductFolder::Logs,
+                _ => client::ProductFolder::Install,
+            };
+            let client = Client::new(config);
+            client.init();
+            match client.open_product_folder(name, folder) {
+                Ok(folders) => {
+                    for folder in folders {
+                        println!(
+                            "{}",
+                            tr!("open-opened", path = folder.display().to_string())
+                        );
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}", tr!("open-failed", error = e.to_string()));
+                    exit(1);
+                }
+            }
+            exit(0)
+        }
+        Some(Commands::Tag {
+            product_name,
+            version,
+            note,
+            clear,
+        }) => {
+            let state_directory = app::get_state_directory();
+            let mut tags = tags::Tags::load(&state_directory);
+            /* clap requires the product along with the version */
+            let (Some(product_name), Some(version)) = (product_name, version) else {
+                let tagged = tags
+                    .all()
+                    .filter(|(product, _, _)| {
+                        product_name
+                            .as_ref()
+                            .is_none_or(|x| x.eq_ignore_ascii_case(product))
+                    })
+                    .collect::<Vec<_>>();
+                if tagged.is_empty() {
+                    println!("{}", tr!("tag-none"));
+                }
+                for (product, version, notes) in tagged {
+                    println!("{} {}: {}", product, version, notes.join("; "));
+                }
+                exit(0)
+            };
+            match note {
+                None if !*clear => {
+                    let notes = tags.notes(product_name, version);
+                    if notes.is_empty() {
+                        println!(
+                            "{}",
+                            tr!(
+                                "tag-untagged",
+                                product = product_name.as_str(),
+                                version = version.as_str()
+                            )
+                        );
+                    }
+                    for note in notes {
+                        println!("{}", note);
+                    }
+                    exit(0)
+                }
+                None => {
+                    if !tags.clear(product_name, version) {
+                        println!(
+                            "{}",
+                            tr!(
+                                "tag-untagged",
+                                product = product_name.as_str(),
+                                version = version.as_str()
+                            )
+                        );
+                        exit(0)
+                    }
+                    println!(
+                        "{}",
+                        tr!(
+                            "tag-cleared",
+                            product = product_name.as_str(),
+                            version = version.as_str()
+                        )
+                    );
+                }
+                Some(note) => {
+                    tags.add(product_name, version, note);
+                    println!(
+                        "{}",
+                        tr!(
+                            "tag-added",
+                            product = product_name.as_str(),
+                            version = version.as_str()
+                        )
+                    );
+                }
+            }
+            if let Err(e) = tags.save(&state_directory) {
+                eprintln!("{}", tr!("tag-save-failed", error = e.to_string()));
+                exit(1);
+            }
             exit(0)
         }
-        Some(Commands::Config { sample }) => {
+        Some(Commands::Init { .. }) | Some(Commands::Auth { .. }) => {}
+        Some(Commands::Config { sample, .. }) => {
             if *sample {
                 let client = ClientConfig::make_sample();
                 let name = app::CLIENT_CONFIG_FILE_NAME;
@@ -224,7 +1543,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                 const MAX: usize = 200;
                 while joined.exists() {
                     if num >= MAX {
-                        eprintln!("Cannot create sample file, maximum number of tried exceeded (200). Try deleting files named {}", app::CLIENT_CONFIG_FILE_NAME);
+                        eprintln!(
+                            "{}",
+                            tr!(
+                                "config-sample-exhausted",
+                                name = app::CLIENT_CONFIG_FILE_NAME
+                            )
+                        );
                         exit(1);
                     }
                     num += 1;
@@ -238,8 +1563,219 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         }
 
         None => {
-            println!("use -h or --help to show help for this program");
+            println!("{}", tr!("usage-hint"));
         }
     }
     Ok(())
 }
+
+/// Where `init` writes a new configuration: the handed-in [config_path] (a file, or a directory to put
+/// [app::CLIENT_CONFIG_FILE_NAME] in), otherwise the user configuration directory
+fn new_config_path(config_path: Option<&PathBuf>) -> PathBuf {
+    match config_path {
+        Some(p) if p.is_dir() => p.join(app::CLIENT_CONFIG_FILE_NAME),
+        Some(p) => p.to_owned(),
+        None => app::get_config_directory().join(app::CLIENT_CONFIG_FILE_NAME),
+    }
+}
+
+/// Prints the configuration search order for `config --where`, marking the file that gets loaded
+fn print_config_search(config_path: Option<&PathBuf>, strict: bool) {
+    let loaded = ClientConfig::find_config_file(config_path, strict).map(|(_, path)| path);
+    match &loaded {
+        Some(path) => {
+            println!(
+                "{}",
+                tr!("config-loaded", path = path.display().to_string())
+            );
+            match std::fs::read_to_string(path).map(|s| ClientConfig::parse(&s)) {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => println!("  {}", tr!("config-parse-failed", error = e.to_string())),
+                Err(e) => println!("  {}", tr!("config-read-failed", error = e.to_string())),
+            }
+        }
+        None => println!("{}", tr!("config-none")),
+    }
+
+    println!("{}", tr!("config-search-order"));
+    let mut found = false;
+    for (i, (source, path)) in ClientConfig::config_search_paths(config_path, strict)
+        .iter()
+        .enumerate()
+    {
+        let state = if found {
+            tr!("config-state-not-checked")
+        } else if Some(path) == loaded.as_ref() {
+            found = true;
+            tr!("config-state-loaded")
+        } else {
+            tr!("config-state-not-found")
+        };
+        println!(
+            "  {:>2}. {:<22} {} ({})",
+            i + 1,
+            source.to_string(),
+            path.display(),
+            state
+        );
+    }
+}
+
+/// Remembers the [flavors] that [product_name] was installed with, and the branch if [target] is one, so the
+/// next `install` of it can leave them out
+fn remember_last_used(product_name: &str, flavors: Vec<String>, target: &Target) {
+    let state_directory = app::get_state_directory();
+    let mut last_used = last_used::LastUsed::load(&state_directory);
+    let branch = match target {
+        Target::Identifier(x) => Some(x.as_str()),
+        _ => None,
+    };
+    last_used.remember(product_name, flavors, branch);
+    if let Err(e) = last_used.save(&state_directory) {
+        log::warn!("Failed to remember the flavor of {}: {}", product_name, e);
+    }
+}
+
+/// [branch] if given, otherwise the branch [product_name] was last installed from, or `master`
+fn branch_or_last_used(product_name: &str, branch: Option<&str>) -> String {
+    if let Some(branch) = branch {
+        return branch.to_owned();
+    }
+    let last_used = last_used::LastUsed::load(&app::get_state_directory());
+    match last_used.get(product_name).and_then(|x| x.branch.as_ref()) {
+        Some(branch) => {
+            println!(
+                "{}",
+                tr!("install-previous-branch", branch = branch.as_str())
+            );
+            branch.to_owned()
+        }
+        None => "master".to_owned(),
+    }
+}
+
+/// Exits with the matching exit code if [e] is an interruption or timeout, saying what [not_done]
+fn exit_if_cancelled(e: &(dyn std::error::Error + 'static), not_done: &str) {
+    if GManError::is_kind(e, GManErrorKind::Interrupted) {
+        eprintln!("{}", tr!("cancel-interrupted", not_done = not_done));
+        exit(app::EXIT_CODE_INTERRUPTED);
+    }
+    if GManError::is_kind(e, GManErrorKind::TimedOut) {
+        eprintln!("{}", tr!("cancel-timed-out", not_done = not_done));
+        exit(app::EXIT_CODE_TIMED_OUT);
+    }
+}
+
+/// Exit code of a failed install: [app::EXIT_CODE_POLICY_VIOLATION] if the configuration's policy refused it, else 1
+fn failure_exit_code(e: &(dyn std::error::Error + 'static)) -> i32 {
+    if GManError::is_kind(e, GManErrorKind::PolicyViolation) {
+        app::EXIT_CODE_POLICY_VIOLATION
+    } else {
+        1
+    }
+}
+
+/// Picks the artifacts of [arch], as given with `--arch`, which clap already checked is an [Architecture]
+fn set_architecture(arch: Option<&str>) {
+    if let Some(architecture) = arch.and_then(|x| Architecture::from_str(x).ok()) {
+        app::set_architecture(architecture);
+    }
+}
+
+/// Offers to finish an installation that a previous run didn't get to complete
+async fn resume_interrupted_install(client: &Client) {
+    if let Err(e) = client.resume_interrupted_install().await {
+        exit_if_cancelled(e.as_ref(), &tr!("not-done-install"));
+        eprintln!("{}", tr!("install-resume-failed", error = e.to_string()));
+    }
+}
+
+/// Builds the search candidate for installing [target] of product [name], resolving the
+/// `nightly`/`latest` aliases to a concrete build on the repositories first.
+/// None if the product/flavor can't be found. Exits if an alias doesn't resolve
+async fn search_candidate_for_target(
+    client: &Client,
+    name: &str,
+    target: &Target,
+    flavor: Option<&str>,
+) -> Option<SearchCandidate> {
+    /* nightly/latest resolve to a concrete build first */
+    let resolved = match target {
+        Target::Nightly | Target::Latest => {
+            let Some(search) =
+                SearchCandidate::new(name, None, None, flavor, &client.config.products)
+            else {
+                eprintln!("{}", tr!("install-no-candidate"));
+                exit(1)
+            };
+            let (branch, since) = match target {
+                Target::Nightly => (
+                    Product::from_name(name, &client.config.products)
+                        .map(|p| p.nightly_branch().to_owned()),
+                    Some(SystemTime::now() - cli::NIGHTLY_WINDOW),
+                ),
+                _ => (None, None),
+            };
+            match client
+                .find_newest_build(&search, branch.as_deref(), since)
+                .await
+            {
+                Ok(Some(found)) => {
+                    println!(
+                        "{}",
+                        tr!(
+                            "install-resolved",
+                            target = target.to_string(),
+                            identifier = found.identifier.as_str(),
+                            version = found.version.to_string()
+                        )
+                    );
+                    Some((found.version.to_string(), found.identifier))
+                }
+                Ok(None) => {
+                    match &branch {
+                        Some(branch) => eprintln!(
+                            "{}",
+                            tr!(
+                                "install-no-nightly",
+                                product = name,
+                                branch = branch.as_str(),
+                                hours = cli::NIGHTLY_WINDOW.as_secs() / 3600
+                            )
+                        ),
+                        None => eprintln!("{}", tr!("install-no-build", product = name)),
+                    }
+                    exit(1)
+                }
+                Err(e) => {
+                    eprintln!(
+                        "{}",
+                        tr!(
+                            "install-resolve-failed",
+                            target = target.to_string(),
+                            error = e.to_string()
+                        )
+                    );
+                    exit(1)
+                }
+            }
+        }
+        _ => None,
+    };
+
+    SearchCandidate::new(
+        name,
+        match (target, &resolved) {
+            (_, Some((version, _))) => Some(version.as_str()),
+            (Target::Version(x), _) => Some(x.as_str()),
+            _ => None,
+        },
+        match (target, &resolved) {
+            (_, Some((_, identifier))) => Some(identifier.as_str()),
+            (Target::Identifier(x), _) => Some(x.as_str()),
+            _ => None,
+        },
+        flavor,
+        &client.config.products,
+    )
+}
@@ -0,0 +1,86 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// A set of installed products, as written by `export` and read by `install --manifest`,
+/// so a machine's exact environment can be reproduced on another one
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Manifest {
+    #[serde(rename = "Products")]
+    pub products: Vec<ManifestEntry>,
+}
+
+/// A single installed product in a [Manifest]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ManifestEntry {
+    #[serde(rename = "Product")]
+    pub product_name: String,
+
+    #[serde(rename = "Version")]
+    pub version: String,
+
+    /// Flavor id, if it could be determined. Defaults to the platform's default flavor when installing
+    #[serde(rename = "Flavor", default, skip_serializing_if = "Option::is_none")]
+    pub flavor: Option<String>,
+
+    /// Branch/tag the build came from, if known
+    #[serde(rename = "Branch", default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+
+    /// Name of the repository the build came from, if known. Informational only
+    #[serde(
+        rename = "Repository",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub repository: Option<String>,
+}
+
+impl Manifest {
+    /// Reads a manifest from [path]. Accepts plain json as well as json5
+    pub fn load<P>(path: P) -> Result<Self, Box<dyn std::error::Error>>
+    where
+        P: AsRef<Path>,
+    {
+        let s = std::fs::read_to_string(path)?;
+        let manifest: Manifest = json5::from_str(&s)?;
+        Ok(manifest)
+    }
+
+    pub fn to_json(&self) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Manifest, ManifestEntry};
+
+    #[test]
+    fn manifest_round_trip() {
+        let manifest = Manifest {
+            products: vec![
+                ManifestEntry {
+                    product_name: "HubKit".into(),
+                    version: "5.2.1-7049".into(),
+                    flavor: Some("WindowsHubKit".into()),
+                    branch: Some("develop".into()),
+                    repository: Some("Gravio TeamCity".into()),
+                },
+                ManifestEntry {
+                    product_name: "GravioStudio".into(),
+                    version: "5.2.4683".into(),
+                    flavor: None,
+                    branch: None,
+                    repository: None,
+                },
+            ],
+        };
+        let json = manifest.to_json().unwrap();
+        assert!(json.contains("\"Product\": \"HubKit\""));
+        assert!(!json.contains("\"Branch\": null"));
+
+        let parsed: Manifest = json5::from_str(&json).unwrap();
+        assert_eq!(parsed, manifest);
+    }
+}
@@ -0,0 +1,136 @@
+use std::path::{Path, PathBuf};
+
+use crate::ClientConfig;
+
+/// Login of a `.netrc` entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetrcLogin {
+    pub login: String,
+    pub password: Option<String>,
+}
+
+/// Location of the user's netrc file: `$NETRC` if set, otherwise `~/.netrc`,
+/// or `~/_netrc` on Windows when there's no `.netrc`
+pub fn netrc_path() -> PathBuf {
+    if let Ok(path) = std::env::var("NETRC") {
+        return PathBuf::from(path);
+    }
+    let dotted = PathBuf::from(ClientConfig::shell_expand("~/.netrc"));
+    if cfg!(windows) && !dotted.exists() {
+        return PathBuf::from(ClientConfig::shell_expand("~/_netrc"));
+    }
+    dotted
+}
+
+/// An entry of a netrc file, `machine` is None for the `default` entry
+#[derive(Debug, Default)]
+struct NetrcEntry {
+    machine: Option<String>,
+    login: Option<String>,
+    password: Option<String>,
+}
+
+fn parse_entries(contents: &str) -> Vec<NetrcEntry> {
+    let mut tokens = Vec::new();
+    let mut in_macro = false;
+    for line in contents.lines() {
+        /* macro definitions run until the next empty line */
+        if in_macro {
+            in_macro = !line.trim().is_empty();
+            continue;
+        }
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        for word in line.split_whitespace() {
+            if word == "macdef" {
+                in_macro = true;
+                break;
+            }
+            tokens.push(word);
+        }
+    }
+
+    let mut entries: Vec<NetrcEntry> = Vec::new();
+    let mut iter = tokens.into_iter();
+    while let Some(token) = iter.next() {
+        match (token, entries.last_mut()) {
+            ("machine", _) => entries.push(NetrcEntry {
+                machine: iter.next().map(|x| x.to_owned()),
+                ..Default::default()
+            }),
+            ("default", _) => entries.push(NetrcEntry::default()),
+            ("login", Some(entry)) => entry.login = iter.next().map(|x| x.to_owned()),
+            ("password", Some(entry)) => entry.password = iter.next().map(|x| x.to_owned()),
+            ("account", _) => {
+                let _ = iter.next();
+            }
+            _ => {}
+        }
+    }
+    entries
+}
+
+/// Finds the login for [host] in the netrc [contents], falling back to the `default` entry.
+///
+/// Follows the usual netrc format: whitespace separated `machine`, `login`, `password` and `account`
+/// tokens, where a `macdef` runs until the next empty line
+pub fn find_login(contents: &str, host: &str) -> Option<NetrcLogin> {
+    let entries = parse_entries(contents);
+    let entry = entries
+        .iter()
+        .find(|x| {
+            x.machine
+                .as_ref()
+                .map(|m| m.eq_ignore_ascii_case(host))
+                .unwrap_or(false)
+        })
+        .or_else(|| entries.iter().find(|x| x.machine.is_none()))?;
+    Some(NetrcLogin {
+        login: entry.login.to_owned()?,
+        password: entry.password.to_owned(),
+    })
+}
+
+/// Reads the login for [host] from the netrc file at [path], if there is one
+pub fn login_for_host(path: &Path, host: &str) -> Option<NetrcLogin> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    find_login(&contents, host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_login, NetrcLogin};
+
+    #[test]
+    fn finds_machine_and_default() {
+        let contents = "# build servers
+machine github.com login octo password gh_token
+machine teamcity.example.com
+    login ci
+    password hunter2
+
+macdef init
+cd /pub
+machine teamcity.example.com login wrong password wrong
+
+default login anonymous password guest
+";
+        assert_eq!(
+            find_login(contents, "teamcity.example.com"),
+            Some(NetrcLogin {
+                login: "ci".into(),
+                password: Some("hunter2".into())
+            })
+        );
+        assert_eq!(
+            find_login(contents, "TeamCity.Example.com").map(|x| x.login),
+            Some("ci".into())
+        );
+        assert_eq!(
+            find_login(contents, "other.example.com").map(|x| x.login),
+            Some("anonymous".into())
+        );
+        assert_eq!(find_login("machine a login b", "c"), None);
+    }
+}
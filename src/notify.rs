@@ -0,0 +1,107 @@
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+
+use crate::team_city::TeamCityChange;
+
+/// Body of a webhook request. Slack and Microsoft Teams incoming webhooks both show the `text`
+#[derive(Serialize, Debug)]
+struct WebhookMessage<'a> {
+    text: &'a str,
+}
+
+/// Name of this machine, so a channel fed by several machines can tell them apart
+pub fn host_name() -> String {
+    if let Ok(name) = std::env::var("COMPUTERNAME") {
+        return name;
+    }
+    std::process::Command::new("hostname")
+        .output()
+        .ok()
+        .map(|x| String::from_utf8_lossy(&x.stdout).trim().to_owned())
+        .filter(|x| !x.is_empty())
+        .unwrap_or_else(|| "unknown host".to_owned())
+}
+
+pub fn install_succeeded_message(product_name: &str, version: &str, host: &str) -> String {
+    format!("{} {} was installed on {}", product_name, version, host)
+}
+
+pub fn install_failed_message(
+    product_name: &str,
+    version: &str,
+    host: &str,
+    error: &str,
+) -> String {
+    format!(
+        "Installing {} {} failed on {}: {}",
+        product_name, version, host, error
+    )
+}
+
+/// The [changes] since [previous_version], one per line under a heading
+pub fn changelog(previous_version: &str, changes: &[TeamCityChange]) -> String {
+    let mut text = format!("{} changes since {}:", changes.len(), previous_version);
+    for change in changes {
+        text.push('\n');
+        text.push_str(&change.to_string());
+    }
+    text
+}
+
+/// POSTs [text] to the [webhook]. Failures are only logged, a notification never fails what it reports on.
+/// The url itself isn't logged, as webhook urls carry their secret in the path
+pub async fn post(http_client: &reqwest::Client, webhook: &str, text: &str) {
+    let body = match serde_json::to_string(&WebhookMessage { text }) {
+        Ok(b) => b,
+        Err(e) => {
+            log::warn!("Failed to create notification: {}", e);
+            return;
+        }
+    };
+    log::debug!("Sending notification: {}", text);
+    match http_client
+        .post(webhook)
+        .header(CONTENT_TYPE, "application/json")
+        .body(body)
+        .send()
+        .await
+    {
+        Ok(response) if response.status().is_success() => {}
+        Ok(response) => log::warn!(
+            "Notification webhook responded with status code {}",
+            response.status()
+        ),
+        Err(e) => log::warn!("Failed to send notification: {}", e.without_url()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{changelog, install_failed_message, WebhookMessage};
+    use crate::team_city::TeamCityChange;
+
+    #[test]
+    fn webhook_body_is_slack_and_teams_text() {
+        let text = install_failed_message("HubKit", "5.2.1-7060", "LAB-3", "exit code 1603");
+        assert_eq!(
+            text,
+            "Installing HubKit 5.2.1-7060 failed on LAB-3: exit code 1603"
+        );
+        assert_eq!(
+            serde_json::to_string(&WebhookMessage { text: &text }).unwrap(),
+            r#"{"text":"Installing HubKit 5.2.1-7060 failed on LAB-3: exit code 1603"}"#
+        );
+    }
+
+    #[test]
+    fn changelog_lists_changes_under_heading() {
+        let change: TeamCityChange = serde_json::from_str(
+            r#"{"id":"42","version":"0123456789abcdef","username":"nf","comment":"Fix the dongle pairing\n\nDetails"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            changelog("5.2.1-7049", &[change]),
+            "1 changes since 5.2.1-7049:\n0123456789  nf                Fix the dongle pairing"
+        );
+    }
+}
@@ -0,0 +1,365 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    client_config::{CandidateRepository, ClientConfig},
+    gman_error::GManError,
+    RepositoryCredentials,
+};
+
+/// Keyring service the tokens are stored under, with the repository name as user
+const KEYRING_SERVICE: &str = "gman";
+
+/// Grant type of the device-code token requests, RFC 8628
+const DEVICE_CODE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// Tokens expiring within this many seconds are refreshed before use
+const EXPIRY_MARGIN_SECONDS: u64 = 60;
+
+/// Polling interval when the identity provider doesn't specify one
+const DEFAULT_POLL_INTERVAL_SECONDS: u64 = 5;
+
+/// Tokens of a repository, as kept in the keyring
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct StoredToken {
+    access_token: String,
+    refresh_token: Option<String>,
+    /// Unix time in seconds, None if the provider didn't say
+    expires_at: Option<u64>,
+}
+
+impl StoredToken {
+    fn from_response(response: TokenResponse, now: u64) -> Self {
+        Self {
+            access_token: response.access_token,
+            refresh_token: response.refresh_token,
+            expires_at: response.expires_in.map(|x| now + x),
+        }
+    }
+
+    fn is_expired(&self, now: u64) -> bool {
+        self.expires_at
+            .map(|x| x <= now + EXPIRY_MARGIN_SECONDS)
+            .unwrap_or(false)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct DeviceAuthorizationResponse {
+    device_code: String,
+    user_code: String,
+    #[serde(alias = "verification_url")]
+    verification_uri: String,
+    expires_in: u64,
+    interval: Option<u64>,
+    /// Ready-made instructions, Azure AD sends these
+    message: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_in: Option<u64>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenErrorResponse {
+    error: String,
+    error_description: Option<String>,
+}
+
+impl std::fmt::Display for TokenErrorResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.error_description {
+            Some(description) => write!(f, "{}: {}", self.error, description),
+            None => write!(f, "{}", self.error),
+        }
+    }
+}
+
+/// The OAuth settings of a repository
+struct OAuthSettings<'a> {
+    client_id: &'a str,
+    device_authorization_url: &'a str,
+    token_url: &'a str,
+    scope: Option<&'a str>,
+}
+
+impl<'a> OAuthSettings<'a> {
+    fn of(repo: &'a CandidateRepository) -> Option<Self> {
+        match &repo.repository_credentials {
+            Some(RepositoryCredentials::OAuth {
+                client_id,
+                device_authorization_url,
+                token_url,
+                scope,
+                ..
+            }) => Some(Self {
+                client_id,
+                device_authorization_url,
+                token_url,
+                scope: scope.as_deref(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+fn find_repository<'a>(
+    config: &'a ClientConfig,
+    repo_name: &str,
+) -> Result<&'a CandidateRepository, GManError> {
+    config
+        .repositories
+        .iter()
+        .find(|x| x.name.eq_ignore_ascii_case(repo_name))
+        .ok_or_else(|| GManError::new(&format!("No repository named {}", repo_name)))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or_default()
+}
+
+fn keyring_entry(repo_name: &str) -> Result<keyring::Entry, keyring::Error> {
+    keyring::Entry::new(KEYRING_SERVICE, repo_name)
+}
+
+fn load_token(repo_name: &str) -> Option<StoredToken> {
+    let secret = match keyring_entry(repo_name).and_then(|x| x.get_password()) {
+        Ok(s) => s,
+        Err(keyring::Error::NoEntry) => return None,
+        Err(e) => {
+            log::warn!(
+                "Failed to read the token of {} from the keyring: {}",
+                repo_name,
+                e
+            );
+            return None;
+        }
+    };
+    serde_json::from_str(&secret).ok()
+}
+
+fn save_token(repo_name: &str, token: &StoredToken) -> Result<(), Box<dyn std::error::Error>> {
+    keyring_entry(repo_name)?.set_password(&serde_json::to_string(token)?)?;
+    Ok(())
+}
+
+/// Sends a form to the [token_url], telling token errors (e.g., `authorization_pending`) apart from failed requests
+async fn request_token(
+    http_client: &reqwest::Client,
+    token_url: &str,
+    form: &[(&str, &str)],
+) -> Result<Result<TokenResponse, TokenErrorResponse>, Box<dyn std::error::Error>> {
+    let response = http_client.post(token_url).form(form).send().await?;
+    let status = response.status();
+    let body = response.text().await?;
+    if status.is_success() {
+        return Ok(Ok(serde_json::from_str(&body)?));
+    }
+    match serde_json::from_str::<TokenErrorResponse>(&body) {
+        Ok(e) => Ok(Err(e)),
+        Err(_) => Err(Box::new(GManError::new(&format!(
+            "Token request failed with status {}",
+            status
+        )))),
+    }
+}
+
+/// Signs in to the OAuth repository [repo_name] with the device-code flow: the user opens the shown
+/// address on any device and enters the code, while gman waits for the identity provider to hand out
+/// the tokens. The tokens are kept in the system keyring
+pub async fn device_login(
+    http_client: &reqwest::Client,
+    config: &ClientConfig,
+    repo_name: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = find_repository(config, repo_name)?;
+    let settings = OAuthSettings::of(repo).ok_or_else(|| {
+        GManError::new(&format!(
+            "Repository {} has no OAuth RepositoryCredentials",
+            repo.name
+        ))
+    })?;
+
+    let mut form = vec![("client_id", settings.client_id)];
+    if let Some(scope) = settings.scope {
+        form.push(("scope", scope));
+    }
+    let response = http_client
+        .post(settings.device_authorization_url)
+        .form(&form)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Err(Box::new(GManError::new(&format!(
+            "Device authorization failed with status {}",
+            response.status()
+        ))));
+    }
+    let authorization: DeviceAuthorizationResponse = serde_json::from_str(&response.text().await?)?;
+
+    match &authorization.message {
+        Some(message) => println!("{}", message),
+        None => println!(
+            "To sign in, open {} and enter the code {}",
+            authorization.verification_uri, authorization.user_code
+        ),
+    }
+
+    let deadline = unix_now() + authorization.expires_in;
+    let mut interval = authorization
+        .interval
+        .unwrap_or(DEFAULT_POLL_INTERVAL_SECONDS);
+    let form = [
+        ("grant_type", DEVICE_CODE_GRANT_TYPE),
+        ("client_id", settings.client_id),
+        ("device_code", authorization.device_code.as_str()),
+    ];
+    loop {
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+        if unix_now() > deadline {
+            return Err(Box::new(GManError::new(
+                "The code expired before signing in, try again",
+            )));
+        }
+        match request_token(http_client, settings.token_url, &form).await? {
+            Ok(token) => {
+                save_token(&repo.name, &StoredToken::from_response(token, unix_now()))?;
+                return Ok(());
+            }
+            Err(e) if e.error == "authorization_pending" => {}
+            Err(e) if e.error == "slow_down" => interval += DEFAULT_POLL_INTERVAL_SECONDS,
+            Err(e) => return Err(Box::new(GManError::new(&format!("Sign in failed, {}", e)))),
+        }
+    }
+}
+
+/// Forgets the tokens of the repository [repo_name]
+pub fn logout(config: &ClientConfig, repo_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = find_repository(config, repo_name)?;
+    match keyring_entry(&repo.name)?.delete_credential() {
+        Ok(_) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+/// Exchanges the [refresh_token] for new tokens. Providers that don't rotate refresh tokens keep the old one
+async fn refresh(
+    http_client: &reqwest::Client,
+    settings: &OAuthSettings<'_>,
+    refresh_token: &str,
+) -> Result<StoredToken, Box<dyn std::error::Error>> {
+    let mut form = vec![
+        ("grant_type", "refresh_token"),
+        ("client_id", settings.client_id),
+        ("refresh_token", refresh_token),
+    ];
+    if let Some(scope) = settings.scope {
+        form.push(("scope", scope));
+    }
+    match request_token(http_client, settings.token_url, &form).await? {
+        Ok(response) => {
+            let mut token = StoredToken::from_response(response, unix_now());
+            if token.refresh_token.is_none() {
+                token.refresh_token = Some(refresh_token.to_owned());
+            }
+            Ok(token)
+        }
+        Err(e) => Err(Box::new(GManError::new(&format!(
+            "Refreshing the token failed, {}",
+            e
+        )))),
+    }
+}
+
+/// Hands the OAuth repositories of [config] their access token from the keyring, refreshing expired
+/// ones first. Repositories without a usable token are left without, and warned about
+pub async fn load_access_tokens(http_client: &reqwest::Client, config: &mut ClientConfig) {
+    for repo in config.repositories.iter_mut() {
+        let Some(settings) = OAuthSettings::of(repo) else {
+            continue;
+        };
+        let Some(mut token) = load_token(&repo.name) else {
+            log::warn!(
+                "Not signed in to repository {}, run `gman auth login --oauth {}`",
+                repo.name,
+                repo.name
+            );
+            continue;
+        };
+
+        if token.is_expired(unix_now()) {
+            log::debug!("Refreshing the OAuth token of repository {}", repo.name);
+            let refreshed = match &token.refresh_token {
+                Some(refresh_token) => refresh(http_client, &settings, refresh_token).await,
+                None => Err("no refresh token".into()),
+            };
+            match refreshed {
+                Ok(t) => {
+                    if let Err(e) = save_token(&repo.name, &t) {
+                        log::warn!(
+                            "Failed to store the refreshed token of {}: {}",
+                            repo.name,
+                            e
+                        );
+                    }
+                    token = t;
+                }
+                Err(e) => {
+                    log::warn!(
+                        "The sign in to repository {} expired ({}), run `gman auth login --oauth {}`",
+                        repo.name,
+                        e,
+                        repo.name
+                    );
+                    continue;
+                }
+            }
+        }
+
+        if let Some(RepositoryCredentials::OAuth { access_token, .. }) =
+            &mut repo.repository_credentials
+        {
+            *access_token = Some(token.access_token);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DeviceAuthorizationResponse, StoredToken, TokenErrorResponse, TokenResponse};
+
+    #[test]
+    fn device_flow_responses() {
+        let authorization: DeviceAuthorizationResponse = serde_json::from_str(
+            r#"{"device_code":"dc","user_code":"ABCD-EFGH","verification_url":"https://example.com/device","expires_in":900}"#,
+        )
+        .unwrap();
+        assert_eq!(authorization.verification_uri, "https://example.com/device");
+        assert_eq!(authorization.interval, None);
+
+        let error: TokenErrorResponse =
+            serde_json::from_str(r#"{"error":"authorization_pending"}"#).unwrap();
+        assert_eq!(error.to_string(), "authorization_pending");
+
+        let response: TokenResponse = serde_json::from_str(
+            r#"{"access_token":"at","refresh_token":"rt","expires_in":3600,"token_type":"Bearer"}"#,
+        )
+        .unwrap();
+        let token = StoredToken::from_response(response, 1000);
+        assert_eq!(token.expires_at, Some(4600));
+        assert!(!token.is_expired(1000));
+        assert!(token.is_expired(4550));
+        assert!(!StoredToken {
+            expires_at: None,
+            ..token
+        }
+        .is_expired(u64::MAX / 2));
+    }
+}
@@ -0,0 +1,443 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+    time::Instant,
+};
+
+use serde::Serialize;
+
+use crate::{
+    candidate::{InstallationCandidate, InstallationResult, InstalledProduct},
+    client::{self, InstallReport, OutdatedProduct},
+};
+
+/// How commands report their outcome, see `--output`
+#[derive(Debug, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Messages meant for people
+    #[default]
+    Text,
+    /// A final result object on stdout for scripts, after the usual messages
+    Json,
+}
+
+/// Whether `install`, `uninstall` and `upgrade` end with a [CommandResult], see [OutputFormat::Json]
+static JSON: AtomicBool = AtomicBool::new(false);
+
+pub fn set_format(format: OutputFormat) {
+    JSON.store(format == OutputFormat::Json, Ordering::SeqCst);
+}
+
+pub fn is_json() -> bool {
+    JSON.load(Ordering::SeqCst)
+}
+
+/// How `list`, `installed` and `cache --list` print what they found, see `--format`
+#[derive(Debug, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+pub enum ListFormat {
+    /// A table meant for people
+    #[default]
+    Table,
+    /// A json array of records, without any other messages on stdout
+    Json,
+    /// Comma separated values with a header line, without any other messages on stdout
+    Csv,
+}
+
+static LIST_FORMAT: OnceLock<ListFormat> = OnceLock::new();
+
+pub fn set_list_format(format: ListFormat) {
+    let _ = LIST_FORMAT.set(format);
+}
+
+pub fn list_format() -> ListFormat {
+    LIST_FORMAT.get().copied().unwrap_or_default()
+}
+
+/// A row of a listing printed with `--format json` or `csv`
+pub trait Record: Serialize {
+    /// Names of the csv columns, the same as the json fields
+    const COLUMNS: &'static [&'static str];
+
+    /// The csv values, the json fields in the order of [Record::COLUMNS]. Nulls are left empty
+    fn values(&self) -> Vec<String> {
+        let fields = match serde_json::to_value(self) {
+            Ok(serde_json::Value::Object(fields)) => fields,
+            _ => serde_json::Map::new(),
+        };
+        Self::COLUMNS
+            .iter()
+            .map(|x| match fields.get(*x) {
+                None | Some(serde_json::Value::Null) => String::new(),
+                Some(serde_json::Value::String(value)) => value.to_owned(),
+                Some(value) => value.to_string(),
+            })
+            .collect()
+    }
+}
+
+/// A build of `list` or `cache --list`, with every field of the [InstallationCandidate]
+#[derive(Debug, Serialize)]
+pub struct CandidateRecord {
+    pub product_name: String,
+    pub version: String,
+    pub identifier: String,
+    pub flavor: String,
+    pub platform: String,
+    pub package_type: String,
+    pub architecture: Option<String>,
+    pub installed: bool,
+    pub expired: bool,
+    /// Pinned in the cache, see `cache pin`
+    pub pinned: bool,
+    /// Found in the cache rather than on a repository
+    pub cached: bool,
+    /// Download size in bytes, if it's known
+    pub size: Option<u64>,
+    pub remote_id: String,
+    pub repo_location: String,
+}
+
+impl CandidateRecord {
+    /// The record of [candidate], with the label [Client::list_cache_with_pins] or
+    /// [Client::list_cached_candidates] put after its identifier taken off as [CandidateRecord::pinned] or
+    /// [CandidateRecord::cached]
+    pub fn new(candidate: &InstallationCandidate, size: Option<u64>) -> Self {
+        let mut identifier = candidate.identifier.as_str();
+        let pinned = match identifier.strip_suffix(client::PINNED_LABEL) {
+            Some(x) => {
+                identifier = x;
+                true
+            }
+            None => false,
+        };
+        let cached = match identifier.strip_suffix(client::CACHED_LABEL) {
+            Some(x) => {
+                identifier = x;
+                true
+            }
+            None => false,
+        };
+        Self {
+            product_name: candidate.product_name.to_owned(),
+            version: candidate.version.to_string(),
+            identifier: identifier.to_owned(),
+            flavor: candidate.flavor.id.to_owned(),
+            platform: candidate.flavor.platform.to_string(),
+            package_type: format!("{:?}", candidate.flavor.package_type),
+            architecture: candidate.flavor.architecture.map(|x| x.to_string()),
+            installed: candidate.installed,
+            expired: candidate.expired,
+            pinned,
+            cached,
+            size,
+            remote_id: candidate.remote_id.to_owned(),
+            repo_location: candidate.repo_location.to_owned(),
+        }
+    }
+}
+
+impl Record for CandidateRecord {
+    const COLUMNS: &'static [&'static str] = &[
+        "product_name",
+        "version",
+        "identifier",
+        "flavor",
+        "platform",
+        "package_type",
+        "architecture",
+        "installed",
+        "expired",
+        "pinned",
+        "cached",
+        "size",
+        "remote_id",
+        "repo_location",
+    ];
+}
+
+/// A product of `installed`, with every field of the [InstalledProduct]
+#[derive(Debug, Serialize)]
+pub struct InstalledRecord {
+    pub product_name: String,
+    pub version: String,
+    pub package_name: String,
+    pub package_type: String,
+    pub flavor: Option<String>,
+    pub path: PathBuf,
+    pub scope: String,
+    /// Bytes the installation takes on disk, if it's known
+    pub size: Option<u64>,
+}
+
+impl InstalledRecord {
+    pub fn new(value: &InstalledProduct, size: Option<u64>) -> Self {
+        Self {
+            product_name: value.product_name.to_owned(),
+            version: value.version.to_string(),
+            package_name: value.package_name.to_owned(),
+            package_type: format!("{:?}", value.package_type),
+            flavor: value.flavor.clone(),
+            path: value.path.to_owned(),
+            scope: value.scope.to_string(),
+            size,
+        }
+    }
+}
+
+impl Record for InstalledRecord {
+    const COLUMNS: &'static [&'static str] = &[
+        "product_name",
+        "version",
+        "package_name",
+        "package_type",
+        "flavor",
+        "path",
+        "scope",
+        "size",
+    ];
+}
+
+/// A product of `list --outdated`, see [OutdatedProduct]
+#[derive(Debug, Serialize)]
+pub struct OutdatedRecord {
+    pub product_name: String,
+    pub flavor: String,
+    pub branch: String,
+    pub installed: String,
+    pub available: String,
+}
+
+impl OutdatedRecord {
+    pub fn new(value: &OutdatedProduct) -> Self {
+        Self {
+            product_name: value.product_name.to_owned(),
+            flavor: value.flavor.to_owned(),
+            branch: value.branch.to_owned(),
+            installed: value.installed.to_string(),
+            available: value.available.to_string(),
+        }
+    }
+}
+
+impl Record for OutdatedRecord {
+    const COLUMNS: &'static [&'static str] =
+        &["product_name", "flavor", "branch", "installed", "available"];
+}
+
+/// Quotes [value] for a csv field where it has to be
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+/// [records] as csv, a header line followed by a line per record
+fn to_csv<T: Record>(records: &[T]) -> String {
+    let mut csv = T::COLUMNS.join(",");
+    csv.push('\n');
+    for record in records {
+        let values = record
+            .values()
+            .iter()
+            .map(|x| csv_field(x))
+            .collect::<Vec<_>>();
+        csv.push_str(&values.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Prints [records] in the [list_format] for scripts, as a json array or csv. Tables are printed by the
+/// commands themselves
+pub fn print_records<T: Record>(records: &[T]) {
+    match list_format() {
+        ListFormat::Table => {}
+        ListFormat::Json => match serde_json::to_string_pretty(records) {
+            Ok(json) => println!("{}", json),
+            Err(e) => log::error!("Failed to serialize the listing: {}", e),
+        },
+        ListFormat::Csv => print!("{}", to_csv(records)),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResultStatus {
+    Succeeded,
+    Skipped,
+    Canceled,
+    Failed,
+}
+
+/// Outcome of an `install`, `uninstall` or `upgrade`, printed as one line of json with `--output json` so
+/// orchestration scripts don't have to parse the messages. Fields that don't apply are null
+#[derive(Debug, Serialize)]
+pub struct CommandResult {
+    pub status: ResultStatus,
+    pub product: String,
+    /// Version installed
+    pub version: Option<String>,
+    /// Version replaced by an install, or removed by an uninstall
+    pub previous_version: Option<String>,
+    /// Artifact installed from
+    pub artifact_path: Option<PathBuf>,
+    /// Bytes the installation takes on disk
+    pub installed_size: Option<u64>,
+    pub duration_ms: u64,
+    pub error: Option<String>,
+}
+
+impl CommandResult {
+    /// [status] of the command on [product] started at [started]
+    pub fn new(status: ResultStatus, product: &str, started: Instant) -> Self {
+        Self {
+            status,
+            product: product.to_owned(),
+            version: None,
+            previous_version: None,
+            artifact_path: None,
+            installed_size: None,
+            duration_ms: started.elapsed().as_millis() as u64,
+            error: None,
+        }
+    }
+
+    /// The command on [product] failed with [error]
+    pub fn failed(product: &str, started: Instant, error: &str) -> Self {
+        Self {
+            error: Some(error.to_owned()),
+            ..Self::new(ResultStatus::Failed, product, started)
+        }
+    }
+
+    /// Outcome of installing [product], with what was installed if it succeeded
+    pub fn install(
+        product: &str,
+        started: Instant,
+        result: &Result<(InstallationResult, Option<InstallReport>), Box<dyn std::error::Error>>,
+    ) -> Self {
+        let (result, report) = match result {
+            Ok(x) => x,
+            Err(e) => return Self::failed(product, started, &e.to_string()),
+        };
+        let status = match result {
+            InstallationResult::Succeeded => ResultStatus::Succeeded,
+            InstallationResult::Skipped => ResultStatus::Skipped,
+            InstallationResult::Canceled => ResultStatus::Canceled,
+        };
+        let mut output = Self::new(status, product, started);
+        if let Some(report) = report {
+            output.version = Some(report.version.to_string());
+            output.previous_version = report.previous_version.as_ref().map(|x| x.to_string());
+            output.artifact_path = Some(report.artifact.to_owned());
+            output.installed_size = report.size;
+        }
+        output
+    }
+
+    /// Prints this as the final line of stdout, with `--output json`
+    pub fn print(&self) {
+        if is_json() {
+            match serde_json::to_string(self) {
+                Ok(json) => println!("{}", json),
+                Err(e) => log::error!("Failed to serialize the result: {}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use crate::{
+        candidate::{InstallationCandidate, Version},
+        gman_error::GManError,
+        product::Flavor,
+    };
+
+    use super::{to_csv, CandidateRecord, CommandResult, InstalledRecord, Record, ResultStatus};
+
+    #[test]
+    fn results_serialize_with_nulls_for_what_doesnt_apply() {
+        let mut result = CommandResult::new(ResultStatus::Succeeded, "HubKit", Instant::now());
+        result.version = Some("5.2.1-7049".into());
+        result.previous_version = Some("5.2.1-7001".into());
+        result.artifact_path = Some("/home/dev/.cache/gman/GravioHubKit.msi".into());
+        result.installed_size = Some(536870912);
+        result.duration_ms = 1500;
+        assert_eq!(
+            serde_json::to_string(&result).unwrap(),
+            r#"{"status":"succeeded","product":"HubKit","version":"5.2.1-7049","previous_version":"5.2.1-7001","artifact_path":"/home/dev/.cache/gman/GravioHubKit.msi","installed_size":536870912,"duration_ms":1500,"error":null}"#
+        );
+
+        let failed = CommandResult::install(
+            "HubKit",
+            Instant::now(),
+            &Err(Box::new(GManError::new("No item found"))),
+        );
+        let json: serde_json::Value = serde_json::to_value(&failed).unwrap();
+        assert_eq!(json["status"], "failed");
+        assert_eq!(json["error"], "No item found");
+        assert!(json["version"].is_null());
+        assert!(json["artifact_path"].is_null());
+    }
+
+    #[test]
+    fn records_as_csv_are_quoted_where_needed() {
+        let record = InstalledRecord {
+            product_name: "HubKit".into(),
+            version: "5.2.1-7049".into(),
+            package_name: "Gravio HubKit, \"Beta\"".into(),
+            package_type: "Msi".into(),
+            flavor: None,
+            path: "C:\\Program Files\\HubKit".into(),
+            scope: "Machine".into(),
+            size: Some(536870912),
+        };
+        assert_eq!(
+            to_csv(&[record]),
+            "product_name,version,package_name,package_type,flavor,path,scope,size\n\
+             HubKit,5.2.1-7049,\"Gravio HubKit, \"\"Beta\"\"\",Msi,,C:\\Program Files\\HubKit,Machine,536870912\n"
+        );
+    }
+
+    #[test]
+    fn candidate_records_have_the_labels_as_fields() {
+        let candidate = InstallationCandidate {
+            remote_id: "123460".into(),
+            repo_location: String::default(),
+            product_name: "HubKit".into(),
+            version: Version::new("5.2.1-7060"),
+            identifier: "develop (pinned)".into(),
+            flavor: Flavor::empty(),
+            installed: false,
+            expired: false,
+        };
+        let record = CandidateRecord::new(&candidate, Some(1024));
+        assert_eq!(record.identifier, "develop");
+        assert!(record.pinned);
+        assert!(!record.cached);
+
+        let values = record.values();
+        assert_eq!(values.len(), CandidateRecord::COLUMNS.len());
+        let value = |column: &str| {
+            let i = CandidateRecord::COLUMNS
+                .iter()
+                .position(|x| *x == column)
+                .unwrap();
+            values[i].as_str()
+        };
+        assert_eq!(value("identifier"), "develop");
+        assert_eq!(value("pinned"), "true");
+        assert_eq!(value("cached"), "false");
+        assert_eq!(value("size"), "1024");
+        assert_eq!(value("architecture"), "");
+    }
+}
@@ -99,3 +99,121 @@ impl Platform {
         None
     }
 }
+
+/// Processor architecture an artifact is built for, for flavors publishing one per architecture, see `--arch`
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub(crate) enum Architecture {
+    X64,
+    Arm64,
+}
+
+impl<'de> Deserialize<'de> for Architecture {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        Architecture::from_str(&value).map_err(|_| {
+            serde::de::Error::invalid_value(
+                serde::de::Unexpected::Str(&value),
+                &"one of {x64, arm64}",
+            )
+        })
+    }
+}
+
+impl Serialize for Architecture {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl FromStr for Architecture {
+    type Err = GManError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_lowercase();
+        match s.as_str() {
+            "x64" | "amd64" | "x86_64" => Ok(Self::X64),
+            "arm64" | "aarch64" => Ok(Self::Arm64),
+            _ => Err(GManError::new("Not a valid Architecture string")),
+        }
+    }
+}
+
+impl Architecture {
+    /// Architecture of the machine itself. Everything but IsWow64Process2 tells an x64 gman emulated on ARM64
+    /// Windows that it runs on x64, so that's asked first. None on machines of other architectures
+    pub fn of_machine() -> Option<Self> {
+        #[cfg(target_os = "windows")]
+        {
+            if let Some(native) = windows_native_machine() {
+                return Some(native);
+            }
+        }
+        match std::env::consts::ARCH {
+            "x86_64" => Some(Architecture::X64),
+            "aarch64" => Some(Architecture::Arm64),
+            _ => None,
+        }
+    }
+}
+
+/// The native architecture of this machine according to IsWow64Process2, which is looked up when it's needed
+/// since Windows before 10 1709 doesn't have it
+#[cfg(target_os = "windows")]
+fn windows_native_machine() -> Option<Architecture> {
+    use std::ffi::c_void;
+
+    const IMAGE_FILE_MACHINE_AMD64: u16 = 0x8664;
+    const IMAGE_FILE_MACHINE_ARM64: u16 = 0xAA64;
+
+    type IsWow64Process2 = unsafe extern "system" fn(*mut c_void, *mut u16, *mut u16) -> i32;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetModuleHandleA(module_name: *const u8) -> *mut c_void;
+        fn GetProcAddress(module: *mut c_void, proc_name: *const u8) -> *mut c_void;
+        fn GetCurrentProcess() -> *mut c_void;
+    }
+
+    let mut process_machine: u16 = 0;
+    let mut native_machine: u16 = 0;
+    /* the names are nul terminated, and the address found is that of IsWow64Process2 */
+    let succeeded = unsafe {
+        let kernel32 = GetModuleHandleA(b"kernel32.dll\0".as_ptr());
+        if kernel32.is_null() {
+            return None;
+        }
+        let address = GetProcAddress(kernel32, b"IsWow64Process2\0".as_ptr());
+        if address.is_null() {
+            return None;
+        }
+        let is_wow64_process2: IsWow64Process2 = std::mem::transmute(address);
+        is_wow64_process2(
+            GetCurrentProcess(),
+            &mut process_machine,
+            &mut native_machine,
+        )
+    };
+    if succeeded == 0 {
+        return None;
+    }
+    match native_machine {
+        IMAGE_FILE_MACHINE_AMD64 => Some(Architecture::X64),
+        IMAGE_FILE_MACHINE_ARM64 => Some(Architecture::Arm64),
+        _ => None,
+    }
+}
+
+impl Display for Architecture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Architecture::X64 => "x64",
+            Architecture::Arm64 => "arm64",
+        })
+    }
+}
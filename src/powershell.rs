@@ -0,0 +1,378 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+
+use crate::{
+    app,
+    gman_error::GManError,
+    system_runner::{CommandOutput, SystemRunner},
+};
+
+/// Prints the execution policy of every scope, by precedence, as `Scope=Policy` lines
+pub const EXECUTION_POLICY_COMMAND: &str =
+    "Get-ExecutionPolicy -List | ForEach-Object { \"$($_.Scope)=$($_.ExecutionPolicy)\" }";
+
+/// Language mode gman's PowerShell needs. The others block the .NET types and methods it uses to find,
+/// install and launch products
+const FULL_LANGUAGE_MODE: &str = "FullLanguage";
+
+/// Checks PowerShell can run what gman needs it for, before any of it is attempted.
+///
+/// ConstrainedLanguage mode, usually enforced by AppLocker or WDAC, breaks every Windows code path and can't
+/// be worked around from here, so it fails with an explanation. An execution policy that blocks scripts only
+/// breaks AppX install scripts, so it's warned about along with `--execution-policy-bypass`
+pub fn check(runner: &dyn SystemRunner) -> Result<(), Box<dyn std::error::Error>> {
+    let command = format!(
+        "$ExecutionContext.SessionState.LanguageMode.ToString(); {}",
+        EXECUTION_POLICY_COMMAND
+    );
+    let output = match PowerShellScript::new(&command).capture(runner) {
+        Ok(x) => x,
+        Err(e) => {
+            log::warn!("Failed to check the PowerShell environment: {}", e);
+            return Ok(());
+        }
+    };
+    let (language_mode, policies) = output.split_once('\n').unwrap_or((&output, ""));
+    let language_mode = language_mode.trim();
+    if language_mode != FULL_LANGUAGE_MODE {
+        return Err(Box::new(GManError::new(&format!(
+            "PowerShell runs in {} mode on this machine, usually enforced by AppLocker or Windows Defender Application Control. \
+            gman needs {} mode to install, uninstall and launch products. Ask your administrator to allow it for gman, \
+            or pass --skip-prerequisites to try anyway",
+            language_mode, FULL_LANGUAGE_MODE
+        ))));
+    }
+    if let Some((scope, policy)) =
+        blocking_execution_policy(policies, app::is_bypassing_execution_policy())
+    {
+        if scope.ends_with("Policy") {
+            eprintln!(
+                "The PowerShell execution policy {} is set by group policy ({}), so AppX install scripts can't run, not even with --execution-policy-bypass",
+                policy, scope
+            );
+        } else {
+            eprintln!(
+                "The PowerShell execution policy {} ({}) keeps AppX install scripts from running. Pass --execution-policy-bypass to run PowerShell with -ExecutionPolicy Bypass",
+                policy, scope
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The scope and policy, among the output of [EXECUTION_POLICY_COMMAND] in [list], that keeps scripts from
+/// running. The scopes are listed by precedence, and the first one that's defined applies. PowerShell runs
+/// with `-ExecutionPolicy Bypass` when [bypassed], which only group policy overrides
+pub fn blocking_execution_policy(list: &str, bypassed: bool) -> Option<(String, String)> {
+    let effective = list
+        .lines()
+        .filter_map(|x| x.trim().split_once('='))
+        .filter(|(scope, _)| !bypassed || scope.ends_with("Policy"))
+        .find(|(_, policy)| !policy.eq_ignore_ascii_case("Undefined"))
+        .map(|(scope, policy)| (scope.to_owned(), policy.to_owned()));
+    match effective {
+        Some((scope, policy)) => {
+            let blocking = policy.eq_ignore_ascii_case("Restricted")
+                || (bypassed && policy.eq_ignore_ascii_case("AllSigned"));
+            blocking.then_some((scope, policy))
+        }
+        /* nothing set is Restricted on Windows clients */
+        None if !bypassed => Some(("Default".to_owned(), "Restricted".to_owned())),
+        None => None,
+    }
+}
+
+/// Value a [PowerShellScript] gets as a variable
+#[derive(Debug, Clone)]
+enum ScriptArgument {
+    One(String),
+    List(Vec<String>),
+}
+
+/// A PowerShell script and the values it works on, run without any of those values being parsed as PowerShell.
+///
+/// The script goes to PowerShell as `-EncodedCommand`, so nothing in it has to survive command line quoting.
+/// Each argument becomes a variable decoded from base64 in front of the script, so quotes (including the
+/// typographic ones PowerShell also accepts) and `$(...)` in a publisher id, a path or a regex stay data.
+/// The values travel inside the command rather than in the environment, which elevated processes don't inherit
+#[derive(Debug, Clone, Default)]
+pub struct PowerShellScript {
+    script: String,
+    arguments: Vec<(String, ScriptArgument)>,
+}
+
+impl PowerShellScript {
+    pub fn new(script: &str) -> Self {
+        Self {
+            script: script.to_owned(),
+            arguments: Vec::new(),
+        }
+    }
+
+    /// Makes [value] available to the script as `$name`
+    pub fn arg(mut self, name: &str, value: &str) -> Self {
+        self.arguments
+            .push((name.to_owned(), ScriptArgument::One(value.to_owned())));
+        self
+    }
+
+    /// Makes [values] available to the script as the array `$name`
+    pub fn arg_list(mut self, name: &str, values: &[String]) -> Self {
+        self.arguments
+            .push((name.to_owned(), ScriptArgument::List(values.to_vec())));
+        self
+    }
+
+    /// Appends the script of [other] as is, along with its arguments
+    pub fn append(mut self, other: PowerShellScript) -> Self {
+        self.script.push_str(&other.script);
+        self.arguments.extend(other.arguments);
+        self
+    }
+
+    /// The script as PowerShell runs it, with the statements setting its arguments in front
+    pub fn text(&self) -> String {
+        let decode = |x: &str| {
+            format!(
+                "[Text.Encoding]::UTF8.GetString([Convert]::FromBase64String('{}'))",
+                BASE64.encode(x.as_bytes())
+            )
+        };
+        let mut text = String::new();
+        for (name, value) in &self.arguments {
+            debug_assert!(name.chars().all(|x| x.is_ascii_alphanumeric()));
+            let value = match value {
+                ScriptArgument::One(x) => decode(x),
+                ScriptArgument::List(x) => format!(
+                    "@({})",
+                    x.iter()
+                        .map(|x| decode(x))
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ),
+            };
+            text.push_str(&format!("${} = {}\n", name, value));
+        }
+        text.push_str(&self.script);
+        text
+    }
+
+    /// Arguments for `powershell` running this script
+    pub fn command_line(&self) -> Vec<String> {
+        let utf16 = self
+            .text()
+            .encode_utf16()
+            .flat_map(|x| x.to_le_bytes())
+            .collect::<Vec<u8>>();
+        vec![
+            "-NoProfile".to_owned(),
+            "-EncodedCommand".to_owned(),
+            BASE64.encode(utf16),
+        ]
+    }
+
+    pub fn run(
+        &self,
+        runner: &dyn SystemRunner,
+    ) -> Result<CommandOutput, Box<dyn std::error::Error>> {
+        let args = self.command_line();
+        runner.run(
+            "powershell",
+            &args.iter().map(|x| x.as_str()).collect::<Vec<&str>>(),
+        )
+    }
+
+    pub fn run_elevated(
+        &self,
+        runner: &dyn SystemRunner,
+    ) -> Result<CommandOutput, Box<dyn std::error::Error>> {
+        let args = self.command_line();
+        runner.run_elevated(
+            "powershell",
+            &args.iter().map(|x| x.as_str()).collect::<Vec<&str>>(),
+        )
+    }
+
+    /// Runs the script and returns its stdout, or an error if it exited unsuccessfully
+    pub fn capture(&self, runner: &dyn SystemRunner) -> Result<String, Box<dyn std::error::Error>> {
+        let args = self.command_line();
+        runner.capture(
+            "powershell",
+            &args.iter().map(|x| x.as_str()).collect::<Vec<&str>>(),
+        )
+    }
+}
+
+/// A script run through [PowerShellScript], decoded from the arguments it was run with
+#[cfg(test)]
+#[derive(Debug)]
+pub struct DecodedScript {
+    /// The script without the statements setting its arguments
+    pub script: String,
+    pub arguments: std::collections::BTreeMap<String, Vec<String>>,
+}
+
+#[cfg(test)]
+impl DecodedScript {
+    pub fn from_args(args: &[String]) -> Self {
+        let encoded = args
+            .iter()
+            .skip_while(|x| *x != "-EncodedCommand")
+            .nth(1)
+            .expect("Expected an -EncodedCommand");
+        let utf16 = BASE64
+            .decode(encoded)
+            .unwrap()
+            .chunks(2)
+            .map(|x| u16::from_le_bytes([x[0], x[1]]))
+            .collect::<Vec<u16>>();
+        let text = String::from_utf16(&utf16).unwrap();
+        let values = regex::Regex::new(r"FromBase64String\('([^']*)'\)").unwrap();
+        let mut arguments = std::collections::BTreeMap::new();
+        let mut rest = text.as_str();
+        /* the arguments are set first, one per line */
+        while let Some((line, remaining)) = rest.split_once('\n') {
+            let Some((name, value)) = line
+                .strip_prefix('$')
+                .and_then(|x| x.split_once(" = "))
+                .filter(|(_, value)| {
+                    value.starts_with("[Text.Encoding]")
+                        || value.starts_with("@([Text.Encoding]")
+                        || *value == "@()"
+                })
+            else {
+                break;
+            };
+            let decoded = values
+                .captures_iter(value)
+                .map(|x| String::from_utf8(BASE64.decode(&x[1]).unwrap()).unwrap())
+                .collect::<Vec<String>>();
+            arguments.insert(name.to_owned(), decoded);
+            rest = remaining;
+        }
+        Self {
+            script: rest.to_owned(),
+            arguments,
+        }
+    }
+
+    /// The value of the argument [name]
+    pub fn arg(&self, name: &str) -> &str {
+        &self.arguments[name][0]
+    }
+}
+
+/// [args] for running [program], with `-ExecutionPolicy Bypass` in front if it's PowerShell and [bypass]
+/// is set, unless they give an execution policy already
+pub fn with_execution_policy<'a>(program: &str, args: &[&'a str], bypass: bool) -> Vec<&'a str> {
+    let mut result = Vec::with_capacity(args.len() + 2);
+    if bypass
+        && program.eq_ignore_ascii_case("powershell")
+        && !args
+            .iter()
+            .any(|x| x.eq_ignore_ascii_case("-ExecutionPolicy"))
+    {
+        result.extend_from_slice(&["-ExecutionPolicy", "Bypass"]);
+    }
+    result.extend_from_slice(args);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::system_runner::{CommandOutput, MockRunner};
+
+    use super::{
+        blocking_execution_policy, check, with_execution_policy, DecodedScript, PowerShellScript,
+    };
+
+    #[test]
+    fn arguments_are_never_parsed_as_powershell() {
+        let publisher = "CN=\"Asteria\"; $(Remove-Item C:\\) \u{2018}quoted\u{2019}";
+        let script =
+            PowerShellScript::new("Get-AppxPackage | Where-Object {$_.Publisher -in $Publishers}")
+                .arg_list(
+                    "Publishers",
+                    &[publisher.to_owned(), "CN=Infoteria".to_owned()],
+                )
+                .append(PowerShellScript::new("\nWrite-Output $Name").arg("Name", "it's"));
+
+        let text = script.text();
+        assert!(!text.contains("Asteria"));
+        assert!(!text.contains("it's"));
+
+        let args = script.command_line();
+        assert_eq!(args[..2], ["-NoProfile", "-EncodedCommand"]);
+        let decoded = DecodedScript::from_args(&args);
+        assert_eq!(
+            decoded.script,
+            "Get-AppxPackage | Where-Object {$_.Publisher -in $Publishers}\nWrite-Output $Name"
+        );
+        assert_eq!(
+            decoded.arguments["Publishers"],
+            vec![publisher, "CN=Infoteria"]
+        );
+        assert_eq!(decoded.arg("Name"), "it's");
+    }
+
+    #[test]
+    fn execution_policy_precedence() {
+        let list = "MachinePolicy=Undefined\nUserPolicy=Undefined\nProcess=Undefined\nCurrentUser=RemoteSigned\nLocalMachine=Restricted\n";
+        assert_eq!(blocking_execution_policy(list, false), None);
+        let list = "MachinePolicy=Undefined\nUserPolicy=Undefined\nProcess=Undefined\nCurrentUser=Undefined\nLocalMachine=Undefined\n";
+        assert_eq!(
+            blocking_execution_policy(list, false),
+            Some(("Default".to_owned(), "Restricted".to_owned()))
+        );
+        assert_eq!(blocking_execution_policy(list, true), None);
+        let list = "MachinePolicy=AllSigned\nUserPolicy=Undefined\nProcess=Undefined\nCurrentUser=Bypass\nLocalMachine=Undefined\n";
+        assert_eq!(
+            blocking_execution_policy(list, true),
+            Some(("MachinePolicy".to_owned(), "AllSigned".to_owned()))
+        );
+        assert_eq!(blocking_execution_policy(list, false), None);
+    }
+
+    #[test]
+    fn constrained_language_fails_the_check() {
+        let runner = MockRunner::new().respond(CommandOutput::ok(
+            "ConstrainedLanguage\r\nMachinePolicy=Undefined\r\nUserPolicy=Undefined\r\n",
+        ));
+        let err = check(&runner).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("PowerShell runs in ConstrainedLanguage mode"));
+
+        let runner = MockRunner::new().respond(CommandOutput::ok(
+            "FullLanguage\r\nMachinePolicy=Undefined\r\nUserPolicy=Undefined\r\nCurrentUser=RemoteSigned\r\n",
+        ));
+        assert!(check(&runner).is_ok());
+    }
+
+    #[test]
+    fn bypass_is_only_added_to_powershell() {
+        assert_eq!(
+            with_execution_policy("powershell", &["-Command", "Get-AppxPackage"], true),
+            vec!["-ExecutionPolicy", "Bypass", "-Command", "Get-AppxPackage"]
+        );
+        assert_eq!(
+            with_execution_policy("powershell", &["-Command", "Get-AppxPackage"], false),
+            vec!["-Command", "Get-AppxPackage"]
+        );
+        assert_eq!(
+            with_execution_policy(
+                "powershell",
+                &["-ExecutionPolicy", "Bypass", "-File", "Provision.ps1"],
+                true
+            ),
+            vec!["-ExecutionPolicy", "Bypass", "-File", "Provision.ps1"]
+        );
+        assert_eq!(
+            with_execution_policy(
+                "msiexec",
+                &["/x", "{6E3B9D62-1F3A-4C55-9A8E-3C1F0E2B7D41}"],
+                true
+            ),
+            vec!["/x", "{6E3B9D62-1F3A-4C55-9A8E-3C1F0E2B7D41}"]
+        );
+    }
+}
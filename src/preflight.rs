@@ -0,0 +1,325 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    app,
+    product::{Flavor, PackageType},
+    system_runner::SystemRunner,
+    util,
+};
+
+/// SID of the local Administrators group, listed by `whoami /groups` for its members even while UAC filters
+/// the token
+#[cfg(target_os = "windows")]
+const ADMINISTRATORS_SID: &str = "S-1-5-32-544";
+
+/// Something installing a product needs that isn't there, and what to do about it
+#[derive(Debug, PartialEq)]
+pub struct MissingPrerequisite {
+    pub problem: String,
+    pub remedy: String,
+}
+
+impl MissingPrerequisite {
+    fn new(problem: String, remedy: &str) -> Self {
+        Self {
+            problem,
+            remedy: remedy.to_owned(),
+        }
+    }
+}
+
+/// Checks everything installing [flavor] of [product_name] will need before anything is downloaded or
+/// uninstalled, so all that's missing is reported at once instead of the install failing at the first of it.
+/// [directories] are the cache and download directories gman writes to
+pub fn check(
+    runner: &dyn SystemRunner,
+    product_name: &str,
+    flavor: &Flavor,
+    directories: &[&Path],
+) -> Vec<MissingPrerequisite> {
+    let mut missing = Vec::new();
+    let mut gman_directories = directories
+        .iter()
+        .map(|x| x.to_path_buf())
+        .collect::<Vec<PathBuf>>();
+    gman_directories.push(app::get_app_temp_directory());
+    for directory in gman_directories {
+        if !util::is_writable_directory(&directory) {
+            missing.push(MissingPrerequisite::new(
+                format!("{} can't be written to", directory.display()),
+                "Give your account write access to it, or set CacheDirectory and TempDownloadDirectory in the configuration to folders it can write to",
+            ));
+        }
+    }
+    for directory in install_directories(product_name, flavor) {
+        if !util::is_writable_directory(&directory) {
+            missing.push(MissingPrerequisite::new(
+                format!(
+                    "{} is installed to {}, which can't be written to",
+                    product_name,
+                    directory.display()
+                ),
+                "Give your account write access to it",
+            ));
+        }
+    }
+    if needs_elevation(flavor) {
+        missing.extend(check_elevation(runner, product_name));
+    }
+    /* provisioning doesn't run the install script */
+    #[cfg(target_os = "windows")]
+    if flavor.package_type == PackageType::AppX && !app::is_provisioning_packages() {
+        missing.extend(check_execution_policy(runner, product_name));
+    }
+    if flavor.package_type == PackageType::Apk {
+        missing.extend(check_adb(runner));
+    }
+    missing
+}
+
+/// The report of everything [missing] for installing [product_name]
+pub fn report(product_name: &str, missing: &[MissingPrerequisite]) -> String {
+    let mut report = format!(
+        "Installing {} needs the following, which is missing:\n",
+        product_name
+    );
+    for prerequisite in missing {
+        report.push_str(&format!(
+            "  - {}\n    {}\n",
+            prerequisite.problem, prerequisite.remedy
+        ));
+    }
+    report
+}
+
+/// Folders the installer of [flavor] puts files into itself, rather than through an elevated installer
+fn install_directories(product_name: &str, flavor: &Flavor) -> Vec<PathBuf> {
+    #[cfg(target_os = "linux")]
+    if flavor.package_type == PackageType::StandaloneExe {
+        let mut directories = vec![crate::desktop::install_directory(product_name)];
+        directories.extend(
+            crate::desktop::bin_link(product_name)
+                .parent()
+                .map(|x| x.to_path_buf()),
+        );
+        return directories;
+    }
+    #[cfg(target_os = "macos")]
+    if flavor.package_type == PackageType::App {
+        return vec![PathBuf::from(crate::candidate::MAC_APPLICATIONS_DIR)];
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    let _ = (product_name, flavor);
+    Vec::new()
+}
+
+/// Whether installing [flavor] needs administrator or root privileges
+fn needs_elevation(flavor: &Flavor) -> bool {
+    match flavor.package_type {
+        /* msiexec asks for elevation itself, unless the package is installed per user */
+        PackageType::Msi => {
+            cfg!(target_os = "windows")
+                && !flavor
+                    .metadata
+                    .as_ref()
+                    .and_then(|x| x.msi_properties.as_ref())
+                    .is_some_and(|x| {
+                        x.iter().any(|(key, value)| {
+                            key.eq_ignore_ascii_case("MSIINSTALLPERUSER") && value == "1"
+                        })
+                    })
+        }
+        PackageType::AppX | PackageType::MsiX => {
+            cfg!(target_os = "windows") && app::is_provisioning_packages()
+        }
+        PackageType::Deb => cfg!(target_os = "linux"),
+        PackageType::Pkg => cfg!(target_os = "macos"),
+        _ => false,
+    }
+}
+
+/// Whether the current user can get administrator rights, via UAC
+#[cfg(target_os = "windows")]
+fn check_elevation(runner: &dyn SystemRunner, product_name: &str) -> Option<MissingPrerequisite> {
+    match runner.capture("whoami", &["/groups"]) {
+        Ok(groups) if groups.contains(ADMINISTRATORS_SID) => None,
+        Ok(_) => Some(MissingPrerequisite::new(
+            format!(
+                "Installing {} needs administrator rights, and {} isn't an administrator",
+                product_name,
+                util::current_user()
+            ),
+            "Run gman from an administrator account",
+        )),
+        Err(e) => {
+            log::debug!("Failed to check for administrator rights: {}", e);
+            None
+        }
+    }
+}
+
+/// Whether the current user is root, or can become it with sudo from here
+#[cfg(not(target_os = "windows"))]
+fn check_elevation(runner: &dyn SystemRunner, product_name: &str) -> Option<MissingPrerequisite> {
+    use std::io::IsTerminal;
+
+    if runner.capture("id", &["-u"]).is_ok_and(|x| x.trim() == "0") {
+        return None;
+    }
+    if app::is_sudo_disabled() {
+        return Some(MissingPrerequisite::new(
+            format!(
+                "Installing {} needs root privileges, and --no-sudo was given",
+                product_name
+            ),
+            "Run gman with sudo, or without --no-sudo",
+        ));
+    }
+    /* sudo can ask for the password itself */
+    if std::env::var_os("SUDO_ASKPASS").is_some() || std::io::stdin().is_terminal() {
+        return None;
+    }
+    match runner.run("sudo", &["-n", "true"]) {
+        Ok(output) if output.status.success() => None,
+        _ => Some(MissingPrerequisite::new(
+            format!(
+                "Installing {} needs root privileges, and sudo can't ask for a password here",
+                product_name
+            ),
+            "Set SUDO_ASKPASS, or run gman with sudo",
+        )),
+    }
+}
+
+/// Whether the PowerShell execution policy lets the install scripts of AppX packages run
+#[cfg(target_os = "windows")]
+fn check_execution_policy(
+    runner: &dyn SystemRunner,
+    product_name: &str,
+) -> Option<MissingPrerequisite> {
+    let list =
+        match crate::powershell::PowerShellScript::new(crate::powershell::EXECUTION_POLICY_COMMAND)
+            .capture(runner)
+        {
+            Ok(x) => x,
+            Err(e) => {
+                log::debug!("Failed to get the PowerShell execution policy: {}", e);
+                return None;
+            }
+        };
+    let (scope, policy) =
+        crate::powershell::blocking_execution_policy(&list, app::is_bypassing_execution_policy())?;
+    Some(MissingPrerequisite::new(
+        format!(
+            "The PowerShell execution policy {} ({}) doesn't let the install script of {} run",
+            policy, scope, product_name
+        ),
+        if scope.ends_with("Policy") {
+            "It's set by group policy, ask your administrator to allow scripts"
+        } else {
+            "Pass --execution-policy-bypass, or allow scripts for your account with `Set-ExecutionPolicy -Scope CurrentUser RemoteSigned`"
+        },
+    ))
+}
+
+/// Whether an Android device is connected that has authorized this computer for USB debugging
+fn check_adb(runner: &dyn SystemRunner) -> Vec<MissingPrerequisite> {
+    let output = match runner.capture("adb", &["devices"]) {
+        Ok(x) => x,
+        Err(e) => {
+            log::debug!("Failed to list Android devices: {}", e);
+            return vec![MissingPrerequisite::new(
+                "adb isn't available".to_owned(),
+                "Install the Android SDK platform tools, and put adb on the PATH",
+            )];
+        }
+    };
+    let devices = adb_devices(&output);
+    if devices.iter().any(|(_, state)| *state == "device") {
+        return Vec::new();
+    }
+    if devices.is_empty() {
+        return vec![MissingPrerequisite::new(
+            "No Android device is connected".to_owned(),
+            "Connect one with USB debugging enabled",
+        )];
+    }
+    devices
+        .iter()
+        .map(|(serial, state)| match *state {
+            "unauthorized" => MissingPrerequisite::new(
+                format!(
+                    "{} hasn't authorized this computer for USB debugging",
+                    serial
+                ),
+                "Unlock the device and allow USB debugging when it asks",
+            ),
+            state => MissingPrerequisite::new(
+                format!("{} is {}", serial, state),
+                "Reconnect it, or restart adb with `adb kill-server`",
+            ),
+        })
+        .collect()
+}
+
+/// Serials and states of the devices listed by `adb devices`
+fn adb_devices(output: &str) -> Vec<(&str, &str)> {
+    output
+        .lines()
+        .skip_while(|x| !x.starts_with("List of devices"))
+        .skip(1)
+        .filter_map(|x| x.split_once('\t'))
+        .map(|(serial, state)| (serial.trim(), state.trim()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::{
+        candidate::InstallationCandidate,
+        product::PackageType,
+        system_runner::{CommandOutput, MockRunner},
+    };
+
+    use super::{check, report};
+
+    #[test]
+    fn missing_prerequisites_are_reported_together() {
+        let mut flavor = InstallationCandidate::from_str(
+            "HubKit@Android@AndroidHubKit@develop@5.2.1-7049@GravioHubKit.apk",
+        )
+        .unwrap()
+        .flavor;
+        flavor.package_type = PackageType::Apk;
+
+        let blocked = std::env::temp_dir()
+            .join("gman_tests")
+            .join("preflight_file");
+        std::fs::create_dir_all(blocked.parent().unwrap()).unwrap();
+        std::fs::write(&blocked, "not a directory").unwrap();
+        let runner = MockRunner::new().respond(CommandOutput::ok(
+            "* daemon started successfully\nList of devices attached\nR58M12ABCDE\tunauthorized\nemulator-5554\toffline\n\n",
+        ));
+
+        let missing = check(&runner, "HubKit", &flavor, &[&blocked.join("cache")]);
+        assert_eq!(missing.len(), 3);
+        assert!(missing[0].problem.contains("preflight_file"));
+        assert_eq!(
+            missing[1].problem,
+            "R58M12ABCDE hasn't authorized this computer for USB debugging"
+        );
+        assert_eq!(missing[2].problem, "emulator-5554 is offline");
+        assert_eq!(runner.calls()[0].command_line(), "adb devices");
+
+        let report = report("HubKit", &missing);
+        assert!(report.starts_with("Installing HubKit needs"));
+        assert_eq!(report.lines().count(), 7);
+
+        let runner = MockRunner::new().respond(CommandOutput::ok(
+            "List of devices attached\nR58M12ABCDE\tdevice\n",
+        ));
+        assert!(check(&runner, "HubKit", &flavor, &[]).is_empty());
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    fmt::Write,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use indicatif::{HumanBytes, ProgressBar, ProgressState, ProgressStyle};
+
+use crate::i18n::tr;
+
+/// How progress is shown, see `--progress`
+#[derive(Debug, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+pub enum ProgressMode {
+    /// Redrawn spinners and bars
+    #[default]
+    Bar,
+    /// A line every few percent, for screen readers and logs
+    Plain,
+}
+
+/// Whether progress is printed as lines instead of drawn, see [ProgressMode::Plain]
+static PLAIN: AtomicBool = AtomicBool::new(false);
+
+/// Plain progress of a download of known length gets a line every this many percent
+const PLAIN_STEP_PERCENT: u64 = 10;
+
+/// Plain progress of a download of unknown length gets a line this often
+const PLAIN_INTERVAL: Duration = Duration::from_secs(5);
+
+pub fn set_mode(mode: ProgressMode) {
+    PLAIN.store(mode == ProgressMode::Plain, Ordering::SeqCst);
+}
+
+fn is_plain() -> bool {
+    PLAIN.load(Ordering::SeqCst)
+}
+
+/// Progress of a download or a longer running step, drawn as a bar/spinner or printed as plain lines
+pub struct Progress {
+    bar: Option<ProgressBar>,
+    label: String,
+    total: Option<u64>,
+    /// Last percent step, or time, a plain line was printed at
+    reported_step: u64,
+    reported_at: Instant,
+}
+
+impl Progress {
+    /// Progress of downloading [total] bytes (if known) of [label]
+    pub fn download(label: &str, total: Option<u64>) -> Self {
+        let bar = (!is_plain()).then(|| {
+            let bar = ProgressBar::new(total.unwrap_or_default());
+            bar.set_style(ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta})")
+                .unwrap()
+                .with_key("eta", |state: &ProgressState, w: &mut dyn Write| write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap())
+                .progress_chars("#>-"));
+            bar
+        });
+        Self {
+            bar,
+            label: label.to_owned(),
+            total,
+            reported_step: 0,
+            reported_at: Instant::now(),
+        }
+    }
+
+    pub fn set_position(&mut self, position: u64) {
+        match &self.bar {
+            Some(bar) => bar.set_position(position),
+            None => {
+                if let Some(line) = self.plain_line(position) {
+                    eprintln!("{}", line);
+                }
+            }
+        }
+    }
+
+    /// The line to print when the download reached [position], if it's time for one
+    fn plain_line(&mut self, position: u64) -> Option<String> {
+        match self.total {
+            Some(total) if total > 0 => {
+                let percent = position.min(total) * 100 / total;
+                let step = percent / PLAIN_STEP_PERCENT;
+                if step <= self.reported_step {
+                    return None;
+                }
+                self.reported_step = step;
+                Some(tr!(
+                    "progress-percent",
+                    label = self.label.as_str(),
+                    percent = percent,
+                    position = HumanBytes(position).to_string(),
+                    total = HumanBytes(total).to_string()
+                ))
+            }
+            _ => {
+                if self.reported_at.elapsed() < PLAIN_INTERVAL {
+                    return None;
+                }
+                self.reported_at = Instant::now();
+                Some(tr!(
+                    "progress-bytes",
+                    label = self.label.as_str(),
+                    position = HumanBytes(position).to_string()
+                ))
+            }
+        }
+    }
+
+    pub fn finish(&self) {
+        match &self.bar {
+            Some(bar) => bar.finish(),
+            None => eprintln!("{}", tr!("progress-done", label = self.label.as_str())),
+        }
+    }
+
+    /// Leaves the progress where it stopped, for failures
+    pub fn abandon(&self) {
+        if let Some(bar) = &self.bar {
+            bar.abandon();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Progress;
+
+    #[test]
+    fn plain_lines_every_step() {
+        let mut progress = Progress {
+            bar: None,
+            label: "GravioHubKit.msi".to_owned(),
+            total: Some(1000),
+            reported_step: 0,
+            reported_at: std::time::Instant::now(),
+        };
+        assert_eq!(progress.plain_line(50), None);
+        let line = progress.plain_line(250).unwrap();
+        assert!(line.starts_with("GravioHubKit.msi: 25% "));
+        assert!(line.contains("250 B"));
+        assert_eq!(progress.plain_line(290), None);
+        assert!(progress.plain_line(300).is_some());
+        assert!(progress.plain_line(1000).unwrap().contains("100%"));
+        assert_eq!(progress.plain_line(1000), None);
+    }
+}
@@ -0,0 +1,230 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    app, candidate::InstallationCandidate, client_config::PublisherIdentity, gman_error::GManError,
+    powershell::PowerShellScript, product::Product, system_runner::SystemRunner, util,
+};
+
+/// Folder of the cache the files of sandboxes are staged in, one folder for each build
+pub const SANDBOX_FOLDER_NAME: &str = "sandbox";
+
+/// Folder the staged files show up in inside the sandbox, on the desktop of the account it logs on as
+const SANDBOX_GMAN_FOLDER: &str = r"C:\Users\WDAGUtilityAccount\Desktop\gman";
+
+const GMAN_FILE_NAME: &str = "gman.exe";
+const CONFIG_FILE_NAME: &str = "gman_config_client.json5";
+const BOOTSTRAP_FILE_NAME: &str = "bootstrap.ps1";
+const WSB_FILE_NAME: &str = "gman.wsb";
+
+/// Runs gman in the sandbox, and says how it went in the window left open
+const BOOTSTRAP_SCRIPT: &str = "& $Gman @Arguments
+if ($LASTEXITCODE -eq 0) {
+    Write-Host 'Installed. Closing Windows Sandbox discards the installation along with everything else in it'
+} else {
+    Write-Host \"gman failed with exit code $LASTEXITCODE\"
+}
+";
+
+/// Stages what Windows Sandbox needs to install [candidate] from its cached [artifact] in [staging]: a copy
+/// of [gman], the artifact, a configuration with only [product] in it, a bootstrap script running
+/// `gman install --file`, and the .wsb configuration mapping them into the sandbox. Only [staging] is mapped,
+/// the sandbox doesn't see the rest of the cache. Returns the .wsb
+pub fn prepare(
+    staging: &Path,
+    candidate: &InstallationCandidate,
+    artifact: &Path,
+    gman: &Path,
+    product: &Product,
+    publisher_identities: &[PublisherIdentity],
+    autorun: Option<bool>,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let Some(artifact_name) = artifact.file_name() else {
+        return Err(Box::new(GManError::new(&format!(
+            "{} isn't a file",
+            artifact.display()
+        ))));
+    };
+    if staging.exists() {
+        std::fs::remove_dir_all(staging)?;
+    }
+    std::fs::create_dir_all(staging)?;
+
+    std::fs::copy(gman, staging.join(GMAN_FILE_NAME))?;
+    util::link_or_copy(artifact, staging.join(artifact_name))?;
+    /* the sandbox installs from the artifact, it needs neither repositories nor their credentials */
+    let config = serde_json::json!({
+        "Repositories": [],
+        "PublisherIdentities": publisher_identities,
+        "Products": [product],
+    });
+    std::fs::write(
+        staging.join(CONFIG_FILE_NAME),
+        serde_json::to_string_pretty(&config)?,
+    )?;
+
+    let mut arguments = vec![
+        "--config".to_owned(),
+        format!(r"{}\{}", SANDBOX_GMAN_FOLDER, CONFIG_FILE_NAME),
+        "--execution-policy-bypass".to_owned(),
+        "install".to_owned(),
+        "--file".to_owned(),
+        format!(
+            r"{}\{}",
+            SANDBOX_GMAN_FOLDER,
+            artifact_name.to_string_lossy()
+        ),
+        "--product".to_owned(),
+        candidate.product_name.to_owned(),
+        "--version".to_owned(),
+        candidate.version.to_string(),
+        "--flavor".to_owned(),
+        candidate.flavor.id.to_owned(),
+        "--prompt".to_owned(),
+        "false".to_owned(),
+    ];
+    if let Some(autorun) = autorun {
+        arguments.extend(["--autorun".to_owned(), autorun.to_string()]);
+    }
+    if let Some(architecture) = app::architecture() {
+        arguments.extend(["--arch".to_owned(), architecture.to_string()]);
+    }
+    let bootstrap = PowerShellScript::new(BOOTSTRAP_SCRIPT)
+        .arg(
+            "Gman",
+            &format!(r"{}\{}", SANDBOX_GMAN_FOLDER, GMAN_FILE_NAME),
+        )
+        .arg_list("Arguments", &arguments);
+    std::fs::write(staging.join(BOOTSTRAP_FILE_NAME), bootstrap.text())?;
+
+    let wsb = staging.join(WSB_FILE_NAME);
+    std::fs::write(&wsb, wsb_configuration(staging))?;
+    Ok(wsb)
+}
+
+/// Starts Windows Sandbox with the configuration [wsb] written by [prepare]. It returns once the sandbox
+/// is starting, the installation runs and stays on in its own window
+pub fn launch(runner: &dyn SystemRunner, wsb: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let output = runner
+        .run("WindowsSandbox.exe", &[&wsb.to_string_lossy()])
+        .map_err(|e| {
+            GManError::new(&format!(
+                "Failed to start Windows Sandbox, is the Windows Sandbox feature turned on? {}",
+                e
+            ))
+        })?;
+    if !output.status.success() {
+        return Err(Box::new(GManError::new(&format!(
+            "Windows Sandbox failed to start: {}",
+            output.stderr.trim()
+        ))));
+    }
+    Ok(())
+}
+
+/// .wsb configuration mapping [staging] read only into the sandbox, and running the bootstrap script from it
+/// once it's logged on
+fn wsb_configuration(staging: &Path) -> String {
+    format!(
+        "<Configuration>\r\n  <MappedFolders>\r\n    <MappedFolder>\r\n      <HostFolder>{}</HostFolder>\r\n      <SandboxFolder>{}</SandboxFolder>\r\n      <ReadOnly>true</ReadOnly>\r\n    </MappedFolder>\r\n  </MappedFolders>\r\n  <LogonCommand>\r\n    <Command>powershell.exe -NoExit -NoProfile -ExecutionPolicy Bypass -File {}\\{}</Command>\r\n  </LogonCommand>\r\n</Configuration>\r\n",
+        xml_escape(&staging.to_string_lossy()),
+        SANDBOX_GMAN_FOLDER,
+        SANDBOX_GMAN_FOLDER,
+        BOOTSTRAP_FILE_NAME
+    )
+}
+
+/// [value] with the characters XML gives a meaning escaped
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::{
+        candidate::InstallationCandidate,
+        product::Product,
+        system_runner::{CommandOutput, MockRunner},
+    };
+
+    use super::{launch, prepare};
+
+    #[test]
+    fn sandbox_installs_the_cached_artifact() {
+        let root = std::env::temp_dir().join("gman_tests").join("sandbox & co");
+        let _ = std::fs::remove_dir_all(&root);
+        let cache = root.join("cache");
+        std::fs::create_dir_all(&cache).unwrap();
+        let candidate = InstallationCandidate::from_str(
+            "HubKit@Windows@WindowsHubKit@develop@5.2.1-7049@GravioHubKit.msi",
+        )
+        .unwrap();
+        let artifact = cache.join(candidate.make_cached_file_name());
+        std::fs::write(&artifact, "msi").unwrap();
+        let gman = root.join("gman.exe");
+        std::fs::write(&gman, "exe").unwrap();
+        let product: Product = serde_json::from_str(
+            r#"{"Name": "HubKit", "Flavors": [{"Platform": "Windows", "Id": "WindowsHubKit", "PackageType": "Msi",
+                "TeamCityMetadata": {"TeamCityId": "Gravio_GravioHubKit4", "TeamCityBinaryPath": "GravioHubKit.msi"}}]}"#,
+        )
+        .unwrap();
+
+        let staging = root.join("staging");
+        let wsb = prepare(
+            &staging,
+            &candidate,
+            &artifact,
+            &gman,
+            &product,
+            &[],
+            Some(true),
+        )
+        .unwrap();
+        let configuration = std::fs::read_to_string(&wsb).unwrap();
+        assert!(configuration.contains(&format!(
+            "<HostFolder>{}</HostFolder>",
+            staging.to_string_lossy().replace('&', "&amp;")
+        )));
+        assert!(configuration
+            .contains(r"-File C:\Users\WDAGUtilityAccount\Desktop\gman\bootstrap.ps1</Command>"));
+        assert_eq!(
+            configuration.matches("<ReadOnly>true</ReadOnly>").count(),
+            1,
+            "only the staged files are mapped, not the cache"
+        );
+        assert_eq!(
+            std::fs::read_to_string(staging.join(candidate.make_cached_file_name())).unwrap(),
+            "msi"
+        );
+        assert_eq!(
+            std::fs::read_to_string(staging.join("gman.exe")).unwrap(),
+            "exe"
+        );
+        let config: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(staging.join("gman_config_client.json5")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(config["Products"][0]["Name"], "HubKit");
+        assert_eq!(config["Repositories"].as_array().unwrap().len(), 0);
+        let bootstrap = std::fs::read_to_string(staging.join("bootstrap.ps1")).unwrap();
+        assert!(bootstrap.contains("& $Gman @Arguments"));
+        assert!(
+            !bootstrap.contains("WindowsHubKit"),
+            "arguments are passed encoded"
+        );
+
+        let runner = MockRunner::new().respond(CommandOutput::ok(""));
+        launch(&runner, &wsb).unwrap();
+        assert_eq!(runner.calls()[0].program, "WindowsSandbox.exe");
+        assert_eq!(
+            runner.calls()[0].args,
+            vec![wsb.to_string_lossy().to_string()]
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use std::time::{Duration, Instant};
+
+use crate::{gman_error::GManError, system_runner::SystemRunner};
+
+/// How long a restarted service has to become active, unless the flavor's `ServiceHealthCheckSeconds` says otherwise
+pub const DEFAULT_HEALTH_CHECK_SECONDS: u64 = 30;
+
+/// How often to ask systemd whether a restarted service is active yet
+const HEALTH_CHECK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Runs `systemctl` with [args] as root, failing with [action] in the message if it does
+fn systemctl(
+    runner: &dyn SystemRunner,
+    args: &[&str],
+    action: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let output = runner.run_elevated("systemctl", args)?;
+    if output.status.success() {
+        return Ok(());
+    }
+    Err(Box::new(GManError::new(&format!(
+        "Failed to {} ({}): {}",
+        action,
+        output.status,
+        output.stderr.trim()
+    ))))
+}
+
+/// Stops the systemd unit [service] before its package is replaced. A unit that isn't installed yet is fine
+pub fn stop(runner: &dyn SystemRunner, service: &str) -> Result<(), Box<dyn std::error::Error>> {
+    log::debug!("Stopping {}", service);
+    let output = runner.run_elevated("systemctl", &["stop", service])?;
+    if !output.status.success() {
+        log::debug!(
+            "{} wasn't stopped, it may not be installed yet: {}",
+            service,
+            output.stderr.trim()
+        );
+    }
+    Ok(())
+}
+
+/// Reloads the unit files systemd knows, then restarts [service] so it runs the newly installed version
+pub fn restart(runner: &dyn SystemRunner, service: &str) -> Result<(), Box<dyn std::error::Error>> {
+    systemctl(runner, &["daemon-reload"], "reload systemd units")?;
+    log::debug!("Restarting {}", service);
+    systemctl(runner, &["restart", service], &format!("restart {}", service))
+}
+
+/// Waits up to [timeout] for [service] to be active, failing with what systemd last said if it isn't by then
+pub fn wait_until_active(
+    runner: &dyn SystemRunner,
+    service: &str,
+    timeout: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let start = Instant::now();
+    loop {
+        let output = runner.run("systemctl", &["is-active", service])?;
+        let state = output.stdout.trim().to_owned();
+        if output.status.success() && state == "active" {
+            log::debug!("{} is active", service);
+            return Ok(());
+        }
+        /* units that crash on start end up `failed`, there's no point waiting for them */
+        if state == "failed" || start.elapsed() >= timeout {
+            return Err(Box::new(GManError::new(&format!(
+                "{} isn't running after the install, systemd reports it {}. See `journalctl -u {}`",
+                service,
+                if state.is_empty() { "unknown" } else { &state },
+                service
+            ))));
+        }
+        std::thread::sleep(HEALTH_CHECK_POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::system_runner::{CommandOutput, MockRunner};
+
+    use super::{restart, stop, wait_until_active};
+
+    #[test]
+    fn stop_and_restart_go_through_systemctl() {
+        let runner = MockRunner::new().respond(CommandOutput::failed(5, "Unit not loaded"));
+        stop(&runner, "graviohubkit.service").unwrap();
+        restart(&runner, "graviohubkit.service").unwrap();
+
+        let calls = runner.calls();
+        assert!(calls.iter().all(|x| x.elevated));
+        assert_eq!(
+            calls.iter().map(|x| x.command_line()).collect::<Vec<String>>(),
+            vec![
+                "systemctl stop graviohubkit.service",
+                "systemctl daemon-reload",
+                "systemctl restart graviohubkit.service",
+            ]
+        );
+    }
+
+    #[test]
+    fn failed_service_is_reported_without_waiting() {
+        let runner = MockRunner::new()
+            .respond(CommandOutput::ok("activating\n"))
+            .respond(CommandOutput {
+                stdout: "failed\n".into(),
+                ..CommandOutput::failed(3, "")
+            });
+        let e = wait_until_active(&runner, "graviohubkit.service", Duration::from_secs(60))
+            .unwrap_err();
+        assert!(e.to_string().contains("systemd reports it failed"));
+        assert_eq!(runner.calls().len(), 2);
+
+        let runner = MockRunner::new().respond(CommandOutput::ok("active\n"));
+        wait_until_active(&runner, "graviohubkit.service", Duration::from_secs(60)).unwrap();
+    }
+}
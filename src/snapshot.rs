@@ -0,0 +1,157 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    candidate::{CandidateKey, InstallationCandidate, Version},
+    gman_error::GManError,
+    manifest::Manifest,
+};
+
+/// Folder inside the cache directory where named snapshots are kept.
+/// Snapshots live next to the artifacts they need, and clearing the cache keeps both
+pub const SNAPSHOT_DIRECTORY_NAME: &str = "snapshots";
+
+pub fn snapshot_directory<P>(cache_directory: P) -> PathBuf
+where
+    P: AsRef<Path>,
+{
+    cache_directory.as_ref().join(SNAPSHOT_DIRECTORY_NAME)
+}
+
+/// Path of the snapshot [name], after checking the name is usable as a file name
+fn snapshot_path<P>(cache_directory: P, name: &str) -> Result<PathBuf, GManError>
+where
+    P: AsRef<Path>,
+{
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        && !name.starts_with('.');
+    if !valid {
+        return Err(GManError::new(&format!(
+            "Invalid snapshot name {}, use only letters, numbers, '-', '_' and '.'",
+            name
+        )));
+    }
+    Ok(snapshot_directory(cache_directory).join(format!("{}.json", name)))
+}
+
+/// Stores [manifest] as the snapshot [name], replacing any previous snapshot of the same name
+pub fn save<P>(
+    cache_directory: P,
+    name: &str,
+    manifest: &Manifest,
+) -> Result<PathBuf, Box<dyn std::error::Error>>
+where
+    P: AsRef<Path>,
+{
+    let path = snapshot_path(&cache_directory, name)?;
+    std::fs::create_dir_all(snapshot_directory(&cache_directory))?;
+    std::fs::write(&path, manifest.to_json()?)?;
+    Ok(path)
+}
+
+/// Reads the snapshot [name]
+pub fn load<P>(cache_directory: P, name: &str) -> Result<Manifest, Box<dyn std::error::Error>>
+where
+    P: AsRef<Path>,
+{
+    let path = snapshot_path(cache_directory, name)?;
+    if !path.exists() {
+        return Err(Box::new(GManError::new(&format!(
+            "No snapshot named {}",
+            name
+        ))));
+    }
+    Manifest::load(path)
+}
+
+/// Names of all stored snapshots, sorted
+pub fn list<P>(cache_directory: P) -> Vec<String>
+where
+    P: AsRef<Path>,
+{
+    let mut names = match std::fs::read_dir(snapshot_directory(cache_directory)) {
+        Ok(entries) => entries
+            .filter_map(|x| x.ok())
+            .filter_map(|x| {
+                let path = x.path();
+                match path.extension() {
+                    Some(ext) if ext == "json" => path
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().to_string()),
+                    _ => None,
+                }
+            })
+            .collect::<Vec<String>>(),
+        Err(_) => Vec::new(),
+    };
+    names.sort();
+    names
+}
+
+/// Whether any stored snapshot needs the cached [candidate] to be restored
+pub fn is_required_by_any<P>(cache_directory: P, candidate: &InstallationCandidate) -> bool
+where
+    P: AsRef<Path>,
+{
+    list(&cache_directory)
+        .iter()
+        .filter_map(|name| load(&cache_directory, name).ok())
+        .any(|manifest| {
+            manifest.products.iter().any(|entry| {
+                let mut key =
+                    CandidateKey::new(&entry.product_name).version(&Version::new(&entry.version));
+                if let Some(flavor) = &entry.flavor {
+                    key = key.flavor(flavor);
+                }
+                key.matches(&candidate.key())
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::{
+        candidate::InstallationCandidate,
+        manifest::{Manifest, ManifestEntry},
+    };
+
+    use super::{is_required_by_any, list, load, save};
+
+    #[test]
+    fn save_list_load() {
+        let dir = std::env::temp_dir().join("gman_tests").join("snapshots");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let manifest = Manifest {
+            products: vec![ManifestEntry {
+                product_name: "HubKit".into(),
+                version: "5.2.1-7049".into(),
+                flavor: Some("WindowsHubKit".into()),
+                branch: Some("develop".into()),
+                repository: None,
+            }],
+        };
+        save(&dir, "release", &manifest).unwrap();
+        save(&dir, "develop", &Manifest::default()).unwrap();
+
+        assert_eq!(list(&dir), vec!["develop", "release"]);
+        assert_eq!(load(&dir, "release").unwrap(), manifest);
+        assert!(load(&dir, "missing").is_err());
+        assert!(save(&dir, "../escape", &manifest).is_err());
+
+        let needed = InstallationCandidate::from_str(
+            "HubKit@Windows@WindowsHubKit@develop@5.2.1-7049@GravioHubKit.msi",
+        )
+        .unwrap();
+        let not_needed = InstallationCandidate::from_str(
+            "HubKit@Windows@WindowsHubKit@develop@5.2.1-7060@GravioHubKit.msi",
+        )
+        .unwrap();
+        assert!(is_required_by_any(&dir, &needed));
+        assert!(!is_required_by_any(&dir, &not_needed));
+    }
+}
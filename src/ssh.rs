@@ -0,0 +1,217 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    client_config::ClientConfig,
+    gman_error::GManError,
+    system_runner::{CommandOutput, SystemRunner},
+};
+
+/// A machine gman installs to over SSH with `install --target`, e.g. a Raspberry Pi of the test rack
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct SshTarget {
+    /// Name the target is picked by with `--target`
+    #[serde(rename = "Name")]
+    pub name: String,
+    /// Host name or address of the machine
+    #[serde(rename = "Host")]
+    pub host: String,
+    /// User to log in as, the one of the local SSH configuration if not set
+    #[serde(rename = "User", skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(rename = "Port", skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// Private key to log in with, `~` is expanded
+    #[serde(rename = "IdentityFile", skip_serializing_if = "Option::is_none")]
+    pub identity_file: Option<String>,
+}
+
+impl SshTarget {
+    /// `user@host`, or just the host
+    fn destination(&self) -> String {
+        match &self.user {
+            Some(user) => format!("{}@{}", user, self.host),
+            None => self.host.to_owned(),
+        }
+    }
+
+    /// Options shared by ssh and scp. Never prompting, as the output of both is captured
+    fn options(&self, port_flag: &str) -> Vec<String> {
+        let mut options = vec!["-o".to_owned(), "BatchMode=yes".to_owned()];
+        if let Some(port) = self.port {
+            options.push(port_flag.to_owned());
+            options.push(port.to_string());
+        }
+        if let Some(identity_file) = &self.identity_file {
+            options.push("-i".to_owned());
+            options.push(ClientConfig::shell_expand(identity_file));
+        }
+        options
+    }
+}
+
+/// The target named [name] among [targets], ignoring case
+pub fn find_target<'a>(targets: &'a [SshTarget], name: &str) -> Option<&'a SshTarget> {
+    targets.iter().find(|x| x.name.eq_ignore_ascii_case(name))
+}
+
+/// Quotes [arg] for the remote shell
+fn shell_quote(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|x| x.is_ascii_alphanumeric() || "-_./=:@+,".contains(x))
+    {
+        return arg.to_owned();
+    }
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// [SystemRunner] running commands on an [SshTarget] through the local `ssh`, so the same install logic
+/// works on a remote machine. Elevated commands use `sudo -n` there, so the remote user needs passwordless
+/// sudo, which the default user of Raspberry Pi OS has
+#[derive(Debug)]
+pub struct SshRunner<'a> {
+    target: &'a SshTarget,
+    local: &'a dyn SystemRunner,
+}
+
+impl<'a> SshRunner<'a> {
+    pub fn new(target: &'a SshTarget, local: &'a dyn SystemRunner) -> Self {
+        Self { target, local }
+    }
+
+    /// Copies the local file at [source] to [destination] on the target with scp
+    pub fn upload(
+        &self,
+        source: &str,
+        destination: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut args = self.target.options("-P");
+        args.push(source.to_owned());
+        args.push(format!("{}:{}", self.target.destination(), destination));
+        let output = self.local.run(
+            "scp",
+            &args.iter().map(|x| x.as_str()).collect::<Vec<&str>>(),
+        )?;
+        if output.status.success() {
+            return Ok(());
+        }
+        Err(Box::new(GManError::new(&format!(
+            "Failed to copy {} to {} ({}): {}",
+            source,
+            self.target.name,
+            output.status,
+            output.stderr.trim()
+        ))))
+    }
+
+    /// Creates a fresh private folder on the target with `mktemp -d` to upload artifacts to, so nothing else
+    /// on the machine can put a file of its own where the installer is expected
+    pub fn make_upload_directory(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let output = self.run("mktemp", &["-d"])?;
+        let directory = output.stdout.trim();
+        if output.status.success() && !directory.is_empty() {
+            return Ok(directory.to_owned());
+        }
+        Err(Box::new(GManError::new(&format!(
+            "Failed to create a temporary folder on {} ({}): {}",
+            self.target.name,
+            output.status,
+            output.stderr.trim()
+        ))))
+    }
+
+    fn run_remote(&self, command: Vec<&str>) -> Result<CommandOutput, Box<dyn std::error::Error>> {
+        let command_line = command
+            .iter()
+            .map(|x| shell_quote(x))
+            .collect::<Vec<String>>()
+            .join(" ");
+        let mut args = self.target.options("-p");
+        args.push(self.target.destination());
+        args.push("--".to_owned());
+        args.push(command_line);
+        self.local.run(
+            "ssh",
+            &args.iter().map(|x| x.as_str()).collect::<Vec<&str>>(),
+        )
+    }
+}
+
+impl SystemRunner for SshRunner<'_> {
+    fn run(
+        &self,
+        program: &str,
+        args: &[&str],
+    ) -> Result<CommandOutput, Box<dyn std::error::Error>> {
+        let mut command = vec![program];
+        command.extend_from_slice(args);
+        self.run_remote(command)
+    }
+
+    fn run_elevated(
+        &self,
+        program: &str,
+        args: &[&str],
+    ) -> Result<CommandOutput, Box<dyn std::error::Error>> {
+        let mut command = vec!["sudo", "-n", program];
+        command.extend_from_slice(args);
+        self.run_remote(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::system_runner::{CommandOutput, MockRunner, SystemRunner};
+
+    use super::{shell_quote, SshRunner, SshTarget};
+
+    fn make_target() -> SshTarget {
+        SshTarget {
+            name: "pi-lab-3".into(),
+            host: "pi-lab-3.local".into(),
+            user: Some("pi".into()),
+            port: Some(2222),
+            identity_file: None,
+        }
+    }
+
+    #[test]
+    fn quotes_for_remote_shell() {
+        assert_eq!(
+            shell_quote("/tmp/hubkit_5.2.1-7049_all.deb"),
+            "/tmp/hubkit_5.2.1-7049_all.deb"
+        );
+        assert_eq!(shell_quote("it's here"), r"'it'\''s here'");
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[test]
+    fn commands_run_over_ssh() {
+        let target = make_target();
+        let local = MockRunner::new().respond(CommandOutput::ok("/tmp/tmp.Xq3vB7\n"));
+        let runner = SshRunner::new(&target, &local);
+        let directory = runner.make_upload_directory().unwrap();
+        runner
+            .upload("/cache/hubkit.deb", &format!("{}/hubkit.deb", directory))
+            .unwrap();
+        runner
+            .run_elevated("apt-get", &["install", "-y", "/tmp/hub kit.deb"])
+            .unwrap();
+
+        let calls = local.calls();
+        assert_eq!(
+            calls[0].command_line(),
+            "ssh -o BatchMode=yes -p 2222 pi@pi-lab-3.local -- mktemp -d"
+        );
+        assert_eq!(
+            calls[1].command_line(),
+            "scp -o BatchMode=yes -P 2222 /cache/hubkit.deb pi@pi-lab-3.local:/tmp/tmp.Xq3vB7/hubkit.deb"
+        );
+        assert!(!calls[2].elevated, "sudo runs on the remote machine");
+        assert_eq!(
+            calls[2].command_line(),
+            "ssh -o BatchMode=yes -p 2222 pi@pi-lab-3.local -- sudo -n apt-get install -y '/tmp/hub kit.deb'"
+        );
+    }
+}
@@ -0,0 +1,312 @@
+use std::{fmt::Display, process::Command};
+
+use crate::gman_error::GManError;
+
+/// Exit information of a command executed by a [SystemRunner]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandStatus {
+    code: Option<i32>,
+}
+
+impl CommandStatus {
+    pub fn from_code(code: Option<i32>) -> Self {
+        Self { code }
+    }
+
+    /// Whether the command exited with a zero exit code
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+
+    /// Exit code of the command, if it wasn't terminated by a signal
+    #[cfg(any(test, target_os = "windows", target_os = "macos"))]
+    pub fn code(&self) -> Option<i32> {
+        self.code
+    }
+}
+
+impl Display for CommandStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.code {
+            Some(code) => write!(f, "exit code: {}", code),
+            None => f.write_str("terminated by signal"),
+        }
+    }
+}
+
+/// Captured result of a command executed by a [SystemRunner]
+#[derive(Debug, Clone)]
+pub struct CommandOutput {
+    pub status: CommandStatus,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+#[cfg(test)]
+impl CommandOutput {
+    /// A successful output with the given stdout
+    pub fn ok(stdout: &str) -> Self {
+        Self {
+            status: CommandStatus::from_code(Some(0)),
+            stdout: stdout.to_owned(),
+            stderr: String::default(),
+        }
+    }
+
+    /// A failed output with the given exit code and stderr
+    pub fn failed(code: i32, stderr: &str) -> Self {
+        Self {
+            status: CommandStatus::from_code(Some(code)),
+            stdout: String::default(),
+            stderr: stderr.to_owned(),
+        }
+    }
+}
+
+impl From<std::process::Output> for CommandOutput {
+    fn from(value: std::process::Output) -> Self {
+        Self {
+            status: CommandStatus::from_code(value.status.code()),
+            stdout: String::from_utf8_lossy(&value.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&value.stderr).to_string(),
+        }
+    }
+}
+
+/// Executes system commands on behalf of gman.
+///
+/// Every platform operation (installers, package managers, PowerShell, hdiutil etc) goes through this,
+/// so the install/uninstall logic can be exercised off-target with a mock implementation
+pub trait SystemRunner: std::fmt::Debug + Send + Sync {
+    /// Runs [program] with [args], returning its output regardless of exit status
+    fn run(
+        &self,
+        program: &str,
+        args: &[&str],
+    ) -> Result<CommandOutput, Box<dyn std::error::Error>>;
+
+    /// Runs [program] with [args] with administrator/root privileges
+    fn run_elevated(
+        &self,
+        program: &str,
+        args: &[&str],
+    ) -> Result<CommandOutput, Box<dyn std::error::Error>>;
+
+    /// Runs [program] with [args] and returns its stdout, or an error if it exited unsuccessfully
+    fn capture(&self, program: &str, args: &[&str]) -> Result<String, Box<dyn std::error::Error>> {
+        let output = self.run(program, args)?;
+        if output.status.success() {
+            Ok(output.stdout)
+        } else {
+            Err(Box::new(GManError::new(&format!(
+                "Command {} failed ({}): {}",
+                program,
+                output.status,
+                output.stderr.trim()
+            ))))
+        }
+    }
+}
+
+/// [SystemRunner] that executes commands on the local machine via [std::process::Command]
+#[derive(Debug, Default)]
+pub struct ProcessRunner;
+
+impl SystemRunner for ProcessRunner {
+    fn run(
+        &self,
+        program: &str,
+        args: &[&str],
+    ) -> Result<CommandOutput, Box<dyn std::error::Error>> {
+        #[cfg(target_os = "windows")]
+        let args = &crate::powershell::with_execution_policy(
+            program,
+            args,
+            crate::app::is_bypassing_execution_policy(),
+        );
+        log::trace!("Running `{} {}`", program, args.join(" "));
+        let output = Command::new(program).args(args).output()?;
+        Ok(output.into())
+    }
+
+    fn run_elevated(
+        &self,
+        program: &str,
+        args: &[&str],
+    ) -> Result<CommandOutput, Box<dyn std::error::Error>> {
+        log::trace!("Running elevated `{} {}`", program, args.join(" "));
+        #[cfg(target_os = "windows")]
+        {
+            /* UAC prompt, waiting on the elevated process for its exit code */
+            let args = crate::powershell::with_execution_policy(
+                program,
+                args,
+                crate::app::is_bypassing_execution_policy(),
+            );
+            let start = if args.is_empty() {
+                "$p = Start-Process -FilePath $Program -Verb RunAs -Wait -PassThru; exit $p.ExitCode"
+            } else {
+                "$p = Start-Process -FilePath $Program -ArgumentList $Arguments -Verb RunAs -Wait -PassThru; exit $p.ExitCode"
+            };
+            crate::powershell::PowerShellScript::new(start)
+                .arg("Program", program)
+                .arg_list(
+                    "Arguments",
+                    &args.iter().map(|x| x.to_string()).collect::<Vec<String>>(),
+                )
+                .run(self)
+        }
+        #[cfg(not(target_os = "windows"))]
+        {
+            use std::io::IsTerminal;
+
+            if is_root() {
+                return self.run(program, args);
+            }
+            let command_line = format!("{} {}", program, args.join(" "));
+            if crate::app::is_sudo_disabled() {
+                return Err(Box::new(GManError::new(&format!(
+                    "`{}` needs root privileges. Run it yourself with sudo, or run gman without --no-sudo",
+                    command_line
+                ))));
+            }
+            let mut sudo_args = vec![];
+            if std::env::var_os("SUDO_ASKPASS").is_some() {
+                /* graphical prompt, which also offers Touch ID where it's set up */
+                sudo_args.push("-A");
+            } else if !std::io::stdin().is_terminal() {
+                /* nobody to type the password, so fail instead of hanging */
+                sudo_args.push("-n");
+            }
+            sudo_args.push(program);
+            sudo_args.extend_from_slice(args);
+            let output = self.run("sudo", &sudo_args)?;
+            if !output.status.success() && output.stderr.contains("a password is required") {
+                return Err(Box::new(GManError::new(&format!(
+                    "`{}` needs root privileges, but sudo can't ask for a password here. Set SUDO_ASKPASS, or run it yourself with sudo",
+                    command_line
+                ))));
+            }
+            Ok(output)
+        }
+    }
+}
+
+/// Whether gman is running as root, so elevated commands don't need sudo
+#[cfg(not(target_os = "windows"))]
+fn is_root() -> bool {
+    Command::new("id")
+        .arg("-u")
+        .output()
+        .is_ok_and(|x| String::from_utf8_lossy(&x.stdout).trim() == "0")
+}
+
+/// A single invocation recorded by the [MockRunner]
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedCall {
+    pub program: String,
+    pub args: Vec<String>,
+    pub elevated: bool,
+}
+
+#[cfg(test)]
+impl RecordedCall {
+    /// The full command line of this call, joined with spaces
+    pub fn command_line(&self) -> String {
+        let mut parts = vec![self.program.to_owned()];
+        parts.extend(self.args.iter().cloned());
+        parts.join(" ")
+    }
+}
+
+/// [SystemRunner] for tests: records every call, and answers them with queued outputs in order.
+/// Once the queue is empty, every call succeeds with empty output
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct MockRunner {
+    responses: std::sync::Mutex<std::collections::VecDeque<CommandOutput>>,
+    calls: std::sync::Mutex<Vec<RecordedCall>>,
+}
+
+#[cfg(test)]
+impl MockRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues an output to be returned by the next unanswered call
+    pub fn respond(self, output: CommandOutput) -> Self {
+        self.responses.lock().unwrap().push_back(output);
+        self
+    }
+
+    /// All calls made to this runner so far
+    pub fn calls(&self) -> Vec<RecordedCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    fn record(&self, program: &str, args: &[&str], elevated: bool) -> CommandOutput {
+        self.calls.lock().unwrap().push(RecordedCall {
+            program: program.to_owned(),
+            args: args.iter().map(|x| x.to_string()).collect(),
+            elevated,
+        });
+        self.responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| CommandOutput::ok(""))
+    }
+}
+
+#[cfg(test)]
+impl SystemRunner for MockRunner {
+    fn run(
+        &self,
+        program: &str,
+        args: &[&str],
+    ) -> Result<CommandOutput, Box<dyn std::error::Error>> {
+        Ok(self.record(program, args, false))
+    }
+
+    fn run_elevated(
+        &self,
+        program: &str,
+        args: &[&str],
+    ) -> Result<CommandOutput, Box<dyn std::error::Error>> {
+        Ok(self.record(program, args, true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CommandOutput, MockRunner, SystemRunner};
+
+    #[test]
+    fn mock_records_and_answers_in_order() {
+        let runner = MockRunner::new()
+            .respond(CommandOutput::ok("first"))
+            .respond(CommandOutput::failed(3, "nope"));
+
+        assert_eq!(runner.capture("echo", &["a"]).unwrap(), "first");
+        assert!(runner.capture("echo", &["b"]).is_err());
+        /* queue exhausted, succeeds with empty output */
+        let out = runner
+            .run_elevated("installer", &["-pkg", "x.pkg"])
+            .unwrap();
+        assert!(out.status.success());
+
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 3);
+        assert_eq!(calls[1].command_line(), "echo b");
+        assert!(calls[2].elevated);
+    }
+
+    #[test]
+    fn status_display() {
+        let out = CommandOutput::failed(1602, "");
+        assert_eq!(out.status.to_string(), "exit code: 1602");
+        assert_eq!(out.status.code(), Some(1602));
+    }
+}
@@ -0,0 +1,142 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::candidate::Version;
+
+/// File in the state directory keeping the notes tagged onto builds
+const TAGS_FILE_NAME: &str = "tags.json";
+
+/// Notes users tagged builds with, e.g. which bugs they reproduce, by product and build version. Shown next
+/// to the builds by `list` and `cache --list`. Products are matched ignoring case, and versions by
+/// [Version::same_build], so `5.2.1-7049` and `5.2.1.7049` share their notes
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct Tags {
+    #[serde(rename = "Products", default)]
+    products: BTreeMap<String, BTreeMap<String, Vec<String>>>,
+}
+
+impl Tags {
+    fn path(state_directory: &Path) -> PathBuf {
+        state_directory.join(TAGS_FILE_NAME)
+    }
+
+    /// The tags kept in [state_directory], none if they can't be read
+    pub fn load(state_directory: &Path) -> Self {
+        std::fs::read_to_string(Self::path(state_directory))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, state_directory: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(state_directory)?;
+        std::fs::write(
+            Self::path(state_directory),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        Ok(())
+    }
+
+    /// The product and version [version] of [product_name] are kept under, if it's tagged
+    fn key(&self, product_name: &str, version: &str) -> Option<(String, String)> {
+        let (product, builds) = self
+            .products
+            .iter()
+            .find(|(x, _)| x.eq_ignore_ascii_case(product_name))?;
+        let version = Version::new(version);
+        builds
+            .keys()
+            .find(|x| Version::new(x).same_build(&version))
+            .map(|x| (product.to_owned(), x.to_owned()))
+    }
+
+    /// Tags [version] of [product_name] with [note], after the notes it already has. The same note isn't
+    /// added twice
+    pub fn add(&mut self, product_name: &str, version: &str, note: &str) {
+        let product = self
+            .products
+            .keys()
+            .find(|x| x.eq_ignore_ascii_case(product_name))
+            .cloned()
+            .unwrap_or_else(|| product_name.to_owned());
+        let key = self
+            .key(&product, version)
+            .map(|(_, x)| x)
+            .unwrap_or_else(|| version.to_owned());
+        let notes = self
+            .products
+            .entry(product)
+            .or_default()
+            .entry(key)
+            .or_default();
+        if !notes.iter().any(|x| x == note) {
+            notes.push(note.to_owned());
+        }
+    }
+
+    /// Removes every note of [version] of [product_name], returning whether it had any
+    pub fn clear(&mut self, product_name: &str, version: &str) -> bool {
+        let Some((product, key)) = self.key(product_name, version) else {
+            return false;
+        };
+        let builds = self.products.get_mut(&product).unwrap();
+        builds.remove(&key);
+        if builds.is_empty() {
+            self.products.remove(&product);
+        }
+        true
+    }
+
+    pub fn notes(&self, product_name: &str, version: &str) -> &[String] {
+        self.key(product_name, version)
+            .and_then(|(product, key)| self.products.get(&product)?.get(&key))
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    /// Every tagged build with its notes, by product and version
+    pub fn all(&self) -> impl Iterator<Item = (&String, &String, &Vec<String>)> {
+        self.products.iter().flat_map(|(product, builds)| {
+            builds
+                .iter()
+                .map(move |(version, notes)| (product, version, notes))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Tags;
+
+    #[test]
+    fn notes_survive_reload_until_cleared() {
+        let state = std::env::temp_dir().join("gman_tests").join("tags");
+        let _ = std::fs::remove_dir_all(&state);
+        assert_eq!(Tags::load(&state), Tags::default());
+
+        let mut tags = Tags::default();
+        tags.add("HubKit", "5.2.1-7049", "repro bug GRV-1234");
+        tags.add("HubKit", "5.2.1-7049", "crashes on startup");
+        tags.add("hubkit", "5.2.1-7049", "repro bug GRV-1234");
+        tags.add("HubKit", "5.2.1-7052", "fixed GRV-1234");
+        tags.add("Studio", "5.2.1-7049", "layout broken");
+        tags.save(&state).unwrap();
+
+        let mut tags = Tags::load(&state);
+        assert_eq!(
+            tags.notes("HubKit", "5.2.1-7049"),
+            ["repro bug GRV-1234", "crashes on startup"]
+        );
+        assert_eq!(tags.notes("hubkit", "5.2.1.7049").len(), 2);
+        assert_eq!(tags.notes("Studio", "5.2.1-7049"), ["layout broken"]);
+        assert!(tags.clear("HubKit", "5.2.1.7049"));
+        assert!(!tags.clear("HubKit", "5.2.1-7049"));
+        assert!(tags.notes("HubKit", "5.2.1-7049").is_empty());
+        assert_eq!(tags.notes("Studio", "5.2.1-7049").len(), 1);
+        assert_eq!(tags.all().count(), 2);
+    }
+}
@@ -1,100 +1,393 @@
-use futures_util::StreamExt;
-use indicatif::{ProgressBar, ProgressState, ProgressStyle};
 use std::{
+    collections::BTreeMap,
     path::{Path, PathBuf},
     str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
-use std::fmt::Write;
-
-use reqwest::{
-    header::{HeaderValue, RANGE},
-    Url,
-};
-use serde::{Deserialize, Deserializer};
+use reqwest::Url;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
 use serde_json::Value;
 
 use crate::{
     app,
     candidate::{InstallationCandidate, SearchCandidate, Version},
+    client_config::RepositoryType,
+    download, folder_repository,
     gman_error::GManError,
+    http_cache::CachedResponse,
     platform::Platform,
-    product::Product,
-    CandidateRepository,
+    product::{Flavor, Product},
+    redact, throttle, CandidateRepository,
 };
 
 #[derive(Debug, Deserialize)]
 pub struct TeamCityArtifacts {
-    #[serde(rename = "count")]
+    #[serde(rename = "count", default)]
     pub count: u32,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct TeamCityBuild {
-    #[serde(rename = "id")]
+    #[serde(rename = "id", deserialize_with = "deserialize_number_or_string")]
     pub id: u32,
-    #[serde(rename = "number")]
+    #[serde(rename = "number", alias = "buildNumber")]
     pub build_number: String,
-    #[serde(rename = "finishDate")]
+    #[serde(rename = "finishDate", alias = "finishOnAgentDate", default)]
     pub finish_date: Option<String>,
-    #[serde(rename = "artifacts")]
+    #[serde(rename = "artifacts", default)]
     pub artifacts: Option<TeamCityArtifacts>,
-    #[serde(rename = "buildTypeId")]
+    #[serde(rename = "buildTypeId", default)]
     pub build_type_id: Option<String>,
-    #[serde(rename = "status")]
+    #[serde(rename = "status", default)]
     pub status: Option<String>,
-    #[serde(rename = "branchName")]
+    #[serde(rename = "branchName", default)]
     pub branch_name: Option<String>,
+    /// Fields this version of gman doesn't know about, see [UnknownFields]
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, Value>,
+}
+
+impl TeamCityBuild {
+    /// Whether the build kept any artifacts. Builds whose artifacts were cleaned up report a count of 0,
+    /// responses that didn't ask for the count are assumed to have some
+    pub fn has_artifacts(&self) -> bool {
+        self.artifacts.as_ref().map(|x| x.count > 0).unwrap_or(true)
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct TeamCityBuilds {
-    #[serde(rename = "count")]
+    #[serde(rename = "count", default)]
     pub count: u32,
-    #[serde(rename = "build")]
+    #[serde(rename = "build", default)]
     pub builds: Vec<TeamCityBuild>,
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, Value>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct TeamCityBranch {
     pub name: String,
-    #[serde(deserialize_with = "skip_intermediate_builds_object")]
+    #[serde(default, deserialize_with = "skip_intermediate_builds_object")]
     pub builds: Vec<TeamCityBuild>,
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, Value>,
+}
+
+/// Accepts ids sent as numbers as well as strings
+fn deserialize_number_or_string<'de, D>(deserializer: D) -> Result<u32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Value::deserialize(deserializer)? {
+        Value::Number(n) => n
+            .as_u64()
+            .and_then(|x| u32::try_from(x).ok())
+            .ok_or_else(|| serde::de::Error::custom(format!("id {} out of range", n))),
+        Value::String(s) => s
+            .parse::<u32>()
+            .map_err(|e| serde::de::Error::custom(format!("invalid id {}: {}", s, e))),
+        other => Err(serde::de::Error::custom(format!(
+            "expected a number or string id, found {}",
+            other
+        ))),
+    }
 }
 
+/// Builds of a branch are wrapped in a `{"count": .., "build": [..]}` object by most server versions,
+/// but may also come as a bare array, or be missing altogether when a branch has no matching builds
 fn skip_intermediate_builds_object<'de, D>(deserializer: D) -> Result<Vec<TeamCityBuild>, D::Error>
 where
     D: Deserializer<'de>,
 {
     let value: Value = Deserialize::deserialize(deserializer)?;
 
-    match value {
-        Value::Object(kvp) => {
-            let mut result = Vec::new();
-
-            let builds = kvp["build"].as_array().unwrap();
-
-            for build_value in builds.to_owned() {
-                let build: TeamCityBuild = serde_json::from_value(build_value)
-                    .map_err(|e| serde::de::Error::custom(format!("{}", e)))?;
-                result.push(build);
+    let builds = match value {
+        Value::Object(mut kvp) => match kvp.remove("build") {
+            Some(Value::Array(builds)) => builds,
+            Some(Value::Null) | None => Vec::new(),
+            Some(other) => {
+                return Err(serde::de::Error::custom(format!(
+                    "Expected an array for 'builds.build', found {}",
+                    other
+                )))
             }
+        },
+        Value::Array(builds) => builds,
+        Value::Null => Vec::new(),
+        _ => return Err(serde::de::Error::custom("Expected an array for 'builds'")),
+    };
 
-            Ok(result)
-        }
-        _ => Err(serde::de::Error::custom("Expected an array for 'builds'")),
+    let mut result = Vec::new();
+    for (index, build_value) in builds.into_iter().enumerate() {
+        let build: TeamCityBuild = serde_json::from_value(build_value)
+            .map_err(|e| serde::de::Error::custom(format!("build[{}]: {}", index, e)))?;
+        result.push(build);
     }
+    Ok(result)
 }
 
 #[derive(Debug, Deserialize)]
 pub struct TeamCityRoot {
-    #[serde(rename = "branch")]
+    #[serde(rename = "branch", alias = "branches", default)]
     pub branches: Vec<TeamCityBranch>,
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, Value>,
+}
+
+/// A single VCS change (commit) included in a build
+#[derive(Debug, Deserialize)]
+pub struct TeamCityChange {
+    #[serde(rename = "id", deserialize_with = "deserialize_number_or_string")]
+    pub id: u32,
+    /// Revision of the change, e.g. the git commit hash
+    #[serde(rename = "version", default)]
+    pub version: String,
+    #[serde(rename = "username", default)]
+    pub username: Option<String>,
+    #[serde(rename = "comment", default)]
+    pub comment: String,
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, Value>,
+}
+
+impl TeamCityChange {
+    /// First line of the commit message
+    pub fn summary(&self) -> &str {
+        self.comment.lines().next().unwrap_or_default().trim()
+    }
+}
+
+/// One line per change: the short revision, who made it and the summary
+impl std::fmt::Display for TeamCityChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.10}  {:<16}  {}",
+            self.version,
+            self.username.as_deref().unwrap_or("unknown"),
+            self.summary()
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TeamCityChanges {
+    /// Reported with every collection, kept so it isn't taken for an unknown field
+    #[allow(dead_code)]
+    #[serde(rename = "count", default)]
+    pub count: u32,
+    #[serde(rename = "change", default)]
+    pub changes: Vec<TeamCityChange>,
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, Value>,
+}
+
+/// A file or directory in a build's artifacts
+#[derive(Debug, Deserialize)]
+pub struct TeamCityArtifactFile {
+    #[serde(rename = "name")]
+    pub name: String,
+    /// Path from the artifact root, which is what `TeamCityBinaryPath` expects
+    #[serde(rename = "fullName", default)]
+    pub full_name: Option<String>,
+    /// Size in bytes, absent for directories
+    #[serde(rename = "size", default)]
+    pub size: Option<u64>,
+    /// SHA-256 checksum, only reported by servers that compute artifact hashes
+    #[serde(rename = "sha256", alias = "checksum", default)]
+    pub sha256: Option<String>,
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TeamCityArtifactFiles {
+    /// Reported with every collection, kept so it isn't taken for an unknown field
+    #[allow(dead_code)]
+    #[serde(rename = "count", default)]
+    pub count: u32,
+    #[serde(rename = "file", default)]
+    pub files: Vec<TeamCityArtifactFile>,
+    #[serde(flatten)]
+    pub unknown: BTreeMap<String, Value>,
+}
+
+/// Responses carrying fields gman doesn't know about, which are logged instead of failing the parse
+trait UnknownFields {
+    /// Paths of the fields that were ignored, e.g. `branch[0].builds[1].newField`
+    fn unknown_fields(&self) -> Vec<String>;
+}
+
+fn prefixed_fields(prefix: &str, unknown: &BTreeMap<String, Value>) -> Vec<String> {
+    unknown
+        .keys()
+        .map(|key| format!("{}{}", prefix, key))
+        .collect()
+}
+
+fn build_unknown_fields(prefix: &str, builds: &[TeamCityBuild]) -> Vec<String> {
+    builds
+        .iter()
+        .enumerate()
+        .flat_map(|(i, build)| prefixed_fields(&format!("{}[{}].", prefix, i), &build.unknown))
+        .collect()
+}
+
+impl UnknownFields for TeamCityRoot {
+    fn unknown_fields(&self) -> Vec<String> {
+        let mut fields = prefixed_fields("", &self.unknown);
+        for (i, branch) in self.branches.iter().enumerate() {
+            let prefix = format!("branch[{}]", i);
+            fields.extend(prefixed_fields(&format!("{}.", prefix), &branch.unknown));
+            fields.extend(build_unknown_fields(
+                &format!("{}.builds", prefix),
+                &branch.builds,
+            ));
+        }
+        fields
+    }
+}
+
+impl UnknownFields for TeamCityBuild {
+    fn unknown_fields(&self) -> Vec<String> {
+        prefixed_fields("", &self.unknown)
+    }
+}
+
+impl UnknownFields for TeamCityBuilds {
+    fn unknown_fields(&self) -> Vec<String> {
+        let mut fields = prefixed_fields("", &self.unknown);
+        fields.extend(build_unknown_fields("build", &self.builds));
+        fields
+    }
+}
+
+impl UnknownFields for TeamCityChanges {
+    fn unknown_fields(&self) -> Vec<String> {
+        let mut fields = prefixed_fields("", &self.unknown);
+        for (i, change) in self.changes.iter().enumerate() {
+            fields.extend(prefixed_fields(&format!("change[{}].", i), &change.unknown));
+        }
+        fields
+    }
+}
+
+impl UnknownFields for TeamCityArtifactFile {
+    fn unknown_fields(&self) -> Vec<String> {
+        prefixed_fields("", &self.unknown)
+    }
+}
+
+impl UnknownFields for TeamCityArtifactFiles {
+    fn unknown_fields(&self) -> Vec<String> {
+        let mut fields = prefixed_fields("", &self.unknown);
+        for (i, file) in self.files.iter().enumerate() {
+            fields.extend(prefixed_fields(&format!("file[{}].", i), &file.unknown));
+        }
+        fields
+    }
+}
+
+/// The part of [body] around [line]:[column], for showing where invalid JSON broke
+fn snippet_around(body: &str, line: usize, column: usize) -> String {
+    const CONTEXT: usize = 120;
+    let text = body.lines().nth(line.saturating_sub(1)).unwrap_or_default();
+    let chars = text.chars().collect::<Vec<char>>();
+    let start = column.saturating_sub(CONTEXT).min(chars.len());
+    let end = (column + CONTEXT).min(chars.len());
+    chars[start..end].iter().collect()
+}
+
+/// The deepest value along [path] that exists in [value]
+fn value_at_path<'v>(value: &'v Value, path: &serde_path_to_error::Path) -> &'v Value {
+    let mut current = value;
+    for segment in path {
+        let next = match segment {
+            serde_path_to_error::Segment::Seq { index } => current.get(index),
+            serde_path_to_error::Segment::Map { key } => current.get(key),
+            _ => None,
+        };
+        match next {
+            Some(v) => current = v,
+            None => break,
+        }
+    }
+    current
+}
+
+/// Tells the user a response from [repo_name] couldn't be used, rather than silently listing nothing
+fn report_parse_failure(repo_name: &str, e: &dyn std::error::Error) {
+    eprintln!(
+        "Failed to parse the response of repository {}: {}{}",
+        repo_name,
+        e,
+        if app::is_debug_parse() {
+            ""
+        } else {
+            " (run with --debug-parse for details)"
+        }
+    );
+}
+
+/// Parses a TeamCity response [body] received from [source].
+///
+/// Fields gman doesn't know are logged rather than failing the parse. When parsing does fail, the error names the
+/// offending path, and with `--debug-parse` the JSON at that path is printed so schema changes can be diagnosed
+fn parse_response<T>(body: &str, source: &str) -> Result<T, Box<dyn std::error::Error>>
+where
+    T: DeserializeOwned + UnknownFields,
+{
+    let value: Value = match serde_json::from_str(body) {
+        Ok(v) => v,
+        Err(e) => {
+            if app::is_debug_parse() {
+                eprintln!(
+                    "Response from {} isn't valid JSON ({}), near:\n{}",
+                    source,
+                    e,
+                    snippet_around(body, e.line(), e.column())
+                );
+            }
+            return Err(Box::new(e));
+        }
+    };
+
+    match serde_path_to_error::deserialize::<_, T>(&value) {
+        Ok(parsed) => {
+            for field in parsed.unknown_fields() {
+                log::debug!(
+                    "Ignoring unknown field {} in response from {}",
+                    field,
+                    source
+                );
+            }
+            Ok(parsed)
+        }
+        Err(e) => {
+            if app::is_debug_parse() {
+                let snippet = value_at_path(&value, e.path());
+                eprintln!(
+                    "Failed to parse response from {} at {}: {}\n{}",
+                    source,
+                    e.path(),
+                    e.inner(),
+                    serde_json::to_string_pretty(snippet).unwrap_or_default()
+                );
+            }
+            Err(Box::new(GManError::new(&format!(
+                "Unexpected response format at {}: {}",
+                e.path(),
+                e.inner()
+            ))))
+        }
+    }
 }
 
 /// Ensures that this url starts with 'http://' or 'https://'.
 /// If no scheme is provided, 'https://' is pre-pended by default
-fn ensure_scheme(url: &str) -> Result<Url, Box<dyn std::error::Error>> {
+pub fn ensure_scheme(url: &str) -> Result<Url, Box<dyn std::error::Error>> {
     let with_scheme = if !url.starts_with("http://") && !url.starts_with("https://") {
         format!("https://{}", url)
     } else {
@@ -104,19 +397,170 @@ fn ensure_scheme(url: &str) -> Result<Url, Box<dyn std::error::Error>> {
     Ok(u)
 }
 
+/// A build linked to from the TeamCity web UI, e.g.
+/// `https://tc.example.com/buildConfiguration/Gravio_GravioHubKit4/20211`
+#[derive(Debug, PartialEq)]
+pub struct BuildUrl {
+    /// Host of the TeamCity server the build is on
+    pub host: String,
+    /// Build configuration, matched against the `TeamCityId` of the configured flavors
+    pub build_type_id: String,
+    /// TeamCity's internal id of the build, not its build number
+    pub build_id: u32,
+}
+
+impl BuildUrl {
+    /// Reads [url] as a link to a build, both the current `buildConfiguration/<buildTypeId>/<buildId>`
+    /// form and the older `viewLog.html?buildId=<buildId>&buildTypeId=<buildTypeId>` one.
+    /// None if [url] isn't a link to a build, e.g. a product name
+    pub fn parse(url: &str) -> Option<BuildUrl> {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return None;
+        }
+        let url = Url::from_str(url).ok()?;
+        let host = url.host_str()?.to_owned();
+        let segments = url.path_segments()?.collect::<Vec<&str>>();
+
+        if let Some(at) = segments.iter().position(|x| *x == "buildConfiguration") {
+            let build_type_id = segments.get(at + 1).filter(|x| !x.is_empty())?;
+            let build_id = segments.get(at + 2)?.parse::<u32>().ok()?;
+            return Some(BuildUrl {
+                host,
+                build_type_id: build_type_id.to_string(),
+                build_id,
+            });
+        }
+        if segments.last() == Some(&"viewLog.html") {
+            let query = |key: &str| {
+                url.query_pairs()
+                    .find(|(k, _)| k == key)
+                    .map(|(_, v)| v.to_string())
+            };
+            return Some(BuildUrl {
+                host,
+                build_type_id: query("buildTypeId").filter(|x| !x.is_empty())?,
+                build_id: query("buildId")?.parse::<u32>().ok()?,
+            });
+        }
+        None
+    }
+}
+
+/// The build with TeamCity's internal id [build_id] on [repo], for installing a build linked to by a [BuildUrl]
+pub async fn get_build_by_id(
+    http_client: &reqwest::Client,
+    repo: &CandidateRepository,
+    build_id: u32,
+) -> Result<TeamCityBuild, Box<dyn std::error::Error>> {
+    let Some(repo_url) = &repo.repository_server else {
+        return Err(Box::new(GManError::new(&format!(
+            "Repository {} has no RepositoryServer",
+            &repo.name
+        ))));
+    };
+    let mut url = ensure_scheme(repo_url)?;
+    url.set_path(&format!("app/rest/builds/id:{}", build_id));
+    url.query_pairs_mut().append_pair(
+        "fields",
+        &format!(
+            "id,number,branchName,buildTypeId,status,{}",
+            ARTIFACT_COUNT_FIELD
+        ),
+    );
+    get_json::<TeamCityBuild>(http_client, repo, url).await
+}
+
+/// Field selecting whether a build still has artifacts, see [TeamCityBuild::has_artifacts]
+const ARTIFACT_COUNT_FIELD: &str = "artifacts($locator(count:1),count:1)";
+
+/// Builds requested per branch or search, so an older build can stand in when the newest lost its artifacts
+const FALLBACK_BUILDS: u32 = 5;
+
+/// Whether [build_id]'s artifacts on [repo] contain the binary of [flavor].
+///
+/// Only a `404` counts as missing, anything else is left for the download to report
+async fn has_binary(
+    http_client: &reqwest::Client,
+    repo: &CandidateRepository,
+    repo_url: &str,
+    build_id: u32,
+    flavor: &Flavor,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut url = ensure_scheme(repo_url)?;
+    url.set_path(&format!(
+        "app/rest/builds/id:{}/artifacts/metadata/{}",
+        build_id,
+        flavor.teamcity_metadata.teamcity_binary_path.to_string_lossy()
+    ));
+    url.query_pairs_mut().append_pair("fields", "name");
+    let request = download::authorize(
+        http_client.get(url).header("Accept", "Application/json"),
+        repo.repository_credentials.as_ref(),
+    );
+    let (_permit, res) = throttle::send(repo, request).await?;
+    Ok(res.status() != 404)
+}
+
+/// The first of [builds] whose artifacts still contain the binary of [flavor], so installs don't resolve
+/// to a build whose artifacts were cleaned up
+async fn first_with_binary<I>(
+    http_client: &reqwest::Client,
+    repo: &CandidateRepository,
+    repo_url: &str,
+    flavor: &Flavor,
+    builds: I,
+) -> Result<Option<TeamCityBuild>, Box<dyn std::error::Error>>
+where
+    I: IntoIterator<Item = TeamCityBuild>,
+{
+    for build in builds {
+        if !build.has_artifacts() {
+            log::debug!(
+                "Skipping build {} of {}, its artifacts are gone",
+                &build.build_number,
+                &flavor.id
+            );
+            continue;
+        }
+        if has_binary(http_client, repo, repo_url, build.id, flavor).await? {
+            return Ok(Some(build));
+        }
+        log::debug!(
+            "Skipping build {} of {}, its artifacts don't contain {}",
+            &build.build_number,
+            &flavor.id,
+            flavor.teamcity_metadata.teamcity_binary_path.display()
+        );
+    }
+    Ok(None)
+}
+
+/// Lists the latest successful build of every branch of [products] on the [valid_repositories].
+///
+/// Responses are kept in [cache_directory] and requested again conditionally, so unchanged branch lists
+/// come back as a cheap `304 Not Modified`
 pub async fn get_builds<'a>(
     http_client: &reqwest::Client,
     current_platform: Platform,
     valid_repositories: &Vec<&CandidateRepository>,
     products: &'a Vec<Product>,
+    cache_directory: &Path,
 ) -> Result<Vec<InstallationCandidate>, Box<dyn std::error::Error>> {
     let mut candidates: Vec<InstallationCandidate> = Vec::new();
 
     for repo in valid_repositories {
+        if repo.repository_type == RepositoryType::Folder {
+            candidates.extend(folder_repository::get_builds(
+                repo,
+                &current_platform,
+                products,
+            ));
+            continue;
+        }
         if let Some(repo_url) = &repo.repository_server {
             log::debug!(
                 "Repo defined a remote url, will fetch from remote '{}'",
-                &repo_url
+                redact::redact_url(repo_url)
             );
 
             for product in products {
@@ -133,28 +577,56 @@ pub async fn get_builds<'a>(
                         "app/rest/buildTypes/id:{}/branches",
                         flavor.teamcity_metadata.teamcity_id
                     ));
-                    url.query_pairs_mut().append_key_only(
-                        "default:true,policy:ACTIVE_HISTORY_AND_ACTIVE_VCS_BRANCHES",
-                    );
-                    url.set_query(Some("fields=branch(name,builds(build(id,number,finishDate,artifacts($locator(count:1),count:1)),count,$locator(state:finished,status:SUCCESS,count:1)))"));
-
-                    let request: reqwest::Request = match &repo.repository_credentials {
-                        Some(credentials) => {
-                            let r = http_client.get(url).header("Accept", "Application/json");
-                            match credentials {
-                                crate::RepositoryCredentials::BearerToken { token } => {
-                                    r.bearer_auth(token).build().unwrap()
-                                }
-                                crate::RepositoryCredentials::BasicAuth { username, password } => {
-                                    r.basic_auth(username, password.to_owned()).build().unwrap()
+                    /* default:any so configurations without (or with an unusual) default branch still list everything */
+                    url.query_pairs_mut()
+                        .append_pair(
+                            "locator",
+                            &format!("default:any,policy:{}", product.branch_policy_for(flavor)),
+                        )
+                        .append_pair("fields", &format!("branch(name,builds(build(id,number,finishDate,{}),count,$locator(state:finished,status:SUCCESS,{},count:{})))", ARTIFACT_COUNT_FIELD, personal_builds(), FALLBACK_BUILDS));
+
+                    let cached = CachedResponse::load(cache_directory, &url);
+                    let mut request = match &repo.repository_credentials {
+                        Some(credentials) => download::authorize(
+                            http_client
+                                .get(url.clone())
+                                .header("Accept", "Application/json"),
+                            Some(credentials),
+                        ),
+                        None => http_client.get(url.clone()),
+                    };
+                    if let Some(cached) = &cached {
+                        request = cached.apply(request);
+                    }
+                    let (_permit, res) = throttle::send(repo, request).await?;
+                    let res_status = res.status();
+                    let body = match (res_status.as_u16(), cached) {
+                        (304, Some(cached)) => {
+                            log::debug!(
+                                "Branches of {} not modified, using cached response",
+                                &flavor.id
+                            );
+                            cached.body
+                        }
+                        (200, _) => {
+                            let headers = res.headers().clone();
+                            let body = res.text().await?;
+                            if let Some(fresh) =
+                                CachedResponse::from_response(&url, &headers, &body)
+                            {
+                                if let Err(e) = fresh.save(cache_directory) {
+                                    log::warn!(
+                                        "Failed to cache response from {}: {}",
+                                        &repo.name,
+                                        e
+                                    );
                                 }
                             }
+                            body
                         }
-                        None => http_client.get(url).build().unwrap(),
+                        _ => String::default(),
                     };
-                    let res = http_client.execute(request).await?;
-                    let res_status = res.status();
-                    if res_status != 200 {
+                    if res_status != 200 && res_status != 304 {
                         if res_status == 401 || res_status == 403 {
                             eprintln!("Not authorized to access repository {}", &repo.name)
                         } else if res_status == 404 {
@@ -167,12 +639,34 @@ pub async fn get_builds<'a>(
                         continue;
                     }
 
-                    let body = res.text().await?;
-                    match serde_json::from_str::<TeamCityRoot>(&body) {
+                    match parse_response::<TeamCityRoot>(&body, &repo.name) {
                         Ok(team_city_root) => {
                             log::debug!("Got reponse from TeamCity build server");
                             for branch in team_city_root.branches {
-                                for build in branch.builds {
+                                if !product.lists_branch(flavor, &branch.name) {
+                                    log::trace!(
+                                        "Skipping branch {} of {}, not in Branches",
+                                        &branch.name,
+                                        &flavor.id
+                                    );
+                                    continue;
+                                }
+                                /* the newest build, marked expired if its artifacts are gone, along with
+                                 * the newest build that still has them. Older ones are only there as fallbacks */
+                                let mut builds = branch.builds.into_iter();
+                                let mut listed = Vec::new();
+                                if let Some(newest) = builds.next() {
+                                    if !newest.has_artifacts() {
+                                        log::debug!(
+                                            "Build {} of {} has expired, its artifacts are gone",
+                                            &newest.build_number,
+                                            &flavor.id
+                                        );
+                                        listed.extend(builds.find(|x| x.has_artifacts()));
+                                    }
+                                    listed.push(newest);
+                                }
+                                for build in listed {
                                     let ci = InstallationCandidate {
                                         remote_id: build.id.to_string(),
                                         version: Version::new(build.build_number.as_str()),
@@ -181,6 +675,7 @@ pub async fn get_builds<'a>(
                                         flavor: flavor.to_owned(),
                                         repo_location: repo_url.to_owned(),
                                         installed: false,
+                                        expired: !build.has_artifacts(),
                                     };
                                     candidates.push(ci);
                                 }
@@ -189,27 +684,71 @@ pub async fn get_builds<'a>(
                         Err(e) => {
                             log::error!(
                                 "Failed to parse TeamCity repository information for repo {}: {}",
-                                &repo_url,
+                                redact::redact_url(repo_url),
                                 e,
                             );
+                            report_parse_failure(&repo.name, e.as_ref());
                         }
                     }
                 }
             }
-        } else if let Some(_repo_path) = &repo.repository_folder {
-            log::debug!("Repo defined a local path, will fetch from file system");
-            todo!()
         }
     }
 
     Ok(candidates)
 }
 
-/// Queries TeamCity repositories for the actual internal id of the build given by the [Candidate]
+/// Queries TeamCity repositories for the actual internal id of the build given by the [Candidate].
+/// `Folder` repositories are looked through instead, see [folder_repository::find_build]
 pub async fn get_with_build_id_by_candidate<'a>(
     http_client: &reqwest::Client,
     candidate: &SearchCandidate,
     valid_repositories: &[&'a CandidateRepository],
+) -> Result<Option<(InstallationCandidate, &'a CandidateRepository)>, Box<dyn std::error::Error>> {
+    find_build_by_candidate(http_client, candidate, None, false, valid_repositories).await
+}
+
+/// [get_with_build_id_by_candidate] for installing [candidate] of [product], before anything is downloaded:
+/// on a branch, the newest build [product] doesn't pass over is found (see [Product::skips_build]), and a
+/// blocked build asked for by its version is refused. With `install --nearest`, an expired build asked for by
+/// its version is replaced by the nearest newer one
+pub async fn get_installable_build_by_candidate<'a>(
+    http_client: &reqwest::Client,
+    candidate: &SearchCandidate,
+    product: &Product,
+    valid_repositories: &[&'a CandidateRepository],
+) -> Result<Option<(InstallationCandidate, &'a CandidateRepository)>, Box<dyn std::error::Error>> {
+    let found = find_build_by_candidate(
+        http_client,
+        candidate,
+        Some(product),
+        app::is_using_nearest_build(),
+        valid_repositories,
+    )
+    .await?;
+    if let Some((build, _)) = &found {
+        if product.skips_build(&build.version, &build.identifier) {
+            return Err(Box::new(GManError::new(&format!(
+                "Build {} of {} is blocked by the configuration ({}), pass --allow-blocked to install it anyway",
+                &build.version,
+                &build.product_name,
+                product
+                    .blocked_reason(&build.version, &build.identifier)
+                    .unwrap_or_default()
+            ))));
+        }
+    }
+    Ok(found)
+}
+
+/// The build [candidate] names. An expired build asked for by its version fails the lookup, unless [nearest]
+/// has it replaced by the nearest newer build on its branch
+async fn find_build_by_candidate<'a>(
+    http_client: &reqwest::Client,
+    candidate: &SearchCandidate,
+    product: Option<&Product>,
+    nearest: bool,
+    valid_repositories: &[&'a CandidateRepository],
 ) -> Result<Option<(InstallationCandidate, &'a CandidateRepository)>, Box<dyn std::error::Error>> {
     if valid_repositories.is_empty() {
         return Err(Box::new(GManError::new(
@@ -218,10 +757,16 @@ pub async fn get_with_build_id_by_candidate<'a>(
     }
 
     for repo in valid_repositories {
+        if repo.repository_type == RepositoryType::Folder {
+            if let Some(found) = folder_repository::find_build(repo, candidate, product) {
+                return Ok(Some((found, repo)));
+            }
+            continue;
+        }
         if let Some(repo_url) = &repo.repository_server {
             log::debug!(
                 "Repo defined a remote url, will fetch from remote '{}'",
-                &repo_url
+                redact::redact_url(repo_url)
             );
 
             let mut url = ensure_scheme(&repo_url)?;
@@ -246,34 +791,34 @@ pub async fn get_with_build_id_by_candidate<'a>(
             url.query_pairs_mut().append_key_only(policy).append_pair(
                 "locator",
                 &format!(
-                    "buildType:{},count:1,status:SUCCESS,{}",
-                    &candidate.flavor.teamcity_metadata.teamcity_id, &filter_for
+                    "buildType:{},count:{},status:SUCCESS,{},{}",
+                    &candidate.flavor.teamcity_metadata.teamcity_id,
+                    FALLBACK_BUILDS,
+                    personal_builds(),
+                    &filter_for
                 ),
+            )
+            .append_pair(
+                "fields",
+                &format!("build(id,number,branchName,{})", ARTIFACT_COUNT_FIELD),
             );
 
-            let request: reqwest::Request = match &repo.repository_credentials {
-                Some(credentials) => {
-                    let r = http_client
+            let request = match &repo.repository_credentials {
+                Some(credentials) => download::authorize(
+                    http_client
                         .get(url.clone())
-                        .header("Accept", "Application/json");
-                    match credentials {
-                        crate::RepositoryCredentials::BearerToken { token } => {
-                            r.bearer_auth(token).build().unwrap()
-                        }
-                        crate::RepositoryCredentials::BasicAuth { username, password } => {
-                            r.basic_auth(username, password.to_owned()).build().unwrap()
-                        }
-                    }
-                }
-                None => http_client.get(url.clone()).build().unwrap(),
+                        .header("Accept", "Application/json"),
+                    Some(credentials),
+                ),
+                None => http_client.get(url.clone()),
             };
 
             log::debug!(
                 "Sending get_build_id request to repo: {}",
-                &url.clone().to_string()
+                redact::redact_url(url.as_str())
             );
 
-            let res = http_client.execute(request).await?;
+            let (permit, res) = throttle::send(repo, request).await?;
             let res_status = res.status();
             if res_status != 200 {
                 if res_status == 401 || res_status == 403 {
@@ -290,14 +835,67 @@ pub async fn get_with_build_id_by_candidate<'a>(
             }
 
             let body = res.text().await?;
+            /* checking the builds' artifacts below takes permits of its own */
+            drop(permit);
 
-            match serde_json::from_str::<TeamCityBuilds>(&body) {
+            match parse_response::<TeamCityBuilds>(&body, &repo.name) {
                 Ok(team_city_root) => {
                     log::debug!("Got reponse from TeamCity build server");
-                    if team_city_root.builds.is_empty() {
-                        continue;
+                    let requested = team_city_root
+                        .builds
+                        .first()
+                        .map(|x| (x.id, x.build_number.to_owned(), x.branch_name.to_owned()));
+                    /* blocked builds on a branch are passed over for older ones */
+                    let installable = team_city_root.builds.into_iter().filter(|x| {
+                        candidate.version.is_some()
+                            || !product.is_some_and(|p| {
+                                p.skips_build(
+                                    &Version::new(&x.build_number),
+                                    x.branch_name.as_deref().unwrap_or_default(),
+                                )
+                            })
+                    });
+                    let mut found = first_with_binary(
+                        http_client,
+                        repo,
+                        repo_url,
+                        &candidate.flavor,
+                        installable,
+                    )
+                    .await?;
+                    /* a specific build was asked for, but TeamCity cleaned up its artifacts */
+                    if let (None, Some(_), Some((id, number, branch))) =
+                        (&found, &candidate.version, requested)
+                    {
+                        if !nearest {
+                            return Err(Box::new(GManError::new(&format!(
+                                "Build {} of {} has expired, its artifacts were cleaned up. Pass --nearest to install the nearest newer build instead",
+                                number, &candidate.product_name
+                            ))));
+                        }
+                        found = get_nearest_newer_build(
+                            http_client,
+                            repo,
+                            repo_url,
+                            &candidate.flavor,
+                            id,
+                            branch.as_deref(),
+                        )
+                        .await?;
+                        match &found {
+                            Some(nearest) => println!(
+                                "Build {} has expired, installing the nearest newer build {} instead",
+                                number, &nearest.build_number
+                            ),
+                            None => {
+                                return Err(Box::new(GManError::new(&format!(
+                                    "Build {} of {} has expired, and no newer build has artifacts",
+                                    number, &candidate.product_name
+                                ))))
+                            }
+                        }
                     }
-                    for build in team_city_root.builds {
+                    if let Some(build) = found {
                         let c = InstallationCandidate {
                             remote_id: build.id.to_string(),
                             product_name: candidate.product_name.to_owned(),
@@ -306,6 +904,7 @@ pub async fn get_with_build_id_by_candidate<'a>(
                             flavor: candidate.flavor.to_owned(),
                             repo_location: repo_url.to_owned(),
                             installed: false,
+                            expired: false,
                         };
                         return Ok(Some((c, repo)));
                     }
@@ -313,15 +912,13 @@ pub async fn get_with_build_id_by_candidate<'a>(
                 Err(e) => {
                     log::error!(
                         "Failed to parse TeamCity repository information for repo {} ({})",
-                        &repo_url,
+                        redact::redact_url(repo_url),
                         e,
                     );
+                    report_parse_failure(&repo.name, e.as_ref());
                     continue;
                 }
             }
-        } else if let Some(_repo_path) = &repo.repository_folder {
-            log::debug!("Repo defined a local path, will fetch from file system");
-            todo!()
         }
     }
 
@@ -330,177 +927,511 @@ pub async fn get_with_build_id_by_candidate<'a>(
     )))
 }
 
-/// Downloads the given artifact from the build server, first into the temp directory, and then moves it to the cache directory
-pub async fn download_artifact<'a, P>(
+/// The oldest successful build after [build_id] on [branch] (or the default branch) whose artifacts still
+/// contain the binary of [flavor], standing in for [build_id] when its artifacts expired
+async fn get_nearest_newer_build(
     http_client: &reqwest::Client,
-    candidate: &'a InstallationCandidate,
     repo: &CandidateRepository,
-    temp_dir: P,
-    cache_dir: P,
-    chunk_size: u64,
-) -> Result<PathBuf, Box<dyn std::error::Error>>
+    repo_url: &str,
+    flavor: &Flavor,
+    build_id: u32,
+    branch: Option<&str>,
+) -> Result<Option<TeamCityBuild>, Box<dyn std::error::Error>> {
+    let branch = match branch {
+        Some(name) => format!("(name:{})", name),
+        None => "default:any".to_owned(),
+    };
+    let mut url = ensure_scheme(repo_url)?;
+    url.set_path("app/rest/builds");
+    url.query_pairs_mut()
+        .append_pair(
+            "locator",
+            &format!(
+                "buildType:{},branch:{},state:finished,status:SUCCESS,{},sinceBuild:(id:{}),count:100",
+                &flavor.teamcity_metadata.teamcity_id,
+                branch,
+                personal_builds(),
+                build_id
+            ),
+        )
+        .append_pair(
+            "fields",
+            &format!("build(id,number,branchName,{})", ARTIFACT_COUNT_FIELD),
+        );
+    let builds = get_json::<TeamCityBuilds>(http_client, repo, url).await?;
+    /* newest first from TeamCity, the nearest is the oldest */
+    first_with_binary(
+        http_client,
+        repo,
+        repo_url,
+        flavor,
+        builds.builds.into_iter().rev(),
+    )
+    .await
+}
+
+/// Sends an authorized GET for [url] to [repo] and parses the JSON response
+async fn get_json<T>(
+    http_client: &reqwest::Client,
+    repo: &CandidateRepository,
+    url: Url,
+) -> Result<T, Box<dyn std::error::Error>>
 where
-    P: AsRef<Path>,
+    T: DeserializeOwned + UnknownFields,
 {
     log::debug!(
-        "Contacting TeamCity for download link on candidate {}",
-        &candidate.remote_id
+        "Sending request to repo {}: {}",
+        &repo.name,
+        redact::redact_url(url.as_str())
     );
+    let request = download::authorize(
+        http_client.get(url).header("Accept", "Application/json"),
+        repo.repository_credentials.as_ref(),
+    );
+    let (_permit, res) = throttle::send(repo, request).await?;
+    let status = res.status();
+    if status == 401 || status == 403 {
+        return Err(Box::new(GManError::new(&format!(
+            "Not authorized to access repository {}",
+            &repo.name
+        ))));
+    }
+    if status != 200 {
+        return Err(Box::new(GManError::new(&format!(
+            "Repository {} responded with status code {}",
+            &repo.name, status
+        ))));
+    }
+    let body = res.text().await?;
+    parse_response::<T>(&body, &repo.name)
+        .inspect_err(|e| report_parse_failure(&repo.name, e.as_ref()))
+}
 
-    if let Some(u) = &repo.repository_server {
-        let uri_str = format!(
-            "{}/repository/download/{}/{}:id/{}",
-            u,
-            candidate.flavor.teamcity_metadata.teamcity_id,
-            candidate.remote_id,
-            candidate
-                .flavor
-                .teamcity_metadata
-                .teamcity_binary_path
-                .to_str()
-                .expect("Expected a valid binary path for downloading"),
-        );
+/// Lists every file and directory in the artifacts of [build] on [repo], without downloading anything
+pub async fn get_artifact_files(
+    http_client: &reqwest::Client,
+    repo: &CandidateRepository,
+    build: &InstallationCandidate,
+) -> Result<Vec<TeamCityArtifactFile>, Box<dyn std::error::Error>> {
+    let repo_url = match &repo.repository_server {
+        Some(u) => u,
+        None => {
+            return Err(Box::new(GManError::new(&format!(
+                "Repository {} has no server to list artifacts from",
+                &repo.name
+            ))))
+        }
+    };
 
-        let url = ensure_scheme(&uri_str)?;
+    let mut url = ensure_scheme(repo_url)?;
+    url.set_path(&format!(
+        "app/rest/builds/id:{}/artifacts/children/",
+        &build.remote_id
+    ));
+    url.query_pairs_mut()
+        .append_pair("locator", "recursive:true")
+        .append_pair("fields", "file(name,fullName,size)");
+    let mut files = get_json::<TeamCityArtifactFiles>(http_client, repo, url)
+        .await?
+        .files;
+    files.sort_by(|a, b| a.full_name.cmp(&b.full_name));
+    Ok(files)
+}
 
-        log::debug!("Downloading from url {}", &url.as_str());
+/// Size in bytes of [build]'s binary on [repo], from the artifact metadata so nothing is downloaded
+pub async fn get_artifact_size(
+    http_client: &reqwest::Client,
+    repo: &CandidateRepository,
+    build: &InstallationCandidate,
+) -> Result<Option<u64>, Box<dyn std::error::Error>> {
+    Ok(get_artifact_metadata(http_client, repo, build).await?.size)
+}
 
-        /* Send HEAD for file size info */
-        let request: reqwest::Request = match &repo.repository_credentials {
-            Some(credentials) => {
-                let r = http_client.head(url.clone());
-                match credentials {
-                    crate::RepositoryCredentials::BearerToken { token } => {
-                        r.bearer_auth(token).build().unwrap()
-                    }
-                    crate::RepositoryCredentials::BasicAuth { username, password } => {
-                        r.basic_auth(username, password.to_owned()).build().unwrap()
-                    }
-                }
-            }
-            None => http_client.get(url.clone()).build().unwrap(),
-        };
-        let response = http_client.execute(request).await?;
-        let res_status = response.status();
-        if res_status != 200 {
-            log::warn!(
-                "Failed to get TeamCity download file size {}, ({})",
-                &repo.name,
-                &res_status,
-            );
-            if res_status == 401 || res_status == 403 {
-                eprintln!("Not authorized to access repository {}", &repo.name);
-                return Err(Box::new(GManError::new("Not authorized")));
-            }
-            if res_status == 404 {
-                eprintln!("File not found on repo {}", &repo.name);
-                return Err(Box::new(GManError::new(&format!(
-                    "File not found on repository {}",
-                    &repo.name
-                ))));
+/// Metadata of [build]'s binary on [repo], e.g. its size and, if the server reports one, its checksum
+pub async fn get_artifact_metadata(
+    http_client: &reqwest::Client,
+    repo: &CandidateRepository,
+    build: &InstallationCandidate,
+) -> Result<TeamCityArtifactFile, Box<dyn std::error::Error>> {
+    let repo_url = match &repo.repository_server {
+        Some(u) => u,
+        None => {
+            return Err(Box::new(GManError::new(&format!(
+                "Repository {} has no server to get artifact metadata from",
+                &repo.name
+            ))))
+        }
+    };
+
+    let mut url = ensure_scheme(repo_url)?;
+    url.set_path(&format!(
+        "app/rest/builds/id:{}/artifacts/metadata/{}",
+        &build.remote_id,
+        build
+            .flavor
+            .teamcity_metadata
+            .teamcity_binary_path
+            .to_string_lossy()
+    ));
+    /* no `fields`, so checksums of servers that have them come along */
+    get_json::<TeamCityArtifactFile>(http_client, repo, url).await
+}
+
+/// Queries [repo] for the VCS changes that went into the builds after [from] up to and including [to],
+/// newest first. Only builds on [to]'s branch are considered, so unrelated branches don't show up
+pub async fn get_changes_between(
+    http_client: &reqwest::Client,
+    repo: &CandidateRepository,
+    from: &InstallationCandidate,
+    to: &InstallationCandidate,
+) -> Result<Vec<TeamCityChange>, Box<dyn std::error::Error>> {
+    let repo_url = match &repo.repository_server {
+        Some(u) => u,
+        None => {
+            return Err(Box::new(GManError::new(&format!(
+                "Repository {} has no server to query changes from",
+                &repo.name
+            ))))
+        }
+    };
+
+    /* failed and canceled builds count too, the changes they picked up aren't repeated in the next build.
+     * Personal builds never do, their changes aren't committed */
+    let branch = if to.identifier == to.version.as_ref() {
+        "default:any".to_owned()
+    } else {
+        format!("(name:{})", to.identifier)
+    };
+    let mut url = ensure_scheme(repo_url)?;
+    url.set_path("app/rest/builds");
+    url.query_pairs_mut()
+        .append_pair(
+            "locator",
+            &format!(
+                "buildType:{},branch:{},defaultFilter:false,personal:false,state:finished,sinceBuild:(id:{}),untilBuild:(id:{}),count:1000",
+                &to.flavor.teamcity_metadata.teamcity_id, branch, &from.remote_id, &to.remote_id
+            ),
+        )
+        .append_pair("fields", "build(id,number,branchName)");
+    let builds = get_json::<TeamCityBuilds>(http_client, repo, url).await?;
+
+    let mut changes: Vec<TeamCityChange> = Vec::new();
+    for build in builds.builds {
+        let mut url = ensure_scheme(repo_url)?;
+        url.set_path("app/rest/changes");
+        url.query_pairs_mut()
+            .append_pair("locator", &format!("build:(id:{})", build.id))
+            .append_pair("fields", "change(id,version,username,comment)");
+        let build_changes = get_json::<TeamCityChanges>(http_client, repo, url).await?;
+        for change in build_changes.changes {
+            if !changes.iter().any(|x| x.id == change.id) {
+                changes.push(change);
             }
-            return Err(Box::new(GManError::new(
-                "Unknown error occurred during download request",
-            )));
         }
-        let length = response
-            .headers()
-            .get(reqwest::header::CONTENT_LENGTH)
-            .ok_or("response doesn't include the content length")?;
-        let length =
-            u64::from_str(length.to_str()?).map_err(|_| "invalid Content-Length header")?;
-
-        let output_file_temp_path = &candidate.make_output_for_candidate(temp_dir);
-        /* create the parent directory if necessary */
-        let prefix = output_file_temp_path.parent().unwrap();
-        tokio::fs::create_dir_all(prefix).await?;
-
-        let mut output_file_temp = tokio::fs::File::create(&output_file_temp_path).await?;
-
-        /* Send GET for body */
-        let request: reqwest::Request = match &repo.repository_credentials {
-            Some(credentials) => {
-                let r = http_client.head(url.clone());
-                match credentials {
-                    crate::RepositoryCredentials::BearerToken { token } => {
-                        r.bearer_auth(token).build().unwrap()
-                    }
-                    crate::RepositoryCredentials::BasicAuth { username, password } => {
-                        r.basic_auth(username, password.to_owned()).build().unwrap()
-                    }
-                }
+    }
+    changes.sort_by_key(|x| std::cmp::Reverse(x.id));
+    Ok(changes)
+}
+
+/// Locator dimension selecting personal builds: none, unless [include] (see `--include-personal`).
+/// Personal builds are developers' uncommitted changes and otherwise end up installed by accident
+fn personal_builds_locator(include: bool) -> &'static str {
+    if include {
+        "personal:any"
+    } else {
+        "personal:false"
+    }
+}
+
+fn personal_builds() -> &'static str {
+    personal_builds_locator(app::is_including_personal_builds())
+}
+
+/// Formats [time] the way TeamCity locators expect dates, e.g. `20240301T101010+0000`
+pub fn teamcity_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or_default();
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+
+    /* civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html */
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}+0000",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+/// Queries the repositories for the newest successful build of [search]'s flavor, for the `latest` and `nightly` targets.
+///
+/// Only builds on [branch] are considered if given, otherwise builds on any branch the product lists.
+/// With [since], builds that finished before then are ignored, and so are the builds the product blocks
+pub async fn get_newest_build<'a>(
+    http_client: &reqwest::Client,
+    search: &SearchCandidate,
+    product: &Product,
+    branch: Option<&str>,
+    since: Option<SystemTime>,
+    valid_repositories: &[&'a CandidateRepository],
+) -> Result<Option<(InstallationCandidate, &'a CandidateRepository)>, Box<dyn std::error::Error>> {
+    /* enough builds to find one on a listed branch without paging */
+    const SEARCH_DEPTH: u32 = 50;
+
+    for repo in valid_repositories {
+        if repo.repository_type == RepositoryType::Folder {
+            if let Some(found) =
+                folder_repository::get_newest_build(repo, search, product, branch, since)
+            {
+                return Ok(Some((found, repo)));
             }
-            None => http_client.get(url.clone()).build().unwrap(),
+            continue;
+        }
+        let repo_url = match &repo.repository_server {
+            Some(u) => u,
+            None => continue,
         };
 
-        let response = http_client.execute(request).await?;
-        let res_status = response.status();
-        if res_status != 200 {
+        let mut locator = format!(
+            "buildType:{},state:finished,status:SUCCESS,{},count:{}",
+            &search.flavor.teamcity_metadata.teamcity_id,
+            personal_builds(),
+            SEARCH_DEPTH
+        );
+        match branch {
+            Some(branch) => locator.push_str(&format!(",branch:(name:{})", branch)),
+            None => locator.push_str(&format!(
+                ",branch:(default:any,policy:{})",
+                product.branch_policy_for(&search.flavor)
+            )),
+        }
+        if let Some(since) = since {
+            locator.push_str(&format!(",sinceDate:{}", teamcity_date(since)));
+        }
+
+        let mut url = ensure_scheme(repo_url)?;
+        url.set_path("app/rest/builds");
+        url.query_pairs_mut()
+            .append_pair("locator", &locator)
+            .append_pair(
+                "fields",
+                &format!("build(id,number,branchName,{})", ARTIFACT_COUNT_FIELD),
+            );
+        log::debug!(
+            "Searching for the newest build: {}",
+            redact::redact_url(url.as_str())
+        );
+
+        let request = download::authorize(
+            http_client.get(url).header("Accept", "Application/json"),
+            repo.repository_credentials.as_ref(),
+        );
+        let (permit, res) = throttle::send(repo, request).await?;
+        if res.status() != 200 {
             log::warn!(
-                "Failed to get TeamCity download file size {}, ({})",
+                "Failed to get builds from repo {}, status code: {}",
                 &repo.name,
-                &res_status,
+                res.status()
             );
-            if res_status == 401 || res_status == 403 {
-                eprintln!("Not authorized to access repository {}", &repo.name);
-                return Err(Box::new(GManError::new("Not authorized")));
-            }
-            if res_status == 404 {
-                eprintln!("File not found on repo {}", &repo.name);
-                return Err(Box::new(GManError::new("File not found")));
-            }
-            return Err(Box::new(GManError::new(
-                "Unknown error occurred during download request",
-            )));
+            continue;
         }
 
-        /* disable logging here  */
-        let last_level = app::disable_logging();
-        let progress_bar = ProgressBar::new(length);
-        progress_bar.set_style(ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta})")
-                .unwrap()
-                .with_key("eta", |state: &ProgressState, w: &mut dyn Write| write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap())
-                .progress_chars("#>-"));
-
-        let mut downloaded: u64 = 0;
-        for range in PartialRangeIter::new(0, length - 1, chunk_size)? {
-            let request: reqwest::Request = match &repo.repository_credentials {
-                Some(credentials) => {
-                    let r = http_client.get(url.clone()).header(RANGE, range);
-                    match credentials {
-                        crate::RepositoryCredentials::BearerToken { token } => {
-                            r.bearer_auth(token).build().unwrap()
-                        }
-                        crate::RepositoryCredentials::BasicAuth { username, password } => {
-                            r.basic_auth(username, password.to_owned()).build().unwrap()
-                        }
-                    }
-                }
-                None => http_client.get(url.clone()).build().unwrap(),
+        let body = res.text().await?;
+        /* checking the builds' artifacts below takes permits of its own */
+        drop(permit);
+        let builds = match parse_response::<TeamCityBuilds>(&body, &repo.name) {
+            Ok(b) => b,
+            Err(e) => {
+                report_parse_failure(&repo.name, e.as_ref());
+                continue;
+            }
+        };
+        let listed = builds
+            .builds
+            .into_iter()
+            .filter(|build| match branch {
+                Some(_) => true,
+                None => build
+                    .branch_name
+                    .as_ref()
+                    .map(|b| product.lists_branch(&search.flavor, b))
+                    .unwrap_or(true),
+            })
+            .filter(|build| {
+                !product.skips_build(
+                    &Version::new(&build.build_number),
+                    build.branch_name.as_deref().or(branch).unwrap_or_default(),
+                )
+            });
+        let newest = first_with_binary(http_client, repo, repo_url, &search.flavor, listed).await?;
+        if let Some(build) = newest {
+            let candidate = InstallationCandidate {
+                remote_id: build.id.to_string(),
+                product_name: search.product_name.to_owned(),
+                version: Version::new(build.build_number.as_str()),
+                identifier: build
+                    .branch_name
+                    .or(branch.map(|x| x.to_owned()))
+                    .unwrap_or(build.build_number.to_owned()),
+                flavor: search.flavor.to_owned(),
+                repo_location: repo_url.to_owned(),
+                installed: false,
+                expired: false,
             };
-            let response = http_client.execute(request).await?;
+            return Ok(Some((candidate, repo)));
+        }
+    }
 
-            let status = response.status();
-            if !(status == 200 || status == 206) {
-                return Err(Box::new(GManError::new("Unexpected error during download")));
-            }
+    Ok(None)
+}
 
-            let mut byte_stream = response.bytes_stream();
-            while let Some(item) = byte_stream.next().await {
-                tokio::io::copy(&mut item?.as_ref(), &mut output_file_temp).await?;
-            }
+/// The SHA-256 checksum of [build]'s binary, read from the artifact [path] of the same build, see
+/// [crate::product::FlavorMetadata::checksum_artifact]
+async fn get_artifact_checksum(
+    http_client: &reqwest::Client,
+    repo: &CandidateRepository,
+    build: &InstallationCandidate,
+    path: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let repo_url = match &repo.repository_server {
+        Some(u) => u,
+        None => {
+            return Err(Box::new(GManError::new(&format!(
+                "Repository {} has no server to get the checksum from",
+                &repo.name
+            ))))
+        }
+    };
+    let mut url = ensure_scheme(repo_url)?;
+    url.set_path(&format!(
+        "app/rest/builds/id:{}/artifacts/content/{}",
+        &build.remote_id, path
+    ));
+    log::debug!(
+        "Getting the checksum of {} from {}",
+        &build.remote_id,
+        redact::redact_url(url.as_str())
+    );
+    let request = download::authorize(http_client.get(url), repo.repository_credentials.as_ref());
+    let (_permit, res) = throttle::send(repo, request).await?;
+    let status = res.status();
+    if status != 200 {
+        return Err(Box::new(GManError::new(&format!(
+            "Checksum artifact {} of build {} couldn't be read, repository {} responded with status code {}",
+            path, &build.version, &repo.name, status
+        ))));
+    }
+    let body = res.text().await?;
+    download::checksum_from_file(&body).ok_or_else(|| {
+        Box::new(GManError::new(&format!(
+            "Checksum artifact {} of build {} doesn't hold a SHA-256 checksum",
+            path, &build.version
+        ))) as Box<dyn std::error::Error>
+    })
+}
 
-            downloaded += chunk_size;
+/// Url of [candidate]'s binary on the TeamCity server at [repo_url]
+pub fn artifact_download_url(
+    repo_url: &str,
+    candidate: &InstallationCandidate,
+) -> Result<Url, Box<dyn std::error::Error>> {
+    let uri_str = format!(
+        "{}/repository/download/{}/{}:id/{}",
+        repo_url,
+        candidate.flavor.teamcity_metadata.teamcity_id,
+        candidate.remote_id,
+        candidate
+            .flavor
+            .teamcity_metadata
+            .teamcity_binary_path
+            .to_str()
+            .expect("Expected a valid binary path for downloading"),
+    );
+    ensure_scheme(&uri_str)
+}
 
-            progress_bar.set_position(downloaded);
-        }
+/// Downloads the given artifact from the build server, first into the temp directory, and then moves it to the cache directory
+pub async fn download_artifact<'a, P>(
+    http_client: &reqwest::Client,
+    candidate: &'a InstallationCandidate,
+    repo: &CandidateRepository,
+    temp_dir: P,
+    cache_dir: P,
+    chunk_size: u64,
+) -> Result<PathBuf, Box<dyn std::error::Error>>
+where
+    P: AsRef<Path>,
+{
+    if repo.repository_type == RepositoryType::Folder {
+        return folder_repository::copy_to_cache(candidate, temp_dir, cache_dir);
+    }
 
-        /* Move file to cache directory */
-        let output_file_cache_path = candidate.make_output_for_candidate(cache_dir);
-        tokio::fs::rename(&output_file_temp_path, &output_file_cache_path).await?;
-        app::enable_logging(last_level);
+    log::debug!(
+        "Contacting TeamCity for download link on candidate {}",
+        &candidate.remote_id
+    );
 
-        Ok(output_file_cache_path)
+    if let Some(u) = &repo.repository_server {
+        let url = artifact_download_url(u, candidate)?;
+
+        /* cross-checked after downloading, proxies have been known to truncate artifacts */
+        let mut expected = match get_artifact_metadata(http_client, repo, candidate).await {
+            Ok(metadata) => download::ExpectedFile {
+                size: metadata.size,
+                sha256: metadata.sha256,
+            },
+            Err(e) => {
+                log::warn!(
+                    "Failed to get the artifact metadata of {}, the download won't be verified: {}",
+                    &candidate.remote_id,
+                    e
+                );
+                download::ExpectedFile::default()
+            }
+        };
+        /* a checksum the flavor declares is required, unlike the one servers may report */
+        let checksum_artifact = candidate
+            .flavor
+            .metadata
+            .as_ref()
+            .and_then(|x| x.checksum_artifact.as_deref());
+        if app::is_skipping_checksums() {
+            log::warn!(
+                "Skipping the checksum verification of {} {}",
+                &candidate.product_name,
+                &candidate.version
+            );
+            expected.sha256 = None;
+        } else if let Some(path) = checksum_artifact {
+            expected.sha256 =
+                Some(get_artifact_checksum(http_client, repo, candidate, path).await?);
+        }
+
+        download::download_to_cache(
+            http_client,
+            &url,
+            Some(repo),
+            &format!("repository {}", &repo.name),
+            &candidate.make_output_for_candidate(temp_dir),
+            &candidate.make_output_for_candidate(cache_dir),
+            chunk_size,
+            Some(&expected),
+        )
+        .await
     } else {
         Err(Box::new(GManError::new(
             "Repository did not have a Server specified",
@@ -508,38 +1439,117 @@ where
     }
 }
 
-#[derive(Debug)]
-struct PartialRangeIter {
-    start: u64,
-    end: u64,
-    buffer_size: u64,
-}
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
 
-impl PartialRangeIter {
-    pub fn new(start: u64, end: u64, buffer_size: u64) -> Result<Self, Box<dyn std::error::Error>> {
-        if buffer_size == 0 {
-            Err("invalid buffer_size, give a value greater than zero.")?;
-        }
-        Ok(PartialRangeIter {
-            start,
-            end,
-            buffer_size,
-        })
-    }
-}
+    use super::{
+        parse_response, personal_builds_locator, teamcity_date, BuildUrl, TeamCityArtifactFiles,
+        TeamCityBuilds, TeamCityChanges, TeamCityRoot, UnknownFields,
+    };
 
-impl Iterator for PartialRangeIter {
-    type Item = HeaderValue;
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.start > self.end {
-            None
-        } else {
-            let prev_start = self.start;
-            self.start += std::cmp::min(self.buffer_size as u64, self.end - self.start + 1);
-            Some(
-                HeaderValue::from_str(&format!("bytes={}-{}", prev_start, self.start - 1))
-                    .expect("string provided by format!"),
+    #[test]
+    fn parse_build_urls() {
+        assert_eq!(
+            BuildUrl::parse("https://tc.example.com/buildConfiguration/Gravio_GravioHubKit4/20211?buildTab=artifacts"),
+            Some(BuildUrl {
+                host: "tc.example.com".into(),
+                build_type_id: "Gravio_GravioHubKit4".into(),
+                build_id: 20211,
+            })
+        );
+        assert_eq!(
+            BuildUrl::parse(
+                "http://tc.example.com:8111/viewLog.html?buildId=20211&buildTypeId=Gravio_GravioHubKit4"
             )
-        }
+            .map(|x| (x.build_type_id, x.build_id)),
+            Some(("Gravio_GravioHubKit4".into(), 20211))
+        );
+        assert_eq!(
+            BuildUrl::parse("https://tc.example.com/buildConfiguration/Gravio_GravioHubKit4"),
+            None
+        );
+        assert_eq!(BuildUrl::parse("hubkit"), None);
+    }
+
+    #[test]
+    fn parse_artifact_files() {
+        let body = r#"{"count":2,"file":[{"name":"WindowsHubKit","fullName":"WindowsHubKit"},{"name":"GravioHubKit.msi","fullName":"WindowsHubKit/GravioHubKit.msi","size":104857600}]}"#;
+        let files = parse_response::<TeamCityArtifactFiles>(body, "test").unwrap();
+        assert_eq!(files.files[0].size, None);
+        assert_eq!(
+            files.files[1].full_name.as_deref(),
+            Some("WindowsHubKit/GravioHubKit.msi")
+        );
+        assert_eq!(files.files[1].size, Some(104857600));
+        assert!(files.unknown_fields().is_empty());
+    }
+
+    #[test]
+    fn parse_changes() {
+        let body = r#"{"count":2,"change":[{"id":"812","version":"9f2c1e0a7b","username":"mika","comment":"Fix sensor reconnect\n\nLonger description"},{"id":811,"version":"41aa03","comment":""}]}"#;
+        let changes = parse_response::<TeamCityChanges>(body, "test").unwrap();
+        assert_eq!(changes.changes[0].id, 812);
+        assert_eq!(changes.changes[0].summary(), "Fix sensor reconnect");
+        assert_eq!(changes.changes[1].username, None);
+        assert_eq!(changes.changes[1].summary(), "");
+        assert!(changes.unknown_fields().is_empty());
+    }
+
+    #[test]
+    fn builds_without_artifacts() {
+        let body = r#"{"count":3,"build":[{"id":3,"number":"3","artifacts":{"count":0}},{"id":2,"number":"2","artifacts":{"count":1}},{"id":1,"number":"1"}]}"#;
+        let builds = parse_response::<TeamCityBuilds>(body, "test").unwrap();
+        assert!(!builds.builds[0].has_artifacts());
+        assert!(builds.builds[1].has_artifacts());
+        /* unknown counts don't hide builds */
+        assert!(builds.builds[2].has_artifacts());
+    }
+
+    #[test]
+    fn personal_builds_excluded_by_default() {
+        assert_eq!(personal_builds_locator(false), "personal:false");
+        assert_eq!(personal_builds_locator(true), "personal:any");
+    }
+
+    #[test]
+    fn teamcity_date_format() {
+        assert_eq!(teamcity_date(UNIX_EPOCH), "19700101T000000+0000");
+        assert_eq!(
+            teamcity_date(UNIX_EPOCH + Duration::from_secs(1_709_287_810)),
+            "20240301T101010+0000"
+        );
+    }
+
+    #[test]
+    fn parse_branch_schema_variants() {
+        /* pre-2023 layout: builds wrapped in an object */
+        let wrapped = r#"{"branch":[{"name":"develop","builds":{"count":1,"build":[{"id":7049,"number":"5.2.1-7049","finishDate":"20240301T101010+0900"}]}}]}"#;
+        let root = parse_response::<TeamCityRoot>(wrapped, "test").unwrap();
+        assert_eq!(root.branches[0].builds[0].build_number, "5.2.1-7049");
+        assert!(root.unknown_fields().is_empty());
+
+        /* bare build arrays, string ids, renamed and new fields, branches without builds */
+        let variant = r#"{"branches":[{"name":"develop","builds":[{"id":"7050","buildNumber":"5.2.1-7050","finishOnAgentDate":"x","newField":1}]},{"name":"empty"}],"count":2}"#;
+        let root = parse_response::<TeamCityRoot>(variant, "test").unwrap();
+        assert_eq!(root.branches[0].builds[0].id, 7050);
+        assert_eq!(root.branches[0].builds[0].build_number, "5.2.1-7050");
+        assert!(root.branches[1].builds.is_empty());
+        assert_eq!(
+            root.unknown_fields(),
+            vec!["count", "branch[0].builds[0].newField"]
+        );
+
+        let builds = parse_response::<TeamCityBuilds>(r#"{"count":0}"#, "test").unwrap();
+        assert!(builds.builds.is_empty());
+    }
+
+    #[test]
+    fn parse_failure_names_path() {
+        let broken = r#"{"count":1,"build":[{"id":1,"number":"1.0"},{"id":2}]}"#;
+        let e = parse_response::<TeamCityBuilds>(broken, "test").unwrap_err();
+        let message = e.to_string();
+        assert!(message.contains("build[1]"), "{}", message);
+        assert!(message.contains("number"), "{}", message);
     }
 }
@@ -0,0 +1,298 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use lazy_static::lazy_static;
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::Instant,
+};
+
+use crate::{gman_error::GManError, CandidateRepository};
+
+/// How many times a request the repository rate limited is sent again before its response is taken as is
+const MAX_RATE_LIMITED_RETRIES: u32 = 3;
+
+/// Wait before retrying a rate limited request when the response doesn't say how long to wait
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// Longest `Retry-After` waited for. Rate limited requests asking for longer fail instead of hanging gman
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+/// Limits on the requests gman makes to one repository's REST API, shared by everything running in this process
+#[derive(Debug)]
+struct RepositoryThrottle {
+    concurrent: Option<Arc<Semaphore>>,
+    interval: Duration,
+    /// Earliest time the next request may be sent
+    next_request: tokio::sync::Mutex<Instant>,
+}
+
+lazy_static! {
+    /// Throttles by repository name, created on the first request to each
+    static ref THROTTLES: Mutex<HashMap<String, Arc<RepositoryThrottle>>> = Mutex::new(HashMap::new());
+    /// Until when each repository that rate limited a request asked to be left alone, by repository name
+    static ref BACKOFFS: Mutex<HashMap<String, Instant>> = Mutex::new(HashMap::new());
+}
+
+/// Leave to send a request to a repository. Requests counted against `MaxConcurrentRequests` are in flight
+/// until it's dropped
+#[derive(Debug)]
+pub struct ThrottlePermit {
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+/// The throttle of [repo], or None if it doesn't limit its requests
+fn throttle_for(repo: &CandidateRepository) -> Option<Arc<RepositoryThrottle>> {
+    if repo.max_concurrent_requests.is_none() && repo.min_request_interval_ms.is_none() {
+        return None;
+    }
+    let mut throttles = THROTTLES.lock().unwrap();
+    let throttle = throttles.entry(repo.name.to_owned()).or_insert_with(|| {
+        Arc::new(RepositoryThrottle {
+            /* a limit of 0 would never let anything through */
+            concurrent: repo
+                .max_concurrent_requests
+                .map(|x| Arc::new(Semaphore::new(x.max(1)))),
+            interval: Duration::from_millis(repo.min_request_interval_ms.unwrap_or_default()),
+            next_request: tokio::sync::Mutex::new(Instant::now()),
+        })
+    });
+    Some(throttle.clone())
+}
+
+/// Waits until [repo]'s `MaxConcurrentRequests` and `MinRequestIntervalMs` allow another request to its API.
+/// Hold on to the permit until the response has been read
+pub async fn acquire(repo: &CandidateRepository) -> ThrottlePermit {
+    let backoff = BACKOFFS.lock().unwrap().get(&repo.name).copied();
+    if let Some(until) = backoff {
+        if until > Instant::now() {
+            tokio::time::sleep_until(until).await;
+        }
+    }
+    let Some(throttle) = throttle_for(repo) else {
+        return ThrottlePermit { _permit: None };
+    };
+    let permit = match &throttle.concurrent {
+        Some(semaphore) => semaphore.clone().acquire_owned().await.ok(),
+        None => None,
+    };
+    if !throttle.interval.is_zero() {
+        let mut next_request = throttle.next_request.lock().await;
+        let now = Instant::now();
+        if *next_request > now {
+            log::debug!(
+                "Waiting {}ms before the next request to {}",
+                (*next_request - now).as_millis(),
+                &repo.name
+            );
+            tokio::time::sleep_until(*next_request).await;
+        }
+        *next_request = Instant::now() + throttle.interval;
+    }
+    ThrottlePermit { _permit: permit }
+}
+
+/// Sends [request] to [repo]'s API once [acquire] allows it. Responses rate limiting the request, 429s and
+/// 503s with a `Retry-After`, are waited out and the request sent again, holding back every other request
+/// to [repo] meanwhile. Hold on to the permit until the response has been read
+pub async fn send(
+    repo: &CandidateRepository,
+    request: reqwest::RequestBuilder,
+) -> Result<(ThrottlePermit, reqwest::Response), Box<dyn std::error::Error>> {
+    let mut retries = 0;
+    loop {
+        let attempt = request.try_clone().ok_or_else(|| {
+            GManError::new(&format!("Request to {} can't be sent again", &repo.name))
+        })?;
+        let permit = acquire(repo).await;
+        let res = attempt.send().await?;
+        let Some(wait) = rate_limited_for(&res, SystemTime::now()) else {
+            return Ok((permit, res));
+        };
+        if retries == MAX_RATE_LIMITED_RETRIES || wait > MAX_RETRY_AFTER {
+            eprintln!(
+                "Repository {} is rate limiting requests, giving up after {} retries and a wait of {}s asked for",
+                &repo.name,
+                retries,
+                wait.as_secs()
+            );
+            return Ok((permit, res));
+        }
+        retries += 1;
+        eprintln!(
+            "Repository {} is rate limiting requests, retrying in {}s",
+            &repo.name,
+            wait.as_secs().max(1)
+        );
+        BACKOFFS
+            .lock()
+            .unwrap()
+            .insert(repo.name.to_owned(), Instant::now() + wait);
+    }
+}
+
+/// How long [res] asks to wait before sending the request again, if it rate limits it
+fn rate_limited_for(res: &reqwest::Response, now: SystemTime) -> Option<Duration> {
+    let retry_after = res
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| retry_after(x, now));
+    match res.status().as_u16() {
+        429 => Some(retry_after.unwrap_or(DEFAULT_RETRY_AFTER)),
+        503 => retry_after,
+        _ => None,
+    }
+}
+
+/// Wait a `Retry-After` header of [value] asks for at [now], given in seconds or as an HTTP date like
+/// `Wed, 21 Oct 2015 07:28:00 GMT`
+fn retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = httpdate::parse_http_date(value).ok()?;
+    Some(at.duration_since(now).unwrap_or(Duration::ZERO))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        time::Instant,
+    };
+
+    use crate::{client_config::RepositoryType, CandidateRepository};
+
+    use super::{acquire, retry_after, send};
+
+    /// Serves [responses] in turn, one per connection, on a local port. Returns its url and how many requests
+    /// it has answered
+    async fn serve(responses: Vec<&'static str>) -> (String, Arc<AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let answered = Arc::new(AtomicUsize::new(0));
+        let counter = answered.clone();
+        tokio::spawn(async move {
+            for response in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+                while !request.windows(4).any(|x| x == b"\r\n\r\n") {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    if read == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buffer[..read]);
+                }
+                counter.fetch_add(1, Ordering::SeqCst);
+                stream.write_all(response.as_bytes()).await.unwrap();
+                stream.shutdown().await.unwrap();
+            }
+        });
+        (url, answered)
+    }
+
+    fn make_repository(name: &str) -> CandidateRepository {
+        CandidateRepository {
+            name: name.into(),
+            repository_type: RepositoryType::TeamCity,
+            platforms: Vec::new(),
+            repository_folder: None,
+            repository_server: Some("teamcity.example.com".into()),
+            repository_credentials: None,
+            use_netrc: false,
+            products: Vec::new(),
+            max_concurrent_requests: Some(1),
+            min_request_interval_ms: Some(50),
+        }
+    }
+
+    /* tokio's clock is paused and only moves on when every task waits, so how busy the machine is doesn't matter */
+    #[tokio::test(start_paused = true)]
+    async fn requests_wait_for_the_interval_and_free_permits() {
+        let repo = make_repository("throttled");
+        let start = Instant::now();
+        drop(acquire(&repo).await);
+        drop(acquire(&repo).await);
+        let _held = acquire(&repo).await;
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        /* the single permit is held, so another request has to wait for it */
+        let waiting = tokio::time::timeout(Duration::from_millis(200), acquire(&repo)).await;
+        assert!(waiting.is_err());
+
+        let unlimited = CandidateRepository {
+            max_concurrent_requests: None,
+            min_request_interval_ms: None,
+            ..make_repository("unlimited")
+        };
+        let start = Instant::now();
+        for _ in 0..10 {
+            let _permit = acquire(&unlimited).await;
+        }
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+
+    #[test]
+    fn retry_after_in_seconds_or_as_a_date() {
+        let now = std::time::UNIX_EPOCH + Duration::from_secs(1_445_412_470);
+        assert_eq!(retry_after("120", now), Some(Duration::from_secs(120)));
+        assert_eq!(
+            retry_after("Wed, 21 Oct 2015 07:28:00 GMT", now),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            retry_after("Wed, 21 Oct 2015 07:27:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(retry_after("soon", now), None);
+        assert_eq!(retry_after("Wed, 21 Oct 2015 07:28 GMT", now), None);
+    }
+
+    #[tokio::test]
+    async fn rate_limited_requests_are_sent_again() {
+        let (url, answered) = serve(vec![
+            "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 1\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        ])
+        .await;
+        let repo = CandidateRepository {
+            max_concurrent_requests: None,
+            min_request_interval_ms: None,
+            ..make_repository("rate_limited")
+        };
+        let start = Instant::now();
+        let (_permit, res) = send(&repo, reqwest::Client::new().get(&url)).await.unwrap();
+        assert_eq!(res.status(), 200);
+        assert_eq!(answered.load(Ordering::SeqCst), 3);
+        assert!(start.elapsed() >= Duration::from_secs(1));
+
+        /* waits longer than MAX_RETRY_AFTER aren't waited out */
+        let (url, answered) = serve(vec![
+            "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 600\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        ])
+        .await;
+        let repo = CandidateRepository {
+            max_concurrent_requests: None,
+            min_request_interval_ms: None,
+            ..make_repository("rate_limited_for_long")
+        };
+        let (_permit, res) = send(&repo, reqwest::Client::new().get(&url)).await.unwrap();
+        assert_eq!(res.status(), 429);
+        assert_eq!(answered.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,214 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::candidate::InstallationCandidate;
+
+/// Name of the crash-recovery marker, kept in the cache directory next to the artifact it refers to
+pub const INSTALL_MARKER_FILE_NAME: &str = ".gman_install_in_progress.json";
+
+/// Step of an installation that was in progress when the marker was last written
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallPhase {
+    /// Previous installations of the product are being removed
+    Uninstalling,
+    /// The installer for the new version is running
+    Installing,
+}
+
+impl Display for InstallPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InstallPhase::Uninstalling => f.write_str("uninstalling the previous version"),
+            InstallPhase::Installing => f.write_str("installing the new version"),
+        }
+    }
+}
+
+/// Describes an installation that is in progress, so it can be resumed if gman is killed or crashes midway
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InstallTransaction {
+    #[serde(rename = "ProductName")]
+    pub product_name: String,
+    #[serde(rename = "FlavorId")]
+    pub flavor_id: String,
+    #[serde(rename = "Version")]
+    pub version: String,
+    #[serde(rename = "Identifier")]
+    pub identifier: String,
+    /// File name of the installer in the cache directory
+    #[serde(rename = "ArtifactFileName")]
+    pub artifact_file_name: String,
+    #[serde(rename = "Phase")]
+    pub phase: InstallPhase,
+    /// Seconds since the unix epoch at which the installation started
+    #[serde(rename = "StartedAt")]
+    pub started_at: u64,
+}
+
+impl InstallTransaction {
+    pub fn new(candidate: &InstallationCandidate, phase: InstallPhase) -> Self {
+        Self {
+            product_name: candidate.product_name.to_owned(),
+            flavor_id: candidate.flavor.id.to_owned(),
+            version: candidate.version.to_string(),
+            identifier: candidate.identifier.to_owned(),
+            artifact_file_name: candidate.make_cached_file_name(),
+            phase,
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|x| x.as_secs())
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn marker_path<P>(cache_directory: P) -> PathBuf
+    where
+        P: AsRef<Path>,
+    {
+        cache_directory.as_ref().join(INSTALL_MARKER_FILE_NAME)
+    }
+
+    /// Reads the marker left behind by an interrupted installation, if any
+    pub fn load<P>(cache_directory: P) -> Option<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = Self::marker_path(cache_directory);
+        let contents = std::fs::read_to_string(&path).ok()?;
+        match serde_json::from_str::<Self>(&contents) {
+            Ok(t) => Some(t),
+            Err(e) => {
+                log::warn!(
+                    "Ignoring unreadable installation marker at {}: {}",
+                    path.display(),
+                    e
+                );
+                let _ = std::fs::remove_file(&path);
+                None
+            }
+        }
+    }
+
+    /// Removes the marker from the cache directory
+    pub fn discard<P>(cache_directory: P)
+    where
+        P: AsRef<Path>,
+    {
+        let _ = std::fs::remove_file(Self::marker_path(cache_directory));
+    }
+
+    /// Path of the cached artifact this installation uses
+    pub fn artifact_path<P>(&self, cache_directory: P) -> PathBuf
+    where
+        P: AsRef<Path>,
+    {
+        cache_directory.as_ref().join(&self.artifact_file_name)
+    }
+
+    /// Writes the marker to disk and returns a guard that removes it again once the installation returns.
+    /// If the process dies (or panics) before that, the marker stays behind for the next run to pick up
+    pub fn begin<P>(
+        self,
+        cache_directory: P,
+    ) -> Result<TransactionMarker, Box<dyn std::error::Error>>
+    where
+        P: AsRef<Path>,
+    {
+        let marker = TransactionMarker {
+            path: Self::marker_path(cache_directory),
+            transaction: self,
+        };
+        marker.write()?;
+        Ok(marker)
+    }
+}
+
+/// Guard for an [InstallTransaction] written to disk
+#[derive(Debug)]
+pub struct TransactionMarker {
+    path: PathBuf,
+    transaction: InstallTransaction,
+}
+
+impl TransactionMarker {
+    fn write(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let s = serde_json::to_string_pretty(&self.transaction)?;
+        std::fs::write(&self.path, s)?;
+        Ok(())
+    }
+
+    /// Records that the installation moved on to [phase]
+    pub fn set_phase(&mut self, phase: InstallPhase) {
+        self.transaction.phase = phase;
+        if let Err(e) = self.write() {
+            log::warn!("Failed to update installation marker: {}", e);
+        }
+    }
+}
+
+impl Drop for TransactionMarker {
+    fn drop(&mut self) {
+        /* a panic is a crash like any other, keep the marker so the installation can be resumed */
+        if std::thread::panicking() {
+            return;
+        }
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::candidate::InstallationCandidate;
+
+    use super::{InstallPhase, InstallTransaction};
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join("gman_tests").join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn marker_written_updated_and_removed() {
+        let dir = temp_dir("transaction_marker");
+        let candidate = InstallationCandidate::from_str(
+            "HubKit@Windows@WindowsHubKit@develop@5.2.1-7049@GravioHubKit.msi",
+        )
+        .unwrap();
+
+        let mut marker = InstallTransaction::new(&candidate, InstallPhase::Uninstalling)
+            .begin(&dir)
+            .unwrap();
+        let loaded = InstallTransaction::load(&dir).unwrap();
+        assert_eq!(loaded.phase, InstallPhase::Uninstalling);
+        assert_eq!(
+            loaded.artifact_path(&dir),
+            candidate.make_output_for_candidate(&dir)
+        );
+
+        marker.set_phase(InstallPhase::Installing);
+        assert_eq!(
+            InstallTransaction::load(&dir).unwrap().phase,
+            InstallPhase::Installing
+        );
+
+        drop(marker);
+        assert!(InstallTransaction::load(&dir).is_none());
+    }
+
+    #[test]
+    fn unreadable_marker_ignored() {
+        let dir = temp_dir("transaction_marker_garbage");
+        std::fs::write(InstallTransaction::marker_path(&dir), "not json").unwrap();
+        assert!(InstallTransaction::load(&dir).is_none());
+        assert!(!InstallTransaction::marker_path(&dir).exists());
+    }
+}
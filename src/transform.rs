@@ -0,0 +1,194 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{gman_error::GManError, system_runner::SystemRunner};
+
+/// Extended attribute macOS puts on downloaded files, which makes Gatekeeper block unnotarized apps
+const QUARANTINE_ATTRIBUTE: &str = "com.apple.quarantine";
+
+/// Removes the quarantine attribute from [path] and everything in it, so Gatekeeper lets it be opened
+pub fn strip_quarantine(
+    runner: &dyn SystemRunner,
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let output = runner.run(
+        "xattr",
+        &["-dr", QUARANTINE_ATTRIBUTE, path.to_string_lossy().as_ref()],
+    )?;
+    /* fails when the attribute isn't there, which is just as good */
+    if !output.status.success() {
+        log::debug!(
+            "No quarantine attribute removed from {}: {}",
+            path.display(),
+            output.stderr.trim()
+        );
+    }
+    Ok(())
+}
+
+/// A step of a flavor's `Transforms`, run on the downloaded artifact before it's installed.
+/// Each step works on the output of the one before it
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+#[serde(tag = "Type")]
+pub enum Transform {
+    /// Extracts the artifact, a zip archive, and continues with the file at [path] inside it
+    Unzip {
+        #[serde(rename = "Path")]
+        path: PathBuf,
+    },
+    /// Renames the artifact to [to], e.g. to give it the extension its installer expects
+    Rename {
+        #[serde(rename = "To")]
+        to: String,
+    },
+    /// Changes the permissions of the artifact, `+x` unless given. Skipped on Windows
+    Chmod {
+        #[serde(rename = "Mode", default = "default_mode")]
+        mode: String,
+    },
+    /// Removes the quarantine attribute so Gatekeeper doesn't block the app. Only on macOS
+    StripQuarantine,
+}
+
+fn default_mode() -> String {
+    "+x".to_owned()
+}
+
+impl Transform {
+    /// Runs this step on [artifact], returning the path of its output
+    fn apply(
+        &self,
+        runner: &dyn SystemRunner,
+        artifact: &Path,
+    ) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let artifact_str = artifact.to_string_lossy().to_string();
+        match self {
+            Transform::Unzip { path } => {
+                let mut extracted = artifact.as_os_str().to_owned();
+                extracted.push(".extracted");
+                let extracted = PathBuf::from(extracted);
+                std::fs::create_dir_all(&extracted)?;
+                let extracted_str = extracted.to_string_lossy().to_string();
+                if cfg!(target_os = "windows") {
+                    /* bsdtar ships with Windows 10 and reads zip archives */
+                    runner.capture("tar", &["-xf", &artifact_str, "-C", &extracted_str])?;
+                } else {
+                    runner.capture("unzip", &["-o", "-q", &artifact_str, "-d", &extracted_str])?;
+                }
+                let inner = extracted.join(path);
+                if !inner.exists() {
+                    return Err(Box::new(GManError::new(&format!(
+                        "{} not found in the archive {}",
+                        path.display(),
+                        artifact.display()
+                    ))));
+                }
+                Ok(inner)
+            }
+            Transform::Rename { to } => {
+                let renamed = artifact.with_file_name(to);
+                std::fs::rename(artifact, &renamed)?;
+                Ok(renamed)
+            }
+            Transform::Chmod { mode } => {
+                if cfg!(target_os = "windows") {
+                    log::debug!("Skipping chmod of {} on Windows", artifact.display());
+                } else {
+                    runner.capture("chmod", &[mode.as_str(), &artifact_str])?;
+                }
+                Ok(artifact.to_path_buf())
+            }
+            Transform::StripQuarantine => {
+                if cfg!(target_os = "macos") {
+                    strip_quarantine(runner, artifact)?;
+                }
+                Ok(artifact.to_path_buf())
+            }
+        }
+    }
+}
+
+/// Puts a copy of [artifact] in [work_directory] through [transforms], returning the path of the result.
+/// The artifact itself is left as it is, so the cache keeps what the repository served
+pub fn apply(
+    runner: &dyn SystemRunner,
+    transforms: &[Transform],
+    artifact: &Path,
+    work_directory: &Path,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if work_directory.exists() {
+        std::fs::remove_dir_all(work_directory)?;
+    }
+    std::fs::create_dir_all(work_directory)?;
+    let file_name = artifact
+        .file_name()
+        .ok_or_else(|| GManError::new(&format!("{} is not a file", artifact.display())))?;
+    let mut current = work_directory.join(file_name);
+    std::fs::copy(artifact, &current)?;
+
+    for transform in transforms {
+        log::debug!("Applying {:?} to {}", transform, current.display());
+        current = transform.apply(runner, &current)?;
+    }
+    Ok(current)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::system_runner::{CommandOutput, MockRunner};
+
+    use super::{apply, Transform};
+
+    #[test]
+    fn parse_transforms() {
+        let transforms: Vec<Transform> = serde_json::from_str(
+            r#"[{"Type":"Unzip","Path":"HandbookX.app"},{"Type":"Rename","To":"x.pkg"},{"Type":"Chmod"},{"Type":"StripQuarantine"}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            transforms,
+            vec![
+                Transform::Unzip {
+                    path: "HandbookX.app".into()
+                },
+                Transform::Rename { to: "x.pkg".into() },
+                Transform::Chmod { mode: "+x".into() },
+                Transform::StripQuarantine,
+            ]
+        );
+    }
+
+    #[test]
+    fn transforms_work_on_a_copy() {
+        let dir = std::env::temp_dir().join("gman_tests").join("transforms");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let artifact = dir.join("hubkit");
+        std::fs::write(&artifact, "binary").unwrap();
+
+        let runner = MockRunner::new().respond(CommandOutput::ok(""));
+        let result = apply(
+            &runner,
+            &[
+                Transform::Rename {
+                    to: "hubkit.run".into(),
+                },
+                Transform::Chmod { mode: "+x".into() },
+            ],
+            &artifact,
+            &dir.join("work"),
+        )
+        .unwrap();
+
+        assert_eq!(result, dir.join("work").join("hubkit.run"));
+        assert!(result.exists());
+        assert!(artifact.exists(), "the cached artifact stays untouched");
+        if !cfg!(target_os = "windows") {
+            assert_eq!(
+                runner.calls()[0].command_line(),
+                format!("chmod +x {}", result.display())
+            );
+        }
+    }
+}
@@ -1,4 +1,13 @@
-use std::{fs, path::Path};
+use std::{fs, io::Write, path::Path};
+
+/// How [link_or_copy] put a file in place
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlacedBy {
+    /// Copy-on-write clone sharing the data, e.g. on APFS, Btrfs, XFS or ReFS
+    Reflink,
+    HardLink,
+    Copy,
+}
 
 pub fn remove_dir_contents<P: AsRef<Path>>(path: P) -> Result<(), Box<dyn std::error::Error>> {
     for entry in fs::read_dir(path)? {
@@ -14,3 +23,127 @@ pub fn remove_dir_contents<P: AsRef<Path>>(path: P) -> Result<(), Box<dyn std::e
     }
     Ok(())
 }
+
+/// Name of the user gman runs as, for telling users apart on shared machines
+pub fn current_user() -> String {
+    ["USERNAME", "USER", "LOGNAME"]
+        .iter()
+        .find_map(|x| std::env::var(x).ok().filter(|x| !x.is_empty()))
+        .unwrap_or_else(|| "unknown".to_owned())
+}
+
+/// Total size in bytes of the files under [path], or of [path] itself if it's a file, following no symlinks
+pub fn directory_size<P: AsRef<Path>>(path: P) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|x| x.ok())
+        .filter_map(|x| x.metadata().ok())
+        .filter(|x| x.is_file())
+        .map(|x| x.len())
+        .sum()
+}
+
+/// Creates the directory [path] so every user of the machine can add files to it, like `/tmp`: sticky and
+/// writable by all on Unix, modifiable by the Users group on Windows. For the state shared by everyone using
+/// the machine. Directories that are already there are left as they are
+pub fn create_shared_directory<P: AsRef<Path>>(path: P) -> std::io::Result<()> {
+    let path = path.as_ref();
+    if path.is_dir() {
+        return Ok(());
+    }
+    fs::create_dir_all(path)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o1777))?;
+    }
+    #[cfg(windows)]
+    {
+        /* S-1-5-32-545 is the Users group whatever the language of Windows, files created inside inherit it */
+        let output = std::process::Command::new("icacls")
+            .arg(path)
+            .args(["/grant", "*S-1-5-32-545:(OI)(CI)M", "/Q"])
+            .output()?;
+        if !output.status.success() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                String::from_utf8_lossy(&output.stdout).trim().to_owned(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Appends [line] to the file at [path], creating it if it isn't there. Files created in a directory made
+/// with [create_shared_directory] can be appended to by every user too
+pub fn append_line<P: AsRef<Path>>(path: P, line: &str) -> std::io::Result<()> {
+    let path = path.as_ref();
+    let created = !path.exists();
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    #[cfg(unix)]
+    if created {
+        use std::os::unix::fs::PermissionsExt;
+        let shared = path
+            .parent()
+            .and_then(|x| x.metadata().ok())
+            .is_some_and(|x| x.permissions().mode() & 0o1002 == 0o1002);
+        if shared {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o666))?;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = created;
+    writeln!(file, "{}", line)
+}
+
+/// Whether files can be created in [path], creating the directory if necessary
+pub fn is_writable_directory<P: AsRef<Path>>(path: P) -> bool {
+    let path = path.as_ref();
+    if fs::create_dir_all(path).is_err() {
+        return false;
+    }
+    let probe = path.join(".gman_write_test");
+    match fs::write(&probe, []) {
+        Ok(_) => {
+            let _ = fs::remove_file(probe);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Puts the file [from] at [to] without duplicating its data where the file system allows it: as a
+/// copy-on-write clone, else as a hard link, and only as a plain copy when neither works (e.g. across volumes)
+pub fn link_or_copy<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> std::io::Result<PlacedBy> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    if reflink_copy::reflink(from, to).is_ok() {
+        return Ok(PlacedBy::Reflink);
+    }
+    if fs::hard_link(from, to).is_ok() {
+        return Ok(PlacedBy::HardLink);
+    }
+    fs::copy(from, to)?;
+    Ok(PlacedBy::Copy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{link_or_copy, PlacedBy};
+
+    #[test]
+    fn link_or_copy_places_file() {
+        let dir = std::env::temp_dir().join("gman_tests").join("link_or_copy");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let from = dir.join("GravioHubKit.msi");
+        let to = dir.join("cached.msi");
+        std::fs::write(&from, "installer").unwrap();
+
+        let placed = link_or_copy(&from, &to).unwrap();
+        assert_ne!(placed, PlacedBy::Copy);
+        assert_eq!(std::fs::read_to_string(&to).unwrap(), "installer");
+    }
+}
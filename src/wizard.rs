@@ -0,0 +1,396 @@
+use std::{
+    io::{BufRead, Write},
+    path::Path,
+};
+
+use crate::{
+    client_config::default_cache, gman_error::GManError, platform::Platform, product::Product,
+};
+
+/// Products gman knows about out of the box, offered by `init`.
+/// Each entry is the name, a short description and the product's configuration, which is written as-is
+const PRODUCT_CATALOG: [(&str, &str, &str); 3] = [
+    (
+        "HubKit",
+        "Gravio HubKit for Windows and Mac",
+        r#"{
+      "Name": "HubKit",
+      "Flavors": [
+        {
+          "Platform": "Windows",
+          "Id": "WindowsHubkit",
+          "PackageType": "Msi",
+          "TeamCityMetadata": {
+            "TeamCityId": "Gravio_GravioHubKit4",
+            "TeamCityBinaryPath": "GravioHubKit.msi"
+          }
+        },
+        {
+          "Platform": "Mac",
+          "Id": "MacHubkit",
+          "PackageType": "App",
+          "TeamCityMetadata": {
+            "TeamCityId": "Gravio_GravioHubKit4",
+            "TeamCityBinaryPath": "GravioHubKit.dmg"
+          },
+          "Metadata": {
+            "CFBundleIdentifier": "com.asteria.mac.gravio4",
+            "CFBundleName": "Gravio HubKit"
+          }
+        }
+      ]
+    }"#,
+    ),
+    (
+        "GravioStudio",
+        "Gravio Studio for Windows (Store, Sideloading) and Mac (Developer ID, App Store)",
+        r#"{
+      "Name": "GravioStudio",
+      "Flavors": [
+        {
+          "Platform": "Windows",
+          "Id": "WindowsAppStore",
+          "PackageType": "AppX",
+          "TeamCityMetadata": {
+            "TeamCityId": "Gravio_GravioStudio4forWindows",
+            "TeamCityBinaryPath": "graviostudio.zip"
+          }
+        },
+        {
+          "Platform": "Windows",
+          "Id": "Sideloading",
+          "PackageType": "AppX",
+          "TeamCityMetadata": {
+            "TeamCityId": "Gravio_GravioStudio4forWindows",
+            "TeamCityBinaryPath": "graviostudio_sideloading.zip"
+          }
+        },
+        {
+          "Platform": "Mac",
+          "Id": "DeveloperId",
+          "PackageType": "App",
+          "TeamCityMetadata": {
+            "TeamCityId": "Gravio_GravioStudio4ForMac",
+            "TeamCityBinaryPath": "developerid/GravioStudio.dmg"
+          },
+          "Metadata": {
+            "CFBundleIdentifier": "com.asteria.mac.graviostudio4",
+            "CFBundleName": "Gravio Studio"
+          }
+        },
+        {
+          "Platform": "Mac",
+          "Id": "MacAppStore",
+          "PackageType": "Pkg",
+          "TeamCityMetadata": {
+            "TeamCityId": "Gravio_GravioStudio4ForMac",
+            "TeamCityBinaryPath": "appstore/Gravio Studio.pkg"
+          },
+          "Metadata": {
+            "CFBundleIdentifier": "com.asteria.mac.graviostudio4",
+            "CFBundleName": "Gravio Studio"
+          }
+        }
+      ]
+    }"#,
+    ),
+    (
+        "HandbookX",
+        "HandbookX for Windows and Android",
+        r#"{
+      "Name": "HandbookX",
+      "Flavors": [
+        {
+          "Platform": "Windows",
+          "Id": "Windows",
+          "PackageType": "MsiX",
+          "TeamCityMetadata": {
+            "TeamCityId": "Hubble_HubbleForWindows10",
+            "TeamCityBinaryPath": "handbookx.msix"
+          }
+        },
+        {
+          "Platform": "Windows",
+          "Id": "Sideloading",
+          "PackageType": "MsiX",
+          "TeamCityMetadata": {
+            "TeamCityId": "Hubble_HubbleForWindows10",
+            "TeamCityBinaryPath": "sideloadinghandbookx.msix"
+          }
+        },
+        {
+          "Platform": "Android",
+          "Id": "Android",
+          "PackageType": "Apk",
+          "TeamCityMetadata": {
+            "TeamCityId": "Hubble_2_HubbleFlutter",
+            "TeamCityBinaryPath": "handbookx-release.apk"
+          }
+        }
+      ]
+    }"#,
+    ),
+];
+
+/// What the user answered in the setup wizard
+#[derive(Debug, PartialEq)]
+pub struct WizardAnswers {
+    pub server_url: String,
+    /// TeamCity access token, if the server needs one
+    pub token: Option<String>,
+    /// Indices into [PRODUCT_CATALOG]
+    pub products: Vec<usize>,
+    /// Cache directory, if not the default
+    pub cache_directory: Option<String>,
+}
+
+fn ask<R, W>(
+    input: &mut R,
+    output: &mut W,
+    question: &str,
+) -> Result<String, Box<dyn std::error::Error>>
+where
+    R: BufRead,
+    W: Write,
+{
+    write!(output, "{}", question)?;
+    output.flush()?;
+    let mut buffer = String::new();
+    if input.read_line(&mut buffer)? == 0 {
+        return Err(Box::new(GManError::new("Setup was canceled")));
+    }
+    Ok(buffer.trim().to_owned())
+}
+
+/// Parses a product selection such as `1, 3`. Empty selects every product
+fn parse_product_selection(selection: &str) -> Result<Vec<usize>, GManError> {
+    if selection.is_empty() {
+        return Ok((0..PRODUCT_CATALOG.len()).collect());
+    }
+    let mut products = Vec::new();
+    for part in selection.split(|c: char| c == ',' || c.is_whitespace()) {
+        if part.is_empty() {
+            continue;
+        }
+        match part.parse::<usize>() {
+            Ok(n) if n >= 1 && n <= PRODUCT_CATALOG.len() => {
+                if !products.contains(&(n - 1)) {
+                    products.push(n - 1);
+                }
+            }
+            _ => {
+                return Err(GManError::new(&format!(
+                    "{} is not a number between 1 and {}",
+                    part,
+                    PRODUCT_CATALOG.len()
+                )))
+            }
+        }
+    }
+    Ok(products)
+}
+
+/// Asks the setup questions on [output], reading the answers from [input]
+pub fn ask_answers<R, W>(
+    input: &mut R,
+    output: &mut W,
+) -> Result<WizardAnswers, Box<dyn std::error::Error>>
+where
+    R: BufRead,
+    W: Write,
+{
+    let server_url = loop {
+        let url = ask(
+            input,
+            output,
+            "TeamCity server url (e.g. https://teamcity.example.com): ",
+        )?;
+        if !url.is_empty() {
+            break url;
+        }
+    };
+
+    let token = ask(
+        input,
+        output,
+        "Access token, from TeamCity > Profile > Access Tokens (empty if guests can download): ",
+    )?;
+
+    writeln!(output, "Products:")?;
+    for (i, (name, description, _)) in PRODUCT_CATALOG.iter().enumerate() {
+        writeln!(output, "  {}. {:<14} {}", i + 1, name, description)?;
+    }
+    let products = loop {
+        let selection = ask(
+            input,
+            output,
+            "Products to add, as numbers separated by commas (empty for all): ",
+        )?;
+        match parse_product_selection(&selection) {
+            Ok(p) => break p,
+            Err(e) => writeln!(output, "{}", e)?,
+        }
+    };
+
+    let cache_directory = ask(
+        input,
+        output,
+        &format!(
+            "Cache directory (empty for {}): ",
+            default_cache().to_string_lossy()
+        ),
+    )?;
+
+    Ok(WizardAnswers {
+        server_url,
+        token: Some(token).filter(|x| !x.is_empty()),
+        products,
+        cache_directory: Some(cache_directory).filter(|x| !x.is_empty()),
+    })
+}
+
+/// Writes the commented configuration file for [answers]
+pub fn render_config(answers: &WizardAnswers) -> Result<String, Box<dyn std::error::Error>> {
+    let mut platforms: Vec<Platform> = Vec::new();
+    let mut names: Vec<&str> = Vec::new();
+    let mut snippets: Vec<&str> = Vec::new();
+    for &i in &answers.products {
+        let (name, _, snippet) = PRODUCT_CATALOG[i];
+        let product: Product = json5::from_str(snippet)?;
+        for flavor in product.flavors {
+            if !platforms.contains(&flavor.platform) {
+                platforms.push(flavor.platform);
+            }
+        }
+        names.push(name);
+        snippets.push(snippet);
+    }
+
+    let mut s = String::new();
+    s.push_str(
+        "// gman configuration, written by `gman init`. Change it with `gman config --edit`\n",
+    );
+    s.push_str("{\n");
+    s.push_str("  // Where downloaded installers are kept\n");
+    match &answers.cache_directory {
+        Some(dir) => s.push_str(&format!(
+            "  \"CacheDirectory\": {},\n",
+            serde_json::to_string(dir)?
+        )),
+        None => s.push_str(&format!(
+            "  // \"CacheDirectory\": {},\n",
+            serde_json::to_string(&format!("~/.cache/{}", crate::app::APP_FOLDER_NAME))?
+        )),
+    }
+    s.push_str("  // Off, Error, Warn, Info, Debug or Trace\n");
+    s.push_str("  \"LogLevel\": \"Off\",\n");
+    s.push_str("  \"Repositories\": [\n");
+    s.push_str("    {\n");
+    s.push_str("      \"Name\": \"TeamCity\",\n");
+    s.push_str("      \"RepositoryType\": \"TeamCity\",\n");
+    s.push_str(&format!(
+        "      \"RepositoryServer\": {},\n",
+        serde_json::to_string(&answers.server_url)?
+    ));
+    match &answers.token {
+        Some(token) => s.push_str(&format!(
+            "      \"RepositoryCredentials\": {{ \"Type\": \"BearerToken\", \"Token\": {} }},\n",
+            serde_json::to_string(token)?
+        )),
+        None => {
+            s.push_str("      // Add { \"Type\": \"BearerToken\", \"Token\": \"...\" } if the server needs credentials\n");
+            s.push_str("      \"RepositoryCredentials\": null,\n");
+        }
+    }
+    s.push_str(&format!(
+        "      \"Platforms\": {},\n",
+        serde_json::to_string(&platforms)?
+    ));
+    s.push_str("      // Products to look for on this repository, from the Products below\n");
+    s.push_str(&format!(
+        "      \"Products\": {}\n",
+        serde_json::to_string(&names)?
+    ));
+    s.push_str("    }\n");
+    s.push_str("  ],\n");
+    s.push_str("  // Products that can be listed and installed. See the README for every available setting\n");
+    s.push_str("  \"Products\": [\n");
+    s.push_str(&format!("    {}\n", snippets.join(",\n    ")));
+    s.push_str("  ]\n");
+    s.push_str("}\n");
+    Ok(s)
+}
+
+/// Runs the interactive setup on the terminal and writes the configuration to [path]
+pub fn run(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    println!(
+        "Setting up gman, the configuration will be written to {}",
+        path.display()
+    );
+    let answers = ask_answers(&mut std::io::stdin().lock(), &mut std::io::stdout())?;
+    let contents = render_config(&answers)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, contents)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::{platform::Platform, ClientConfig, RepositoryCredentials};
+
+    use super::{ask_answers, render_config, WizardAnswers};
+
+    #[test]
+    fn answers_render_valid_config() {
+        let mut input = Cursor::new("\nhttps://teamcity.example.com\nsecret\n4\n1, 3\n\n");
+        let mut output = Vec::new();
+        let answers = ask_answers(&mut input, &mut output).unwrap();
+        assert_eq!(
+            answers,
+            WizardAnswers {
+                server_url: "https://teamcity.example.com".into(),
+                token: Some("secret".into()),
+                products: vec![0, 2],
+                cache_directory: None,
+            }
+        );
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .contains("4 is not a number between 1 and 3"));
+
+        let config = ClientConfig::parse(&render_config(&answers).unwrap()).unwrap();
+        let repo = &config.repositories[0];
+        assert_eq!(
+            repo.repository_server.as_deref(),
+            Some("https://teamcity.example.com")
+        );
+        assert!(matches!(
+            &repo.repository_credentials,
+            Some(RepositoryCredentials::BearerToken { token }) if token == "secret"
+        ));
+        assert_eq!(
+            repo.platforms,
+            vec![Platform::Windows, Platform::Mac, Platform::Android]
+        );
+        assert_eq!(repo.products, vec!["HubKit", "HandbookX"]);
+        assert_eq!(config.products.len(), 2);
+    }
+
+    #[test]
+    fn guest_access_and_custom_cache() {
+        let answers = WizardAnswers {
+            server_url: "teamcity.example.com".into(),
+            token: None,
+            products: vec![1],
+            cache_directory: Some("C:\\gman\\cache".into()),
+        };
+        let config = ClientConfig::parse(&render_config(&answers).unwrap()).unwrap();
+        assert!(config.repositories[0].repository_credentials.is_none());
+        assert_eq!(config.products[0].name, "GravioStudio");
+        assert_eq!(config.products[0].flavors.len(), 4);
+    }
+}